@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use tar::EntryType;
+use zip::{ZipArchive, ZipWriter};
+
+///Kinds of archives whose entries can be renamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+///Kind of an archive by its extension
+fn kind(path: &Path) -> Result<Kind> {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    match name {
+        n if n.ends_with(".zip") => Ok(Kind::Zip),
+        n if n.ends_with(".tar") => Ok(Kind::Tar),
+        n if n.ends_with(".tar.gz") || n.ends_with(".tgz") => Ok(Kind::TarGz),
+        _ => Err(anyhow!(
+            "Archive must be a '.zip','.tar','.tar.gz' or '.tgz' file:{:?}",
+            path
+        )),
+    }
+}
+
+///Reader of a tar archive,decompressed if gzipped
+fn tar_reader(path: &Path, kind: Kind) -> Result<tar::Archive<Box<dyn Read>>> {
+    let file = BufReader::new(File::open(path)?);
+    let reader: Box<dyn Read> = match kind {
+        Kind::TarGz => Box::new(GzDecoder::new(file)),
+        _ => Box::new(file),
+    };
+
+    Ok(tar::Archive::new(reader))
+}
+
+///Names of entries in archive order with whether they are directories,'/' separated without
+///the trailing '/'
+pub fn entries(path: &Path) -> Result<Vec<(String, bool)>> {
+    match kind(path)? {
+        Kind::Zip => {
+            let mut archive = ZipArchive::new(File::open(path)?)?;
+            (0..archive.len())
+                .map(|i| {
+                    let entry = archive.by_index_raw(i)?;
+                    Ok((
+                        entry.name().trim_end_matches('/').to_owned(),
+                        entry.is_dir(),
+                    ))
+                })
+                .collect()
+        }
+        kind => tar_reader(path, kind)?
+            .entries()?
+            .map(|entry| {
+                let entry = entry?;
+                let name = entry.path()?.to_string_lossy().into_owned();
+                Ok((
+                    name.trim_end_matches('/').to_owned(),
+                    entry.header().entry_type().is_dir(),
+                ))
+            })
+            .collect(),
+    }
+}
+
+///Rewrite an archive with entries renamed by their names,contents copied without recompressing
+///zip entries;written beside the archive and moved over it only when complete and synced
+pub fn rewrite(path: &Path, renamed: &HashMap<String, String>) -> Result<()> {
+    let kind = kind(path)?;
+    let tmp = path.with_file_name(format!(
+        ".{}.fdn-archive",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let written = match kind {
+        Kind::Zip => rewrite_zip(path, &tmp, renamed),
+        kind => rewrite_tar(path, kind, &tmp, renamed),
+    };
+    if let Err(err) = written.and_then(|()| Ok(fs::rename(&tmp, path)?)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+///New name of an entry,the trailing '/' of a directory kept
+fn new_name(name: &str, renamed: &HashMap<String, String>) -> String {
+    let trimmed = name.trim_end_matches('/');
+    match renamed.get(trimmed) {
+        Some(new) => format!("{}{}", new, &name[trimmed.len()..]),
+        None => name.to_owned(),
+    }
+}
+
+fn rewrite_zip(path: &Path, tmp: &Path, renamed: &HashMap<String, String>) -> Result<()> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut writer = ZipWriter::new(BufWriter::new(File::create(tmp)?));
+    writer.set_raw_comment(archive.comment().into());
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        let name = new_name(entry.name(), renamed);
+        writer.raw_copy_file_rename(entry, name)?;
+    }
+    let file = writer
+        .finish()?
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+fn rewrite_tar(
+    path: &Path,
+    kind: Kind,
+    tmp: &Path,
+    renamed: &HashMap<String, String>,
+) -> Result<()> {
+    let file = BufWriter::new(File::create(tmp)?);
+    let file = match kind {
+        Kind::TarGz => {
+            let gz = GzEncoder::new(file, Compression::default());
+            write_tar(path, kind, gz, renamed)?.finish()?
+        }
+        _ => write_tar(path, kind, file, renamed)?,
+    };
+    let file = file.into_inner().map_err(io::IntoInnerError::into_error)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+///Write entries of a tar archive renamed into a writer and return the writer
+fn write_tar<W: Write>(
+    path: &Path,
+    kind: Kind,
+    writer: W,
+    renamed: &HashMap<String, String>,
+) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    let mut archive = tar_reader(path, kind)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let origin = entry.path()?.to_string_lossy().into_owned();
+        let name = new_name(&origin, renamed);
+        let mut header = entry.header().clone();
+        match header.entry_type() {
+            //links point at entries which may be renamed too,hard links by their names and
+            //symlinks relative to their directories
+            EntryType::Link | EntryType::Symlink => {
+                let target = entry
+                    .link_name()?
+                    .map(|t| t.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let target = match header.entry_type() {
+                    EntryType::Link => renamed_path(&target, renamed),
+                    _ => symlink_target(&origin, &name, &target, renamed),
+                };
+                builder.append_link(&mut header, &name, &target)?;
+            }
+            _ => builder.append_data(&mut header, &name, &mut entry)?,
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+///New name of an entry or a directory without an entry of its own,whose new name is taken from
+///an entry under it since every component is renamed by itself
+fn renamed_path(path: &str, renamed: &HashMap<String, String>) -> String {
+    let path = path.trim_end_matches('/');
+    if let Some(new) = renamed.get(path) {
+        return new.clone();
+    }
+    let prefix = format!("{}/", path);
+    match renamed.iter().find(|(k, _)| k.starts_with(&prefix)) {
+        Some((_, new)) => new
+            .split('/')
+            .take(path.split('/').count())
+            .collect::<Vec<_>>()
+            .join("/"),
+        None => path.to_owned(),
+    }
+}
+
+///Directory of an entry inside the archive,"" at the top
+fn parent_entry(name: &str) -> &str {
+    name.trim_end_matches('/')
+        .rsplit_once('/')
+        .map_or("", |(dir, _)| dir)
+}
+
+///Target of a symlink renamed from origin to name,a relative target pointing at a renamed entry
+///made to point at its new name;absolute targets and those leaving the archive kept
+fn symlink_target(
+    origin: &str,
+    name: &str,
+    target: &str,
+    renamed: &HashMap<String, String>,
+) -> String {
+    if target.starts_with('/') {
+        return target.to_owned();
+    }
+    let mut resolved: Vec<&str> = parent_entry(origin)
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect();
+    for c in target.split('/') {
+        match c {
+            "" | "." => {}
+            ".." => {
+                if resolved.pop().is_none() {
+                    return target.to_owned();
+                }
+            }
+            c => resolved.push(c),
+        }
+    }
+    let resolved = resolved.join("/");
+    let new = renamed_path(&resolved, renamed);
+    if new == resolved {
+        return target.to_owned();
+    }
+
+    let from: Vec<_> = parent_entry(name)
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect();
+    let to: Vec<_> = new.split('/').collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    from[common..]
+        .iter()
+        .map(|_| "..")
+        .chain(to[common..].iter().copied())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+///Renames of entries planned by renaming each component of their names,a component of a
+///directory planned as a directory and the last one of a file as a file
+pub fn planned(
+    entries: &[(String, bool)],
+    plan: &dyn Fn(&str, bool) -> Result<String>,
+) -> Result<HashMap<String, String>> {
+    let mut renamed = HashMap::new();
+    let mut by_new: HashMap<String, &str> = HashMap::new();
+    for (name, is_dir) in entries {
+        let comps: Vec<_> = name.split('/').collect();
+        let new = comps
+            .iter()
+            .enumerate()
+            .map(|(i, c)| match c.is_empty() || *c == "." || *c == ".." {
+                true => Ok(c.to_string()),
+                false => {
+                    //a planned component must stay one name so that entries can't escape
+                    let new = plan(c, i + 1 == comps.len() && !is_dir)?;
+                    match new.is_empty() || new.contains(['/', '\\']) || new == "." || new == ".." {
+                        true => Err(anyhow!(
+                            "Entry {:?} would have {:?} renamed to {:?},not a name",
+                            name,
+                            c,
+                            new
+                        )),
+                        false => Ok(new),
+                    }
+                }
+            })
+            .collect::<Result<Vec<_>>>()?
+            .join("/");
+        if let Some(other) = by_new.insert(new.clone(), name) {
+            if other != name {
+                return Err(anyhow!(
+                    "Entries {:?} and {:?} would both be renamed to {:?}",
+                    other,
+                    name,
+                    new
+                ));
+            }
+        }
+        if new != *name {
+            renamed.insert(name.clone(), new);
+        }
+    }
+
+    Ok(renamed)
+}
+
+///Directory inside the archive and name of an entry
+pub fn split_entry(name: &str) -> (PathBuf, &str) {
+    match name.rsplit_once('/') {
+        Some((dir, base)) => (PathBuf::from(dir), base),
+        None => (PathBuf::new(), name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use anyhow::Result;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::archive::{entries, planned, rewrite, symlink_target};
+
+    #[test]
+    fn test_archive() {
+        let plan = |c: &str, _is_file: bool| -> Result<String> { Ok(c.replace(' ', "_")) };
+        let list = [
+            ("a b".to_owned(), true),
+            ("a b/c d.txt".to_owned(), false),
+            ("ok.txt".to_owned(), false),
+        ];
+        let renamed = planned(&list, &plan).unwrap();
+        assert_eq!(renamed.len(), 2);
+        assert_eq!(renamed["a b/c d.txt"], "a_b/c_d.txt");
+        let clash = [("a b".to_owned(), false), ("a_b".to_owned(), false)];
+        assert!(planned(&clash, &plan).is_err());
+        //zip-slip by a planned component
+        for bad in ["..", "../x", "a\\b", ""] {
+            let plan = |_: &str, _: bool| -> Result<String> { Ok(bad.to_owned()) };
+            assert!(planned(&[("ok.txt".to_owned(), false)], &plan).is_err());
+        }
+
+        let renamed = planned(&list, &plan).unwrap();
+        assert_eq!(
+            symlink_target("a b/ln", "a_b/ln", "c d.txt", &renamed),
+            "c_d.txt"
+        );
+        assert_eq!(
+            symlink_target("top", "top", "./a b/c d.txt", &renamed),
+            "a_b/c_d.txt"
+        );
+        assert_eq!(symlink_target("x/ln", "x/ln", "../a b", &renamed), "../a_b");
+        assert_eq!(symlink_target("ln", "ln", "ok.txt", &renamed), "ok.txt");
+        assert_eq!(symlink_target("ln", "ln", "../a b", &renamed), "../a b");
+        assert_eq!(symlink_target("ln", "ln", "/a b", &renamed), "/a b");
+
+        let tmp = tempfile::tempdir().unwrap();
+        let zip_path = tmp.path().join("t.zip");
+        let mut zip = ZipWriter::new(File::create(&zip_path).unwrap());
+        zip.add_directory("a b/", SimpleFileOptions::default())
+            .unwrap();
+        zip.start_file("a b/c d.txt", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"data").unwrap();
+        zip.finish().unwrap();
+
+        let tar_path = tmp.path().join("t.tar.gz");
+        let gz = flate2::write::GzEncoder::new(
+            File::create(&tar_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "a b/c d.txt", &b"data"[..])
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        tar.append_link(&mut header, "a b/ln", "c d.txt").unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        for path in [zip_path, tar_path] {
+            let list = entries(&path).unwrap();
+            let renamed = planned(&list, &plan).unwrap();
+            rewrite(&path, &renamed).unwrap();
+            let names: Vec<_> = entries(&path)
+                .unwrap()
+                .into_iter()
+                .map(|(n, _)| n)
+                .collect();
+            assert!(names.contains(&"a_b/c_d.txt".to_owned()));
+            assert!(!names.contains(&"a b/c d.txt".to_owned()));
+        }
+        let gz = flate2::read::GzDecoder::new(File::open(tmp.path().join("t.tar.gz")).unwrap());
+        let mut tar = tar::Archive::new(gz);
+        let link = tar
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .find(|e| e.header().entry_type() == tar::EntryType::Symlink)
+            .unwrap();
+        assert_eq!(link.path().unwrap().to_str(), Some("a_b/ln"));
+        assert_eq!(link.link_name().unwrap().unwrap().to_str(), Some("c_d.txt"));
+        assert!(entries(&tmp.path().join("t.rar")).is_err());
+    }
+}
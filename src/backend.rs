@@ -0,0 +1,456 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, FileTimes},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+///Metadata of a file or directory needed by renames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsMeta {
+    pub is_dir: bool,
+    pub len: u64,
+    ///None if not supported by the filesystem
+    pub modified: Option<SystemTime>,
+    ///None if not supported by the filesystem
+    pub created: Option<SystemTime>,
+}
+
+impl FsMeta {
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+}
+
+///Filesystem operations which renames are applied through
+pub trait FsBackend {
+    ///Rename a file or directory
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()>;
+
+    ///Check a file or directory exists
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    ///Metadata of a file or directory
+    fn metadata(&self, path: &Path) -> io::Result<FsMeta>;
+
+    ///Paths of entries in a directory
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    ///Create a directory and all its missing parents
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        match self.metadata(path) {
+            Ok(m) if m.is_dir => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Can not create {:?}", path),
+            )),
+        }
+    }
+
+    ///Check names of a directory are case insensitive,None if it is to be probed on the local
+    ///filesystem
+    fn case_insensitive(&self, _dir: &Path) -> Option<bool> {
+        None
+    }
+
+    ///Regular files under a directory in path order,down to the depth where 1 is the directory
+    ///itself
+    fn files(&self, dir: &Path, depth: usize) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        if depth == 0 {
+            return Ok(files);
+        }
+        let mut entries = self.read_dir(dir)?;
+        entries.sort();
+        for p in entries {
+            match self.metadata(&p)?.is_dir {
+                true => files.extend(self.files(&p, depth - 1)?),
+                false => files.push(p),
+            }
+        }
+
+        Ok(files)
+    }
+
+    ///Move a file to the trash so that it can be restored
+    fn trash(&mut self, path: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Can not move {:?} to the trash", path),
+        ))
+    }
+
+    ///Restore the file of a path latest moved to the trash
+    fn restore(&mut self, path: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Can not restore {:?} from the trash", path),
+        ))
+    }
+}
+
+///The local filesystem
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFs;
+
+impl FsBackend for RealFs {
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        match fs::rename(from, to) {
+            Err(err) if err.kind() == io::ErrorKind::CrossesDevices && from.is_file() => {
+                move_across(from, to)
+            }
+            rlt => rlt,
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    ///Moved to the trash of the OS,the freedesktop trash,the recycle bin or the Finder trash
+    #[cfg(feature = "trash")]
+    fn trash(&mut self, path: &Path) -> io::Result<()> {
+        trash::delete(path).map_err(io::Error::other)
+    }
+
+    ///Restored only where the trash can be listed,on Linux and Windows;paths compared by their
+    ///canonical directories as the trash keeps them
+    #[cfg(all(
+        feature = "trash",
+        any(
+            target_os = "windows",
+            all(
+                unix,
+                not(target_os = "macos"),
+                not(target_os = "ios"),
+                not(target_os = "android")
+            )
+        )
+    ))]
+    fn restore(&mut self, path: &Path) -> io::Result<()> {
+        let canonical = |p: &Path| match (p.parent(), p.file_name()) {
+            (Some(dir), Some(name)) => {
+                fs::canonicalize(dir).map_or(p.to_path_buf(), |d| d.join(name))
+            }
+            _ => p.to_path_buf(),
+        };
+        let path = canonical(path);
+        let latest = trash::os_limited::list()
+            .map_err(io::Error::other)?
+            .into_iter()
+            .filter(|item| canonical(&item.original_path()) == path)
+            .max_by_key(|item| item.time_deleted)
+            .ok_or(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{:?} not found in the trash", path),
+            ))?;
+
+        trash::os_limited::restore_all([latest]).map_err(io::Error::other)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMeta> {
+        let m = fs::metadata(path)?;
+        Ok(FsMeta {
+            is_dir: m.is_dir(),
+            len: m.len(),
+            modified: m.modified().ok(),
+            created: m.created().ok(),
+        })
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect()
+    }
+
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+}
+
+///Move a file to another filesystem by copying it,cloned where supported,with its modification
+///time kept,then removing the origin
+fn move_across(from: &Path, to: &Path) -> io::Result<()> {
+    let modified = fs::metadata(from)?.modified()?;
+    clone_or_copy(from, to)?;
+    File::options()
+        .write(true)
+        .open(to)?
+        .set_times(FileTimes::new().set_modified(modified))?;
+
+    fs::remove_file(from)
+}
+
+///Copy a file,its data cloned by reflink on filesystems supporting it such as btrfs and XFS so
+///that the copy is instant and shares space,otherwise copied in full;true if cloned
+pub fn clone_or_copy(from: &Path, to: &Path) -> io::Result<bool> {
+    if reflink(from, to).is_ok() {
+        return Ok(true);
+    }
+    fs::copy(from, to)?;
+
+    Ok(false)
+}
+
+///Clone a file into a new one by the FICLONE ioctl,the new one removed if cloning fails
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let src = File::open(from)?;
+    let dst = File::options().write(true).create_new(true).open(to)?;
+    //SAFETY:both descriptors stay open during the call
+    if unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } != 0 {
+        let err = io::Error::last_os_error();
+        drop(dst);
+        let _ = fs::remove_file(to);
+        return Err(err);
+    }
+
+    dst.set_permissions(src.metadata()?.permissions())
+}
+
+///Cloning left to the copy,which clones by itself on APFS
+#[cfg(not(target_os = "linux"))]
+fn reflink(_from: &Path, _to: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+///In-memory filesystem for tests and dry runs,parents of added entries are created as directories
+#[derive(Debug, Default, Clone)]
+pub struct MemFs {
+    entries: BTreeMap<PathBuf, FsMeta>,
+    trashed: Vec<(PathBuf, FsMeta)>,
+}
+
+impl MemFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Add a file of len bytes
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P, len: u64) {
+        self.add_file_at(path, len, None);
+    }
+
+    ///Add a file of len bytes modified and created at the time
+    pub fn add_file_at<P: AsRef<Path>>(&mut self, path: P, len: u64, time: Option<SystemTime>) {
+        self.add(
+            path.as_ref(),
+            FsMeta {
+                is_dir: false,
+                len,
+                modified: time,
+                created: time,
+            },
+        );
+    }
+
+    ///Add a directory
+    pub fn add_dir<P: AsRef<Path>>(&mut self, path: P) {
+        self.add(
+            path.as_ref(),
+            FsMeta {
+                is_dir: true,
+                len: 0,
+                modified: None,
+                created: None,
+            },
+        );
+    }
+
+    ///All paths in order
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries.keys().cloned().collect()
+    }
+
+    fn add(&mut self, path: &Path, meta: FsMeta) {
+        for parent in path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() {
+                break;
+            }
+            self.entries.entry(parent.to_path_buf()).or_insert(FsMeta {
+                is_dir: true,
+                len: 0,
+                modified: None,
+                created: None,
+            });
+        }
+        self.entries.insert(path.to_path_buf(), meta);
+    }
+}
+
+impl FsBackend for MemFs {
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let meta = self.metadata(from)?;
+        if from == to {
+            return Ok(());
+        }
+        if let Ok(t_meta) = self.metadata(to) {
+            if t_meta.is_dir != meta.is_dir || t_meta.is_dir {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{:?} exists", to),
+                ));
+            }
+        }
+        if to.starts_with(from) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} is inside {:?}", to, from),
+            ));
+        }
+
+        //move the entry and all entries under it
+        let moved: Vec<_> = self
+            .entries
+            .keys()
+            .filter(|p| p.starts_with(from))
+            .cloned()
+            .collect();
+        for p in moved {
+            if let Some(m) = self.entries.remove(&p) {
+                let rest = p.strip_prefix(from).unwrap_or(Path::new(""));
+                match rest.as_os_str().is_empty() {
+                    true => self.entries.insert(to.to_path_buf(), m),
+                    false => self.entries.insert(to.join(rest), m),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn trash(&mut self, path: &Path) -> io::Result<()> {
+        let meta = self.metadata(path)?;
+        if meta.is_dir {
+            return Err(io::Error::other(format!("{:?} is a directory", path)));
+        }
+        self.entries.remove(path);
+        self.trashed.push((path.to_path_buf(), meta));
+
+        Ok(())
+    }
+
+    fn restore(&mut self, path: &Path) -> io::Result<()> {
+        let i = self
+            .trashed
+            .iter()
+            .rposition(|(p, _)| p == path)
+            .ok_or(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{:?} not found in the trash", path),
+            ))?;
+        if self.exists(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} exists", path),
+            ));
+        }
+        let (path, meta) = self.trashed.remove(i);
+        self.add(&path, meta);
+
+        Ok(())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMeta> {
+        self.entries.get(path).copied().ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{:?} not found", path),
+        ))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.metadata(path)?.is_dir {
+            return Err(io::Error::other(format!("{:?} is not a directory", path)));
+        }
+
+        Ok(self
+            .entries
+            .keys()
+            .filter(|p| p.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        match self.metadata(path) {
+            Ok(m) if !m.is_dir => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} is not a directory", path),
+            )),
+            Ok(_) => Ok(()),
+            Err(_) => {
+                self.add_dir(path);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use crate::backend::{clone_or_copy, move_across, FsBackend, MemFs};
+
+    #[test]
+    fn test_clone_or_copy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            tmp.path().join("a"),
+            tmp.path().join("b"),
+            tmp.path().join("c"),
+        );
+        fs::write(&a, "data").unwrap();
+        fs::write(&b, "old").unwrap();
+
+        clone_or_copy(&a, &b).unwrap();
+        assert_eq!(fs::read_to_string(&b).unwrap(), "data");
+        assert!(a.exists());
+
+        let modified = fs::metadata(&a).unwrap().modified().unwrap();
+        move_across(&a, &c).unwrap();
+        assert!(!a.exists());
+        assert_eq!(fs::read_to_string(&c).unwrap(), "data");
+        assert_eq!(fs::metadata(&c).unwrap().modified().unwrap(), modified);
+    }
+
+    #[test]
+    fn test_mem_fs() {
+        let mut fs = MemFs::new();
+        fs.add_file("/d/a b/x.txt", 3);
+        fs.add_file("/d/y.txt", 1);
+        assert!(fs.metadata(Path::new("/d/a b")).unwrap().is_dir);
+        assert_eq!(fs.read_dir(Path::new("/d")).unwrap().len(), 2);
+
+        fs.rename(Path::new("/d/a b"), Path::new("/d/a_b")).unwrap();
+        assert!(!fs.exists(Path::new("/d/a b/x.txt")));
+        assert_eq!(fs.metadata(Path::new("/d/a_b/x.txt")).unwrap().len, 3);
+
+        assert!(fs
+            .rename(Path::new("/d/y.txt"), Path::new("/d/a_b"))
+            .is_err());
+        assert!(fs
+            .rename(Path::new("/d/z.txt"), Path::new("/d/w.txt"))
+            .is_err());
+
+        assert_eq!(
+            fs.files(Path::new("/d"), 2).unwrap(),
+            [Path::new("/d/a_b/x.txt"), Path::new("/d/y.txt")]
+        );
+        assert_eq!(
+            fs.files(Path::new("/d"), 1).unwrap(),
+            [Path::new("/d/y.txt")]
+        );
+
+        fs.trash(Path::new("/d/y.txt")).unwrap();
+        assert!(!fs.exists(Path::new("/d/y.txt")));
+        assert!(fs.trash(Path::new("/d/a_b")).is_err());
+        fs.restore(Path::new("/d/y.txt")).unwrap();
+        assert_eq!(fs.metadata(Path::new("/d/y.txt")).unwrap().len, 1);
+        assert!(fs.restore(Path::new("/d/y.txt")).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use directories::UserDirs;
+use serde_json::Value;
+
+use crate::{utils::default_db_path, Args};
+
+///Actions of 'fdn daemon'
+pub const DAEMON_ACTIONS: [&str; 4] = ["start", "stop", "status", "run"];
+
+///Default config file of the daemon under the config directory
+pub const DAEMON_CONFIG_NAME: &str = "daemon.json";
+
+const DAEMON_PID_NAME: &str = "daemon.pid";
+const DAEMON_LOG_NAME: &str = "daemon.log";
+const DAEMON_SOCKET_NAME: &str = "daemon.sock";
+
+///A directory watched by the daemon with its own depth,excluded paths and fdn options
+#[derive(Debug, Clone, PartialEq)]
+pub struct Root {
+    pub path: PathBuf,
+    pub depth: usize,
+    pub exclude: Vec<String>,
+    ///fdn options applied to the root such as ["--case","lower"]
+    pub options: Vec<String>,
+    ///seconds a new file must stay unchanged before it is normalized
+    pub debounce: u64,
+}
+
+impl Root {
+    ///Command line of the root as if fdn were run on it
+    pub fn args(&self) -> Result<Args> {
+        let mut cmd = vec!["fdn".to_owned()];
+        cmd.extend(self.options.iter().cloned());
+        cmd.extend(["-d".to_owned(), self.depth.to_string()]);
+        for ex in &self.exclude {
+            cmd.extend(["-X".to_owned(), ex.clone()]);
+        }
+
+        Args::try_parse_from(&cmd).map_err(|e| anyhow!("Invalid options of {:?}:{}", self.path, e))
+    }
+}
+
+///Parse daemon config such as {"roots":[{"path":"~/Downloads","depth":1,"exclude":[],
+///"options":["--case","lower"],"debounce":2}]},only path required and '~' expanded to home
+pub fn parse_config(s: &str, home: Option<&Path>) -> Result<Vec<Root>> {
+    let v: Value = serde_json::from_str(s)?;
+    let roots = v
+        .get("roots")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("Daemon config must have a 'roots' array"))?;
+    let strings = |r: &Value, key: &str| -> Vec<String> {
+        r.get(key)
+            .and_then(Value::as_array)
+            .map(|a| {
+                a.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    roots
+        .iter()
+        .map(|r| {
+            let path = r
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("Root of daemon config must have a 'path':{}", r))?;
+            let path = match (path.strip_prefix("~/"), home) {
+                (Some(rest), Some(home)) => home.join(rest),
+                _ => PathBuf::from(path),
+            };
+            let root = Root {
+                path,
+                depth: r.get("depth").and_then(Value::as_u64).unwrap_or(1) as usize,
+                exclude: strings(r, "exclude"),
+                options: strings(r, "options"),
+                debounce: r.get("debounce").and_then(Value::as_u64).unwrap_or(2),
+            };
+            root.args()?;
+
+            Ok(root)
+        })
+        .collect()
+}
+
+///Load daemon config from a file
+pub fn load_config(path: &Path) -> Result<Vec<Root>> {
+    let s = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read daemon config {:?}:{}", path, e))?;
+    let home = UserDirs::new().map(|u| u.home_dir().to_path_buf());
+
+    parse_config(&s, home.as_deref())
+}
+
+///Path of a file of the daemon under the config directory
+fn daemon_file(name: &str) -> Result<PathBuf> {
+    let db = default_db_path()?;
+    Ok(db.parent().unwrap_or(Path::new(".")).join(name))
+}
+
+///Default path of the daemon config
+pub fn config_path() -> Result<PathBuf> {
+    daemon_file(DAEMON_CONFIG_NAME)
+}
+
+///Path of the log the started daemon writes into
+pub fn log_path() -> Result<PathBuf> {
+    daemon_file(DAEMON_LOG_NAME)
+}
+
+///Path of the file keeping the process id of the running daemon
+pub fn pid_path() -> Result<PathBuf> {
+    daemon_file(DAEMON_PID_NAME)
+}
+
+///Path of the unix socket the daemon serves JSON-RPC requests on
+pub fn socket_path() -> Result<PathBuf> {
+    daemon_file(DAEMON_SOCKET_NAME)
+}
+
+///Process id of the running daemon,None if it is not running
+pub fn running_pid() -> Result<Option<u32>> {
+    let Ok(s) = fs::read_to_string(pid_path()?) else {
+        return Ok(None);
+    };
+
+    Ok(s.trim().parse().ok().filter(|pid| alive(*pid)))
+}
+
+///Check a process is alive
+#[cfg(not(windows))]
+fn alive(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+///Check a process is alive
+#[cfg(windows)]
+fn alive(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+}
+
+///Ask a process to terminate,the daemon stops after the scan in progress;killed at once on
+///Windows,where taskkill can only ask processes with windows to close
+pub fn terminate(pid: u32) -> Result<()> {
+    let output = match cfg!(windows) {
+        true => Command::new("taskkill")
+            .args(["/F", "/PID", &pid.to_string()])
+            .output()?,
+        false => Command::new("kill")
+            .args(["-TERM", &pid.to_string()])
+            .output()?,
+    };
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Failed to stop daemon {}:{}",
+            pid,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::daemon::{parse_config, Root};
+
+    #[test]
+    fn test_parse_config() {
+        let roots = parse_config(
+            r#"{"roots":[
+                {"path":"~/Downloads","options":["--case","lower"],"debounce":5},
+                {"path":"/data/in","depth":3,"exclude":["/data/in/keep"]}
+            ]}"#,
+            Some(Path::new("/home/me")),
+        )
+        .unwrap();
+        assert_eq!(
+            roots[0],
+            Root {
+                path: PathBuf::from("/home/me/Downloads"),
+                depth: 1,
+                exclude: Vec::new(),
+                options: vec!["--case".to_owned(), "lower".to_owned()],
+                debounce: 5,
+            }
+        );
+        let args = roots[1].args().unwrap();
+        assert_eq!(args.max_depth, 3);
+        assert_eq!(args.exclude_path, ["/data/in/keep"]);
+        assert_eq!(roots[0].args().unwrap().case, "lower");
+
+        assert!(parse_config(r#"{"roots":[{"depth":1}]}"#, None).is_err());
+        assert!(parse_config(r#"{"roots":[{"path":"a","options":["--nope"]}]}"#, None).is_err());
+        assert!(parse_config("[]", None).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use std::{fmt::Write, sync::LazyLock};
+
+use anyhow::{anyhow, Result};
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveDate,
+};
+use regex::{Captures, Regex};
+
+///English month names and abbreviations,the first three letters are enough to tell them apart
+const MONTH: &str = r"(jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)\.?";
+
+///Years recognized in dates
+const YEAR: &str = r"((?:19|20)\d{2})";
+
+///Separators between parts of dates
+const SEP: &str = r"[\s._/-]";
+
+///Order of the year,month and day groups of a date pattern
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    Ymd,
+    ///day first for '.' and '-',month first for '/' unless the month would be over 12
+    Numeric,
+    ///month name then day
+    Mdy,
+    ///day then month name
+    Dmy,
+}
+
+///Date patterns tried in order,each with the order of its groups
+static PATTERNS: LazyLock<Vec<(Regex, Order)>> = LazyLock::new(|| {
+    [
+        (
+            format!(r"{}(0[1-9]|1[0-2])(0[1-9]|[12]\d|3[01])", YEAR),
+            Order::Ymd,
+        ),
+        (
+            format!(r"{}{}+(\d{{1,2}}){}+(\d{{1,2}})", YEAR, SEP, SEP),
+            Order::Ymd,
+        ),
+        (
+            format!(r"(\d{{1,2}})([./-])(\d{{1,2}})[./-]{}", YEAR),
+            Order::Numeric,
+        ),
+        (
+            format!(
+                r"{}{}+(\d{{1,2}})(?:st|nd|rd|th)?,?{}+{}",
+                MONTH, SEP, SEP, YEAR
+            ),
+            Order::Mdy,
+        ),
+        (
+            format!(
+                r"(\d{{1,2}})(?:st|nd|rd|th)?{}+{},?{}+{}",
+                SEP, MONTH, SEP, YEAR
+            ),
+            Order::Dmy,
+        ),
+    ]
+    .into_iter()
+    .map(|(p, o)| (Regex::new(&format!("(?i){}", p)).unwrap(), o))
+    .collect()
+});
+
+///Check a strftime format of dates,which must not make path separators such as '%D' does
+pub fn check_format(format: &str) -> Result<String> {
+    if StrftimeItems::new(format).any(|i| i == Item::Error) {
+        return Err(anyhow!("Invalid date format:{:?}", format));
+    }
+    let sample = NaiveDate::from_ymd_opt(2000, 1, 2)
+        .and_then(|d| d.and_hms_opt(3, 4, 5))
+        .unwrap_or_default();
+    let mut out = String::new();
+    if write!(out, "{}", sample.format(format)).is_err() {
+        return Err(anyhow!("Invalid date format:{:?}", format));
+    }
+    if out.contains(['/', '\\']) {
+        return Err(anyhow!(
+            "Date format must make a name without path separators:{:?}",
+            format
+        ));
+    }
+
+    Ok(format.to_owned())
+}
+
+///Month of a month name such as "Dec" or "december"
+fn month_of(name: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ]
+    .iter()
+    .position(|m| name.starts_with(m))
+    .map(|i| i as u32 + 1)
+}
+
+///Date of the captures of a pattern,None if it is not a valid date
+fn date_of(caps: &Captures, order: Order) -> Option<NaiveDate> {
+    let num = |i: usize| caps.get(i)?.as_str().parse::<u32>().ok();
+    let (y, m, d) = match order {
+        Order::Ymd => (num(1)?, num(2)?, num(3)?),
+        Order::Numeric => {
+            let (a, b) = (num(1)?, num(3)?);
+            match (&caps[2], a > 12) {
+                ("/", false) => (num(4)?, a, b),
+                _ => (num(4)?, b, a),
+            }
+        }
+        Order::Mdy => (num(3)?, month_of(&caps[1])?, num(2)?),
+        Order::Dmy => (num(3)?, month_of(&caps[2])?, num(1)?),
+    };
+
+    NaiveDate::from_ymd_opt(y as i32, m, d)
+}
+
+///Check the char is of the same kind as the char next to it,both digits or both letters
+fn joined(c: Option<char>, next: Option<char>) -> bool {
+    match (c, next) {
+        (Some(c), Some(n)) => {
+            (c.is_ascii_digit() && n.is_ascii_digit()) || (c.is_alphabetic() && n.is_alphabetic())
+        }
+        _ => false,
+    }
+}
+
+///Rewrite dates in a stem such as "31.12.2023","Dec 31 2023" and "20231231" in the strftime
+///format,dates joined to digits or letters of the same kind and invalid dates kept
+pub fn dates_reformatted(stem: &str, format: &str) -> String {
+    PATTERNS.iter().fold(stem.to_owned(), |s, (re, order)| {
+        let mut out = String::with_capacity(s.len());
+        let mut last = 0;
+        for caps in re.captures_iter(&s) {
+            let m = caps.get(0).unwrap();
+            let (head, tail) = (&s[..m.start()], &s[m.end()..]);
+            if joined(head.chars().next_back(), m.as_str().chars().next())
+                || joined(m.as_str().chars().next_back(), tail.chars().next())
+            {
+                continue;
+            }
+            let Some(date) = date_of(&caps, *order) else {
+                continue;
+            };
+            out.push_str(&s[last..m.start()]);
+            out.push_str(&date.format(format).to_string());
+            last = m.end();
+        }
+        out.push_str(&s[last..]);
+
+        out
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dates::{check_format, dates_reformatted};
+
+    #[test]
+    fn test_dates_reformatted() {
+        let f = "%Y-%m-%d";
+        assert_eq!(dates_reformatted("scan 31.12.2023", f), "scan 2023-12-31");
+        assert_eq!(
+            dates_reformatted("Dec 31 2023 party", f),
+            "2023-12-31 party"
+        );
+        assert_eq!(
+            dates_reformatted("IMG_20231231_1234", f),
+            "IMG_2023-12-31_1234"
+        );
+        assert_eq!(
+            dates_reformatted("December 1st, 2023 and 2 Jan 2024", f),
+            "2023-12-01 and 2024-01-02"
+        );
+        assert_eq!(dates_reformatted("12/31/2023", f), "2023-12-31");
+        assert_eq!(dates_reformatted("2023_1_5", f), "2023-01-05");
+        assert_eq!(dates_reformatted("20231231", "%d.%m.%Y"), "31.12.2023");
+        //invalid dates,longer numbers and words kept
+        assert_eq!(dates_reformatted("20231331", f), "20231331");
+        assert_eq!(dates_reformatted("120231231", f), "120231231");
+        assert_eq!(dates_reformatted("Mayday 3 2023", f), "Mayday 3 2023");
+        assert_eq!(dates_reformatted("31.02.2023", f), "31.02.2023");
+
+        assert!(check_format("%Y%m%d").is_ok());
+        assert!(check_format("%Q").is_err());
+        assert!(check_format("%Y/%m").is_err());
+        assert!(check_format("%D").is_err());
+        assert!(check_format("%Y\\%m").is_err());
+    }
+}
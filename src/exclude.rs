@@ -0,0 +1,190 @@
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use regex::Regex;
+use tracing::warn;
+
+use crate::canonical_path;
+
+///How an exclude entry matches paths walked from the root
+#[derive(Debug)]
+enum Matcher {
+    ///the path and everything under it
+    Path(PathBuf),
+    ///a glob with '/' matching a path relative to the root or any of its ancestors
+    Glob(Regex),
+    ///a glob without '/' matching a name at any depth
+    Name(Regex),
+}
+
+///Exclude entries of a scan resolved against its root,remembering which ones matched
+#[derive(Debug, Default)]
+pub struct Excludes {
+    root: PathBuf,
+    entries: Vec<(String, Matcher, Cell<bool>)>,
+}
+
+impl Excludes {
+    ///Resolve entries against the root as given:a path absolute or relative to the root,or a glob
+    ///of '*','?' and '**' relative to it;paths outside the root kept canonical so that an ancestor
+    ///of the root excludes all of it
+    pub fn new(root: &Path, entries: &[String]) -> Result<Self> {
+        let c_root = canonical_path(root);
+        let mut resolved = Vec::new();
+        for e in entries {
+            let matcher = if e.contains(['*', '?']) {
+                let glob = e.trim_start_matches("./").trim_end_matches('/');
+                match glob.contains('/') {
+                    true => Matcher::Glob(glob_regex(glob)?),
+                    false => Matcher::Name(glob_regex(glob)?),
+                }
+            } else {
+                let c_exc = canonical_path(&root.join(e));
+                match c_exc.strip_prefix(&c_root) {
+                    Ok(rest) if rest.as_os_str().is_empty() => Matcher::Path(root.to_path_buf()),
+                    Ok(rest) => Matcher::Path(root.join(rest)),
+                    Err(_) => Matcher::Path(c_exc),
+                }
+            };
+            resolved.push((e.clone(), matcher, Cell::new(false)));
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            entries: resolved,
+        })
+    }
+
+    ///Check a path walked from the root is excluded by any entry,marking the ones matching it
+    pub fn excluded(&self, path: &Path) -> bool {
+        self.excluded_by(path).is_some()
+    }
+
+    ///The first entry excluding a path walked from the root,marking all the ones matching it
+    pub fn excluded_by(&self, path: &Path) -> Option<&str> {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        let mut hit = None;
+        for (e, matcher, matched) in &self.entries {
+            let is_match = match matcher {
+                Matcher::Path(p) => path.starts_with(p),
+                Matcher::Glob(re) => rel
+                    .ancestors()
+                    .filter(|a| !a.as_os_str().is_empty())
+                    .any(|a| re.is_match(&slashed(a))),
+                Matcher::Name(re) => rel
+                    .components()
+                    .any(|c| re.is_match(&c.as_os_str().to_string_lossy())),
+            };
+            if is_match {
+                matched.set(true);
+                hit = hit.or(Some(e.as_str()));
+            }
+        }
+
+        hit
+    }
+
+    ///Entries which matched nothing so far
+    pub fn unmatched(&self) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(_, _, matched)| !matched.get())
+            .map(|(e, _, _)| e.as_str())
+            .collect()
+    }
+
+    ///Warn of entries which matched nothing under the root
+    pub fn warn_unmatched(&self) {
+        warn_unmatched(std::slice::from_ref(self));
+    }
+}
+
+///Warn once of entries which matched nothing under any root of a scan of several roots
+pub fn warn_unmatched(excludes: &[Excludes]) {
+    let Some(first) = excludes.first() else {
+        return;
+    };
+    let roots: Vec<_> = excludes.iter().map(|x| &x.root).collect();
+    for e in first.unmatched() {
+        if excludes.iter().all(|x| x.unmatched().contains(&e)) {
+            warn!("Exclude {:?} matched nothing under {:?}", e, roots);
+        }
+    }
+}
+
+///Path relative to the root with components joined by '/'
+fn slashed(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+///Anchored regex of a glob,'**' for any path,'*' for any chars of a name and '?' for a char
+fn glob_regex(glob: &str) -> Result<Regex> {
+    let mut re = String::from("^");
+    let mut rest = glob;
+    while let Some(c) = rest.chars().next() {
+        let (part, len) = match rest {
+            r if r.starts_with("**/") => ("(?:.*/)?".to_owned(), 3),
+            r if r.starts_with("**") => (".*".to_owned(), 2),
+            _ if c == '*' => ("[^/]*".to_owned(), 1),
+            _ if c == '?' => ("[^/]".to_owned(), 1),
+            _ => (regex::escape(&c.to_string()), c.len_utf8()),
+        };
+        re.push_str(&part);
+        rest = &rest[len..];
+    }
+    re.push('$');
+
+    Ok(Regex::new(&re)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use crate::{
+        canonical_path,
+        exclude::{glob_regex, Excludes},
+    };
+
+    #[test]
+    fn test_excludes() {
+        let re = glob_regex("**/cache/*.tmp").unwrap();
+        assert!(re.is_match("cache/a.tmp"));
+        assert!(re.is_match("x/y/cache/a.tmp"));
+        assert!(!re.is_match("cache/sub/a.tmp"));
+        assert!(glob_regex("a?.txt").unwrap().is_match("ab.txt"));
+        assert!(!glob_regex("a?.txt").unwrap().is_match("a/.txt"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("sub/deep")).unwrap();
+        let entries = ["sub/deep", "*.bak", "**/build", "missing", "/elsewhere"].map(str::to_owned);
+        let exs = Excludes::new(root, &entries).unwrap();
+        assert!(exs.excluded(&root.join("sub/deep/a.txt")));
+        assert!(!exs.excluded(&root.join("sub/a.txt")));
+        assert!(exs.excluded(&root.join("sub/a.bak")));
+        assert_eq!(
+            exs.excluded_by(&root.join("sub/deep/a.bak")),
+            Some("sub/deep")
+        );
+        assert!(exs.excluded(&root.join("x/build/y/a.txt")));
+        assert_eq!(exs.unmatched(), ["missing", "/elsewhere"]);
+
+        //an ancestor of the root outside it excludes all of the root
+        let c_root = canonical_path(root);
+        let parent = c_root.parent().unwrap().to_string_lossy().into_owned();
+        let exs = Excludes::new(&c_root.join("sub"), &[parent]).unwrap();
+        assert!(exs.excluded(&c_root.join("sub/a.txt")));
+        assert!(exs.unmatched().is_empty());
+
+        let exs = Excludes::new(Path::new("/r"), &[]).unwrap();
+        assert!(!exs.excluded(Path::new("/r/a")));
+        assert!(exs.unmatched().is_empty());
+    }
+}
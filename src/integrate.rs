@@ -0,0 +1,339 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+///Actions of 'fdn integrate'
+pub const INTEGRATE_ACTIONS: [&str; 2] = ["install", "remove"];
+
+///File managers 'fdn integrate' installs a context menu entry into
+pub const INTEGRATIONS: [&str; 4] = ["nautilus", "dolphin", "finder", "sendto"];
+
+///Title of the context menu entry
+pub const MENU_TITLE: &str = "Normalize with fdn";
+
+///A file of the glue of a context menu entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glue {
+    pub path: PathBuf,
+    pub content: String,
+    pub executable: bool,
+}
+
+///Glue running fdn with the arguments on the files selected in a file manager:a Nautilus script,
+///a Dolphin service menu,a Finder Quick Action or a Windows 'Send to' entry;data_dir is the
+///per-user data directory of the OS,'~/.local/share' or '%APPDATA%';files passed after '--' so
+///that a name starting with '-' is not read as an option
+pub fn glue(
+    target: &str,
+    exe: &Path,
+    argv: &[String],
+    data_dir: &Path,
+    home: &Path,
+) -> Result<Vec<Glue>> {
+    let exe = exe.to_string_lossy();
+    match target {
+        "nautilus" => {
+            let cmd: Vec<_> = [exe.as_ref()]
+                .into_iter()
+                .chain(argv.iter().map(String::as_str))
+                .map(sh_quoted)
+                .collect();
+            Ok(vec![Glue {
+                path: data_dir.join("nautilus/scripts").join(MENU_TITLE),
+                content: format!(
+                    "#!/bin/sh\n#{}:selected files passed as arguments\nexec {} -- \"$@\"\n",
+                    MENU_TITLE,
+                    cmd.join(" ")
+                ),
+                executable: true,
+            }])
+        }
+        "dolphin" => {
+            let cmd: Vec<_> = [exe.as_ref()]
+                .into_iter()
+                .chain(argv.iter().map(String::as_str))
+                .map(desktop_quoted)
+                .collect();
+            Ok(vec![Glue {
+                path: data_dir.join("kio/servicemenus/fdn.desktop"),
+                content: format!(
+                    "[Desktop Entry]\nType=Service\nMimeType=all/all;\nActions=normalize\n\
+                     X-KDE-Priority=TopLevel\n\n[Desktop Action normalize]\nName={}\n\
+                     Icon=edit-rename\nExec={} -- %F\n",
+                    MENU_TITLE,
+                    cmd.join(" ")
+                ),
+                executable: true,
+            }])
+        }
+        "finder" => {
+            let cmd: Vec<_> = [exe.as_ref()]
+                .into_iter()
+                .chain(argv.iter().map(String::as_str))
+                .map(sh_quoted)
+                .collect();
+            let dir = home
+                .join("Library/Services")
+                .join(format!("{}.workflow", MENU_TITLE))
+                .join("Contents");
+            Ok(vec![
+                Glue {
+                    path: dir.join("Info.plist"),
+                    content: INFO_PLIST.replace("{title}", MENU_TITLE),
+                    executable: false,
+                },
+                Glue {
+                    path: dir.join("document.wflow"),
+                    content: WFLOW.replace(
+                        "{command}",
+                        &xml_escaped(&format!("{} -- \"$@\"", cmd.join(" "))),
+                    ),
+                    executable: false,
+                },
+            ])
+        }
+        "sendto" => {
+            let cmd = [exe.as_ref()]
+                .into_iter()
+                .chain(argv.iter().map(String::as_str))
+                .map(cmd_quoted)
+                .collect::<Result<Vec<_>>>()?;
+            Ok(vec![Glue {
+                path: data_dir
+                    .join("Microsoft/Windows/SendTo")
+                    .join(format!("{}.cmd", MENU_TITLE)),
+                content: format!("@echo off\r\n{} -- %*\r\npause\r\n", cmd.join(" ")),
+                executable: false,
+            }])
+        }
+        _ => Err(anyhow!(
+            "File manager must be one of {:?}:{:?}",
+            INTEGRATIONS,
+            target
+        )),
+    }
+}
+
+///Check a file manager runs on this OS,Finder on macOS,'Send to' on Windows and the others on
+///other systems
+pub fn native(target: &str) -> bool {
+    let os = std::env::consts::OS;
+    match target {
+        "finder" => os == "macos",
+        "sendto" => os == "windows",
+        _ => os != "macos" && os != "windows",
+    }
+}
+
+///Argument single-quoted for sh
+fn sh_quoted(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+///Argument double-quoted for the Exec key of a desktop entry,'%' doubled
+fn desktop_quoted(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', r"\\\\")
+        .replace('"', r#"\\""#)
+        .replace('`', r"\\`")
+        .replace('$', r"\\$")
+        .replace('%', "%%");
+
+    format!("\"{}\"", escaped)
+}
+
+///Argument double-quoted for a batch file,'%' doubled so that it is not expanded;a '"' refused
+///since cmd has no way to escape it inside quotes
+fn cmd_quoted(arg: &str) -> Result<String> {
+    if arg.contains('"') {
+        return Err(anyhow!(
+            "Argument of a 'Send to' entry can not contain '\"':{:?}",
+            arg
+        ));
+    }
+
+    Ok(format!("\"{}\"", arg.replace('%', "%%")))
+}
+
+///Text escaped for XML
+fn xml_escaped(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+///Info.plist of the Quick Action,offered for files and folders in Finder
+const INFO_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>{title}</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>public.item</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#;
+
+///Workflow of the Quick Action,one 'Run Shell Script' action given the files as arguments
+const WFLOW: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>521</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>AMParameterProperties</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<dict/>
+					<key>inputMethod</key>
+					<dict/>
+					<key>shell</key>
+					<dict/>
+				</dict>
+				<key>AMProvides</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{command}</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/sh</string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CFBundleVersion</key>
+				<string>2.0.3</string>
+				<key>Class Name</key>
+				<string>RunShellScriptAction</string>
+				<key>InputUUID</key>
+				<string>0F5A2C1E-6C3B-4B8E-9F0A-1D2E3F4A5B6C</string>
+				<key>OutputUUID</key>
+				<string>7A8B9C0D-1E2F-4A3B-8C4D-5E6F7A8B9C0D</string>
+				<key>UUID</key>
+				<string>3C4D5E6F-7A8B-4C9D-0E1F-2A3B4C5D6E7F</string>
+			</dict>
+		</dict>
+	</array>
+	<key>connectors</key>
+	<dict/>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceApplicationBundleID</key>
+		<string>com.apple.finder</string>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::integrate::{cmd_quoted, desktop_quoted, glue, sh_quoted};
+
+    #[test]
+    fn test_glue() {
+        let (exe, data, home) = (
+            Path::new("/opt/my fdn/fdn"),
+            Path::new("/h/.local/share"),
+            Path::new("/h"),
+        );
+        let argv = ["-i".to_owned(), "--preset".to_owned(), "tv".to_owned()];
+
+        let g = glue("nautilus", exe, &argv, data, home).unwrap();
+        assert_eq!(
+            g[0].path,
+            Path::new("/h/.local/share/nautilus/scripts/Normalize with fdn")
+        );
+        assert!(g[0]
+            .content
+            .ends_with("exec '/opt/my fdn/fdn' '-i' '--preset' 'tv' -- \"$@\"\n"));
+        assert!(g[0].executable);
+
+        let g = glue("dolphin", exe, &argv, data, home).unwrap();
+        assert!(g[0]
+            .content
+            .contains("Exec=\"/opt/my fdn/fdn\" \"-i\" \"--preset\" \"tv\" -- %F\n"));
+
+        let g = glue("finder", exe, &argv, data, home).unwrap();
+        assert_eq!(g.len(), 2);
+        assert!(g[1]
+            .path
+            .starts_with("/h/Library/Services/Normalize with fdn.workflow"));
+        assert!(g[1].content.contains("-- &quot;$@&quot;"));
+
+        let g = glue("sendto", exe, &argv, data, home).unwrap();
+        assert!(g[0]
+            .content
+            .contains("\"/opt/my fdn/fdn\" \"-i\" \"--preset\" \"tv\" -- %*"));
+        let quoted = ["--date-prefix".to_owned(), "%Y\"".to_owned()];
+        assert!(glue("sendto", exe, &quoted, data, home).is_err());
+        assert!(glue("explorer", exe, &argv, data, home).is_err());
+
+        assert_eq!(sh_quoted("it's"), r"'it'\''s'");
+        assert_eq!(desktop_quoted("100%$"), r#""100%%\\$""#);
+        assert_eq!(cmd_quoted("%Y-%m").unwrap(), r#""%%Y-%%m""#);
+    }
+}
@@ -1,30 +1,226 @@
 use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
     cmp::Ordering,
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env,
     ffi::OsStr,
-    fs,
-    path::{Path, PathBuf},
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, IsTerminal, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf, MAIN_SEPARATOR},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        mpsc, Arc, LazyLock,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, Result};
+use chrono::Local;
 use clap::{ArgAction, Parser, Subcommand};
+use daemon::DAEMON_ACTIONS;
+use dates::dates_reformatted;
+use deunicode::deunicode_char;
+use directories::{BaseDirs, UserDirs};
+use integrate::{glue, native, INTEGRATE_ACTIONS, INTEGRATIONS};
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecursiveMode,
+};
+use rayon::{prelude::*, ThreadPoolBuilder};
 use regex::Regex;
 use rusqlite::Connection;
 use rustc_serialize::hex::FromHex;
+use script::Script;
+use serde_json::json;
+use serve::{read_request, write_response, Request, DEFAULT_LISTEN};
+use tracing::warn;
+use unicode_normalization::UnicodeNormalization;
 use walkdir::WalkDir;
+use xxhash_rust::xxh3::xxh3_64;
 
+use exclude::Excludes;
+use packs::{load_manifest, pack_tag, parse_pack, Pack, BUILTIN_PACKS, PACK_ACTIONS};
+use presets::{
+    half_width, ids_stripped, plain_punctuation, plex_layout, tv_episode, EBOOK_TEMPLATE,
+    EPISODE_AT, PRESETS,
+};
+use report::Report;
+use scope::{in_scope, parse_scope};
+use template::hash::hash_file;
 use utils::{
-    db::{insert_term_word, retrieve_records, retrieve_separators, retrieve_to_sep_words},
-    decrypted, delete_records, delete_term_word, delete_to_sep_word, encrypted, hashed_name,
-    insert_record, insert_to_sep_word, open_db, retrieve_term_words, s_compare,
+    color_enabled,
+    db::{
+        backup_db, copy_config_row, delete_alias, delete_config_row, delete_pack, delete_tag_rows,
+        diff_config_rows, insert_term_word, kind_table, open_other_db, open_rules_db,
+        release_record, restore_db, retrieve_alias, retrieve_aliases, retrieve_config_rows,
+        retrieve_packs, retrieve_records_by_hash, retrieve_same_record, retrieve_separators,
+        retrieve_to_sep_words, upsert_alias, upsert_pack, CONFIG_DIR_NAME, DB_PARTS,
+    },
+    decrypted, default_db_path, delete_acronym, delete_checkpoint, delete_checkpoint_done,
+    delete_ext_alias, delete_regex_rule, delete_setting, delete_small_word, delete_stopword,
+    delete_term_word, delete_to_sep_word, display_width, encrypted, hashed_name, insert_acronym,
+    insert_checkpoint, insert_operation, insert_record, insert_regex_rule, insert_small_word,
+    insert_stopword, insert_to_sep_word, latest_operation_id, open_db, purge_term_words,
+    restore_term_word, retrieve_acronyms, retrieve_checkpoints, retrieve_deleted_term_words,
+    retrieve_ext_aliases, retrieve_operations, retrieve_pending_paths, retrieve_regex_rules,
+    retrieve_rule_stats, retrieve_session_operations, retrieve_setting, retrieve_settings,
+    retrieve_small_words, retrieve_stopwords, retrieve_term_words, retrieve_toggled_rows,
+    s_compare, schema_version, truncate_width, update_checkpoint_path, update_config_row,
+    update_enabled, update_tag, update_tag_enabled, upsert_ext_alias, upsert_rule_hits,
+    upsert_setting, validators, violations, SCHEMA_VERSION,
 };
+use watch::{is_temp, Stamp, Watcher};
+use wordsplit::split_words;
 
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod backend;
+pub mod daemon;
+pub mod dates;
+pub mod exclude;
+pub mod integrate;
+pub mod magic;
+pub mod mapping;
+#[cfg(feature = "mtp")]
+pub mod mtp;
+pub mod packs;
+pub mod presets;
+pub mod renamer;
+pub mod report;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod scope;
+pub mod script;
+pub mod serve;
+#[cfg(feature = "sftp")]
+pub mod sftp;
+pub mod template;
+pub mod tui;
 pub mod utils;
+pub mod watch;
+#[cfg(feature = "webdav")]
+pub mod webdav;
+pub mod wordsplit;
+
+pub use backend::{clone_or_copy, FsBackend, FsMeta, MemFs, RealFs};
+pub use renamer::{DatePrefix, RenameAction, RenamePlan, Renamer};
+pub use template::{Provider, Template};
+
+///Keys of settings which can be set by config
+pub const SETTING_KEYS: [&str; 4] = ["log_file", "purge_days", "rule_stats", "skip_files"];
+
+///Keys of '--sort','name' of older versions read as 'natural'
+pub const SORT_KEYS: [&str; 4] = ["natural", "lexical", "mtime", "size"];
+
+///Policies for names not valid UTF-8 of '--non-utf8'
+pub const NON_UTF8_POLICIES: [&str; 3] = ["skip", "lossy", "error"];
+
+///Policies for a target taken by another file of '--on-conflict'
+pub const ON_CONFLICTS: [&str; 2] = ["skip", "overwrite"];
+
+///Actions of 'fdn alias'
+pub const ALIAS_ACTIONS: [&str; 4] = ["save", "run", "list", "delete"];
+
+///Time between checks of the notifications of the daemon's roots and of its config
+const DAEMON_INTERVAL: Duration = Duration::from_secs(1);
+
+///Time between checks of the notifications of 'fdn watch'
+const WATCH_TICK: Duration = Duration::from_millis(200);
+
+///Latest operations looked through for the rename to undo
+const RPC_UNDO_SCAN: usize = 1000;
+
+///Time between polls of the REST API listener for connections
+const SERVE_POLL: Duration = Duration::from_millis(50);
+
+///Time a client of the REST API has to send its request
+const SERVE_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+///Stages of the normalization pipeline in order
+pub const STAGES: [&str; 24] = [
+    "decode",
+    "invisibles",
+    "unicode-normalize",
+    "emoji",
+    "half-width",
+    "pinyin",
+    "ascii",
+    "presets",
+    "strip-brackets",
+    "split-camel",
+    "split-words",
+    "stopwords",
+    "to-sep-words",
+    "term-words",
+    "regex-rules",
+    "dates",
+    "versions",
+    "pad-numbers",
+    "remove-continuous-sep",
+    "remove-dup-words",
+    "trim-sep",
+    "case",
+    "extension",
+    "script",
+];
+
+///Unicode normalization forms of stems
+pub const UNICODE_FORMS: [&str; 3] = ["nfc", "nfd", "nfkc"];
+
+///Encodings of stems which can be decoded
+pub const DECODINGS: [&str; 2] = ["url", "html"];
+
+///Pairs of brackets whose segments can be stripped
+pub const BRACKET_PAIRS: [&str; 9] = [
+    "[]", "()", "{}", "<>", "【】", "（）", "《》", "「」", "〔〕",
+];
+
+///Cases of stems,'title-smart' keeps small words lowercase and acronyms as configured
+pub const CASES: [&str; 5] = ["keep", "lower", "upper", "title", "title-smart"];
+
+///Policies of emoji in stems
+pub const EMOJI_POLICIES: [&str; 3] = ["keep", "strip", "name"];
+
+///Cases of pinyin syllables
+pub const PINYIN_CASES: [&str; 2] = ["lower", "title"];
+
+///Cases which extensions are changed into
+pub const EXT_CASES: [&str; 3] = ["keep", "lower", "upper"];
+
+///Extensions of more than one part kept whole by default
+pub const COMPOUND_EXTS: [&str; 8] = [
+    "tar.gz", "tar.bz2", "tar.xz", "tar.zst", "tar.lz", "d.ts", "d.mts", "d.cts",
+];
+
+///OS junk files never renamed if setting 'skip_files' not set,'*' for any chars
+pub const DEFAULT_SKIPS: [&str; 4] = [".DS_Store", "Thumbs.db", "desktop.ini", "._*"];
+
+///Days to keep deleted term words before purging if setting 'purge_days' not set
+const DEFAULT_PURGE_DAYS: u32 = 30;
+
+///Passes of a replacing loop before giving up on rules which never converge
+const MAX_PASSES: usize = 64;
+
+///Lock file created in the root of a directory tree during an in-place run
+pub const LOCK_FILE_NAME: &str = ".fdn.lock";
+
+///Time an in-place run waits for a tree locked by another run unless told otherwise
+const LOCK_WAIT: Duration = Duration::from_secs(10);
+
+///Time between tries to lock a tree locked by another run
+const LOCK_POLL: Duration = Duration::from_millis(100);
+
+///Set by SIGINT or SIGTERM,a run stops after the current rename
+static ABORTED: AtomicBool = AtomicBool::new(false);
 
 #[derive(Debug, Parser, Clone)]
 #[command(author,about="File and Directory Names",long_about=None)]
 pub struct Args {
-    ///file path
+    ///files or directories mixed in one plan,directories walked to max depth,'-f' path if none
     #[arg(required = false)]
     pub files: Option<Vec<String>>,
 
@@ -44,11 +240,28 @@ pub struct Args {
     #[arg(short = 't', long, default_value = "f")]
     pub filetype: String,
 
-    ///not ignore hidden file
-    #[arg(short = 'I', long, default_value = "false")]
+    ///'skip' hidden files and files in hidden directories under input paths,'include' them or
+    ///process 'only' them
+    #[arg(long, default_value = "skip", value_parser = ["skip", "include", "only"])]
+    pub hidden: String,
+
+    ///same as '--hidden include',kept for old scripts
+    #[arg(
+        short = 'I',
+        long,
+        default_value = "false",
+        hide = true,
+        conflicts_with = "hidden"
+    )]
     not_ignore_hidden: bool,
 
-    ///exclude file or directory
+    ///not skip OS junk files such as '.DS_Store','Thumbs.db','desktop.ini' and '._*',or those
+    ///of setting 'skip_files'
+    #[arg(long, default_value = "false")]
+    pub no_default_skips: bool,
+
+    ///exclude a file or directory by a path absolute or relative to the scanned directory,or a
+    ///glob such as '**/build' or '*.bak',warned if matching nothing
     #[arg(short = 'X', long, default_values_t = Vec::<String>::new(), action = ArgAction::Append)]
     pub exclude_path: Vec<String>,
 
@@ -60,18 +273,449 @@ pub struct Args {
     #[arg(short = 'R', long, default_value = "false")]
     pub reverse_chainly: bool,
 
+    ///warn when a name restored by reverse change violates the active rules or validators
+    #[arg(long, default_value = "false")]
+    pub revalidate: bool,
+
+    ///record directories of renamed files encrypted by their names,so that reverse change skips a
+    ///file moved to another directory
+    #[arg(long, default_value = "false")]
+    pub record_dir: bool,
+
+    ///reverse change a file moved out of the directory recorded by '--record-dir'
+    #[arg(long, default_value = "false")]
+    pub accept_relocation: bool,
+
+    ///'skip' a rename whose target is taken by another file or 'overwrite' it,built with feature
+    ///'trash',the other file moved to the trash of the OS first and logged as 'trashed' so that a
+    ///rollback or reversing the rename restores it
+    #[arg(long, default_value = "skip", value_parser = ON_CONFLICTS)]
+    pub on_conflict: String,
+
+    ///rename files of a storage backend instead of local files,such as 's3://bucket/prefix' for
+    ///object keys under the prefix,built with feature 's3','sftp://user@host/path' for files of a
+    ///server over SFTP,built with feature 'sftp','davs://host/path' for files of a WebDAV share
+    ///over https,'dav://' over http,built with feature 'webdav',or 'mtp://device/storage/dir' for
+    ///files of a phone or camera,built with feature 'mtp'
+    #[arg(long, value_name = "URL")]
+    pub backend: Option<String>,
+
     ///align origin and edited
     #[arg(short = 'a', long, default_value = "false")]
     align: bool,
 
+    ///colorize output,'auto','always' or 'never'
+    #[arg(long, default_value = "auto", value_parser = ["auto", "always", "never"])]
+    pub color: String,
+
+    ///output format,'plain','table' or 'json'
+    #[arg(long, default_value = "plain", value_parser = ["plain", "table", "json"])]
+    pub format: String,
+
+    ///sort changes and group them by directory
+    #[arg(short = 'g', long, default_value = "false")]
+    pub group_by_dir: bool,
+
+    ///show the rules which fired for every rename in order,not for '-r' or 'fdn clean' whose
+    ///targets are not planned by rules alone
+    #[arg(long, default_value = "false", conflicts_with = "reverse")]
+    pub explain: bool,
+
+    ///table width used by table format
+    #[arg(short = 'w', long, default_value = "100")]
+    pub width: usize,
+
     ///print version
     #[arg(short = 'V', long)]
     pub version: bool,
 
+    ///validate targets before apply,'portable-charset','max-length=N','regex=RE' or 'policy=FILE'
+    #[arg(long, action = ArgAction::Append)]
+    pub validate: Vec<String>,
+
+    ///commit records to database every n applied renames so that an interrupted run keeps them,0
+    ///for only once at the end
+    #[arg(long, default_value = "100")]
+    pub commit_every: usize,
+
+    ///process at most n renames in this run,paths are sorted for a deterministic order and the
+    ///rest are left for 'fdn resume' or later runs
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    ///wait for another fdn run on the same or a nested directory tree to finish however long it
+    ///takes,instead of at most 10 seconds
+    #[arg(long, default_value = "false", conflicts_with = "fail_if_locked")]
+    pub wait: bool,
+
+    ///fail at once if another fdn run holds the same or a nested directory tree,instead of
+    ///waiting at most 10 seconds for it
+    #[arg(long, default_value = "false")]
+    pub fail_if_locked: bool,
+
+    ///roll back renames already applied in this session if interrupted by a signal
+    #[arg(long, default_value = "false")]
+    pub rollback_on_abort: bool,
+
+    ///record a file failing to rename as failed and go on with the others instead of aborting,
+    ///the run still exiting nonzero
+    #[arg(long, default_value = "false")]
+    pub keep_going: bool,
+
+    ///policy for a name not valid UTF-8,'skip' to report it as skipped,'lossy' to rename it by
+    ///its decodable part with invalid bytes dropped,not recorded for undo,or 'error' to abort;
+    ///reverse runs skip it unless 'error',as it has nothing to reverse
+    #[arg(long, default_value = "skip", value_parser = NON_UTF8_POLICIES)]
+    pub non_utf8: String,
+
+    ///report of files at the end of a run,'text' for skipped and failed files with numbers of
+    ///files by outcome if any,'json' for outcomes of all files or 'none'
+    #[arg(long, default_value = "text", value_parser = ["text", "json", "none"])]
+    pub report: String,
+
+    ///append applied renames to the log file,default from config setting 'log_file'
+    #[arg(long)]
+    pub log_file: Option<String>,
+
+    ///number of threads planning names and applying renames in parallel,directories are always
+    ///processed serially
+    #[arg(short = 'j', long, default_value = "1")]
+    pub jobs: usize,
+
+    ///number files of every directory in order,a number of previous numbering is replaced
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["limit", "reverse", "reverse_chainly"]
+    )]
+    pub number: bool,
+
+    ///where sequence numbers are put,'suffix' or 'prefix'
+    #[arg(long, default_value = "suffix", value_parser = ["suffix", "prefix"])]
+    pub number_at: String,
+
+    ///first sequence number
+    #[arg(long, default_value = "1")]
+    pub start: usize,
+
+    ///min digits of sequence numbers,padded with zeros
+    #[arg(long, default_value = "3")]
+    pub pad: usize,
+
+    ///order of the plan,numbering and directories,'natural' for numbers in names by value such as
+    ///'file2' before 'file10','lexical','mtime' or 'size',directories always after their contents
+    #[arg(long, default_value = "natural", value_parser = sort_key)]
+    pub sort: String,
+
+    ///prepend modification dates of files to names in strftime format,names already starting
+    ///with a date skipped
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "%Y-%m-%d",
+        value_parser = |f: &str| DatePrefix::new(f, false).map(|d| d.format)
+    )]
+    pub date_prefix: Option<String>,
+
+    ///prefix the creation date instead of the modification date
+    #[arg(long, default_value = "false", requires = "date_prefix")]
+    pub created: bool,
+
+    ///render names of regular files by template,such as "{tag.artist} - {tag.track:02} -
+    ///{tag.title}.{ext}",names with missing values only normalized
+    #[arg(long, value_parser = |t: &str| Template::parse(t))]
+    pub template: Option<Template>,
+
+    ///enable built-in rule packs,'tv' for TV episodes such as 'S01E02','1x02' or 'Season 1
+    ///Episode 2','plex-movie' and 'plex-tv' move files into 'Title (Year)/Title (Year).ext' and
+    ///'Show/Season 01/Show - S01E01 - Episode.ext' with directories created when needed,'ebook'
+    ///renders 'Author - Title (Year).ext' from metadata of epub and pdf files unless '--template'
+    ///is given,built with feature 'ebook-meta','cjk' converts full-width chars such as
+    ///'ＡＢＣ１２３（）' and ideographic spaces into half-width ones,'punctuation' converts
+    ///typographic quotes,dashes,ellipses and non-breaking spaces into ASCII ones,'strip-ids'
+    ///removes trailing UUIDs,hex hashes and random download suffixes such as
+    ///'report_f81d4fae-7dec-11d0-a765-00a0c91e6bf6'
+    #[arg(long, action = ArgAction::Append, value_parser = PRESETS)]
+    pub preset: Vec<String>,
+
+    ///where canonical episode tags of preset 'tv' are put,'keep','start' or 'end'
+    #[arg(long, default_value = "keep", value_parser = EPISODE_AT)]
+    pub episode_at: String,
+
+    ///case of extensions after aliases from config such as 'jpeg:jpg' applied,'keep','lower' or
+    ///'upper'
+    #[arg(long, default_value = "keep", value_parser = EXT_CASES)]
+    pub ext_case: String,
+
+    ///decode stems before rules,'url' for percent-encoding such as '%20' and '%C3%A9','html' for
+    ///entities such as '&amp;' and '&#8211;'
+    #[arg(long, value_delimiter = ',', value_parser = DECODINGS)]
+    pub decode: Vec<String>,
+
+    ///keep invisible and control chars of stems such as zero-width spaces,byte order marks and
+    ///bidi controls,which are removed before any other rule by default
+    #[arg(long, default_value = "false")]
+    pub keep_invisibles: bool,
+
+    ///normalize stems into the unicode form before rules,'nfc','nfd' or 'nfkc',such as
+    ///decomposed names from macOS into composed ones by 'nfc'
+    #[arg(long, value_parser = UNICODE_FORMS)]
+    pub unicode_normalize: Option<String>,
+
+    ///remove segments in the pairs of brackets such as '[1080p]','(proper)' and '【字幕组】',
+    ///given as '--strip-brackets '[]' '()'' before '--' or other options
+    #[arg(long, num_args = 1.., action = ArgAction::Append, value_parser = BRACKET_PAIRS)]
+    pub strip_brackets: Vec<String>,
+
+    ///keep bracketed segments which look like years such as '(2019)' when stripping brackets
+    #[arg(long, default_value = "false", requires = "strip_brackets")]
+    pub keep_years: bool,
+
+    ///insert the separator at lower to upper case transitions such as 'MyProjectReport' into
+    ///'My_Project_Report',acronyms from config such as 'PDF' kept whole and split from the next
+    ///word such as 'PDFScholar' into 'PDF_Scholar'
+    #[arg(long, default_value = "false")]
+    pub split_camel: bool,
+
+    ///split long runs of letters without separators such as 'annualreportfinalversion' into
+    ///words of an embedded English dictionary joined by the separator,runs which are not all
+    ///dictionary words kept
+    #[arg(long, default_value = "false")]
+    pub split_words: bool,
+
+    ///languages of stopwords from config removed besides those of all languages,such as 'de'
+    #[arg(long, action = ArgAction::Append)]
+    pub stopword_lang: Vec<String>,
+
+    ///rewrite dates in stems such as '31.12.2023','Dec 31 2023' and '20231231' in strftime
+    ///format,'%Y-%m-%d' if no format given,'/' read as month first and '.' or '-' as day first
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "%Y-%m-%d",
+        value_parser = dates::check_format
+    )]
+    pub date_format: Option<String>,
+
+    ///rewrite version tokens of stems such as 'v1.2','V02','ver_3' and 'version 4' into 'v' and
+    ///the number of parts,3 if not given,such as 'v1.2.0'
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "3",
+        value_parser = clap::value_parser!(u8).range(1..=9)
+    )]
+    pub normalize_versions: Option<u8>,
+
+    ///zero-pad every part of normalized versions to the width,such as 'v01.02.00' by 2
+    #[arg(long, default_value = "1", requires = "normalize_versions",
+        value_parser = clap::value_parser!(u8).range(1..=9))]
+    pub version_pad: u8,
+
+    ///zero-pad standalone integers of stems to the digits such as 'track 3' into 'track 003' by 3,
+    ///numbers of dates,versions or joined to letters kept,restored by '-r' as other renames
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=20))]
+    pub pad_numbers: Option<u8>,
+
+    ///case of stems,'keep','lower','upper','title' or 'title-smart' which capitalizes words but
+    ///small words from config such as 'of' and 'the' unless first or last,acronyms from config
+    ///such as 'PDF' and mixed-case words such as 'iPhone' kept,so 'the_lord_of_the_rings'
+    ///becomes 'The_Lord_of_the_Rings'
+    #[arg(long, default_value = "keep", value_parser = CASES)]
+    pub case: String,
+
+    ///collapse words repeated right after themselves in any case such as 'final_final_report' into
+    ///one,words of digits only kept
+    #[arg(long, default_value = "false")]
+    pub dedup_words: bool,
+
+    ///what to do with emoji of stems,'keep','strip' or 'name' which replaces them with their
+    ///unicode names joined by the separator such as '🎉' into 'PARTY POPPER'
+    #[arg(long, default_value = "keep", value_parser = EMOJI_POLICIES)]
+    pub emoji: String,
+
+    ///transliterate non-ASCII chars of stems such as 'é','ß' and 'Ж' into ASCII 'e','ss' and 'Zh'
+    ///before separators are handled
+    #[arg(long, default_value = "false")]
+    pub ascii: bool,
+
+    ///convert Han chars of stems into toneless Mandarin pinyin syllables such as '北京' into
+    ///'bei_jing'
+    #[arg(long, default_value = "false")]
+    pub pinyin: bool,
+
+    ///case of pinyin syllables,'lower' or 'title'
+    #[arg(long, default_value = "lower", value_parser = PINYIN_CASES)]
+    pub pinyin_case: String,
+
+    ///separator between pinyin syllables,the configured separator if not given
+    #[arg(long)]
+    pub pinyin_sep: Option<String>,
+
+    ///extensions of more than one part kept whole,replacing the default ones if given
+    #[arg(long, action = ArgAction::Append, default_values = COMPOUND_EXTS)]
+    pub compound_ext: Vec<String>,
+
+    ///report files of identical contents but different names before renaming,'skip' also
+    ///leaves duplicates unrenamed and 'link' replaces them by hard links to the first one in
+    ///place,logged as 'linked' and undone by copies
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "report",
+        value_parser = ["report", "skip", "link"]
+    )]
+    pub detect_dupes: Option<String>,
+
+    ///correct wrong or missing extensions of regular files by types detected from contents,such
+    ///as '.jpg' of a PNG file into '.png'
+    #[arg(long, default_value = "false")]
+    pub fix_ext: bool,
+
+    ///pick the files or directories to process in a fuzzy finder before the plan is applied,
+    ///built with feature 'tui'
+    #[arg(long, default_value = "false")]
+    pub pick: bool,
+
+    ///case sensitivity of the filesystem when targets are checked to collide,'auto' probes
+    ///every directory and targets differing only in case collide if insensitive
+    #[arg(long, default_value = "auto", value_parser = ["auto", "sensitive", "insensitive"])]
+    pub fs_case: String,
+
+    ///session id of this run,shared by all operations logged in this run
+    #[arg(skip = new_session_id())]
+    pub session: String,
+
+    ///command line of this run,saved in checkpoint so that an interrupted run can be resumed
+    #[arg(skip = env::args().collect::<Vec<_>>())]
+    pub argv: Vec<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+impl Args {
+    ///Check a path which is hidden or not is processed by the hidden policy
+    pub fn hidden_kept(&self, hidden: bool) -> bool {
+        match (self.hidden.as_str(), self.not_ignore_hidden) {
+            ("include", _) | (_, true) => true,
+            ("only", _) => hidden,
+            _ => !hidden,
+        }
+    }
+
+    ///Names of files skipped by regular_files:setting 'skip_files' split by ',' or the default
+    ///ones,none by '--no-default-skips'
+    pub fn skips(&self) -> Result<Vec<String>> {
+        if self.no_default_skips {
+            return Ok(Vec::new());
+        }
+
+        Ok(match retrieve_setting(&open_db(None)?, "skip_files")? {
+            Some(v) => v
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            None => DEFAULT_SKIPS.map(str::to_owned).to_vec(),
+        })
+    }
+
+    ///Input paths from positional files or file path option,canonical and duplicates removed
+    pub fn input_paths(&self) -> Vec<PathBuf> {
+        let paths = match self.files {
+            Some(ref vs) => vs.iter().map(|v| canonical_path(Path::new(v))).collect(),
+            None => vec![canonical_path(Path::new(&self.file_path))],
+        };
+
+        unique_paths(paths)
+    }
+
+    ///Exclude entries resolved against the same canonical base as the root,relative ones and globs
+    ///relative to the root,so that they match paths listed from the root;a file given as the root
+    ///standing for its directory
+    pub fn excludes_under(&self, root: &Path) -> Result<Excludes> {
+        match root.is_file() {
+            true => Excludes::new(root.parent().unwrap_or(root), &self.exclude_path),
+            false => Excludes::new(root, &self.exclude_path),
+        }
+    }
+}
+
+///Operations of 'fdn config' on config rows by '<KIND>:<ID>' as listed,kinds 'sep','to-sep',
+///'term','re','ext','acronym','small' and 'stop'
+#[derive(Debug, clap::Args, Clone)]
+pub struct RowOps {
+    ///Enable a config row by '<KIND>:<ID>' such as 'term:3',or all rows with a tag by
+    ///'tag:<TAG>'
+    #[arg(long)]
+    pub enable: Option<String>,
+
+    ///Disable a config row by '<KIND>:<ID>' such as 'term:3' without deleting it,or all rows
+    ///with a tag by 'tag:<TAG>'
+    #[arg(long)]
+    pub disable: Option<String>,
+
+    ///Tag a config row by '<KIND>:<ID>=<TAG>' such as 'term:3=music',empty tag to untag
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    ///Delete a config row by '<KIND>:<ID>' as listed such as 'sep:2' or 'term:3'
+    #[arg(long)]
+    pub delete_id: Option<String>,
+
+    ///Update the value of a config row by '<KIND>:<ID>:<VALUE>' as listed such as
+    ///'term:3:Feat.' or 're:1:_'
+    #[arg(long)]
+    pub update_id: Option<String>,
+}
+
+///Backup,restore,diff and merge of the database by 'fdn config'
+#[derive(Debug, clap::Args, Clone)]
+pub struct BackupOps {
+    ///Show rules only in the database,only in another database or different in both
+    #[arg(long, value_name = "DB")]
+    pub diff: Option<String>,
+
+    ///Merge rules of another database,rules only in it added and different ones replaced if
+    ///confirmed
+    #[arg(long, value_name = "DB")]
+    pub merge: Option<String>,
+
+    ///Backup the database into a new file by the SQLite backup API,rules,settings and records
+    #[arg(long, group = "db_file")]
+    pub backup: Option<String>,
+
+    ///Restore the database from a backup file
+    #[arg(long, group = "db_file")]
+    pub restore_backup: Option<String>,
+
+    ///Backup or restore only records of runs:records for reverse,operations,checkpoints and
+    ///rule stats
+    #[arg(long, requires = "db_file", conflicts_with = "rules_only")]
+    pub records: bool,
+
+    ///Backup or restore only rules and settings
+    #[arg(long, requires = "db_file")]
+    pub rules_only: bool,
+}
+
+impl BackupOps {
+    ///Part of the database selected,one of DB_PARTS
+    pub fn part(&self) -> &'static str {
+        match (self.records, self.rules_only) {
+            (true, _) => DB_PARTS[2],
+            (_, true) => DB_PARTS[1],
+            _ => DB_PARTS[0],
+        }
+    }
+}
+
 #[derive(Debug, Subcommand, Clone)]
 pub enum Commands {
     ///Config pattern
@@ -80,20 +724,359 @@ pub enum Commands {
         #[arg(short = 'l', long, default_value = "false")]
         list: bool,
 
-        ///Config Separators,Terms ...
+        ///Config Separators,Terms,Regex rules('re:pattern:replacement'),Extension
+        ///aliases('ext:jpeg:jpg'),Acronyms('acronym:PDF'),Small words('small:of'),
+        ///Stopwords('stop:copy of' or 'stop:de:kopie von' for a language) ...
         #[arg(short = 'c', long)]
         add: Option<String>,
 
-        ///Delete configurations
+        ///Delete configurations,deleted term words can be restored
         #[arg(short = 'd', long)]
         delete: Option<String>,
+
+        ///Restore a deleted term word by id
+        #[arg(long)]
+        restore: Option<i32>,
+
+        ///Compare plans of current directory with and without a candidate rule
+        #[arg(long, default_value = "false", requires = "with")]
+        compare: bool,
+
+        ///Candidate rule to compare,such as 're:foo:bar','key:value' or 'word'
+        #[arg(long)]
+        with: Option<String>,
+
+        ///Set a setting by 'key=value',such as 'log_file=/path/to/fdn.log'
+        #[arg(long)]
+        set: Option<String>,
+
+        ///Unset a setting by key
+        #[arg(long)]
+        unset: Option<String>,
+
+        ///Register a Rhai script whose 'fn rename(stem,ext,path,meta)' rewrites stems after the
+        ///rules,for logic and lookups rules can not express,unset by '--unset script',built with
+        ///feature 'script'
+        #[arg(long)]
+        script: Option<String>,
+
+        ///Show how often every configured rule fired,counted if setting 'rule_stats=on'
+        #[arg(long, default_value = "false")]
+        stats: bool,
+
+        ///Match the added term word in any case
+        #[arg(long, default_value = "false", requires = "add")]
+        ignore_case: bool,
+
+        ///Match the added term word only where not joined to letters or digits,such as 'cat' of
+        ///'my cat' but not of 'concatenate'
+        #[arg(long, default_value = "false", requires = "add")]
+        whole_word: bool,
+
+        ///Apply the added to separator word,term word or regex rule only to files of extensions
+        ///or MIME classes such as 'video','audio','image','text','document','archive' or
+        ///'mp4,srt'
+        #[arg(long, requires = "add")]
+        scope: Option<String>,
+
+        #[command(flatten)]
+        rows: Box<RowOps>,
+
+        #[command(flatten)]
+        db: Box<BackupOps>,
+    },
+
+    ///Show operation log
+    Log {
+        ///Keep waiting for new operations
+        #[arg(short = 'f', long, default_value = "false")]
+        follow: bool,
+
+        ///Only show operations under the directory
+        #[arg(short = 'D', long)]
+        dir: Option<String>,
+
+        ///Only show operations with the status,such as renamed,reversed or failed
+        #[arg(short = 's', long)]
+        status: Option<String>,
+
+        ///Number of latest operations to show
+        #[arg(short = 'n', long, default_value = "10")]
+        lines: usize,
+    },
+
+    ///Clean a directory in one shot:normalize,remove duplicate markers and resolve collisions
+    Clean {
+        ///Directory to clean
+        #[arg(default_value = ".")]
+        dir: String,
+
+        ///Apply without confirmation
+        #[arg(short = 'y', long, default_value = "false")]
+        yes: bool,
+    },
+
+    ///Try rules on names without touching the filesystem
+    Try {
+        ///Names to normalize
+        #[clap(required = true)]
+        names: Vec<String>,
+
+        ///Normalize names as directory names,no extension kept
+        #[arg(short = 'D', long, default_value = "false")]
+        dir: bool,
+
+        ///Show the rules which fired
+        #[arg(short = 'e', long, default_value = "false")]
+        explain: bool,
+    },
+
+    ///Resume an interrupted in-place run
+    Resume {
+        ///Session id of the run,default the latest interrupted one
+        session: Option<String>,
+
+        ///List interrupted runs
+        #[arg(short = 'l', long, default_value = "false")]
+        list: bool,
+    },
+
+    ///Normalize names of entries inside a zip or tar archive,listed unless '-i' rewrites the
+    ///archive,built with feature 'archive'
+    Archive {
+        ///Archive of '.zip','.tar','.tar.gz' or '.tgz'
+        file: String,
+    },
+
+    ///Check the rules against names of input paths or given names
+    Check {
+        ///Names to check,default names of files under input paths
+        names: Vec<String>,
+
+        ///Report names which a second normalization pass would change again
+        #[arg(long, default_value = "false")]
+        idempotent: bool,
+    },
+
+    ///Suggest rules learned from existing names of a directory and add the accepted ones
+    Suggest {
+        ///Directory to learn from,nothing under it is changed
+        #[arg(short = 'p', long, default_value = ".")]
+        path: String,
+
+        ///Least number of names a separator,word or junk token is found in to be suggested
+        #[arg(short = 'n', long, default_value = "3")]
+        min_count: usize,
+
+        ///Add all suggestions without asking
+        #[arg(short = 'y', long, default_value = "false")]
+        yes: bool,
+    },
+
+    ///Watch a directory by notifications of the OS and normalize new files in place once they
+    ///stop changing,temporary files of downloads such as '*.part' and '*.crdownload' ignored until
+    ///renamed
+    Watch {
+        ///Directory to watch
+        #[arg(default_value = ".")]
+        dir: String,
+
+        ///Scan the directory every interval instead of relying on notifications,for file systems
+        ///sending none such as network shares
+        #[arg(long, default_value = "false")]
+        poll: bool,
+
+        ///Milliseconds between scans of the directory with '--poll'
+        #[arg(long, default_value = "1000")]
+        interval: u64,
+
+        ///Seconds a new file must stay unchanged before it is normalized
+        #[arg(long, default_value = "2")]
+        debounce: u64,
+    },
+
+    ///List,install,update or remove rule packs such as 'scene','cjk-punctuation' or 'academic',
+    ///rules of a pack tagged 'pack:<NAME>' and rules of your own kept on conflicts
+    Preset {
+        ///'list','install' a pack by name,URL or manifest file,'update' installed packs whose
+        ///version changed or 'remove' a pack with its rules
+        #[arg(value_parser = PACK_ACTIONS)]
+        action: String,
+
+        ///Name,URL or manifest file of the pack
+        name: Option<String>,
+    },
+
+    ///Save invocations of fdn under names and run them again,stored in the database with the rest
+    ///of the config
+    Alias {
+        ///'save' the arguments after '--','run' a saved one with arguments after '--' appended,
+        ///'list' or 'delete' them
+        #[arg(value_parser = ALIAS_ACTIONS)]
+        action: String,
+
+        ///Name of the alias,letters,digits,'-' or '_'
+        name: Option<String>,
+
+        ///Arguments of fdn such as '-- -f ~/Downloads -d 2 -i'
+        #[arg(last = true, allow_hyphen_values = true)]
+        argv: Vec<String>,
+    },
+
+    ///Install or remove a 'Normalize with fdn' entry in the context menu of a file manager,running
+    ///fdn in place on the selected files
+    Integrate {
+        ///'install' or 'remove' the entry
+        #[arg(value_parser = INTEGRATE_ACTIONS)]
+        action: String,
+
+        ///'nautilus' or 'dolphin' on Linux,'finder' on macOS or 'sendto' on Windows
+        #[arg(value_parser = INTEGRATIONS)]
+        target: String,
+
+        ///Arguments of fdn put before the selected files,default '-i' such as '-- -i --preset tv'
+        #[arg(last = true, allow_hyphen_values = true)]
+        argv: Vec<String>,
+    },
+
+    ///Manage the daemon watching the roots of its config file,each with its own depth,excludes
+    ///and fdn options,reloaded when the file changes
+    Daemon {
+        ///'start' in the background,'stop','status' or 'run' in the foreground
+        #[arg(value_parser = DAEMON_ACTIONS)]
+        action: String,
+
+        ///Config file of the daemon,default 'daemon.json' under the config directory
+        #[arg(short = 'c', long)]
+        config: Option<String>,
+    },
+
+    ///Serve a REST API to preview and apply renames of a path,query history and undo,answered
+    ///in the JSON of '--format json',for web UIs and home automation
+    Serve {
+        ///Address to listen on
+        #[arg(long, default_value = DEFAULT_LISTEN)]
+        listen: String,
+
+        ///Bearer token requests must carry in 'Authorization',needed on non-loopback addresses
+        #[arg(long)]
+        token: Option<String>,
+    },
+
+    ///Report naming styles of a directory:separators,case styles,non-ASCII names,length outliers
+    ///and duplicate stems
+    Audit {
+        ///Directory to audit,nothing under it is changed
+        #[arg(short = 'p', long, default_value = ".")]
+        path: String,
+
+        ///Print in JSON
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+
+    ///Browse regular files of a directory with proposed names side by side in a terminal UI,toggle,
+    ///edit and filter renames and apply the selected ones,built with feature 'tui'
+    Tui {
+        ///Directory to browse
+        #[arg(default_value = ".")]
+        dir: String,
+    },
+
+    ///Rename regular files of a directory to names pasted from the clipboard or stdin,one name per
+    ///line matched with files in order
+    PasteNames {
+        ///Directory whose regular files are renamed
+        #[arg(default_value = ".")]
+        dir: String,
+
+        ///Read names from stdin instead of the clipboard
+        #[arg(long, default_value = "false")]
+        stdin: bool,
+
+        ///Order files are matched with names,'natural','lexical','mtime' or 'size'
+        #[arg(long, default_value = "natural", value_parser = sort_key)]
+        sort: String,
+
+        ///Append extensions of files to the names
+        #[arg(long, default_value = "false")]
+        keep_ext: bool,
+    },
+
+    ///Rename paths by rows of 'source,target' of a CSV or TSV file,checked all together before any
+    ///rename and recorded for reverse
+    Map {
+        ///Mapping file,tab separated if its extension is '.tsv'
+        #[arg(short = 'f', long)]
+        file: String,
+
+        ///Directory sources and targets are relative to
+        #[arg(short = 'r', long, default_value = ".")]
+        root: String,
+    },
+
+    ///Move regular files of subdirectories up into a directory with normalized names,collisions
+    ///suffixed by '_N' and moves recorded for reverse
+    Flatten {
+        ///Directory to flatten
+        #[arg(short = 'p', long, default_value = ".")]
+        path: String,
+
+        ///Levels of subdirectories flattened,all if not given
+        #[arg(long)]
+        depth: Option<usize>,
+
+        ///Prefix names by their former directories joined by the separator such as
+        ///'sub-dir__file.txt'
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "__")]
+        encode_path: Option<String>,
+    },
+
+    ///Move regular files of a directory into a layout rendered by a template such as
+    ///"{ext}/{year}/{name}",directories created and moves recorded for reverse
+    Organize {
+        ///Layout of files relative to the directory,'/' separating directories
+        #[arg(short = 't', long, value_parser = |t: &str| Template::parse(t))]
+        template: Template,
+
+        ///Directory to organize
+        #[arg(short = 'p', long, default_value = ".")]
+        path: String,
+    },
+
+    ///Rename and reverse a sample of files in memory to verify names round trip on this platform
+    Selftest {
+        ///Directory to sample file names from,nothing under it is changed
+        #[arg(short = 'p', long, default_value = ".")]
+        path: String,
+
+        ///Max number of files sampled
+        #[arg(short = 'n', long, default_value = "100")]
+        sample: usize,
+    },
+
+    ///Show compiled in features,supported stages,template placeholders and schema versions
+    Capabilities {
+        ///Print in JSON
+        #[arg(long, default_value = "false")]
+        json: bool,
     },
 
     ///Change file name directly
     Mv {
-        ///Input source file path and target file name
-        #[clap(required = true)]
+        ///Input source file path and target file name,or source paths and a target directory
+        ///such as 'a.txt b.txt dest/' which sources are moved into
+        #[clap(required_unless_present = "pairs", conflicts_with = "pairs")]
         inputs: Vec<String>,
+
+        ///Pairs of source path and target file name such as 'a.txt:b.txt',renamed all together
+        #[arg(long, num_args = 1..)]
+        pairs: Vec<String>,
+
+        ///Create missing directories of targets such as 'New Folder/My File.txt',new directories
+        ///and target names normalized by rules
+        #[arg(long, default_value = "false")]
+        parents: bool,
     },
 }
 
@@ -120,12 +1103,55 @@ impl Default for Separator {
 pub struct ToSepWord {
     id: i32,
     pub value: String,
+    ///extensions or MIME classes the word applies to,empty for all files
+    pub scope: String,
 }
 
 pub struct TermWord {
     id: i32,
     pub key: String,
     pub value: String,
+    pub flags: TermFlags,
+    ///extensions or MIME classes the word applies to,empty for all files
+    pub scope: String,
+}
+
+///Matching options of a term word,plain substring matching if none set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TermFlags {
+    ///match letters in any case
+    pub ignore_case: bool,
+    ///match only where not joined to letters or digits
+    pub whole_word: bool,
+}
+
+impl TermFlags {
+    ///Flags in short form,'i' for ignore case and 'w' for whole word,'-' if none
+    fn short(&self) -> String {
+        let s: String = [(self.ignore_case, 'i'), (self.whole_word, 'w')]
+            .iter()
+            .filter_map(|(on, c)| on.then_some(*c))
+            .collect();
+        match s.is_empty() {
+            true => "-".to_owned(),
+            false => s,
+        }
+    }
+
+    ///Regex matching the key by the flags,None for plain substring matching;boundaries of whole
+    ///words checked on replacing since look around is not supported
+    fn matcher(&self, key: &str) -> Result<Option<Regex>> {
+        if !self.ignore_case && !self.whole_word {
+            return Ok(None);
+        }
+        let case = if self.ignore_case { "(?i)" } else { "" };
+
+        Ok(Some(Regex::new(&format!(
+            "{}{}",
+            case,
+            regex::escape(key)
+        ))?))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -134,6 +1160,8 @@ pub struct Record {
     hashed_current_name: String,
     encrypted_pre_name: String,
     count: i32,
+    ///directory of the renamed file encrypted by its name,None if not recorded
+    encrypted_dir: Option<String>,
 }
 
 impl Record {
@@ -145,515 +1173,8530 @@ impl Record {
             hashed_current_name: hashed,
             encrypted_pre_name: encrypted,
             count: 1,
+            encrypted_dir: None,
         })
     }
-}
 
-///Return absolute paths
-pub fn regular_files(directory: &Path, depth: usize, excludes: Vec<&Path>) -> Result<Vec<PathBuf>> {
-    let mut paths: Vec<_> = WalkDir::new(directory)
-        .max_depth(depth)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter_map(|entry| {
-            if entry.file_type().is_file() {
-                Some(entry.into_path())
-            } else {
-                None
-            }
+    ///Record the directory of the renamed file encrypted by its current name
+    pub fn with_dir(self, dir: &Path, target: &str) -> Result<Self> {
+        let dir = os2string(Some(canonical_path(dir).as_os_str()))?;
+        Ok(Self {
+            encrypted_dir: Some(encrypted(&dir, target)?),
+            ..self
         })
-        .collect();
-
-    paths.retain(|path| !excludes.iter().any(|exc| path.starts_with(exc)));
+    }
+}
 
-    Ok(paths)
+pub struct RegexRule {
+    id: i32,
+    pub pattern: String,
+    pub replacement: String,
+    ///extensions or MIME classes the rule applies to,empty for all files
+    pub scope: String,
 }
 
-///Return directories
-pub fn directories(directory: &Path, depth: usize, excludes: Vec<&Path>) -> Result<Vec<PathBuf>> {
-    let mut paths: Vec<_> = WalkDir::new(directory)
-        .max_depth(depth)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter_map(|entry| {
-            if entry.file_type().is_dir() {
-                Some(entry.into_path())
-            } else {
-                None
-            }
-        })
-        .collect();
+///A config row of any kind which is disabled or tagged,rule in config syntax
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigRow {
+    pub kind: String,
+    pub id: i32,
+    pub rule: String,
+    pub tag: String,
+    pub enabled: bool,
+}
 
-    paths.retain(|path| !excludes.iter().any(|exc| path.starts_with(exc)));
+///A config row differing between the database and another one,None on the side it is absent
+///from,columns of different values if on both sides
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleDiff {
+    pub key: String,
+    pub local: Option<ConfigRow>,
+    pub other: Option<ConfigRow>,
+    pub columns: Vec<String>,
+}
 
-    paths.sort_by(|a, b| {
-        let a_str = a.as_os_str();
-        let b_str = b.as_os_str();
-        match a_str.cmp(b_str) {
-            Ordering::Equal => a_str.len().cmp(&b_str.len()),
-            other => other.reverse(),
-        }
-    });
+///Scopes of to separator words,term words and regex rules of a rule set by index,empty or
+///missing for rules of all files
+#[derive(Debug, Clone, Default)]
+pub struct RuleScopes {
+    pub to_sep_words: Vec<String>,
+    pub term_words: Vec<String>,
+    pub regex_rules: Vec<String>,
+}
 
-    Ok(paths)
+impl RuleScopes {
+    ///Check no rule is scoped
+    pub fn is_empty(&self) -> bool {
+        [&self.to_sep_words, &self.term_words, &self.regex_rules]
+            .iter()
+            .all(|scopes| scopes.iter().all(String::is_empty))
+    }
 }
 
-///Create DirBase struct from abs_path
-fn dir_base(abs_path: &Path) -> Option<DirBase> {
-    if let (Some(base), Some(dir_path)) = (abs_path.file_name(), abs_path.parent()) {
-        if let (Some(dir), Some(base)) = (dir_path.to_str(), base.to_str()) {
-            Some(DirBase {
-                dir: dir.to_owned(),
-                base: base.to_owned(),
+///All rules used to normalize a name
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    pub sep: String,
+    pub to_sep_words: Vec<String>,
+    pub term_words: Vec<(String, String)>,
+    ///matchers of term words with flags by key,others matched as plain substrings
+    pub term_matchers: HashMap<String, (Regex, TermFlags)>,
+    pub regex_rules: Vec<(Regex, String)>,
+    ///extensions or MIME classes to separator words,term words and regex rules apply to
+    pub scopes: RuleScopes,
+    ///place of canonical TV episode tags,'keep','start' or 'end',None if preset 'tv' not enabled
+    pub episode_at: Option<String>,
+    ///pairs of brackets whose segments are removed,and whether segments of years are kept
+    pub strip_brackets: (Vec<String>, bool),
+    ///acronyms kept whole when camel case words are split
+    pub acronyms: Vec<String>,
+    ///split camel case words by sep
+    pub split_camel: bool,
+    ///split long runs of letters into dictionary words by sep
+    pub split_words: bool,
+    ///strftime format dates of stems are rewritten in,None to keep dates as they are
+    pub date_format: Option<String>,
+    ///parts and zero-padding width of normalized versions,None to keep versions as they are
+    pub versions: Option<(usize, usize)>,
+    ///digits standalone integers are zero-padded to,None to keep them as they are
+    pub pad_numbers: Option<usize>,
+    ///collapse words repeated right after themselves
+    pub dedup_words: bool,
+    ///case of stems,one of CASES,kept if empty
+    pub case: String,
+    ///words kept lowercase by case 'title-smart' unless first or last
+    pub small_words: Vec<String>,
+    ///stopwords removed at word boundaries with their languages,empty for all languages
+    pub stopwords: Vec<(String, String)>,
+    ///languages of stopwords removed besides those of all languages
+    pub stopword_langs: Vec<String>,
+    ///alternation of the stopwords removed,longest first,with the index in stopwords of the
+    ///stopword of every group;rebuilt whenever stopwords or their languages change
+    pub stopword_matcher: Option<(Regex, Vec<usize>)>,
+    ///aliases of extensions by lower case extension,such as "jpeg" into "jpg"
+    pub ext_aliases: HashMap<String, String>,
+    ///case of extensions,one of EXT_CASES,kept if empty
+    pub ext_case: String,
+    ///extensions of more than one part such as "tar.gz" kept whole
+    pub compound_exts: Vec<String>,
+    ///encodings of stems decoded in order,of DECODINGS
+    pub decode: Vec<String>,
+    ///keep invisible and control chars of stems which are removed otherwise
+    pub keep_invisibles: bool,
+    ///unicode normalization form of stems,one of UNICODE_FORMS,None to keep stems as they are
+    pub unicode_form: Option<String>,
+    ///policy of emoji,one of EMOJI_POLICIES,kept if empty
+    pub emoji: String,
+    ///transliterate stems into ASCII
+    pub ascii: bool,
+    ///convert full-width ASCII variants and ideographic spaces of stems into half-width
+    pub half_width: bool,
+    ///convert typographic punctuation and spaces of stems into ASCII
+    pub punctuation: bool,
+    ///remove trailing UUIDs,hex hashes and random suffixes of stems
+    pub strip_ids: bool,
+    ///case of pinyin syllables and separator between them,None if Han chars are kept
+    pub pinyin: Option<(String, String)>,
+    ///script rewriting stems after the rules,None if no script is registered
+    pub script: Option<Arc<Script>>,
+}
+
+impl RuleSet {
+    ///Load rules from database via database connection
+    pub fn load(conn: &Connection) -> Result<Self> {
+        let sep = retrieve_separators(conn)?;
+        let sep = match sep.first() {
+            Some(sep) => sep.value.clone(),
+            None => Separator::default().value,
+        };
+        let regex_rules = retrieve_regex_rules(conn)?;
+        let term_words = retrieve_term_words(conn)?;
+        let to_sep_words = retrieve_to_sep_words(conn)?;
+        let mut term_matchers = HashMap::new();
+        for w in &term_words {
+            if let Some(re) = w.flags.matcher(&w.key)? {
+                term_matchers.insert(w.key.clone(), (re, w.flags));
+            }
+        }
+
+        Ok(Self {
+            sep,
+            to_sep_words: to_sep_words.iter().map(|w| w.value.clone()).collect(),
+            term_words: term_words
+                .iter()
+                .map(|w| (w.key.clone(), w.value.clone()))
+                .collect(),
+            term_matchers,
+            regex_rules: regex_rules
+                .iter()
+                .map(|r| Ok((Regex::new(&r.pattern)?, r.replacement.clone())))
+                .collect::<Result<Vec<_>>>()?,
+            scopes: RuleScopes {
+                to_sep_words: to_sep_words.into_iter().map(|w| w.scope).collect(),
+                term_words: term_words.into_iter().map(|w| w.scope).collect(),
+                regex_rules: regex_rules.into_iter().map(|r| r.scope).collect(),
+            },
+            episode_at: None,
+            strip_brackets: (Vec::new(), false),
+            acronyms: retrieve_acronyms(conn)?,
+            split_camel: false,
+            split_words: false,
+            date_format: None,
+            versions: None,
+            pad_numbers: None,
+            dedup_words: false,
+            case: String::new(),
+            small_words: retrieve_small_words(conn)?,
+            stopwords: retrieve_stopwords(conn)?,
+            stopword_langs: Vec::new(),
+            stopword_matcher: None,
+            ext_aliases: retrieve_ext_aliases(conn)?.into_iter().collect(),
+            ext_case: String::new(),
+            compound_exts: Vec::new(),
+            decode: Vec::new(),
+            keep_invisibles: false,
+            unicode_form: None,
+            emoji: String::new(),
+            ascii: false,
+            half_width: false,
+            punctuation: false,
+            strip_ids: false,
+            pinyin: None,
+            script: retrieve_setting(conn, "script")?
+                .map(|p| Script::load(Path::new(&p)).map(Arc::new))
+                .transpose()?,
+        }
+        .with_stopword_matcher())
+    }
+
+    ///Apply presets,extension case and compound extensions of command line
+    pub fn with_args(self, args: &Args) -> Self {
+        let pinyin_sep = args.pinyin_sep.clone().unwrap_or_else(|| self.sep.clone());
+        self.with_presets(&args.preset, &args.episode_at)
+            .with_ext_case(&args.ext_case)
+            .with_compound_exts(&args.compound_ext)
+            .with_decode(&args.decode)
+            .with_keep_invisibles(args.keep_invisibles)
+            .with_unicode_form(args.unicode_normalize.as_deref())
+            .with_emoji(&args.emoji)
+            .with_strip_brackets(&args.strip_brackets, args.keep_years)
+            .with_split_camel(args.split_camel)
+            .with_split_words(args.split_words)
+            .with_stopword_langs(&args.stopword_lang)
+            .with_dedup_words(args.dedup_words)
+            .with_case(&args.case)
+            .with_pad_numbers(args.pad_numbers.map(usize::from))
+            .with_date_format(args.date_format.as_deref())
+            .with_versions(
+                args.normalize_versions
+                    .map(|parts| (parts as usize, args.version_pad as usize)),
+            )
+            .with_ascii(args.ascii)
+            .with_pinyin(args.pinyin.then(|| (args.pinyin_case.clone(), pinyin_sep)))
+    }
+
+    ///Convert Han chars of stems into pinyin syllables in the case,joined by sep
+    pub fn with_pinyin(self, pinyin: Option<(String, String)>) -> Self {
+        Self { pinyin, ..self }
+    }
+
+    ///Decode stems of the encodings before rules
+    pub fn with_decode(self, decode: &[String]) -> Self {
+        Self {
+            decode: decode.to_vec(),
+            ..self
+        }
+    }
+
+    ///Keep invisible and control chars of stems instead of removing them first
+    pub fn with_keep_invisibles(self, keep_invisibles: bool) -> Self {
+        Self {
+            keep_invisibles,
+            ..self
+        }
+    }
+
+    ///Remove segments of stems in the pairs of brackets,segments of years kept if keep_years
+    pub fn with_strip_brackets(self, pairs: &[String], keep_years: bool) -> Self {
+        Self {
+            strip_brackets: (pairs.to_vec(), keep_years),
+            ..self
+        }
+    }
+
+    ///Split camel case words of stems by sep
+    pub fn with_split_camel(self, split_camel: bool) -> Self {
+        Self {
+            split_camel,
+            ..self
+        }
+    }
+
+    ///Split long runs of letters of stems into dictionary words by sep
+    pub fn with_split_words(self, split_words: bool) -> Self {
+        Self {
+            split_words,
+            ..self
+        }
+    }
+
+    ///Rewrite dates of stems in the strftime format
+    pub fn with_date_format(self, date_format: Option<&str>) -> Self {
+        Self {
+            date_format: date_format.map(str::to_owned),
+            ..self
+        }
+    }
+
+    ///Rewrite version tokens of stems into the parts,each zero-padded to the width
+    pub fn with_versions(self, versions: Option<(usize, usize)>) -> Self {
+        Self { versions, ..self }
+    }
+
+    ///Zero-pad standalone integers of stems to the digits
+    pub fn with_pad_numbers(self, pad_numbers: Option<usize>) -> Self {
+        Self {
+            pad_numbers,
+            ..self
+        }
+    }
+
+    ///Change stems into the case,one of CASES
+    pub fn with_case(self, case: &str) -> Self {
+        Self {
+            case: case.to_owned(),
+            ..self
+        }
+    }
+
+    ///Collapse words repeated right after themselves
+    pub fn with_dedup_words(self, dedup_words: bool) -> Self {
+        Self {
+            dedup_words,
+            ..self
+        }
+    }
+
+    ///Remove stopwords of the languages besides those of all languages
+    pub fn with_stopword_langs(self, langs: &[String]) -> Self {
+        Self {
+            stopword_langs: langs.to_vec(),
+            ..self
+        }
+        .with_stopword_matcher()
+    }
+
+    ///Remove stopwords case insensitively where they are not joined to letters or digits,such as
+    ///"final" of "report final" but not of "finally",by one search of the alternation;a step
+    ///recorded per stopword removed in the order of stopwords
+    fn stopwords_removed(&self, s: String, steps: &mut Vec<Step>) -> String {
+        let Some((re, words)) = &self.stopword_matcher else {
+            return s;
+        };
+        let joined = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+
+        let mut spans = Vec::new();
+        let mut at = 0;
+        while let Some(caps) = re.captures_at(&s, at) {
+            let (Some(m), Some(group)) =
+                (caps.get(0), caps.iter().skip(1).position(|g| g.is_some()))
+            else {
+                break;
+            };
+            let word = &self.stopwords[words[group]].0;
+            //boundaries only matter where the stopword itself starts or ends with a letter or digit
+            if (joined(word.chars().next()) && joined(s[..m.start()].chars().next_back()))
+                || (joined(word.chars().next_back()) && joined(s[m.end()..].chars().next()))
+            {
+                //a match joined to a word may hide one starting inside it
+                at = m.start() + s[m.start()..].chars().next().map_or(1, char::len_utf8);
+            } else {
+                spans.push((words[group], m.range()));
+                at = m.end();
+            }
+        }
+
+        let removed = |upto: usize| {
+            let mut out = String::with_capacity(s.len());
+            let mut last = 0;
+            for (_, r) in spans.iter().filter(|(i, _)| *i <= upto) {
+                out.push_str(&s[last..r.start]);
+                last = r.end;
+            }
+            out.push_str(&s[last..]);
+            out
+        };
+        let mut fired: Vec<_> = spans.iter().map(|(i, _)| *i).collect();
+        fired.sort_unstable();
+        fired.dedup();
+
+        fired.into_iter().fold(s.clone(), |before, i| {
+            let (word, lang) = &self.stopwords[i];
+            step(
+                steps,
+                STAGES[11],
+                &stopword_rule(word, lang),
+                before,
+                removed(i),
+            )
+        })
+    }
+
+    ///Build the alternation of the stopwords removed,so that a stem is searched once for all of
+    ///them;the longest stopword wins where several start
+    fn with_stopword_matcher(self) -> Self {
+        let mut words: Vec<_> = (0..self.stopwords.len())
+            .filter(|i| {
+                let lang = &self.stopwords[*i].1;
+                lang.is_empty() || self.stopword_langs.contains(lang)
             })
+            .collect();
+        words.sort_by_key(|i| std::cmp::Reverse(self.stopwords[*i].0.chars().count()));
+        let alternation: Vec<_> = words
+            .iter()
+            .map(|i| format!("({})", regex::escape(&self.stopwords[*i].0)))
+            .collect();
+        let stopword_matcher = match words.is_empty() {
+            true => None,
+            false => match Regex::new(&format!("(?i){}", alternation.join("|"))) {
+                Ok(re) => Some((re, words)),
+                Err(err) => {
+                    warn!("Stopwords are not removed:{}", err);
+                    None
+                }
+            },
+        };
+
+        Self {
+            stopword_matcher,
+            ..self
+        }
+    }
+
+    ///Strip emoji of stems or replace them with their names by the policy
+    pub fn with_emoji(self, emoji: &str) -> Self {
+        Self {
+            emoji: emoji.to_owned(),
+            ..self
+        }
+    }
+
+    ///Transliterate stems into ASCII before separators are handled
+    pub fn with_ascii(self, ascii: bool) -> Self {
+        Self { ascii, ..self }
+    }
+
+    ///Normalize stems into the unicode form before rules,words of rules normalized too so that
+    ///they still match
+    pub fn with_unicode_form(self, form: Option<&str>) -> Self {
+        let Some(form) = form else {
+            return Self {
+                unicode_form: None,
+                ..self
+            };
+        };
+        let norm = |s: &String| unicode_normalized(s, form);
+
+        Self {
+            to_sep_words: self.to_sep_words.iter().map(norm).collect(),
+            term_words: self
+                .term_words
+                .iter()
+                .map(|(k, v)| (norm(k), norm(v)))
+                .collect(),
+            term_matchers: self
+                .term_matchers
+                .iter()
+                .filter_map(|(k, (_, flags))| {
+                    let k = norm(k);
+                    let re = flags.matcher(&k).ok().flatten()?;
+                    Some((k, (re, *flags)))
+                })
+                .collect(),
+            unicode_form: Some(form.to_owned()),
+            ..self
+        }
+    }
+
+    ///Keep extensions of more than one part such as "tar.gz" whole
+    pub fn with_compound_exts(self, compound_exts: &[String]) -> Self {
+        Self {
+            compound_exts: compound_exts.to_vec(),
+            ..self
+        }
+    }
+
+    ///Change extensions into the case,one of EXT_CASES
+    pub fn with_ext_case(self, ext_case: &str) -> Self {
+        Self {
+            ext_case: ext_case.to_owned(),
+            ..self
+        }
+    }
+
+    ///Extension in the case of ext_case
+    pub fn ext_cased(&self, ext: &str) -> String {
+        match self.ext_case.as_str() {
+            "lower" => ext.to_lowercase(),
+            "upper" => ext.to_uppercase(),
+            _ => ext.to_owned(),
+        }
+    }
+
+    ///Enable built-in rule packs
+    pub fn with_presets(self, presets: &[String], episode_at: &str) -> Self {
+        Self {
+            episode_at: presets
+                .iter()
+                .any(|p| p == "tv")
+                .then(|| episode_at.to_owned()),
+            half_width: presets.iter().any(|p| p == "cjk"),
+            punctuation: presets.iter().any(|p| p == "punctuation"),
+            strip_ids: presets.iter().any(|p| p == "strip-ids"),
+            ..self
+        }
+    }
+
+    ///Rules applying to a file of the extension,those of all files only for None
+    pub fn scoped(&self, ext: Option<&str>) -> Cow<'_, Self> {
+        if self.scopes.is_empty() {
+            return Cow::Borrowed(self);
+        }
+        fn kept<T: Clone>(rules: &[T], scopes: &[String], ext: Option<&str>) -> Vec<T> {
+            rules
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| scopes.get(*i).is_none_or(|s| in_scope(s, ext)))
+                .map(|(_, r)| r.clone())
+                .collect()
+        }
+
+        Cow::Owned(Self {
+            to_sep_words: kept(&self.to_sep_words, &self.scopes.to_sep_words, ext),
+            term_words: kept(&self.term_words, &self.scopes.term_words, ext),
+            regex_rules: kept(&self.regex_rules, &self.scopes.regex_rules, ext),
+            scopes: RuleScopes::default(),
+            ..self.clone()
+        })
+    }
+
+    ///Add a rule in config syntax,"re:pattern:replacement" for regex rule,"ext:from:to" for
+    ///extension alias,"acronym:WORD" for acronym,"small:word" for small word,"stop:[lang:]word"
+    ///for stopword,"key:value" for term word and others for to separator word
+    pub fn add_rule(&mut self, rule: &str) -> Result<()> {
+        if let Some(stopword) = rule.strip_prefix("stop:") {
+            let (lang, word) = parse_stopword(stopword)?;
+            self.stopwords.push((word.to_owned(), lang.to_owned()));
+            *self = std::mem::take(self).with_stopword_matcher();
+        } else if let Some(word) = rule.strip_prefix("acronym:") {
+            self.acronyms.push(parse_acronym(word)?.to_owned());
+        } else if let Some(word) = rule.strip_prefix("small:") {
+            self.small_words
+                .push(parse_small_word(word)?.to_lowercase());
+        } else if let Some(alias) = rule.strip_prefix("ext:") {
+            let (ext, alias) = parse_ext_alias(alias)?;
+            self.ext_aliases
+                .insert(ext.to_lowercase(), alias.to_owned());
+        } else if let Some(re) = rule.strip_prefix("re:") {
+            let (pattern, replacement) = parse_regex_rule(re)?;
+            self.regex_rules
+                .push((Regex::new(pattern)?, replacement.to_owned()));
         } else {
-            None
+            match parse_term_rule(rule) {
+                (key, Some(value)) => self.term_words.push((key, value)),
+                (word, None) => self.to_sep_words.push(word),
+            }
         }
-    } else {
-        None
+
+        Ok(())
+    }
+
+    ///Normalize a file stem by rules
+    pub fn normalize_stem(&self, stem: &str) -> Result<String> {
+        Ok(self.trace_stem(stem)?.0)
+    }
+
+    ///Normalize a file stem by rules and return the steps which changed it in order
+    pub fn trace_stem(&self, stem: &str) -> Result<(String, Vec<Step>)> {
+        let mut steps = Vec::new();
+
+        //decoding first since decoded chars may be invisible too
+        let mut f_stem = stem.to_owned();
+        for encoding in &self.decode {
+            let after = match encoding.as_str() {
+                "url" => percent_decoded(&f_stem, unsafe_in_name),
+                _ => entities_decoded(&f_stem, unsafe_in_name),
+            };
+            f_stem = step(&mut steps, STAGES[0], encoding, f_stem, after);
+        }
+
+        //invisible chars removed first so that rules see what is shown
+        let f_stem = match self.keep_invisibles {
+            true => f_stem,
+            false => {
+                let after = invisibles_removed(&f_stem);
+                step(&mut steps, STAGES[1], "invisibles", f_stem, after)
+            }
+        };
+
+        //unicode normalization before any rule matches
+        let f_stem = match &self.unicode_form {
+            Some(form) => {
+                let after = unicode_normalized(&f_stem, form).replace(['/', '\\'], &self.sep);
+                step(&mut steps, STAGES[2], form, f_stem, after)
+            }
+            None => f_stem,
+        };
+
+        //emoji before transliteration which would spell them in its own way
+        let f_stem = match self.emoji.as_str() {
+            "strip" | "name" => {
+                let after = emoji_replaced(&f_stem, &self.emoji, &self.sep);
+                step(&mut steps, STAGES[3], &self.emoji, f_stem, after)
+            }
+            _ => f_stem,
+        };
+
+        //half-width before chars are converted or matched by rules
+        let f_stem = match self.half_width {
+            true => {
+                let after = half_width(&f_stem);
+                step(&mut steps, STAGES[4], "cjk", f_stem, after)
+            }
+            false => f_stem,
+        };
+
+        //pinyin before transliteration which would take Han chars too
+        let f_stem = match &self.pinyin {
+            Some((case, sep)) => {
+                let after = pinyin_converted(&f_stem, case, sep);
+                step(&mut steps, STAGES[5], case, f_stem, after)
+            }
+            None => f_stem,
+        };
+
+        //transliteration before separators,chars without ASCII form kept
+        let f_stem = match self.ascii {
+            true => {
+                let after = ascii_transliterated(&f_stem, &self.sep);
+                step(&mut steps, STAGES[6], "ascii", f_stem, after)
+            }
+            false => f_stem,
+        };
+
+        //presets before words are split by sep
+        let f_stem = match self.punctuation {
+            true => {
+                let after = plain_punctuation(&f_stem);
+                step(&mut steps, STAGES[7], "punctuation", f_stem, after)
+            }
+            false => f_stem,
+        };
+        let f_stem = match self.strip_ids {
+            true => {
+                let after = ids_stripped(&f_stem);
+                step(&mut steps, STAGES[7], "strip-ids", f_stem, after)
+            }
+            false => f_stem,
+        };
+        let f_stem = match &self.episode_at {
+            Some(at) => {
+                let after = tv_episode(&f_stem, at, &self.sep);
+                step(&mut steps, STAGES[7], "tv", f_stem, after)
+            }
+            None => f_stem,
+        };
+
+        //brackets stripped before they are taken as separators
+        let (pairs, keep_years) = &self.strip_brackets;
+        let f_stem = pairs.iter().fold(f_stem, |f_stem, pair| {
+            let after = brackets_stripped(&f_stem, pair, *keep_years);
+            step(&mut steps, STAGES[8], pair, f_stem, after)
+        });
+
+        //camel case words split before separators are handled
+        let f_stem = match self.split_camel {
+            true => {
+                let after = camel_split(&f_stem, &self.sep, &self.acronyms);
+                step(&mut steps, STAGES[9], "split-camel", f_stem, after)
+            }
+            false => f_stem,
+        };
+        let f_stem = match self.split_words {
+            true => {
+                let after = split_words(&f_stem, &self.sep);
+                step(&mut steps, STAGES[10], "split-words", f_stem, after)
+            }
+            false => f_stem,
+        };
+
+        //stopwords removed while words are still apart by their own separators
+        let f_stem = self.stopwords_removed(f_stem, &mut steps);
+
+        //replace to sep words
+        let f_stem = converge(&f_stem, &mut steps, |f_stem, steps| {
+            self.to_sep_words
+                .iter()
+                .fold(f_stem.to_owned(), |f_stem, k| {
+                    let after = f_stem.replace(k, &self.sep);
+                    step(steps, STAGES[12], k, f_stem, after)
+                })
+        });
+
+        //term words
+        let f_stem = converge(&f_stem, &mut steps, |f_stem, steps| {
+            self.term_words
+                .iter()
+                .fold(f_stem.to_owned(), |f_stem, (k, v)| {
+                    let after = match self.term_matchers.get(k) {
+                        Some((re, flags)) => term_replaced(re, flags.whole_word, &f_stem, v),
+                        None => f_stem.replace(k, v),
+                    };
+                    step(steps, STAGES[13], &format!("{}:{}", k, v), f_stem, after)
+                })
+        });
+
+        //regex rules,every rule applied only once
+        let f_stem = self.regex_rules.iter().fold(f_stem, |f_stem, (re, v)| {
+            let after = re.replace_all(&f_stem, v.as_str()).to_string();
+            step(
+                &mut steps,
+                STAGES[14],
+                &format!("{}:{}", re, v),
+                f_stem,
+                after,
+            )
+        });
+
+        //dates after rules so that separators of the format are kept
+        let f_stem = match &self.date_format {
+            Some(format) => {
+                let after = dates_reformatted(&f_stem, format);
+                step(&mut steps, STAGES[15], format, f_stem, after)
+            }
+            None => f_stem,
+        };
+
+        let f_stem = match self.versions {
+            Some((parts, width)) => {
+                let after = versions_normalized(&f_stem, parts, width);
+                let rule = format!("{}:{}", parts, width);
+                step(&mut steps, STAGES[16], &rule, f_stem, after)
+            }
+            None => f_stem,
+        };
+
+        let f_stem = match self.pad_numbers {
+            Some(digits) => {
+                let after = numbers_padded(&f_stem, digits);
+                step(&mut steps, STAGES[17], &digits.to_string(), f_stem, after)
+            }
+            None => f_stem,
+        };
+
+        //remove continuous
+        let after = remove_continuous(&f_stem, &self.sep)?;
+        let f_stem = step(&mut steps, STAGES[18], &self.sep, f_stem, after);
+
+        //remove repeated words once separators are single
+        let f_stem = match self.dedup_words {
+            true => {
+                let after = remove_dup_words(&f_stem, &self.sep);
+                step(&mut steps, STAGES[19], &self.sep, f_stem, after)
+            }
+            false => f_stem,
+        };
+
+        //remove prefix and suffix sep
+        let after = remove_prefix_sep_suffix_sep(&f_stem, &self.sep).to_owned();
+        let f_stem = step(&mut steps, STAGES[20], &self.sep, f_stem, after);
+
+        //case last so that words are already apart
+        let f_stem = match self.case.as_str() {
+            "" | "keep" => f_stem,
+            case => {
+                let after = stem_cased(&f_stem, case, &self.small_words, &self.acronyms);
+                step(&mut steps, STAGES[21], case, f_stem, after)
+            }
+        };
+
+        Ok((f_stem, steps))
     }
 }
 
-///Check a unix path is hidden or not
-#[cfg(unix)]
-fn is_hidden_unix(path: &Path) -> bool {
-    match path.file_name() {
-        Some(n) => match n.to_str() {
-            Some(s) => s.starts_with('.'),
-            None => false,
-        },
-        None => false,
+///Change a string into the case,one of CASES,words of title cases as runs of letters or digits
+///with inner apostrophes,acronyms matched case-insensitively and written as configured
+fn stem_cased(s: &str, case: &str, small_words: &[String], acronyms: &[String]) -> String {
+    static WORD: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"[\p{L}\p{N}]+(?:['’][\p{L}\p{N}]+)*").unwrap());
+
+    match case {
+        "lower" => return s.to_lowercase(),
+        "upper" => return s.to_uppercase(),
+        "title" | "title-smart" => {}
+        _ => return s.to_owned(),
+    }
+    let smart = case == "title-smart";
+    let words: Vec<_> = WORD.find_iter(s).collect();
+
+    let mut out = String::with_capacity(s.len());
+    let mut last = 0;
+    for (i, m) in words.iter().enumerate() {
+        let word = m.as_str();
+        let lower = word.to_lowercase();
+        let mut chars = word.chars();
+        let first = chars.next().map(|c| c.to_uppercase().collect::<String>());
+        let rest: String = chars.collect();
+        let cased = match acronyms.iter().find(|a| a.to_lowercase() == lower) {
+            Some(acronym) => acronym.clone(),
+            None if smart
+                && rest.chars().any(char::is_uppercase)
+                && rest != rest.to_uppercase() =>
+            {
+                word.to_owned()
+            }
+            None if smart && i > 0 && i + 1 < words.len() && small_words.contains(&lower) => lower,
+            None => first.unwrap_or_default() + rest.to_lowercase().as_str(),
+        };
+        out.push_str(&s[last..m.start()]);
+        out.push_str(&cased);
+        last = m.end();
     }
+    out.push_str(&s[last..]);
+
+    out
 }
 
-#[cfg(windows)]
-use std::os::windows::fs::MetadataExt;
-#[cfg(windows)]
-use winapi::um::winnt::FILE_ATTRIBUTE_HIDDEN;
+///Rewrite version tokens such as "v1.2","V02","ver_3" and "version 4" into "v" and at least
+///the number of parts,missing parts as 0 and every part zero-padded to the width,tokens joined
+///to letters kept such as "dev1" and "v8engine"
+fn versions_normalized(s: &str, parts: usize, width: usize) -> String {
+    static VERSION: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?i)(?:version|ver|v)[ ._-]?(\d+(?:\.\d+)*)").unwrap());
 
-///Check a windows path is hidden or not
-#[cfg(windows)]
-fn is_hidden_windows(path: &Path) -> bool {
-    match path.metadata() {
-        Ok(metadata) => metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0,
-        Err(_) => false, // If there's an error, assume it's not hidden
+    let mut out = String::with_capacity(s.len());
+    let mut last = 0;
+    for caps in VERSION.captures_iter(s) {
+        let m = caps.get(0).unwrap();
+        if s[..m.start()].ends_with(char::is_alphanumeric)
+            || s[m.end()..].starts_with(char::is_alphanumeric)
+        {
+            continue;
+        }
+        let mut nums: Vec<String> = caps[1]
+            .split('.')
+            .map(|n| format!("{:0w$}", n.parse::<u64>().unwrap_or(0), w = width))
+            .collect();
+        while nums.len() < parts {
+            nums.push(format!("{:0w$}", 0, w = width));
+        }
+        out.push_str(&s[last..m.start()]);
+        out.push('v');
+        out.push_str(&nums.join("."));
+        last = m.end();
     }
+    out.push_str(&s[last..]);
+
+    out
 }
 
-///Check a path is hidden or not
-fn is_hidden(path: &Path) -> bool {
-    #[cfg(unix)]
-    {
-        is_hidden_unix(path)
+///Zero-pad standalone integers of a string to the digits,integers joined to letters or to other
+///numbers by '.',',',':','-' or '/' such as those of dates and versions kept
+fn numbers_padded(s: &str, digits: usize) -> String {
+    static INTEGER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d+").unwrap());
+
+    //a neighbor char of a number and the char beyond it
+    let joined = |c: Option<char>, beyond: Option<char>| match c {
+        Some(c) if c.is_alphanumeric() => true,
+        Some('.' | ',' | ':' | '-' | '/') => beyond.is_some_and(|b| b.is_ascii_digit()),
+        _ => false,
+    };
+
+    let mut out = String::with_capacity(s.len());
+    let mut last = 0;
+    for m in INTEGER.find_iter(s) {
+        let mut head = s[..m.start()].chars().rev();
+        let mut tail = s[m.end()..].chars();
+        if joined(head.next(), head.next()) || joined(tail.next(), tail.next()) {
+            continue;
+        }
+        out.push_str(&s[last..m.start()]);
+        out.push_str(&format!("{:0>w$}", m.as_str(), w = digits));
+        last = m.end();
     }
-    #[cfg(windows)]
-    {
-        is_hidden_windows(path)
+    out.push_str(&s[last..]);
+
+    out
+}
+
+///Remove segments in a pair of brackets such as "[]" from a string,innermost first for nested
+///ones,segments of years such as "(2019)" kept if keep_years
+fn brackets_stripped(s: &str, pair: &str, keep_years: bool) -> String {
+    static YEAR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(19|20)\d{2}\s*$").unwrap());
+
+    let mut chars = pair.chars();
+    let (Some(open), Some(close)) = (chars.next(), chars.next()) else {
+        return s.to_owned();
+    };
+    let (o, c) = (
+        regex::escape(&open.to_string()),
+        regex::escape(&close.to_string()),
+    );
+    let Ok(re) = Regex::new(&format!("{}([^{}{}]*){}", o, o, c, c)) else {
+        return s.to_owned();
+    };
+
+    //years kept under a placeholder so that segments around them still go
+    let mut kept = Vec::new();
+    let mut out = s.to_owned();
+    loop {
+        let after = re
+            .replace_all(&out, |caps: &regex::Captures| {
+                match keep_years && YEAR.is_match(&caps[1]) {
+                    true => {
+                        kept.push(caps[0].to_owned());
+                        format!("\u{0}{}\u{0}", kept.len() - 1)
+                    }
+                    false => String::new(),
+                }
+            })
+            .into_owned();
+        if after == out {
+            break;
+        }
+        out = after;
+    }
+
+    kept.iter().enumerate().fold(out, |out, (i, k)| {
+        out.replace(&format!("\u{0}{}\u{0}", i), k)
+    })
+}
+
+///Insert sep at lower to upper case transitions of a string,acronyms at starts of words kept
+///whole and separated from an upper case letter after them,an acronym matched only at the end of
+///a word or before an upper to lower case boundary so that 'ID' is not taken from 'IDLE'
+fn camel_split(s: &str, sep: &str, acronyms: &[String]) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::with_capacity(s.len());
+    let mut after_acronym = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let transition = i > 0 && chars[i - 1].is_lowercase() && c.is_uppercase();
+        if transition {
+            out.push_str(sep);
+        }
+
+        let at_word = i == 0 || !chars[i - 1].is_alphabetic() || transition || after_acronym;
+        let acronym = acronyms
+            .iter()
+            .filter(|a| at_word && !a.is_empty())
+            .filter(|a| {
+                let a: Vec<char> = a.chars().collect();
+                chars[i..].starts_with(&a)
+                    && match chars[i + a.len()..] {
+                        [] => true,
+                        [c, ..] if !c.is_alphabetic() => true,
+                        [u, l, ..] => u.is_uppercase() && l.is_lowercase(),
+                        _ => false,
+                    }
+            })
+            .max_by_key(|a| a.chars().count());
+        match acronym {
+            Some(a) => {
+                out.push_str(a);
+                i += a.chars().count();
+                after_acronym = chars.get(i).is_some_and(|c| c.is_uppercase());
+                if after_acronym {
+                    out.push_str(sep);
+                }
+            }
+            None => {
+                out.push(c);
+                i += 1;
+                after_acronym = false;
+            }
+        }
+    }
+
+    out
+}
+
+///Percent-encode a path of an URL,'/' and unreserved chars kept
+pub fn url_encoded(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+///Decode percent-encoded UTF-8 sequences of a string such as "%20" and "%C3%A9",sequences
+///which are not UTF-8 kept
+pub fn url_decoded(s: &str) -> String {
+    percent_decoded(s, |_| false)
+}
+
+///Decode percent-encoded UTF-8 sequences of a string,sequences which are not UTF-8 or decode
+///into a kept char left encoded
+fn percent_decoded(s: &str, kept: fn(char) -> bool) -> String {
+    static ENCODED: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?:%[0-9a-fA-F]{2})+").unwrap());
+
+    ENCODED
+        .replace_all(s, |caps: &regex::Captures| {
+            let bytes: Vec<u8> = caps[0]
+                .split('%')
+                .skip(1)
+                .filter_map(|h| u8::from_str_radix(h, 16).ok())
+                .collect();
+            let Ok(decoded) = String::from_utf8(bytes) else {
+                return caps[0].to_owned();
+            };
+            //every decoded byte is 3 chars such as "%2F" of the sequence
+            decoded
+                .char_indices()
+                .map(|(i, c)| match kept(c) {
+                    true => caps[0][i * 3..(i + c.len_utf8()) * 3].to_owned(),
+                    false => c.to_string(),
+                })
+                .collect::<String>()
+        })
+        .into_owned()
+}
+
+///Check a char must not be decoded into a name,a path separator or a control char
+fn unsafe_in_name(c: char) -> bool {
+    c == '/' || c == '\\' || c.is_control()
+}
+
+///Decode HTML entities of a string such as "&amp;","&#8211;" and "&#x2014;",unknown entities
+///kept
+pub fn html_decoded(s: &str) -> String {
+    entities_decoded(s, |_| false)
+}
+
+///Decode HTML entities of a string,unknown entities and those of a kept char left encoded
+fn entities_decoded(s: &str, kept: fn(char) -> bool) -> String {
+    static ENTITY: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"&(#[xX][0-9a-fA-F]+|#\d+|[a-zA-Z]+);").unwrap());
+
+    ENTITY
+        .replace_all(s, |caps: &regex::Captures| {
+            let e = &caps[1];
+            let c = match e {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{A0}'),
+                "ndash" => Some('–'),
+                "mdash" => Some('—'),
+                "hellip" => Some('…'),
+                "lsquo" => Some('‘'),
+                "rsquo" => Some('’'),
+                "ldquo" => Some('“'),
+                "rdquo" => Some('”'),
+                "copy" => Some('©'),
+                "reg" => Some('®'),
+                "trade" => Some('™'),
+                _ => match e.strip_prefix("#x").or(e.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => e.strip_prefix('#').and_then(|d| d.parse().ok()),
+                }
+                .and_then(char::from_u32),
+            };
+            c.filter(|c| !kept(*c))
+                .map(String::from)
+                .unwrap_or_else(|| caps[0].to_owned())
+        })
+        .into_owned()
+}
+
+///Check a char is not shown,control chars,zero-width spaces,word joiners,bidi controls,byte
+///order marks,soft hyphens and other format chars;zero-width non-joiner and joiner kept since
+///scripts such as Persian and emoji sequences need them
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(c,
+            '\u{00AD}'
+            | '\u{034F}'
+            | '\u{061C}'
+            | '\u{115F}'..='\u{1160}'
+            | '\u{17B4}'..='\u{17B5}'
+            | '\u{180B}'..='\u{180F}'
+            | '\u{200B}'
+            | '\u{200E}'..='\u{200F}'
+            | '\u{2028}'..='\u{202E}'
+            | '\u{2060}'..='\u{206F}'
+            | '\u{3164}'
+            | '\u{FEFF}'
+            | '\u{FFA0}'
+            | '\u{FFF0}'..='\u{FFF8}'
+            | '\u{1D173}'..='\u{1D17A}'
+            | '\u{E0000}'..='\u{E0FFF}')
+}
+
+///Remove invisible chars from a string
+fn invisibles_removed(s: &str) -> String {
+    s.chars().filter(|c| !is_invisible(*c)).collect()
+}
+
+///Check a char is an emoji of the pictographic blocks,dingbats or regional indicators
+fn is_emoji(c: char) -> bool {
+    matches!(c,
+        '\u{231A}'..='\u{231B}'
+        | '\u{23E9}'..='\u{23F3}'
+        | '\u{23F8}'..='\u{23FA}'
+        | '\u{2600}'..='\u{27BF}'
+        | '\u{2B1B}'..='\u{2B1C}'
+        | '\u{2B50}'
+        | '\u{2B55}'
+        | '\u{1F000}'..='\u{1F02F}'
+        | '\u{1F0A0}'..='\u{1F0FF}'
+        | '\u{1F1E6}'..='\u{1F1FF}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F680}'..='\u{1F6FF}'
+        | '\u{1F7E0}'..='\u{1F7EB}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{1FA70}'..='\u{1FAFF}')
+}
+
+///Check a char only joins or modifies emoji,such as the zero width joiner,variation selectors,
+///the keycap and tags
+fn is_emoji_component(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200D}' | '\u{FE0E}' | '\u{FE0F}' | '\u{20E3}' | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+///Strip emoji of a string by policy 'strip' or replace them with their unicode names joined by
+///sep by policy 'name',names separated by sep from adjacent letters or digits
+fn emoji_replaced(s: &str, policy: &str, sep: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !is_emoji(c) {
+            out.push(c);
+            continue;
+        }
+        //components and emoji joined to this one
+        let mut emoji = String::from(c);
+        while let Some(&c) = chars.peek() {
+            match (is_emoji(c), is_emoji_component(c)) {
+                (true, _) => emoji.push(c),
+                (_, true) => {}
+                _ => break,
+            }
+            chars.next();
+        }
+        if policy == "name" {
+            if out.ends_with(char::is_alphanumeric) {
+                out.push_str(sep);
+            }
+            out.push_str(&unames(&emoji).replace(',', sep));
+            if chars.peek().is_some_and(|c| c.is_alphanumeric()) {
+                out.push_str(sep);
+            }
+        }
+    }
+
+    out
+}
+
+///Normalize a string into the unicode form,'nfc','nfd' or 'nfkc',which folds look-alikes such
+///as '／' into path separators
+fn unicode_normalized(s: &str, form: &str) -> String {
+    match form {
+        "nfd" => s.nfd().collect(),
+        "nfkc" => s.nfkc().collect(),
+        _ => s.nfc().collect(),
+    }
+}
+
+///Check a char is a Han char of CJK unified ideographs or their extensions
+fn is_han(c: char) -> bool {
+    matches!(c,
+        '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2EBEF}'
+        | '\u{30000}'..='\u{3134F}')
+}
+
+///Convert Han chars into toneless Mandarin pinyin syllables in the case,'lower' or 'title',
+///syllables separated by sep from each other and from adjacent letters or digits
+fn pinyin_converted(s: &str, case: &str, sep: &str) -> String {
+    let mut out = String::new();
+    //whether the last pushed char is a syllable or a letter or digit
+    let (mut after_syllable, mut after_alnum) = (false, false);
+    for c in s.chars() {
+        match is_han(c).then(|| deunicode_char(c)).flatten() {
+            Some(syllable) => {
+                let syllable = syllable.trim();
+                if after_syllable || after_alnum {
+                    out.push_str(sep);
+                }
+                match case {
+                    "lower" => out.push_str(&syllable.to_lowercase()),
+                    _ => out.push_str(syllable),
+                }
+                (after_syllable, after_alnum) = (true, false);
+            }
+            None => {
+                if after_syllable && c.is_alphanumeric() {
+                    out.push_str(sep);
+                }
+                out.push(c);
+                (after_syllable, after_alnum) = (false, c.is_alphanumeric());
+            }
+        }
+    }
+
+    out
+}
+
+///Transliterate a string into ASCII such as "é" into "e" and "Ж" into "Zh",chars without
+///ASCII form kept and path separators from look-alikes such as '／' and '∕' turned into sep
+fn ascii_transliterated(s: &str, sep: &str) -> String {
+    s.chars()
+        .map(|c| match c.is_ascii() {
+            true => c.to_string(),
+            false => deunicode_char(c).map_or(c.to_string(), |t| t.replace(['/', '\\'], sep)),
+        })
+        .collect()
+}
+
+///Record a step if the rule changed the name,return the name after the rule
+fn step(
+    steps: &mut Vec<Step>,
+    stage: &'static str,
+    rule: &str,
+    before: String,
+    after: String,
+) -> String {
+    if before != after {
+        steps.push(Step {
+            stage,
+            rule: rule.to_owned(),
+            before,
+            after: after.clone(),
+        });
+    }
+
+    after
+}
+
+///Repeat a replacing pass until the name stops changing,a pass leading back to an earlier name
+///is dropped so that oscillating rules stop there and show up in a second normalization
+fn converge<F>(stem: &str, steps: &mut Vec<Step>, pass: F) -> String
+where
+    F: Fn(&str, &mut Vec<Step>) -> String,
+{
+    let mut f_stem = stem.to_owned();
+    let mut seen = HashSet::from([f_stem.clone()]);
+    for _ in 0..MAX_PASSES {
+        let mut p_steps = Vec::new();
+        let after = pass(&f_stem, &mut p_steps);
+        if !seen.insert(after.clone()) {
+            break;
+        }
+        steps.append(&mut p_steps);
+        f_stem = after;
+    }
+
+    f_stem
+}
+
+///A transformation which changed a name during normalization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    ///stage of the pipeline,one of STAGES
+    pub stage: &'static str,
+    pub rule: String,
+    pub before: String,
+    pub after: String,
+}
+
+///Split regex rule "pattern:replacement" at the last colon and check the pattern
+fn parse_regex_rule(rule: &str) -> Result<(&str, &str)> {
+    match rule.rsplit_once(':') {
+        Some((pattern, replacement)) => {
+            Regex::new(pattern)?;
+            Ok((pattern, replacement))
+        }
+        None => Err(anyhow!(
+            "regex rule must be 're:pattern:replacement':{:?}",
+            rule
+        )),
+    }
+}
+
+///Parse a term word "key:value" or a to separator word without ':',the key or word unescaped
+///by '\:' for ':' and '\\' for '\' so that it can have ':',the value taken as it is
+fn parse_term_rule(rule: &str) -> (String, Option<String>) {
+    let mut key = String::new();
+    let mut chars = rule.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(n @ (':' | '\\'))) => {
+                chars.next();
+                key.push(n);
+            }
+            (':', _) => return (key, Some(chars.as_str().to_owned())),
+            (c, _) => key.push(c),
+        }
+    }
+
+    (key, None)
+}
+
+///Replace matches of a term word matcher by the value,only those not joined to letters or digits
+///on either side if whole_word
+fn term_replaced(re: &Regex, whole_word: bool, s: &str, value: &str) -> String {
+    if !whole_word {
+        return re.replace_all(s, regex::NoExpand(value)).into_owned();
+    }
+
+    let joined = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let (mut replaced, mut last, mut at) = (String::new(), 0, 0);
+    while let Some(m) = re.find_at(s, at) {
+        if !joined(s[..m.start()].chars().next_back()) && !joined(s[m.end()..].chars().next()) {
+            replaced.push_str(&s[last..m.start()]);
+            replaced.push_str(value);
+            last = m.end();
+            at = m.end().max(m.start() + 1);
+        } else {
+            //a match joined to a word may hide one starting inside it
+            at = m.start() + s[m.start()..].chars().next().map_or(1, char::len_utf8);
+        }
+        if at > s.len() {
+            break;
+        }
+    }
+    replaced.push_str(&s[last..]);
+
+    replaced
+}
+
+///Parse a stopword such as "copy of" or "de:kopie von" into its language,empty for all
+///languages,and the word
+fn parse_stopword(stopword: &str) -> Result<(&str, &str)> {
+    let (lang, word) = match stopword.split_once(':') {
+        Some((lang, word))
+            if (2..=3).contains(&lang.len()) && lang.chars().all(|c| c.is_ascii_lowercase()) =>
+        {
+            (lang, word)
+        }
+        _ => ("", stopword),
+    };
+    match word.trim().is_empty() {
+        true => Err(anyhow!(
+            "stopword must be 'stop:[lang:]word' such as 'stop:copy of' or 'stop:de:kopie von':{:?}",
+            stopword
+        )),
+        false => Ok((lang, word)),
+    }
+}
+
+///Stopword in config syntax without the prefix,such as "copy of" or "de:kopie von"
+fn stopword_rule(word: &str, lang: &str) -> String {
+    match lang.is_empty() {
+        true => word.to_owned(),
+        false => format!("{}:{}", lang, word),
+    }
+}
+
+///Parse an acronym such as "PDF",letters or digits only
+fn parse_acronym(word: &str) -> Result<&str> {
+    match !word.is_empty() && word.chars().all(char::is_alphanumeric) {
+        true => Ok(word),
+        false => Err(anyhow!(
+            "acronym must be 'acronym:WORD' of letters or digits such as 'acronym:PDF':{:?}",
+            word
+        )),
+    }
+}
+
+///Parse a small word such as "of",letters only
+fn parse_small_word(word: &str) -> Result<&str> {
+    match !word.is_empty() && word.chars().all(char::is_alphabetic) {
+        true => Ok(word),
+        false => Err(anyhow!(
+            "small word must be 'small:word' of letters such as 'small:of':{:?}",
+            word
+        )),
+    }
+}
+
+///Parse an extension alias such as "jpeg:jpg"
+fn parse_ext_alias(alias: &str) -> Result<(&str, &str)> {
+    match alias.split_once(':') {
+        Some((ext, alias))
+            if !ext.is_empty()
+                && !alias.is_empty()
+                && !ext.contains('.')
+                && !alias.contains('.') =>
+        {
+            Ok((ext, alias))
+        }
+        _ => Err(anyhow!(
+            "extension alias must be 'ext:from:to' such as 'ext:jpeg:jpg':{:?}",
+            alias
+        )),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Operation {
+    id: i32,
+    pub session: String,
+    pub dir: String,
+    pub origin: String,
+    pub target: String,
+    pub status: String,
+    pub created: String,
+}
+
+impl Operation {
+    pub fn new(session: &str, dir_base: &DirBase, target: &str, status: &str) -> Self {
+        let dir = Path::new(&dir_base.dir);
+        let dir = dir.canonicalize().unwrap_or(dir.to_path_buf());
+        Self {
+            id: 0,
+            session: session.to_owned(),
+            dir: dir.to_string_lossy().to_string(),
+            origin: dir_base.base.to_owned(),
+            target: target.to_owned(),
+            status: status.to_owned(),
+            created: String::new(),
+        }
+    }
+}
+
+///Checkpoint of an interrupted in-place run
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub session: String,
+    pub argv: Vec<String>,
+    pub pending: usize,
+    pub created: String,
+}
+
+///Session of a run,every applied operation is logged with it
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub log_file: Option<PathBuf>,
+}
+
+impl Session {
+    ///Create session from args,log file from args or config setting 'log_file'
+    pub fn new(args: &Args, conn: &Connection) -> Result<Self> {
+        let log_file = match args.log_file {
+            Some(ref f) => Some(f.to_owned()),
+            None => retrieve_setting(conn, "log_file")?,
+        };
+
+        Ok(Self {
+            id: args.session.clone(),
+            log_file: log_file.map(PathBuf::from),
+        })
+    }
+
+    ///Log operation into database and append it into log file if any
+    fn log(&self, conn: &Connection, op: &Operation) -> Result<()> {
+        insert_operation(conn, op)?;
+
+        if let Some(ref log_file) = self.log_file {
+            let v = json!({
+                "time": Local::now().to_rfc3339(),
+                "session": op.session,
+                "dir": op.dir,
+                "old": op.origin,
+                "new": op.target,
+                "status": op.status,
+            });
+            let mut f = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_file)?;
+            writeln!(f, "{}", v)?;
+        }
+
+        Ok(())
+    }
+}
+
+///Database connection,renamer,filesystem and session shared by all renames of a run
+pub struct Context {
+    pub conn: Connection,
+    pub renamer: Renamer,
+    pub fs: RefCell<Box<dyn FsBackend>>,
+    pub session: Session,
+    ///commit every n applied operations,0 for only once at the end
+    pub commit_every: usize,
+    pending: Cell<usize>,
+    ///paths marked processed in checkpoint since the last commit
+    marked: Cell<usize>,
+    ///hits of rules not saved yet,None if rule stats is off
+    stats: Option<RefCell<HashMap<(&'static str, String), usize>>>,
+    ///'auto','sensitive' or 'insensitive'
+    fs_case: String,
+    ///directories probed to be case insensitive or not
+    folds: RefCell<HashMap<PathBuf, bool>>,
+    ///origins by targets of renames in this run,targets in lowercase if case insensitive
+    claimed: RefCell<HashMap<PathBuf, PathBuf>>,
+    ///origins of renames planned in this run,free as targets of later renames,in lowercase if
+    ///case insensitive
+    vacated: RefCell<HashSet<PathBuf>>,
+    ///outcomes of files of this run
+    pub report: RefCell<Report>,
+    ///record directories of renamed files
+    record_dir: bool,
+    ///reverse files moved out of their recorded directories
+    accept_relocation: bool,
+    ///one of ON_CONFLICTS
+    on_conflict: String,
+}
+
+impl Context {
+    ///Open database once and load rules and session from it,renames applied through the backend
+    ///of args if any
+    pub fn new(args: &Args) -> Result<Self> {
+        let fs: Box<dyn FsBackend> = match args.backend {
+            Some(ref url) => open_backend(url, args.max_depth)?.0,
+            None => Box::new(RealFs),
+        };
+
+        Self::on_fs(args, fs)
+    }
+
+    ///Open database once and load rules and session from it,renames applied through a backend
+    ///already opened
+    pub fn on_fs(args: &Args, fs: Box<dyn FsBackend>) -> Result<Self> {
+        if args.on_conflict == "overwrite" && !cfg!(feature = "trash") {
+            return Err(anyhow!(
+                "fdn is built without feature 'trash',needed by '--on-conflict overwrite'"
+            ));
+        }
+
+        Ok(Self::with_conn(open_db(None)?, args)?.with_fs(fs))
+    }
+
+    ///Load rules and session from database connection,renames applied on local filesystem
+    pub fn with_conn(conn: Connection, args: &Args) -> Result<Self> {
+        let date_prefix = args
+            .date_prefix
+            .as_deref()
+            .map(|f| DatePrefix::new(f, args.created))
+            .transpose()?;
+        let rules = RuleSet::load(&conn)?.with_args(args);
+        let template = match (&args.template, args.preset.iter().any(|p| p == "ebook")) {
+            (Some(t), _) => Some(t.clone()),
+            (None, true) if !cfg!(feature = "ebook-meta") => {
+                return Err(anyhow!(
+                    "fdn is built without feature 'ebook-meta',needed by '--preset ebook'"
+                ));
+            }
+            (None, true) => Some(Template::parse(EBOOK_TEMPLATE)?.sanitized()),
+            (None, false) => None,
+        };
+        let renamer = Renamer::new(rules)
+            .with_validators(validators(&args.validate)?)
+            .with_date_prefix(date_prefix)
+            .with_template(template, template::providers())
+            .with_fix_ext(args.fix_ext);
+        let session = Session::new(args, &conn)?;
+        let stats = (retrieve_setting(&conn, "rule_stats")?.as_deref() == Some("on"))
+            .then(RefCell::default);
+
+        Ok(Self {
+            conn,
+            renamer,
+            fs: RefCell::new(Box::new(RealFs)),
+            session,
+            commit_every: args.commit_every,
+            pending: Cell::new(0),
+            marked: Cell::new(0),
+            stats,
+            fs_case: args.fs_case.clone(),
+            folds: RefCell::default(),
+            claimed: RefCell::default(),
+            vacated: RefCell::default(),
+            report: RefCell::default(),
+            record_dir: args.record_dir,
+            accept_relocation: args.accept_relocation,
+            on_conflict: args.on_conflict.clone(),
+        })
+    }
+
+    ///Record of a rename of origin to target in dir,the directory recorded if asked
+    fn record(&self, dir: &Path, origin: &str, target: &str) -> Result<Record> {
+        let rd = Record::new(origin, target)?;
+        match self.record_dir {
+            true => rd.with_dir(dir, target),
+            false => Ok(rd),
+        }
+    }
+
+    ///Record to reverse a file by with the name it restores,among records of its name newest
+    ///first:those of its directory,otherwise unrecorded ones and those of other directories if
+    ///relocation is accepted;records restoring different names chosen on a terminal when applied
+    ///in place,otherwise the file skipped
+    fn chosen_record(
+        &self,
+        rds: Vec<Record>,
+        dir_base: &DirBase,
+        in_place: bool,
+    ) -> Result<Option<(Record, String)>> {
+        let path = Path::new(&dir_base.dir).join(&dir_base.base);
+        let skip = |reason: String| {
+            warn!("Skip {:?},{}", dir_base.base, reason);
+            self.report.borrow_mut().skip(&path, &reason);
+        };
+        let here = canonical_path(Path::new(&dir_base.dir));
+        let (mut in_here, mut unrecorded, mut elsewhere) = (Vec::new(), Vec::new(), Vec::new());
+        for rd in rds {
+            match rd.encrypted_dir.as_deref() {
+                None => unrecorded.push(rd),
+                Some(enc) => {
+                    let dir = decrypted_name(enc, &dir_base.base)?;
+                    match Path::new(&dir) == here {
+                        true => in_here.push(rd),
+                        false => elsewhere.push((rd, dir)),
+                    }
+                }
+            }
+        }
+        let candidates = match (in_here.is_empty(), self.accept_relocation) {
+            (false, _) => in_here,
+            (true, false) => unrecorded,
+            (true, true) => {
+                let mut all: Vec<_> = unrecorded
+                    .into_iter()
+                    .chain(elsewhere.drain(..).map(|(rd, _)| rd))
+                    .collect();
+                all.sort_by_key(|rd| -rd.id);
+                all
+            }
+        };
+        if let (true, Some((_, dir))) = (candidates.is_empty(), elsewhere.first()) {
+            skip(format!(
+                "renamed in {:?},use '--accept-relocation' to reverse it here",
+                dir
+            ));
+            return Ok(None);
+        }
+
+        let mut origins: Vec<(Record, String)> = Vec::new();
+        for rd in candidates {
+            let origin = decrypted_name(&rd.encrypted_pre_name, &dir_base.base)?;
+            if !origins.iter().any(|(_, o)| *o == origin) {
+                origins.push((rd, origin));
+            }
+        }
+        if origins.len() < 2 {
+            return Ok(origins.pop());
+        }
+        if !in_place || !io::stdin().is_terminal() {
+            let names: Vec<_> = origins.iter().map(|(_, o)| o.as_str()).collect();
+            skip(format!(
+                "records restore different names {:?},choose one by reversing it in place on a \
+                 terminal",
+                names
+            ));
+            return Ok(None);
+        }
+
+        println!("{:?} was renamed from different names:", path);
+        for (i, (rd, origin)) in origins.iter().enumerate() {
+            println!("  {}) {} ({} times)", i + 1, origin, rd.count);
+        }
+        print!("Restore which one? [1-{}/N] ", origins.len());
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        match answer.trim().parse::<usize>() {
+            Ok(i) if (1..=origins.len()).contains(&i) => Ok(Some(origins.swap_remove(i - 1))),
+            _ => {
+                skip("no name chosen to restore".to_owned());
+                Ok(None)
+            }
+        }
+    }
+
+    ///Check names of a directory are case insensitive by the fs case option or probing
+    fn case_insensitive(&self, dir: &Path) -> bool {
+        match self.fs_case.as_str() {
+            "sensitive" => false,
+            "insensitive" => true,
+            _ => *self
+                .folds
+                .borrow_mut()
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| {
+                    self.fs
+                        .borrow()
+                        .case_insensitive(dir)
+                        .unwrap_or_else(|| case_insensitive(dir))
+                }),
+        }
+    }
+
+    ///Check a file other than the origin takes the target of a rename,targets differing only in
+    ///case taking the origin itself on case insensitive filesystems
+    fn taken(&self, dir_base: &DirBase, target: &str) -> bool {
+        let dir = Path::new(&dir_base.dir);
+        let same_file =
+            self.case_insensitive(dir) && target.to_lowercase() == dir_base.base.to_lowercase();
+
+        !same_file && self.fs.borrow().exists(&dir.join(target))
+    }
+
+    ///Check a rename may overwrite the file taking its target
+    fn overwrites(&self, dir_base: &DirBase, target: &str) -> bool {
+        let dir = Path::new(&dir_base.dir);
+        let is_file = |p: &Path| self.fs.borrow().metadata(p).is_ok_and(|m| m.is_file());
+
+        self.on_conflict == "overwrite"
+            && is_file(&dir.join(&dir_base.base))
+            && is_file(&dir.join(target))
+    }
+
+    ///Move the file taking the target of a rename to the trash if overwriting,logged as
+    ///'trashed'
+    fn displace(&self, dir_base: &DirBase, target: &str) -> Result<()> {
+        if !self.taken(dir_base, target) || !self.overwrites(dir_base, target) {
+            return Ok(());
+        }
+        let t_path = Path::new(&dir_base.dir).join(target);
+        let (Some(dir), Some(base)) = (t_path.parent(), t_path.file_name()) else {
+            return Ok(());
+        };
+        let taken = DirBase {
+            dir: os2string(Some(dir.as_os_str()))?,
+            base: os2string(Some(base))?,
+        };
+        let rlt = self.fs.borrow_mut().trash(&t_path);
+
+        log_renamed(self, &taken, &taken.base, "trashed", rlt)
+    }
+
+    ///Key of a path among renames of this run,in lowercase if case insensitive
+    fn run_key(&self, path: &Path) -> PathBuf {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        match self.case_insensitive(dir) {
+            true => PathBuf::from(path.to_string_lossy().to_lowercase()),
+            false => path.to_path_buf(),
+        }
+    }
+
+    ///Claim the target of a rename in this run,false if another rename already claimed it;the
+    ///origin is vacated once its target is claimed
+    fn claim(&self, origin: &Path, target: &Path) -> bool {
+        let key = self.run_key(target);
+        let mut claimed = self.claimed.borrow_mut();
+        match claimed.get(&key) {
+            Some(other) if other != origin => false,
+            _ => {
+                claimed.insert(key, origin.to_path_buf());
+                self.vacated.borrow_mut().insert(self.run_key(origin));
+                true
+            }
+        }
+    }
+
+    ///Check a path is the origin of a rename planned earlier in this run,so that a chain of
+    ///renames such as 'b'->'c' before 'a'->'b' isn't skipped before it's applied
+    fn vacated(&self, path: &Path) -> bool {
+        self.vacated.borrow().contains(&self.run_key(path))
+    }
+
+    ///Take a path back from the vacated origins once its rename failed
+    fn unvacate(&self, path: &Path) {
+        self.vacated.borrow_mut().remove(&self.run_key(path));
+    }
+
+    ///Apply renames through the filesystem instead of local filesystem
+    pub fn with_fs(mut self, fs: Box<dyn FsBackend>) -> Self {
+        self.fs = RefCell::new(fs);
+        self
+    }
+
+    ///Begin a transaction,all records and operations are written in it until commit
+    pub fn begin(&self) -> Result<()> {
+        if self.conn.is_autocommit() {
+            self.conn.execute_batch("BEGIN")?;
+        }
+
+        Ok(())
+    }
+
+    ///Commit the transaction if any,rule hits saved with it
+    pub fn commit(&self) -> Result<()> {
+        if let Some(stats) = &self.stats {
+            for ((stage, rule), hits) in stats.borrow_mut().drain() {
+                upsert_rule_hits(&self.conn, stage, &rule, hits)?;
+            }
+        }
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("COMMIT")?;
+        }
+        self.pending.set(0);
+        self.marked.set(0);
+
+        Ok(())
+    }
+
+    ///Count the rules which fire on a path if rule stats is on,every rule counted once per path
+    fn hit(&self, path: &Path) -> Result<()> {
+        let Some(stats) = &self.stats else {
+            return Ok(());
+        };
+        let meta = self.fs.borrow().metadata(path).ok();
+        let (_, steps) = trace_entry(
+            path,
+            &os2string(path.file_name())?,
+            meta.as_ref(),
+            &self.renamer.rules,
+        )?;
+
+        let fired: HashSet<_> = steps.into_iter().map(|s| (s.stage, s.rule)).collect();
+        let mut stats = stats.borrow_mut();
+        fired
+            .into_iter()
+            .for_each(|k| *stats.entry(k).or_default() += 1);
+
+        Ok(())
+    }
+
+    ///Save paths to process with optional targets as checkpoint of session
+    pub fn checkpoint(&self, argv: &[String], paths: &[(&PathBuf, Option<String>)]) -> Result<()> {
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|(p, t)| (p.to_string_lossy().to_string(), t.clone()))
+            .collect();
+
+        insert_checkpoint(&self.conn, &self.session.id, argv, &paths)
+    }
+
+    ///Mark a path processed in checkpoint,marks committed in batches of commit_every as renames
+    ///so that a resumed run skips them
+    fn processed(&self, path: &Path) -> Result<()> {
+        update_checkpoint_path(&self.conn, &self.session.id, &path.to_string_lossy())?;
+        self.marked.set(self.marked.get() + 1);
+        if self.commit_every > 0 && self.marked.get() >= self.commit_every {
+            self.commit()?;
+            self.begin()?;
+        }
+
+        Ok(())
+    }
+
+    ///Finish checkpoint of an in-place run,an aborted run is summarized and its renames are
+    ///rolled back if asked
+    fn finish(&self, finished: bool, rollback_on_abort: bool) -> Result<()> {
+        if aborted() {
+            if rollback_on_abort {
+                let n = self.rollback()?;
+                delete_checkpoint(&self.conn, &self.session.id)?;
+                warn!("Aborted,{} applied renames rolled back", n);
+                return Ok(());
+            }
+            let n = retrieve_session_operations(&self.conn, &self.session.id)?
+                .iter()
+                .filter(|op| op.status == "renamed" || op.status == "reversed")
+                .count();
+            warn!("Aborted,{} renames applied", n);
+        }
+
+        self.checkpoint_done(finished)
+    }
+
+    ///Reverse renames applied in this session,latest first
+    fn rollback(&self) -> Result<usize> {
+        let ops: Vec<_> = retrieve_session_operations(&self.conn, &self.session.id)?
+            .into_iter()
+            .filter(|op| {
+                matches!(
+                    op.status.as_str(),
+                    "renamed" | "reversed" | "trashed" | "linked"
+                )
+            })
+            .collect();
+
+        for op in ops.iter().rev() {
+            if op.status == "linked" {
+                let dup = DirBase {
+                    dir: op.dir.clone(),
+                    base: op.origin.clone(),
+                };
+                let rlt = unlink_dup(&Path::new(&op.dir).join(&op.origin));
+                log_renamed(self, &dup, &op.target, "unlinked", rlt)?;
+                continue;
+            }
+            //restored once the rename overwriting it is rolled back
+            if op.status == "trashed" {
+                let taken = DirBase {
+                    dir: op.dir.clone(),
+                    base: op.origin.clone(),
+                };
+                let path = Path::new(&op.dir).join(&op.origin);
+                let rlt = self.fs.borrow_mut().restore(&path);
+                if let Err(err) = log_renamed(self, &taken, &op.origin, "restored", rlt) {
+                    warn!("{:?} left in the trash,{}", path, err);
+                }
+                continue;
+            }
+            let back = DirBase {
+                dir: op.dir.clone(),
+                base: op.target.clone(),
+            };
+            rename_logged(self, &back, &op.origin, "rolled-back")?;
+            if op.status == "renamed" {
+                //the record this run bumped,not the newest of its name
+                let renamed = DirBase {
+                    dir: op.dir.clone(),
+                    base: op.origin.clone(),
+                };
+                let rd = renamed_record(self, &renamed, &op.target)?;
+                if let Some(rd) = retrieve_same_record(&self.conn, &rd)? {
+                    release_record(&self.conn, &rd)?;
+                }
+            } else {
+                let dir = Path::new(&op.dir);
+                insert_record(&self.conn, self.record(dir, &op.target, &op.origin)?)?;
+            }
+        }
+
+        Ok(ops
+            .iter()
+            .filter(|op| op.status != "trashed" && op.status != "linked")
+            .count())
+    }
+
+    ///Drop processed paths from checkpoint,warn how to continue if any path left
+    fn checkpoint_done(&self, finished: bool) -> Result<()> {
+        delete_checkpoint_done(&self.conn, &self.session.id)?;
+        if !finished {
+            warn!(
+                "Run stopped with paths left,continue it by 'fdn resume {}'",
+                self.session.id
+            );
+        }
+
+        Ok(())
+    }
+
+    ///Count an applied operation and commit periodically if commit_every set
+    fn applied(&self) -> Result<()> {
+        self.pending.set(self.pending.get() + 1);
+        if self.commit_every > 0 && self.pending.get() >= self.commit_every {
+            self.commit()?;
+            self.begin()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        //renames already applied on disk,so keep their records
+        let _ = self.commit();
+    }
+}
+
+///Advisory lock on a directory tree,held by an in-place run so that other runs can't race it
+#[derive(Debug)]
+pub struct TreeLock {
+    file: File,
+    path: PathBuf,
+    ///locks of the root held exclusive and of its ancestors held shared in the lock directory,so
+    ///that runs on nested trees exclude each other
+    nested: Vec<File>,
+}
+
+impl TreeLock {
+    ///Lock the tree of an input path if args apply changes in place
+    pub fn for_run(input: &Path, args: &Args) -> Result<Option<Self>> {
+        if !args.in_place {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::acquire(&Self::root(input), lock_wait(args))?))
+    }
+
+    ///Root of the tree of an input path,the directory itself or that of a file
+    fn root(input: &Path) -> PathBuf {
+        match input.is_dir() {
+            true => input.to_path_buf(),
+            false => input
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .to_path_buf(),
+        }
+    }
+
+    ///Lock trees of input paths if args apply changes in place,a tree shared by inputs or nested
+    ///in the tree of another input locked once by the outermost one
+    pub fn for_inputs(inputs: &[PathBuf], args: &Args) -> Result<Vec<Self>> {
+        if !args.in_place {
+            return Ok(Vec::new());
+        }
+        let roots = unique_paths(
+            inputs
+                .iter()
+                .map(|input| {
+                    let root = Self::root(input);
+                    root.canonicalize().unwrap_or(root)
+                })
+                .collect(),
+        );
+        let (wait, started) = (lock_wait(args), Instant::now());
+
+        roots
+            .iter()
+            .filter(|root| !roots.iter().any(|r| r != *root && root.starts_with(r)))
+            .map(|root| {
+                Self::acquire_in(&lock_dir()?, root, wait.saturating_sub(started.elapsed()))
+            })
+            .collect()
+    }
+
+    ///Lock the tree rooted at dir,waiting at most wait for another run to release it
+    pub fn acquire(dir: &Path, wait: Duration) -> Result<Self> {
+        Self::acquire_in(&lock_dir()?, dir, wait)
+    }
+
+    ///Lock the tree rooted at dir by its lock file and by lock files of the paths in the lock
+    ///directory,waiting at most wait for another run to release them
+    pub fn acquire_in(locks: &Path, dir: &Path, wait: Duration) -> Result<Self> {
+        let started = Instant::now();
+        let path = dir.join(LOCK_FILE_NAME);
+        let file = loop {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(&path)?;
+            if !lock_file(&file, true, dir, wait, started)? {
+                let pid = fs::read_to_string(&path).unwrap_or_default();
+                return Err(anyhow!(
+                    "{:?} is locked by another fdn run(pid {}),use '--wait' to wait for it",
+                    dir,
+                    pid.trim()
+                ));
+            }
+
+            //the previous holder removes the lock file on release,so lock the new one again
+            if same_file(&file, &path) {
+                file.set_len(0)?;
+                write!(file, "{}", process::id())?;
+                break file;
+            }
+        };
+
+        //ancestors first as in every run,so that runs waiting for each other can't deadlock
+        fs::create_dir_all(locks)?;
+        let root = dir.canonicalize()?;
+        let mut nested = Vec::new();
+        for d in root.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            let key = xxh3_64(d.as_os_str().as_encoded_bytes());
+            let n_file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(locks.join(format!("{:016x}.lock", key)))?;
+            if !lock_file(&n_file, d == root, dir, wait, started)? {
+                //lock file of the root removed on drop,before others are unlocked
+                drop(Self { file, path, nested });
+                return Err(anyhow!(
+                    "A tree nesting or nested in {:?} is locked by another fdn run,use '--wait' \
+                     to wait for it",
+                    dir
+                ));
+            }
+            nested.push(n_file);
+        }
+
+        Ok(Self { file, path, nested })
+    }
+}
+
+///Time to wait for a tree locked by another run,forever by '--wait',not at all by
+///'--fail-if-locked'
+fn lock_wait(args: &Args) -> Duration {
+    match (args.wait, args.fail_if_locked) {
+        (true, _) => Duration::MAX,
+        (false, true) => Duration::ZERO,
+        (false, false) => LOCK_WAIT,
+    }
+}
+
+///Directory of lock files of the trees locked by runs,beside the database
+fn lock_dir() -> Result<PathBuf> {
+    let db_path = default_db_path()?;
+
+    Ok(db_path.with_file_name("locks"))
+}
+
+///Lock a file exclusive or shared,polling until wait has passed since started,return false if
+///it is still locked by another run
+fn lock_file(
+    file: &File,
+    exclusive: bool,
+    dir: &Path,
+    wait: Duration,
+    started: Instant,
+) -> Result<bool> {
+    let mut warned = false;
+    loop {
+        let rlt = match exclusive {
+            true => file.try_lock(),
+            false => file.try_lock_shared(),
+        };
+        match rlt {
+            Ok(()) => return Ok(true),
+            Err(TryLockError::WouldBlock) if started.elapsed() < wait => {
+                if !warned {
+                    warn!("Waiting for another fdn run on {:?}", dir);
+                    warned = true;
+                }
+                thread::sleep(LOCK_POLL);
+            }
+            Err(TryLockError::WouldBlock) => return Ok(false),
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+    }
+}
+
+impl Drop for TreeLock {
+    fn drop(&mut self) {
+        //remove before unlock,so a waiting run finds its file gone and retries
+        let _ = fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+        self.nested.iter().for_each(|f| {
+            let _ = f.unlock();
+        });
+    }
+}
+
+///Check an opened file is still the file at path
+#[cfg(unix)]
+fn same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+///Check an opened file is still the file at path
+#[cfg(not(unix))]
+fn same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+///Stop runs after the current rename on SIGINT or SIGTERM,exit at once on the second signal
+pub fn handle_signals() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if ABORTED.swap(true, AtomicOrdering::SeqCst) {
+            process::exit(130);
+        }
+    })?;
+
+    Ok(())
+}
+
+///Check the run is aborted by a signal
+pub fn aborted() -> bool {
+    ABORTED.load(AtomicOrdering::SeqCst)
+}
+
+///Return a new session id generated from current time and process id
+pub fn new_session_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    hashed_name(&format!("{}{}", nanos, process::id()))
+        .chars()
+        .take(8)
+        .collect()
+}
+
+///Check a file name matches a skip pattern,case insensitively and a '*' for any chars
+pub fn is_skipped(name: &str, skips: &[String]) -> bool {
+    let name = name.to_lowercase();
+    skips.iter().any(|s| {
+        let s = s.to_lowercase();
+        match s.split_once('*') {
+            Some((head, tail)) => {
+                name.len() >= head.len() + tail.len()
+                    && name.starts_with(head)
+                    && name.ends_with(tail)
+            }
+            None => name == s,
+        }
+    })
+}
+
+///Absolute path with '.','..' and symlinks of its directory resolved,the last component kept
+///as is so that a symlink itself is renamed rather than its target;a path not existing only made
+///absolute
+pub fn canonical_path(path: &Path) -> PathBuf {
+    let abs = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+    //no file name for the root or a path ending with '..'
+    match (abs.parent(), abs.file_name()) {
+        (Some(dir), Some(name)) => fs::canonicalize(dir).map_or(abs.clone(), |d| d.join(name)),
+        _ => fs::canonicalize(&abs).unwrap_or(abs),
+    }
+}
+
+///Deepest directory containing all paths,a file path standing for its directory
+pub fn common_dir(paths: &[PathBuf]) -> PathBuf {
+    let dirs = paths.iter().map(|p| match p.is_dir() {
+        true => p.as_path(),
+        false => p.parent().unwrap_or(p),
+    });
+
+    dirs.reduce(|common, dir| {
+        common
+            .ancestors()
+            .find(|a| dir.starts_with(a))
+            .unwrap_or(Path::new(""))
+    })
+    .unwrap_or(Path::new(""))
+    .to_path_buf()
+}
+
+///Paths with duplicates removed,the first one kept in order
+pub fn unique_paths(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+
+    paths
+        .into_iter()
+        .filter(|p| seen.insert(p.clone()))
+        .collect()
+}
+
+///Return absolute paths,files of names matching skips left out
+pub fn regular_files(
+    directory: &Path,
+    depth: usize,
+    excludes: &Excludes,
+    skips: &[String],
+) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<_> = WalkDir::new(directory)
+        .max_depth(depth)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            if entry.file_type().is_file() {
+                Some(entry.into_path())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    paths.retain(|path| !excludes.excluded(path));
+    paths.retain(|path| {
+        !is_skipped(
+            &path.file_name().unwrap_or_default().to_string_lossy(),
+            skips,
+        )
+    });
+
+    Ok(paths)
+}
+
+///Return directories
+pub fn directories(directory: &Path, depth: usize, excludes: &Excludes) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<_> = WalkDir::new(directory)
+        .max_depth(depth)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            if entry.file_type().is_dir() {
+                Some(entry.into_path())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    paths.retain(|path| !excludes.excluded(path));
+
+    //subdirectories before their parents so that they are renamed first
+    paths.sort_by(|a, b| natural_path_cmp(b, a));
+
+    Ok(paths)
+}
+
+///Create DirBase struct from abs_path
+fn dir_base(abs_path: &Path) -> Option<DirBase> {
+    if let (Some(base), Some(dir_path)) = (abs_path.file_name(), abs_path.parent()) {
+        if let (Some(dir), Some(base)) = (dir_path.to_str(), base.to_str()) {
+            Some(DirBase {
+                dir: dir.to_owned(),
+                base: base.to_owned(),
+            })
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+///Check a unix path is hidden or not
+#[cfg(unix)]
+fn is_hidden_unix(path: &Path) -> bool {
+    match path.file_name() {
+        Some(n) => match n.to_str() {
+            Some(s) => s.starts_with('.'),
+            None => false,
+        },
+        None => false,
+    }
+}
+
+#[cfg(windows)]
+use std::os::windows::fs::MetadataExt;
+#[cfg(windows)]
+use winapi::um::winnt::FILE_ATTRIBUTE_HIDDEN;
+
+///Check a windows path is hidden or not
+#[cfg(windows)]
+fn is_hidden_windows(path: &Path) -> bool {
+    match path.metadata() {
+        Ok(metadata) => metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0,
+        Err(_) => false, // If there's an error, assume it's not hidden
+    }
+}
+
+///Check a directory is on a case insensitive filesystem by looking up one of its entries by the
+///name in swapped case,the platform default if it has no such entry
+pub fn case_insensitive(dir: &Path) -> bool {
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+    let swapped = |s: &str| -> String {
+        s.chars()
+            .flat_map(|c| match c.is_uppercase() {
+                true => c.to_lowercase().collect::<Vec<_>>(),
+                false => c.to_uppercase().collect(),
+            })
+            .collect()
+    };
+    let names: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok()?.file_name().into_string().ok())
+        .collect();
+    let probe = names
+        .iter()
+        .map(|n| swapped(n))
+        .find(|s| !names.contains(s));
+
+    match probe {
+        Some(s) => dir.join(s).symlink_metadata().is_ok(),
+        None => cfg!(any(target_os = "macos", windows)),
+    }
+}
+
+///Check a path or any of its directories under the root is hidden,the root itself not checked
+pub fn hidden_in(path: &Path, root: &Path) -> bool {
+    path.ancestors()
+        .take_while(|a| *a != root && a.starts_with(root))
+        .any(is_hidden)
+}
+
+///Check a path is hidden or not
+fn is_hidden(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        is_hidden_unix(path)
+    }
+    #[cfg(windows)]
+    {
+        is_hidden_windows(path)
+    }
+}
+
+///Check a path is fdn's own database,lock file or inside a fdn config directory
+fn is_fdn_own(path: &Path, db_path: &Path) -> bool {
+    if path
+        .components()
+        .any(|c| c.as_os_str() == OsStr::new(CONFIG_DIR_NAME))
+        || path.file_name() == Some(OsStr::new(LOCK_FILE_NAME))
+    {
+        return true;
+    }
+
+    //database file and its journal files,such as fdn.db-journal
+    let path = path.canonicalize().unwrap_or(path.to_path_buf());
+    match (
+        path.parent(),
+        path.file_name(),
+        db_path.parent(),
+        db_path.file_name(),
+    ) {
+        (Some(dir), Some(name), Some(db_dir), Some(db_name)) => {
+            dir == db_dir
+                && name
+                    .to_string_lossy()
+                    .starts_with(&*db_name.to_string_lossy())
+        }
+        _ => false,
+    }
+}
+
+///Remove fdn's own database and config files from paths and print a notice for each of them
+fn exclude_fdn_own(paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let db_path = default_db_path()?;
+    let db_path = db_path.canonicalize().unwrap_or(db_path);
+
+    Ok(paths
+        .into_iter()
+        .filter(|p| {
+            let own = is_fdn_own(p, &db_path);
+            //lock file always exists during an in-place run,skip it quietly
+            if own && p.file_name() != Some(OsStr::new(LOCK_FILE_NAME)) {
+                warn!("Skip fdn's own database or config file:{:?}", p);
+            }
+            !own
+        })
+        .collect())
+}
+
+///Remove continuouse "word" in "source"
+fn remove_continuous(source: &str, word: &str) -> Result<String> {
+    let re = Regex::new(&format!(r"(?i){}{}+", word, word))?;
+    Ok(re.replace_all(source, word).to_string())
+}
+
+///Remove words of letters repeated right after themselves in "source" split by "sep",compared in
+///any case,words of digits only such as "1_1" kept
+fn remove_dup_words(source: &str, sep: &str) -> String {
+    if sep.is_empty() {
+        return source.to_owned();
+    }
+    let mut words: Vec<&str> = Vec::new();
+    for w in source.split(sep) {
+        let dup = w.chars().any(char::is_alphabetic)
+            && words
+                .last()
+                .is_some_and(|l| l.to_lowercase() == w.to_lowercase());
+        if !dup {
+            words.push(w);
+        }
+    }
+
+    words.join(sep)
+}
+
+///Remove prefix separator and suffix separator
+fn remove_prefix_sep_suffix_sep<'a>(s: &'a str, sep: &'a str) -> &'a str {
+    let s = s.strip_prefix(sep).unwrap_or(s);
+    s.strip_suffix(&sep).unwrap_or(s)
+}
+
+///Rename a file or directory's name into target and log the operation with status or "failed"
+fn rename_logged(ctx: &Context, dir_base: &DirBase, target: &str, status: &str) -> Result<()> {
+    let rlt = rename_into(
+        &mut **ctx.fs.borrow_mut(),
+        Path::new(&dir_base.dir),
+        &dir_base.base,
+        target,
+    );
+
+    log_renamed(ctx, dir_base, target, status, rlt)
+}
+
+///Rename base into target under dir,missing directories of target such as "Show/Season 01/"
+///created first
+fn rename_into(fs: &mut dyn FsBackend, dir: &Path, base: &str, target: &str) -> io::Result<()> {
+    if let Some(sub) = Path::new(target).parent() {
+        if !sub.as_os_str().is_empty() {
+            fs.create_dir_all(&dir.join(sub))?;
+        }
+    }
+
+    fs.rename(&dir.join(base), &dir.join(target))
+}
+
+///Path of a path relative to a directory such as "../a.txt",both made absolute and '.' and '..'
+///resolved lexically
+fn relative_path(dir: &Path, path: &Path) -> Result<PathBuf> {
+    let lexical = |p: &Path| -> Result<PathBuf> {
+        let p = if p.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            p
+        };
+        let mut abs = PathBuf::new();
+        for c in std::path::absolute(p)?.components() {
+            match c {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    abs.pop();
+                }
+                c => abs.push(c),
+            }
+        }
+        Ok(abs)
+    };
+    let (dir, path) = (lexical(dir)?, lexical(path)?);
+    let (dir, path): (Vec<_>, Vec<_>) = (dir.components().collect(), path.components().collect());
+    let common = dir.iter().zip(&path).take_while(|(a, b)| a == b).count();
+
+    Ok(dir[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .chain(path[common..].iter().copied())
+        .collect())
+}
+
+///Log the result of a rename with status or "failed"
+fn log_renamed(
+    ctx: &Context,
+    dir_base: &DirBase,
+    target: &str,
+    status: &str,
+    rlt: io::Result<()>,
+) -> Result<()> {
+    let status = if rlt.is_ok() { status } else { "failed" };
+    let op = Operation::new(&ctx.session.id, dir_base, target, status);
+    ctx.session.log(&ctx.conn, &op)?;
+
+    Ok(rlt?)
+}
+
+///Normalize a file name by rules without touching the filesystem,extension kept
+pub fn normalize_name(name: &str, rules: &RuleSet) -> String {
+    plan_base(name, true, rules).unwrap_or_else(|_| name.to_owned())
+}
+
+///Normalize a name twice and return both results if the second pass changes it again,None
+///for an idempotent name
+pub fn second_pass(name: &str, rules: &RuleSet) -> Option<(String, String)> {
+    let first = normalize_name(name, rules);
+    let second = normalize_name(&first, rules);
+
+    (first != second).then_some((first, second))
+}
+
+///Assert every name is idempotent under the rules,a helper for tests of rule sets
+pub fn assert_idempotent<S: AsRef<str>>(rules: &RuleSet, names: &[S]) {
+    names.iter().for_each(|name| {
+        let name = name.as_ref();
+        if let Some((first, second)) = second_pass(name, rules) {
+            panic!("{:?} not idempotent:{:?} -> {:?}", name, first, second);
+        }
+    });
+}
+
+///Report names which a second normalization pass changes again,error if any
+pub fn check_idempotent(files: Vec<PathBuf>, names: &[String], args: &Args) -> Result<()> {
+    let rules = RuleSet::load(&open_db(None)?)?.with_args(args);
+    let names: Vec<_> = names
+        .iter()
+        .cloned()
+        .chain(
+            files
+                .iter()
+                .filter_map(|f| f.file_name().and_then(OsStr::to_str).map(str::to_owned)),
+        )
+        .collect();
+
+    let mut failed = 0;
+    names.iter().for_each(|name| {
+        if let Some((first, second)) = second_pass(name, &rules) {
+            failed += 1;
+            println!("   {}\n-->{}\n-->{}", name, first, second);
+        }
+    });
+
+    match failed {
+        0 => Ok(()),
+        n => Err(anyhow!(
+            "{} of {} names not idempotent,the rules do not converge",
+            n,
+            names.len()
+        )),
+    }
+}
+
+///Normalize names of entries inside a zip or tar archive by rules,each directory or file
+///renamed listed once,and rewrite the archive if applied in place
+#[cfg(feature = "archive")]
+pub fn archive_names(file: &Path, args: &Args) -> Result<()> {
+    let rules = RuleSet::load(&open_db(None)?)?.with_args(args);
+    let entries = archive::entries(file)?;
+    let plan = |name: &str, is_file: bool| plan_base(name, is_file, &rules);
+    let renamed = archive::planned(&entries, &plan)?;
+
+    //a renamed directory listed once though entries under it are renamed with it
+    let mut changes = BTreeMap::new();
+    for (origin, target) in &renamed {
+        let comps: Vec<_> = origin.split('/').collect();
+        for (i, (o, t)) in comps.iter().zip(target.split('/')).enumerate() {
+            if *o != t {
+                changes.insert(comps[..=i].join("/"), t.to_owned());
+            }
+        }
+    }
+    let color = color_enabled(&args.color);
+    for (origin, target) in &changes {
+        let (dir, base) = archive::split_entry(origin);
+        let d_b = DirBase {
+            dir: file.join(dir).to_string_lossy().into_owned(),
+            base: base.to_owned(),
+        };
+        print_change(&d_b, target, args, color, None)?;
+    }
+    if args.in_place && !renamed.is_empty() {
+        archive::rewrite(file, &renamed)?;
+    }
+
+    Ok(())
+}
+
+///Normalize names of entries inside an archive
+#[cfg(not(feature = "archive"))]
+pub fn archive_names(_file: &Path, _args: &Args) -> Result<()> {
+    Err(anyhow!("fdn is built without feature 'archive'"))
+}
+
+///Plan the target name of a base name by rules,extension kept for regular file
+pub(crate) fn plan_base(base_name: &str, is_file: bool, rules: &RuleSet) -> Result<String> {
+    Ok(trace_base(base_name, is_file, rules)?.0)
+}
+
+///Plan the target name of a base name by rules and return the steps which changed it
+fn trace_base(base_name: &str, is_file: bool, rules: &RuleSet) -> Result<(String, Vec<Step>)> {
+    let meta = FsMeta {
+        is_dir: !is_file,
+        len: 0,
+        modified: None,
+        created: None,
+    };
+    trace_entry(Path::new(base_name), base_name, Some(&meta), rules)
+}
+
+///Plan the target name of an entry by rules and return the steps which changed it,the path and
+///metadata of the entry given to the script,extension kept only if metadata says a regular file
+pub(crate) fn trace_entry(
+    path: &Path,
+    base_name: &str,
+    meta: Option<&FsMeta>,
+    rules: &RuleSet,
+) -> Result<(String, Vec<Step>)> {
+    let is_file = meta.is_some_and(|m| m.is_file());
+    let (f_stem, f_ext) = match is_file {
+        true => split_ext(base_name, &rules.compound_exts),
+        false => (base_name, None),
+    };
+
+    let (f_stem, mut steps) = rules.scoped(f_ext).trace_stem(f_stem)?;
+    let f_ext = f_ext.map(|f_ext| {
+        //aliases before case
+        let lower = f_ext.to_lowercase();
+        let f_ext = match rules.ext_aliases.get(&lower) {
+            Some(alias) => {
+                let rule = format!("{}:{}", lower, alias);
+                step(
+                    &mut steps,
+                    STAGES[22],
+                    &rule,
+                    f_ext.to_owned(),
+                    alias.clone(),
+                )
+            }
+            None => f_ext.to_owned(),
+        };
+        let after = rules.ext_cased(&f_ext);
+        step(&mut steps, STAGES[22], &rules.ext_case, f_ext, after)
+    });
+    let f_stem = match &rules.script {
+        Some(script) => match script.stem(&f_stem, f_ext.as_deref(), path, meta) {
+            Ok(after) => {
+                let rule = script.path.to_string_lossy();
+                step(&mut steps, STAGES[23], &rule, f_stem, after)
+            }
+            Err(err) => {
+                warn!("Keep normalized name of {:?},{}", path, err);
+                f_stem
+            }
+        },
+        None => f_stem,
+    };
+
+    Ok((
+        match f_ext {
+            Some(f_ext) => format!("{}.{}", f_stem, f_ext),
+            None => f_stem,
+        },
+        steps,
+    ))
+}
+
+///Steps which turn the origin into the edited name,None if the edited name is not from rules
+fn explain_change(
+    rules: &RuleSet,
+    dir_base: &DirBase,
+    edit: &str,
+    args: &Args,
+) -> Option<Vec<Step>> {
+    let meta = FsMeta {
+        is_dir: args.filetype == "d",
+        len: 0,
+        modified: None,
+        created: None,
+    };
+    let path = Path::new(&dir_base.dir).join(&dir_base.base);
+    match trace_entry(&path, &dir_base.base, Some(&meta), rules) {
+        Ok((planned, steps)) if planned == edit => Some(steps),
+        _ => None,
+    }
+}
+
+///Rename a file or directory's name into specific target or by default
+fn fdn_f(
+    ctx: &Context,
+    dir_base: &DirBase,
+    target: Option<String>,
+    in_place: bool,
+) -> Result<String> {
+    let s_path = Path::new(&dir_base.dir).join(&dir_base.base);
+    let given = target.is_some();
+    let action = match target {
+        Some(tn) => ctx.renamer.action(&s_path, &tn),
+        None => {
+            ctx.hit(&s_path)?;
+            ctx.renamer.plan_in(&**ctx.fs.borrow(), &s_path)?
+        }
+    };
+    let base_name = uncollided(
+        ctx,
+        dir_base,
+        checked_target(ctx, dir_base, &action, given)?,
+    );
+
+    //take effect
+    if base_name != dir_base.base && in_place {
+        ctx.displace(dir_base, &base_name)?;
+        rename_logged(ctx, dir_base, &base_name, "renamed")
+            .inspect_err(|_| ctx.unvacate(&s_path))?;
+        record_renamed(ctx, dir_base, &base_name)?;
+    }
+
+    Ok(base_name)
+}
+
+///Plan a name not valid UTF-8 by its decodable part with invalid bytes dropped and rename the
+///file to it if in place;the origin shown with invalid bytes as U+FFFD and the rename not
+///recorded since the name can not be restored
+fn fdn_lossy(
+    ctx: &Context,
+    path: &Path,
+    target: Option<String>,
+    in_place: bool,
+) -> Result<(DirBase, String)> {
+    let (Some(dir), Some(name)) = (path.parent().and_then(Path::to_str), path.file_name()) else {
+        return Err(anyhow!("Directory is not valid UTF-8:{:?}", path));
+    };
+    let dir_base = DirBase {
+        dir: dir.to_owned(),
+        base: name.to_string_lossy().into_owned(),
+    };
+    let decodable = dir_base.base.replace(char::REPLACEMENT_CHARACTER, "");
+    if decodable.is_empty() {
+        return Err(anyhow!("Name has no valid UTF-8 part:{:?}", path));
+    }
+    let d_path = Path::new(dir).join(&decodable);
+    let given = target.is_some();
+    let action = match target {
+        Some(tn) => ctx.renamer.action(&d_path, &tn),
+        None => {
+            let meta = ctx.fs.borrow().metadata(path).ok();
+            ctx.renamer.plan_entry(&d_path, meta.as_ref())?
+        }
+    };
+    let base_name = uncollided(
+        ctx,
+        &dir_base,
+        checked_target(ctx, &dir_base, &action, given)?,
+    );
+
+    //a violating or colliding target keeps the origin,which is left untouched
+    if base_name != dir_base.base && in_place {
+        ctx.displace(&dir_base, &base_name)?;
+        ctx.fs
+            .borrow_mut()
+            .rename(path, &Path::new(dir).join(&base_name))?;
+        warn!(
+            "{:?} renamed from a name not valid UTF-8,not recorded",
+            base_name
+        );
+    }
+
+    Ok((dir_base, base_name))
+}
+
+///Return target of action relative to the directory if it passes the validators and stays a name
+///in the directory,otherwise warn and keep the origin,reported as skipped;a target given such as
+///by plex may have subdirectories
+fn checked_target(
+    ctx: &Context,
+    dir_base: &DirBase,
+    action: &RenameAction,
+    given: bool,
+) -> Result<String> {
+    let skip = |reason: String| {
+        warn!("Skip {:?},{}", dir_base.base, reason);
+        ctx.report.borrow_mut().skip(&action.from, &reason);
+        Ok(dir_base.base.clone())
+    };
+    if !action.violations.is_empty() {
+        return skip(format!(
+            "target {:?} violates {}",
+            action.to.file_name().unwrap_or_default(),
+            action.violations.join(",")
+        ));
+    }
+
+    let target = os2string(
+        action
+            .to
+            .strip_prefix(&dir_base.dir)
+            .ok()
+            .map(Path::as_os_str),
+    )?;
+    if unsafe_target(&target, given) {
+        return skip(format!(
+            "target {:?} is not a name in the directory",
+            target
+        ));
+    }
+
+    Ok(target)
+}
+
+///Check a target would leave its directory or isn't a name:empty,'.','..',or with a path
+///separator unless given with subdirectories,which must be plain names
+fn unsafe_target(target: &str, given: bool) -> bool {
+    if matches!(target, "" | "." | "..") {
+        return true;
+    }
+    match given {
+        true => Path::new(target)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_))),
+        false => target.contains(['/', '\\']),
+    }
+}
+
+///Return the target if no other rename of this run has it and no other file is there unless the
+///file is overwritten,otherwise warn and keep the origin,reported as skipped;targets differing
+///only in case colliding on case insensitive filesystems
+fn uncollided(ctx: &Context, dir_base: &DirBase, target: String) -> String {
+    let dir = Path::new(&dir_base.dir);
+    let origin = dir.join(&dir_base.base);
+    if target == dir_base.base {
+        return target;
+    }
+    let t_path = dir.join(&target);
+    if ctx.taken(dir_base, &target) && !ctx.overwrites(dir_base, &target) && !ctx.vacated(&t_path) {
+        let reason = format!("target {:?} exists", target);
+        warn!("Skip {:?},{}", dir_base.base, reason);
+        ctx.report.borrow_mut().skip(&origin, &reason);
+        return dir_base.base.clone();
+    }
+    if ctx.claim(&origin, &t_path) {
+        return target;
+    }
+
+    let reason = format!("target {:?} collides with another rename", target);
+    warn!("Skip {:?},{}", dir_base.base, reason);
+    ctx.report.borrow_mut().skip(&origin, &reason);
+    dir_base.base.clone()
+}
+
+///Insert the record of an applied rename so that it can be reversed,a target in other
+///directories recorded by its name with the origin relative to its directory
+fn record_renamed(ctx: &Context, dir_base: &DirBase, target: &str) -> Result<()> {
+    insert_record(&ctx.conn, renamed_record(ctx, dir_base, target)?)?;
+    ctx.applied()
+}
+
+///Record of an applied rename,a target in other directories recorded by its name with the origin
+///relative to its directory
+fn renamed_record(ctx: &Context, dir_base: &DirBase, target: &str) -> Result<Record> {
+    let t_path = Path::new(target);
+    let dir = Path::new(&dir_base.dir);
+    match (t_path.parent(), t_path.file_name()) {
+        (Some(sub), Some(name)) if !sub.as_os_str().is_empty() => {
+            let origin = relative_path(&dir.join(sub), &dir.join(&dir_base.base))?;
+            ctx.record(
+                &dir.join(sub),
+                &os2string(Some(origin.as_os_str()))?,
+                &os2string(Some(name))?,
+            )
+        }
+        _ => ctx.record(dir, &dir_base.base, target),
+    }
+}
+
+///Plan names in parallel and apply non-conflicting renames concurrently,results kept in order
+fn fdn_fs_jobs(
+    ctx: &Context,
+    items: &[(DirBase, Option<String>)],
+    args: &Args,
+) -> Result<Vec<(DirBase, String, Result<()>)>> {
+    let pool = ThreadPoolBuilder::new().num_threads(args.jobs).build()?;
+    let renamer = &ctx.renamer;
+
+    let planned = pool.install(|| {
+        items
+            .par_iter()
+            .map(|(d_b, tn)| {
+                let s_path = Path::new(&d_b.dir).join(&d_b.base);
+                match tn {
+                    Some(tn) => Ok(renamer.action(&s_path, tn)),
+                    None => renamer.plan_path(&s_path),
+                }
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+    let planned = items
+        .iter()
+        .zip(planned)
+        .map(|((d_b, tn), action)| {
+            if tn.is_none() {
+                ctx.hit(&action.from)?;
+            }
+            Ok((
+                d_b.clone(),
+                uncollided(ctx, d_b, checked_target(ctx, d_b, &action, tn.is_some())?),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    //renames beyond the limit are left for later runs
+    let mut left = args.limit.unwrap_or(usize::MAX);
+    let planned: Vec<_> = planned
+        .into_iter()
+        .take_while(|(d_b, tn)| {
+            if d_b.base != *tn {
+                if left == 0 {
+                    return false;
+                }
+                left -= 1;
+            }
+            true
+        })
+        .collect();
+
+    if !args.in_place {
+        return Ok(planned
+            .into_iter()
+            .map(|(d_b, tn)| (d_b, tn, Ok(())))
+            .collect());
+    }
+    if aborted() {
+        return Err(anyhow!("Aborted by signal"));
+    }
+
+    //a rename conflicts if its target is also the origin or target of another rename in batch
+    let path = |d_b: &DirBase, base: &str| Path::new(&d_b.dir).join(base);
+    let changed: Vec<_> = planned.iter().filter(|(d_b, tn)| d_b.base != *tn).collect();
+    let origins: HashSet<_> = changed
+        .iter()
+        .map(|(d_b, _)| path(d_b, &d_b.base))
+        .collect();
+    let mut seen = HashSet::new();
+    let dups: HashSet<_> = changed
+        .iter()
+        .map(|(d_b, tn)| path(d_b, tn))
+        .filter(|t| !seen.insert(t.clone()))
+        .collect();
+    let free = |d_b: &DirBase, tn: &str| {
+        let t = path(d_b, tn);
+        d_b.base != tn && !origins.contains(&t) && !dups.contains(&t)
+    };
+
+    let renamed: Vec<_> = pool.install(|| {
+        planned
+            .par_iter()
+            .map(|(d_b, tn)| match free(d_b, tn) {
+                true => Some(rename_into(&mut RealFs, Path::new(&d_b.dir), &d_b.base, tn)),
+                false => None,
+            })
+            .collect()
+    });
+
+    //log and record in order,conflicting renames applied serially here
+    planned
+        .into_iter()
+        .zip(renamed)
+        .map(|((d_b, tn), rlt)| {
+            //a target vacated by a rename which failed is still taken
+            let rlt = match rlt {
+                Some(rlt) => log_renamed(ctx, &d_b, &tn, "renamed", rlt),
+                None if d_b.base != tn && ctx.taken(&d_b, &tn) => {
+                    let rlt = Err(io::Error::from(io::ErrorKind::AlreadyExists));
+                    log_renamed(ctx, &d_b, &tn, "renamed", rlt)
+                }
+                None if d_b.base != tn => rename_logged(ctx, &d_b, &tn, "renamed"),
+                None => Ok(()),
+            };
+            let rlt = match (rlt, d_b.base != tn) {
+                (Ok(()), true) => record_renamed(ctx, &d_b, &tn),
+                (rlt, _) => rlt,
+            };
+            Ok((d_b, tn, rlt))
+        })
+        .collect()
+}
+
+///Print origin and edited name in the output format of args,with the rules which fired if
+///explain is given
+fn print_change(
+    dir_base: &DirBase,
+    edit: &str,
+    args: &Args,
+    color: bool,
+    explain: Option<&RuleSet>,
+) -> Result<()> {
+    let origin = &dir_base.base;
+    if origin.eq(edit) {
+        return Ok(());
+    }
+    let arrow = if args.in_place { "==>" } else { "-->" };
+
+    match args.format.as_str() {
+        "table" => {
+            //two columns and the arrow column between them
+            let col = args.width.saturating_sub(arrow.len() + 2).max(2) / 2;
+            let (o_r, e_r) = s_compare(
+                &truncate_width(origin, col),
+                &truncate_width(edit, col),
+                "",
+                color,
+            )?;
+            let pad = " ".repeat(col.saturating_sub(display_width(&o_r)?));
+            println!("{}{} {} {}", o_r, pad, arrow, e_r);
+        }
+        "json" => {
+            let mut v = json!({
+                "dir": dir_base.dir,
+                "origin": origin,
+                "target": edit,
+                "applied": args.in_place,
+            });
+            if let Some(rules) = explain {
+                v["steps"] = steps_json(explain_change(rules, dir_base, edit, args));
+            }
+            println!("{}", v);
+
+            return Ok(());
+        }
+        _ => {
+            let (o_r, e_r) = match args.align {
+                true => fname_compare(origin, edit, "a", color, &args.compound_ext)?,
+                false => fname_compare(origin, edit, "", color, &args.compound_ext)?,
+            };
+            println!("   {}\n{}{}", o_r, arrow, e_r);
+        }
+    }
+    if let Some(rules) = explain {
+        match explain_change(rules, dir_base, edit, args) {
+            Some(steps) => steps
+                .iter()
+                .for_each(|s| println!("   {} {:?}:{} -> {}", s.stage, s.rule, s.before, s.after)),
+            None => println!("   target given explicitly"),
+        }
+    }
+
+    Ok(())
+}
+
+///Steps in JSON,null if the target is not from rules
+fn steps_json(steps: Option<Vec<Step>>) -> serde_json::Value {
+    match steps {
+        Some(steps) => steps
+            .iter()
+            .map(
+                |s| json!({"stage": s.stage, "rule": s.rule, "before": s.before, "after": s.after}),
+            )
+            .collect(),
+        None => serde_json::Value::Null,
+    }
+}
+
+///Print changes sorted and grouped by directory,a directory header followed by its changes
+fn print_grouped(
+    mut changes: Vec<(DirBase, String)>,
+    args: &Args,
+    color: bool,
+    explain: Option<&RuleSet>,
+) -> Result<()> {
+    changes.retain(|(d_b, edit)| !d_b.base.eq(edit));
+    changes.sort_by(|(a, _), (b, _)| a.dir.cmp(&b.dir).then(a.base.cmp(&b.base)));
+
+    let mut groups: Vec<(String, Vec<(DirBase, String)>)> = Vec::new();
+    for (d_b, edit) in changes {
+        match groups.last_mut() {
+            Some((dir, group)) if *dir == d_b.dir => group.push((d_b, edit)),
+            _ => groups.push((d_b.dir.clone(), vec![(d_b, edit)])),
+        }
+    }
+
+    groups.iter().try_for_each(|(dir, group)| -> Result<()> {
+        if args.format == "json" {
+            let renames: Vec<_> = group
+                .iter()
+                .map(|(d_b, edit)| {
+                    let mut v = json!({"origin": d_b.base, "target": edit});
+                    if let Some(rules) = explain {
+                        v["steps"] = steps_json(explain_change(rules, d_b, edit, args));
+                    }
+                    v
+                })
+                .collect();
+            let v = json!({"dir": dir, "applied": args.in_place, "renames": renames});
+            println!("{}", v);
+        } else {
+            println!("{}:", dir);
+            group
+                .iter()
+                .try_for_each(|(d_b, edit)| print_change(d_b, edit, args, color, explain))?;
+        }
+        Ok(())
+    })
+}
+
+///Firstly rename files or directories's name into targets or by default,then do post-processing work
+pub fn fdn_fs_post(origins: Vec<PathBuf>, targets: Vec<String>, args: Args) -> Result<()> {
+    fdn_fs_in(Context::new(&args)?, origins, targets, args)
+}
+
+///Rename files or directories's name into targets or by default in a context,then do
+///post-processing work
+pub fn fdn_fs_in(
+    ctx: Context,
+    origins: Vec<PathBuf>,
+    targets: Vec<String>,
+    args: Args,
+) -> Result<()> {
+    let mut tgts: Vec<Option<String>> = vec![None];
+
+    if targets.is_empty() {
+        tgts.resize(origins.len(), None);
+    } else if origins.len() != targets.len() {
+        return Err(anyhow!(
+            "origins length {:?} must equal to targets length {:?}",
+            origins.len(),
+            targets.len()
+        ));
+    } else {
+        tgts = targets.into_iter().map(Some).collect();
+    }
+
+    let color = color_enabled(&args.color);
+
+    let kept = exclude_fdn_own(origins.clone())?;
+    let mut changes = Vec::new();
+    let explain = args.explain.then_some(&ctx.renamer.rules);
+    ctx.renamer.prefetch(&kept);
+
+    let mut items: Vec<_> = origins
+        .iter()
+        .zip(tgts)
+        .filter(|(of, _tn)| kept.contains(of))
+        .collect();
+    if args.limit.is_some() {
+        items.sort_by(|(a, _), (b, _)| natural_path_cmp(a, b));
+    }
+    let total = items.len();
+    let mut left = args.limit.unwrap_or(usize::MAX);
+    let mut processed = 0;
+    if args.in_place {
+        ctx.checkpoint(&args.argv, &items)?;
+    }
+    ctx.begin()?;
+
+    let settled = |of: &Path, origin: &str, rlt: &str| {
+        let status = match (origin == rlt, args.in_place) {
+            (true, _) => "unchanged",
+            (false, true) => "renamed",
+            (false, false) => "planned",
+        };
+        ctx.report.borrow_mut().settle(of, status);
+    };
+    let non_utf8 = |of: &Path| ctx.report.borrow_mut().skip(of, "name is not valid UTF-8");
+
+    let rlt = if args.jobs > 1
+        && args.non_utf8 == "skip"
+        && args.on_conflict == "skip"
+        && args.backend.is_none()
+        && origins.iter().all(|of| of.is_file())
+    {
+        let items: Vec<_> = items
+            .iter()
+            .filter_map(|(of, tn)| {
+                let d_b = dir_base(of);
+                if d_b.is_none() {
+                    non_utf8(of);
+                }
+                d_b.map(|d_b| (d_b, tn.clone()))
+            })
+            .collect();
+        fdn_fs_jobs(&ctx, &items, &args).and_then(|done| {
+            //print all finished renames before reporting the first failure
+            let mut first_err = None;
+            processed = done.len();
+            for (d_b, rlt, applied) in done {
+                let of = Path::new(&d_b.dir).join(&d_b.base);
+                match applied {
+                    Ok(()) => ctx.processed(&of)?,
+                    Err(err) => {
+                        ctx.report.borrow_mut().fail(&of, &err.to_string());
+                        if !args.keep_going {
+                            first_err.get_or_insert(err);
+                        }
+                        continue;
+                    }
+                }
+                settled(&of, &d_b.base, &rlt);
+                if args.group_by_dir {
+                    changes.push((d_b, rlt));
+                } else {
+                    print_change(&d_b, &rlt, &args, color, explain)?;
+                }
+            }
+            first_err.map_or(Ok(()), Err)
+        })
+    } else {
+        items.into_iter().try_for_each(|(of, tn)| -> Result<()> {
+            if aborted() {
+                return Err(anyhow!("Aborted by signal"));
+            }
+            if left == 0 {
+                return Ok(());
+            }
+            processed += 1;
+            let done = match (dir_base(of), args.non_utf8.as_str()) {
+                (Some(d_b), _) => fdn_f(&ctx, &d_b, tn, args.in_place).map(|rlt| (d_b, rlt)),
+                (None, "lossy") => fdn_lossy(&ctx, of, tn, args.in_place),
+                (None, "error") => Err(anyhow!("Name is not valid UTF-8:{:?}", of)),
+                (None, _) => {
+                    non_utf8(of);
+                    return Ok(());
+                }
+            };
+            let (d_b, rlt) = match done {
+                Ok(done) => done,
+                Err(err) => {
+                    ctx.report.borrow_mut().fail(of, &err.to_string());
+                    match args.keep_going {
+                        true => return Ok(()),
+                        false => return Err(err),
+                    }
+                }
+            };
+            if args.in_place {
+                ctx.processed(of)?;
+            }
+            settled(of, &d_b.base, &rlt);
+            if rlt != d_b.base {
+                left -= 1;
+            }
+
+            if args.group_by_dir {
+                changes.push((d_b, rlt));
+            } else {
+                print_change(&d_b, &rlt, &args, color, explain)?;
+            }
+            Ok(())
+        })
+    };
+    if args.in_place {
+        ctx.finish(rlt.is_ok() && processed == total, args.rollback_on_abort)?;
+    }
+    ctx.commit()?;
+    let printed = rlt.and_then(|()| print_grouped(changes, &args, color, explain));
+    let reported = print_report(&ctx.report.borrow(), &args.report);
+
+    printed.and(reported)
+}
+
+///Print the report of a run in the format of args,'text' only if any file is skipped or failed;
+///an error if any file failed,so that a run going on after failures still exits nonzero
+fn print_report(report: &Report, format: &str) -> Result<()> {
+    match format {
+        "json" => println!("{}", report.json()),
+        "text" if report.has_problems() => println!("{}", report.text()),
+        _ => {}
+    }
+
+    match report.failed() {
+        0 => Ok(()),
+        n => Err(anyhow!("Files failed:{}", n)),
+    }
+}
+
+///Warn if a restored name violates the validators or would be changed by the active rules
+fn revalidate_restored(ctx: &Context, restored: &str, is_file: bool) -> Result<()> {
+    let mut vs = violations(&ctx.renamer.validators, restored);
+    let planned = plan_base(restored, is_file, &ctx.renamer.rules)?;
+    if planned != restored {
+        vs.push(format!("rules:would be renamed to {:?}", planned));
+    }
+    if !vs.is_empty() {
+        warn!("Restored name {:?} violates {}", restored, vs.join(","));
+    }
+
+    Ok(())
+}
+
+///Revertly rename a file or directory's name
+fn fdn_rf(
+    ctx: &Context,
+    dir_base: &DirBase,
+    in_place: bool,
+    revalidate: bool,
+) -> Result<Option<String>> {
+    let rds = retrieve_records_by_hash(&ctx.conn, &hashed_name(&dir_base.base))?;
+    let Some((rd, base_name)) = ctx.chosen_record(rds, dir_base, in_place)? else {
+        return Ok(None);
+    };
+
+    if revalidate {
+        let is_file = ctx
+            .fs
+            .borrow()
+            .metadata(&Path::new(&dir_base.dir).join(&dir_base.base))
+            .map(|m| m.is_file())
+            .unwrap_or(false);
+        revalidate_restored(ctx, &base_name, is_file)?;
+    }
+    //take effect
+    if in_place {
+        //Only rename successfully then ...
+        rename_logged(ctx, dir_base, &base_name, "reversed")?;
+        release_record(&ctx.conn, &rd)?;
+        ctx.applied()?;
+        restore_overwritten(ctx, dir_base, &base_name)?;
+    }
+
+    Ok(Some(base_name))
+}
+
+///Restore the file a rename overwrote once the rename from origin is reversed,a failure warned
+///and the file left in the trash
+fn restore_overwritten(ctx: &Context, dir_base: &DirBase, origin: &str) -> Result<()> {
+    let dir = canonical_path(Path::new(&dir_base.dir))
+        .to_string_lossy()
+        .to_string();
+    let ops = retrieve_operations(&ctx.conn, 0, Some(&dir), None, RPC_UNDO_SCAN)?;
+    if overwritten(&ops, &dir, origin, &dir_base.base).is_none() {
+        return Ok(());
+    }
+    let taken = DirBase {
+        dir: dir.clone(),
+        base: dir_base.base.clone(),
+    };
+    let path = Path::new(&dir).join(&dir_base.base);
+    let rlt = ctx.fs.borrow_mut().restore(&path);
+    if let Err(err) = log_renamed(ctx, &taken, &taken.base, "restored", rlt) {
+        warn!("{:?} left in the trash,{}", path, err);
+    }
+
+    Ok(())
+}
+
+///The 'trashed' operation of the file the latest rename from origin to target in a directory
+///overwrote,logged before the rename in its session;None if it overwrote none or the file is
+///restored
+fn overwritten<'a>(
+    ops: &'a [Operation],
+    dir: &str,
+    origin: &str,
+    target: &str,
+) -> Option<&'a Operation> {
+    let i = ops.iter().rposition(|op| {
+        op.status == "renamed" && op.dir == dir && op.origin == origin && op.target == target
+    })?;
+    let j = ops[..i].iter().rposition(|op| {
+        op.status == "trashed"
+            && op.session == ops[i].session
+            && op.dir == dir
+            && op.origin == target
+    })?;
+    let restored = ops[j + 1..]
+        .iter()
+        .any(|op| op.status == "restored" && op.dir == dir && op.origin == target);
+
+    (!restored).then_some(&ops[j])
+}
+
+///Name or directory decrypted from a record by the current name
+fn decrypted_name(enc: &str, key: &str) -> Result<String> {
+    Ok(String::from_utf8(decrypted(enc, key)?.from_hex()?)?)
+}
+
+///Firstly revertly rename files or directories's name,then do post-processing work
+pub fn fdn_rfs_post(files: Vec<PathBuf>, args: Args) -> Result<()> {
+    fdn_rfs_in(Context::new(&args)?, files, args)
+}
+
+///Revertly rename files or directories's name in a context,then do post-processing work
+pub fn fdn_rfs_in(ctx: Context, files: Vec<PathBuf>, args: Args) -> Result<()> {
+    let color = color_enabled(&args.color);
+
+    let files = exclude_fdn_own(files)?;
+    let mut changes = Vec::new();
+
+    let mut files: Vec<_> = files.iter().collect();
+    if args.limit.is_some() {
+        files.sort();
+    }
+    let total = files.len();
+    let mut left = args.limit.unwrap_or(usize::MAX);
+    let mut processed = 0;
+    if args.in_place {
+        let paths: Vec<_> = files.iter().map(|f| (*f, None)).collect();
+        ctx.checkpoint(&args.argv, &paths)?;
+    }
+    ctx.begin()?;
+
+    let rlt = files.into_iter().try_for_each(|f| -> Result<()> {
+        if aborted() {
+            return Err(anyhow!("Aborted by signal"));
+        }
+        if left == 0 {
+            return Ok(());
+        }
+        processed += 1;
+        let mut frc = Some(f.clone());
+        let mut reversed = false;
+        while let Some(ref f) = frc {
+            if aborted() {
+                return Err(anyhow!("Aborted by signal"));
+            }
+            //a name not valid UTF-8 is never a recorded target,lossy renames are not recorded
+            let done = match (dir_base(f), args.non_utf8.as_str()) {
+                (Some(d_b), _) => fdn_rf(&ctx, &d_b, args.in_place, args.revalidate)
+                    .map(|rlt| rlt.map(|rf_base| (d_b, rf_base))),
+                (None, "error") => Err(anyhow!("Name is not valid UTF-8:{:?}", f)),
+                (None, _) => {
+                    ctx.report.borrow_mut().skip(f, "name is not valid UTF-8");
+                    break;
+                }
+            };
+            match done {
+                Ok(Some((dir_base, rf_base))) => {
+                    reversed = true;
+                    if args.reverse_chainly {
+                        frc = Some(Path::new(&dir_base.dir).join(rf_base.clone()));
+                    } else {
+                        frc = None;
+                    }
+                    if args.group_by_dir {
+                        changes.push((dir_base, rf_base));
+                    } else {
+                        print_change(&dir_base, &rf_base, &args, color, None)?;
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    ctx.report.borrow_mut().fail(f, &err.to_string());
+                    match args.keep_going {
+                        true => break,
+                        false => return Err(err),
+                    }
+                }
+            }
+        }
+        if args.in_place {
+            ctx.processed(f)?;
+        }
+        let status = match (reversed, args.in_place) {
+            (false, _) => "unchanged",
+            (true, true) => "renamed",
+            (true, false) => "planned",
+        };
+        ctx.report.borrow_mut().settle(f, status);
+        if reversed {
+            left -= 1;
+        }
+
+        Ok(())
+    });
+    if args.in_place {
+        ctx.finish(rlt.is_ok() && processed == total, args.rollback_on_abort)?;
+    }
+    ctx.commit()?;
+    let printed = rlt.and_then(|()| print_grouped(changes, &args, color, None));
+    let reported = print_report(&ctx.report.borrow(), &args.report);
+
+    printed.and(reported)
+}
+
+pub(crate) fn os2string(input: Option<&OsStr>) -> Result<String> {
+    match input {
+        Some(os_str) => match os_str.to_str() {
+            Some(valid_str) => Ok(valid_str.to_string()),
+            None => Err(anyhow!("Invalid UTF-8 sequence")),
+        },
+        None => Err(anyhow!("Option is None")),
+    }
+}
+
+///return unicode names of every character of the string and name separated by "," sign
+fn unames(s: &str) -> String {
+    s.chars()
+        .filter_map(|c| unicode_names2::name(c).map(|n| n.to_string()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+///list all separators stored in database via database connection
+fn list_separators(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_separators(conn)?;
+    let s = "Separator";
+    println!("{} ID\tValue\tDescription", s);
+    rlts.sort_by_key(|sep| sep.id);
+    rlts.iter().for_each(|sep| {
+        println!(
+            "{} sep:{}\t{}\t{}",
+            " ".repeat(s.len()),
+            sep.id,
+            sep.value,
+            unames(&sep.value)
+        );
+    });
+
+    Ok(())
+}
+
+///list all to separator words stored in database via database connection
+fn list_to_sep_words(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_to_sep_words(conn)?;
+    let s = "ToSepWord";
+    println!("{} ID\tValue\tDescription\tScope", s);
+    rlts.sort_by_key(|tsw| tsw.id);
+    rlts.iter().for_each(|tsw| {
+        println!(
+            "{} to-sep:{}\t{}\t{}\t{}",
+            " ".repeat(s.len()),
+            tsw.id,
+            tsw.value.replace('\r', "\\r").replace('\n', "\\n"),
+            unames(&tsw.value),
+            tsw.scope
+        );
+    });
+
+    Ok(())
+}
+
+///list all term words stored in database via database connection
+fn list_term_words(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_term_words(conn)?;
+    let s = "TermWord";
+    println!("{} ID\tKey\tValue\tFlags\tScope", s);
+    rlts.sort_by_key(|tw| tw.id);
+    rlts.iter().for_each(|tw| {
+        println!(
+            "{} term:{}\t{}\t{}\t{}\t{}",
+            " ".repeat(s.len()),
+            tw.id,
+            tw.key,
+            tw.value.replace('\r', "\\r").replace('\n', "\\n"),
+            tw.flags.short(),
+            tw.scope
+        );
+    });
+
+    Ok(())
+}
+
+///list all soft deleted term words stored in database via database connection
+fn list_deleted_term_words(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_deleted_term_words(conn)?;
+    if rlts.is_empty() {
+        return Ok(());
+    }
+    let s = "DeletedTermWord";
+    println!("{} ID\tKey\tValue", s);
+    rlts.sort_by_key(|tw| tw.id);
+    rlts.iter().for_each(|tw| {
+        println!(
+            "{} {}\t{}\t{}",
+            " ".repeat(s.len()),
+            tw.id,
+            tw.key,
+            tw.value.replace('\r', "\\r").replace('\n', "\\n")
+        );
+    });
+
+    Ok(())
+}
+
+///Purge term words deleted more than 'purge_days' days ago
+fn purge_deleted(conn: &Connection) -> Result<()> {
+    let days = match retrieve_setting(conn, "purge_days")? {
+        Some(v) => v.parse()?,
+        None => DEFAULT_PURGE_DAYS,
+    };
+    purge_term_words(conn, days)?;
+
+    Ok(())
+}
+
+///list all regex rules stored in database via database connection
+fn list_regex_rules(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_regex_rules(conn)?;
+    let s = "RegexRule";
+    println!("{} ID\tPattern\tReplacement\tScope", s);
+    rlts.sort_by_key(|r| r.id);
+    rlts.iter().for_each(|r| {
+        println!(
+            "{} re:{}\t{}\t{}\t{}",
+            " ".repeat(s.len()),
+            r.id,
+            r.pattern,
+            r.replacement,
+            r.scope
+        );
+    });
+
+    Ok(())
+}
+
+///list all stopwords stored in database via database connection
+fn list_stopwords(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_config_rows(conn, "stop")?;
+    let s = "Stopword";
+    println!("{} ID\tLang\tWord", s);
+    rlts.sort_by_cached_key(|r| {
+        (
+            parse_stopword(&r.rule[5..]).map(|(l, _)| l.to_owned()).ok(),
+            r.id,
+        )
+    });
+    for r in &rlts {
+        let (lang, word) = parse_stopword(&r.rule[5..])?;
+        let lang = if lang.is_empty() { "*" } else { lang };
+        println!(
+            "{} stop:{}\t{}\t{:?}",
+            " ".repeat(s.len()),
+            r.id,
+            lang,
+            word
+        );
+    }
+
+    Ok(())
+}
+
+///list all acronyms stored in database via database connection
+fn list_acronyms(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_config_rows(conn, "acronym")?;
+    let s = "Acronym";
+    println!("{} ID\tWord", s);
+    rlts.sort_by(|a, b| a.rule.cmp(&b.rule));
+    rlts.iter().for_each(|r| {
+        println!("{} acronym:{}\t{}", " ".repeat(s.len()), r.id, &r.rule[8..]);
+    });
+
+    Ok(())
+}
+
+///list all small words stored in database via database connection
+fn list_small_words(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_config_rows(conn, "small")?;
+    let s = "SmallWord";
+    println!("{} ID\tWord", s);
+    rlts.sort_by(|a, b| a.rule.cmp(&b.rule));
+    rlts.iter().for_each(|r| {
+        println!("{} small:{}\t{}", " ".repeat(s.len()), r.id, &r.rule[6..]);
+    });
+
+    Ok(())
+}
+
+///list all extension aliases stored in database via database connection
+fn list_ext_aliases(conn: &Connection) -> Result<()> {
+    let mut rlts = retrieve_config_rows(conn, "ext")?;
+    let s = "ExtAlias";
+    println!("{} ID\tExt\tAlias", s);
+    rlts.sort_by(|a, b| a.rule.cmp(&b.rule));
+    rlts.iter().for_each(|r| {
+        let (k, v) = r.rule[4..].split_once(':').unwrap_or((&r.rule[4..], ""));
+        println!("{} ext:{}\t{}\t{}", " ".repeat(s.len()), r.id, k, v);
+    });
+
+    Ok(())
+}
+
+///list all disabled or tagged config rows stored in database via database connection
+fn list_toggled_rows(conn: &Connection) -> Result<()> {
+    let rlts = retrieve_toggled_rows(conn)?;
+    if rlts.is_empty() {
+        return Ok(());
+    }
+    let s = "ConfigRow";
+    println!("{} ID\tRule\tTag\tEnabled", s);
+    rlts.iter().for_each(|r| {
+        println!(
+            "{} {}:{}\t{}\t{}\t{}",
+            " ".repeat(s.len()),
+            r.kind,
+            r.id,
+            r.rule.replace('\r', "\\r").replace('\n', "\\n"),
+            r.tag,
+            r.enabled
+        );
+    });
+
+    Ok(())
+}
+
+///list all settings stored in database via database connection
+fn list_settings(conn: &Connection) -> Result<()> {
+    let s = "Setting";
+    println!("{} Key\tValue", s);
+    retrieve_settings(conn)?.iter().for_each(|(k, v)| {
+        println!("{} {}\t{}", " ".repeat(s.len()), k, v);
+    });
+
+    Ok(())
+}
+
+///Print hits of configured rules,most fired first and dead rules last
+pub fn config_stats() -> Result<()> {
+    let conn = open_db(None)?;
+    if retrieve_setting(&conn, "rule_stats")?.as_deref() != Some("on") {
+        warn!("Rule stats is off,turn it on by 'fdn config --set rule_stats=on'");
+    }
+    let rules = RuleSet::load(&conn)?;
+    let stats = retrieve_rule_stats(&conn)?;
+
+    let mut rows: Vec<_> = rules
+        .to_sep_words
+        .iter()
+        .map(|w| (STAGES[12], w.clone()))
+        .chain(
+            rules
+                .term_words
+                .iter()
+                .map(|(k, v)| (STAGES[13], format!("{}:{}", k, v))),
+        )
+        .chain(
+            rules
+                .regex_rules
+                .iter()
+                .map(|(re, v)| (STAGES[14], format!("{}:{}", re, v))),
+        )
+        .chain(
+            rules
+                .stopwords
+                .iter()
+                .map(|(word, lang)| (STAGES[11], stopword_rule(word, lang))),
+        )
+        .chain(
+            rules
+                .ext_aliases
+                .iter()
+                .map(|(k, v)| (STAGES[22], format!("{}:{}", k, v))),
+        )
+        .map(|(stage, rule)| {
+            let hits = stats
+                .get(&(stage.to_owned(), rule.clone()))
+                .copied()
+                .unwrap_or(0);
+            (stage, rule, hits)
+        })
+        .collect();
+    rows.sort_by_key(|(_, _, hits)| std::cmp::Reverse(*hits));
+
+    let s = "RuleStat";
+    println!("{} Hits\tStage\tRule", s);
+    rows.iter().for_each(|(stage, rule, hits)| {
+        println!("{} {}\t{}\t{:?}", " ".repeat(s.len()), hits, stage, rule);
+    });
+
+    Ok(())
+}
+
+///List all configurations
+pub fn config_list() -> Result<()> {
+    let conn = open_db(None)?;
+    purge_deleted(&conn)?;
+    list_separators(&conn)?;
+    list_to_sep_words(&conn)?;
+    list_term_words(&conn)?;
+    list_deleted_term_words(&conn)?;
+    list_regex_rules(&conn)?;
+    list_ext_aliases(&conn)?;
+    list_acronyms(&conn)?;
+    list_small_words(&conn)?;
+    list_stopwords(&conn)?;
+    list_toggled_rows(&conn)?;
+    list_settings(&conn)?;
+
+    Ok(())
+}
+
+///Add configuration into database,flags used by term words only and scope by to separator
+///words,term words and regex rules only
+pub fn config_add(word: &str, flags: TermFlags, scope: Option<&str>) -> Result<()> {
+    let conn = open_db(None)?;
+    let scope = scope.map(parse_scope).transpose()?.unwrap_or_default();
+    match insert_config_rule(&conn, word, flags, &scope)? {
+        "stop" => list_stopwords(&conn),
+        "acronym" => list_acronyms(&conn),
+        "small" => list_small_words(&conn),
+        "ext" => list_ext_aliases(&conn),
+        "re" => list_regex_rules(&conn),
+        "term" => list_term_words(&conn),
+        _ => list_to_sep_words(&conn),
+    }
+}
+
+///Insert a rule in config syntax with its flags and parsed scope via database connection,kind of
+///the rule returned
+fn insert_config_rule(
+    conn: &Connection,
+    word: &str,
+    flags: TermFlags,
+    scope: &str,
+) -> Result<&'static str> {
+    let scoped = ["stop:", "acronym:", "small:", "ext:"]
+        .iter()
+        .find(|p| word.starts_with(*p));
+    if let (Some(prefix), false) = (scoped, scope.is_empty()) {
+        return Err(anyhow!("Rules of {:?} can not be scoped", prefix));
+    }
+    let term = scoped.is_none() && !word.starts_with("re:") && parse_term_rule(word).1.is_some();
+    if flags != TermFlags::default() && !term {
+        return Err(anyhow!(
+            "Only term words such as 'ft.:feat.' can match in any case or whole words:{:?}",
+            word
+        ));
+    }
+    if let Some(stopword) = word.strip_prefix("stop:") {
+        let (lang, word) = parse_stopword(stopword)?;
+        insert_stopword(conn, word, lang)?;
+
+        return Ok("stop");
+    }
+    if let Some(acronym) = word.strip_prefix("acronym:") {
+        insert_acronym(conn, parse_acronym(acronym)?)?;
+
+        return Ok("acronym");
+    }
+    if let Some(small) = word.strip_prefix("small:") {
+        insert_small_word(conn, parse_small_word(small)?)?;
+
+        return Ok("small");
+    }
+    if let Some(alias) = word.strip_prefix("ext:") {
+        let (ext, alias) = parse_ext_alias(alias)?;
+        upsert_ext_alias(conn, ext, alias)?;
+
+        return Ok("ext");
+    }
+    if let Some(re) = word.strip_prefix("re:") {
+        let (pattern, replacement) = parse_regex_rule(re)?;
+        insert_regex_rule(conn, pattern, replacement, scope)?;
+
+        return Ok("re");
+    }
+    match parse_term_rule(word) {
+        (key, Some(value)) => {
+            flags.matcher(&key)?;
+            insert_term_word(conn, &key, &value, flags, scope)?;
+
+            Ok("term")
+        }
+        (word, None) => {
+            insert_to_sep_word(conn, &word, scope)?;
+
+            Ok("to-sep")
+        }
+    }
+}
+
+///Install a pack into the database in a transaction:rows of its former version replaced,rules
+///it has in common with the database or conflicting with them left to the database;numbers of
+///rules added and of conflicting ones returned
+fn install_pack(conn: &mut Connection, pack: &Pack, source: &str) -> Result<(usize, usize)> {
+    let pack_conn = open_rules_db()?;
+    for r in &pack.rules {
+        let scope = match r.scope.is_empty() {
+            true => String::new(),
+            false => parse_scope(&r.scope)?,
+        };
+        insert_config_rule(&pack_conn, &r.rule, r.flags, &scope)
+            .map_err(|e| anyhow!("Invalid rule {:?} of pack {:?}:{}", r.rule, pack.name, e))?;
+    }
+
+    let tx = conn.transaction()?;
+    delete_tag_rows(&tx, &pack.tag())?;
+    let diffs = diff_config_rows(&tx, &pack_conn)?;
+    let mut added = 0;
+    for o in diffs
+        .iter()
+        .filter(|d| d.local.is_none())
+        .flat_map(|d| &d.other)
+    {
+        copy_config_row(&pack_conn, &tx, &o.kind, o.id, None)?;
+        update_tag(&tx, &o.kind, tx.last_insert_rowid() as i32, &pack.tag())?;
+        added += 1;
+    }
+    let conflicts = diffs
+        .iter()
+        .filter(|d| d.local.is_some() && d.other.is_some());
+    upsert_pack(&tx, &pack.name, &pack.version, source)?;
+    tx.commit()?;
+
+    Ok((added, conflicts.count()))
+}
+
+///Command line of saved arguments with extra ones appended,an alias running another one refused
+fn alias_args(argv: &[String], extra: &[String]) -> Result<Args> {
+    let cmd = ["fdn".to_owned()]
+        .into_iter()
+        .chain(argv.iter().cloned())
+        .chain(extra.iter().cloned());
+    let args = Args::try_parse_from(cmd).map_err(|e| anyhow!("Invalid arguments:{}", e))?;
+
+    match args.command {
+        Some(Commands::Alias { .. }) => Err(anyhow!("An alias can not run 'fdn alias'")),
+        _ => Ok(args),
+    }
+}
+
+///Arguments 'fdn alias run <NAME>' stands for,others as they are
+pub fn expand_alias(args: Args) -> Result<Args> {
+    let Some(Commands::Alias {
+        action,
+        name: Some(name),
+        argv: extra,
+    }) = &args.command
+    else {
+        return Ok(args);
+    };
+    if action != "run" {
+        return Ok(args);
+    }
+    let argv = retrieve_alias(&open_db(None)?, name)?
+        .ok_or_else(|| anyhow!("Alias not exists:{:?}", name))?;
+
+    alias_args(&serde_json::from_str::<Vec<String>>(&argv)?, extra)
+}
+
+///Save,list or delete invocation aliases,arguments checked before saved
+pub fn alias(action: &str, name: Option<&str>, argv: &[String]) -> Result<()> {
+    let conn = open_db(None)?;
+    match (action, name) {
+        ("list", _) => {
+            let s = "Alias";
+            println!("{} Name\tArguments", s);
+            for (name, argv) in retrieve_aliases(&conn)? {
+                let argv: Vec<String> = serde_json::from_str(&argv)?;
+                println!("{} {}\t{}", " ".repeat(s.len()), name, argv.join(" "));
+            }
+            Ok(())
+        }
+        ("save", Some(name)) => {
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(anyhow!(
+                    "Alias name must be letters,digits,'-' or '_':{:?}",
+                    name
+                ));
+            }
+            alias_args(argv, &[])?;
+            upsert_alias(&conn, name, &serde_json::to_string(argv)?)?;
+            println!("Saved alias {}:{}", name, argv.join(" "));
+            Ok(())
+        }
+        ("delete", Some(name)) => match delete_alias(&conn, name)? {
+            true => {
+                println!("Deleted alias {}", name);
+                Ok(())
+            }
+            false => Err(anyhow!("Alias not exists:{:?}", name)),
+        },
+        (action, None) => Err(anyhow!("Alias action {:?} needs a name", action)),
+        _ => Err(anyhow!("Unknown alias action:{:?}", action)),
+    }
+}
+
+///Install or remove the glue of a context menu entry of a file manager,calling back into this
+///executable with the arguments
+pub fn integrate(action: &str, target: &str, argv: &[String]) -> Result<()> {
+    if !native(target) {
+        return Err(anyhow!("File manager {:?} does not run on this OS", target));
+    }
+    let argv = match argv.is_empty() {
+        true => vec!["-i".to_owned()],
+        false => argv.to_vec(),
+    };
+    alias_args(&argv, &[])?;
+    let exe = std::env::current_exe()?;
+    let data_dir = BaseDirs::new()
+        .map(|b| b.data_dir().to_path_buf())
+        .ok_or_else(|| anyhow!("No data directory found"))?;
+    let home = UserDirs::new()
+        .map(|u| u.home_dir().to_path_buf())
+        .ok_or_else(|| anyhow!("No home directory found"))?;
+
+    for g in glue(target, &exe, &argv, &data_dir, &home)? {
+        match action {
+            "install" => {
+                if let Some(parent) = g.path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&g.path, &g.content)?;
+                #[cfg(unix)]
+                if g.executable {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&g.path, fs::Permissions::from_mode(0o755))?;
+                }
+                println!("Installed {:?}", g.path);
+            }
+            "remove" => match fs::remove_file(&g.path) {
+                Ok(()) => println!("Removed {:?}", g.path),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            },
+            _ => return Err(anyhow!("Unknown integrate action:{:?}", action)),
+        }
+    }
+    //the Quick Action is a bundle directory
+    if action == "remove" && target == "finder" {
+        let bundle = home
+            .join("Library/Services")
+            .join(format!("{}.workflow", integrate::MENU_TITLE));
+        if bundle.exists() {
+            fs::remove_dir_all(&bundle)?;
+        }
+    }
+
+    Ok(())
+}
+
+///List,install,update or remove rule packs,rows of a pack tagged 'pack:<NAME>'
+pub fn preset(action: &str, name: Option<&str>) -> Result<()> {
+    let mut conn = open_db(None)?;
+    let installed = retrieve_packs(&conn)?;
+    let version = |name: &str| {
+        installed
+            .iter()
+            .find(|(n, ..)| n == name)
+            .map(|(_, v, _)| v.as_str())
+    };
+
+    match (action, name) {
+        ("list", _) => {
+            println!("Pack Version\tInstalled\tDescription");
+            for (name, manifest) in BUILTIN_PACKS {
+                let pack = parse_pack(manifest)?;
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    name,
+                    pack.version,
+                    version(name).unwrap_or("-"),
+                    pack.description
+                );
+            }
+            for (name, v, source) in &installed {
+                if !BUILTIN_PACKS.iter().any(|(n, _)| n == name) {
+                    println!("{}\t-\t{}\t{}", name, v, source);
+                }
+            }
+            Ok(())
+        }
+        ("install", Some(source)) => {
+            let pack = parse_pack(&load_manifest(source)?)?;
+            if version(&pack.name) == Some(pack.version.as_str()) {
+                println!("Pack {} {} is already installed", pack.name, pack.version);
+                return Ok(());
+            }
+            let (added, conflicts) = install_pack(&mut conn, &pack, source)?;
+            println!(
+                "Installed pack {} {}:{} rules added,{} conflicting rules of your own kept",
+                pack.name, pack.version, added, conflicts
+            );
+            Ok(())
+        }
+        ("update", name) => {
+            for (n, v, source) in installed
+                .iter()
+                .filter(|(n, ..)| name.is_none_or(|m| m == n))
+            {
+                let pack = parse_pack(&load_manifest(source)?)?;
+                if pack.version == *v {
+                    println!("Pack {} {} is up to date", n, v);
+                    continue;
+                }
+                let (added, conflicts) = install_pack(&mut conn, &pack, source)?;
+                println!(
+                    "Updated pack {} {} to {}:{} rules added,{} conflicting rules kept",
+                    n, v, pack.version, added, conflicts
+                );
+            }
+            Ok(())
+        }
+        ("remove", Some(name)) => {
+            let tx = conn.transaction()?;
+            let n = delete_tag_rows(&tx, &pack_tag(name))?;
+            if !delete_pack(&tx, name)? {
+                return Err(anyhow!("Pack is not installed:{:?}", name));
+            }
+            tx.commit()?;
+            println!("Removed pack {}:{} rules deleted", name, n);
+            Ok(())
+        }
+        (action, None) => Err(anyhow!("Pack action {:?} needs a pack name", action)),
+        _ => Err(anyhow!("Unknown pack action:{:?}", action)),
+    }
+}
+
+///Set a setting by "key=value" in the database
+pub fn config_set(kv: &str) -> Result<()> {
+    let conn = open_db(None)?;
+    match kv.split_once('=') {
+        Some((key, value)) if SETTING_KEYS.contains(&key) => {
+            upsert_setting(&conn, key, value)?;
+            list_settings(&conn)
+        }
+        _ => Err(anyhow!(
+            "setting must be 'key=value' and key in {:?}:{:?}",
+            SETTING_KEYS,
+            kv
+        )),
+    }
+}
+
+///Register a script by its absolute path in the database once it compiles
+pub fn config_script(file: &Path) -> Result<()> {
+    let path = file
+        .canonicalize()
+        .map_err(|e| anyhow!("Failed to read script {:?}:{}", file, e))?;
+    Script::load(&path)?;
+    let conn = open_db(None)?;
+    upsert_setting(&conn, "script", &path.to_string_lossy())?;
+    list_settings(&conn)
+}
+
+///Unset a setting by key in the database
+pub fn config_unset(key: &str) -> Result<()> {
+    let conn = open_db(None)?;
+    delete_setting(&conn, key)?;
+    list_settings(&conn)
+}
+
+///Delete configuration in the database
+pub fn config_delete(word: &str) -> Result<()> {
+    let conn = open_db(None)?;
+    purge_deleted(&conn)?;
+    if let Some(stopword) = word.strip_prefix("stop:") {
+        let (lang, word) = parse_stopword(stopword)?;
+        if delete_stopword(&conn, word, lang)? {
+            list_stopwords(&conn)?;
+        }
+
+        return Ok(());
+    }
+    if let Some(acronym) = word.strip_prefix("acronym:") {
+        if delete_acronym(&conn, acronym)? {
+            list_acronyms(&conn)?;
+        }
+
+        return Ok(());
+    }
+    if let Some(small) = word.strip_prefix("small:") {
+        if delete_small_word(&conn, small)? {
+            list_small_words(&conn)?;
+        }
+
+        return Ok(());
+    }
+    if let Some(ext) = word.strip_prefix("ext:") {
+        //"ext:jpeg" or "ext:jpeg:jpg"
+        let ext = ext.split_once(':').map_or(ext, |(ext, _)| ext);
+        if delete_ext_alias(&conn, ext)? {
+            list_ext_aliases(&conn)?;
+        }
+
+        return Ok(());
+    }
+    if let Some(re) = word.strip_prefix("re:") {
+        let (pattern, replacement) = parse_regex_rule(re)?;
+        let rlts = retrieve_regex_rules(&conn)?;
+        let the_rule = rlts
+            .iter()
+            .find(|&r| r.pattern == pattern && r.replacement == replacement);
+        if let Some(r) = the_rule {
+            delete_regex_rule(&conn, r.id)?;
+            list_regex_rules(&conn)?;
+        }
+
+        return Ok(());
+    }
+    match parse_term_rule(word) {
+        (key, Some(value)) => {
+            let rlts = retrieve_term_words(&conn)?;
+            let the_word = rlts.iter().find(|&w| w.key == key && w.value == value);
+            if let Some(w) = the_word {
+                delete_term_word(&conn, w.id)?;
+                list_term_words(&conn)?;
+            }
+        }
+        (word, None) => {
+            let rlts = retrieve_to_sep_words(&conn)?;
+            let the_word = rlts.iter().find(|&w| w.value == word);
+            if let Some(w) = the_word {
+                delete_to_sep_word(&conn, w.id)?;
+                list_to_sep_words(&conn)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+///Restore a deleted term word by id in the database
+pub fn config_restore(id: i32) -> Result<()> {
+    let conn = open_db(None)?;
+    if !restore_term_word(&conn, id)? {
+        return Err(anyhow!("no deleted term word with id {}", id));
+    }
+    list_term_words(&conn)
+}
+
+///Delete a config row by '<KIND>:<ID>',term words soft deleted
+pub fn config_delete_id(row: &str) -> Result<()> {
+    let conn = open_db(None)?;
+    let (kind, id) = config_row_id(row)?;
+    if !delete_config_row(&conn, kind, id)? {
+        return Err(anyhow!("No config row {:?}", row));
+    }
+    list_kind(&conn, kind)
+}
+
+///Update the value of a config row by '<KIND>:<ID>:<VALUE>',the value of a separator,to
+///separator word or term word,the replacement of a regex rule,the alias of an extension alias
+///or the word of others
+pub fn config_update_id(spec: &str) -> Result<()> {
+    let conn = open_db(None)?;
+    let mut parts = spec.splitn(3, ':');
+    let (Some(kind), Some(id), Some(value)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(anyhow!("Update must be '<KIND>:<ID>:<VALUE>':{:?}", spec));
+    };
+    let row = format!("{}:{}", kind, id);
+    let (kind, id) = config_row_id(&row)?;
+    let value = match kind {
+        "acronym" => parse_acronym(value)?.to_owned(),
+        "small" => parse_small_word(value)?.to_lowercase(),
+        "stop" => parse_stopword(value)?.1.to_owned(),
+        "re" => parse_regex_rule(&format!(".:{}", value))?.1.to_owned(),
+        "sep" | "to-sep" | "ext" if value.is_empty() => {
+            return Err(anyhow!("Value of {:?} must not be empty", row))
+        }
+        _ => value.to_owned(),
+    };
+    if !update_config_row(&conn, kind, id, &value)? {
+        return Err(anyhow!("No config row {:?}", row));
+    }
+    list_kind(&conn, kind)
+}
+
+///List config rows of a kind
+fn list_kind(conn: &Connection, kind: &str) -> Result<()> {
+    match kind {
+        "sep" => list_separators(conn),
+        "to-sep" => list_to_sep_words(conn),
+        "term" => list_term_words(conn),
+        "re" => list_regex_rules(conn),
+        "ext" => list_ext_aliases(conn),
+        "acronym" => list_acronyms(conn),
+        "small" => list_small_words(conn),
+        _ => list_stopwords(conn),
+    }
+}
+
+///Backup a part of the database into a new file,one of DB_PARTS
+pub fn config_backup(path: &Path, part: &str) -> Result<()> {
+    let conn = open_db(None)?;
+    backup_db(&conn, path, part)?;
+    println!("Backed up {} of the database into {:?}", part, path);
+
+    Ok(())
+}
+
+///Restore a part of the database from a backup file,one of DB_PARTS
+pub fn config_restore_backup(path: &Path, part: &str) -> Result<()> {
+    let mut conn = open_db(None)?;
+    restore_db(&mut conn, path, part)?;
+    println!("Restored the database from {:?}", path);
+
+    Ok(())
+}
+
+///A line of a differing rule:'-' only in the database,'+' only in the other one and '~' in both
+///with columns of different values
+fn diff_line(d: &RuleDiff) -> String {
+    let rule = |r: &ConfigRow| {
+        format!(
+            "{}:{}\t{}",
+            r.kind,
+            r.id,
+            r.rule.replace('\r', "\\r").replace('\n', "\\n")
+        )
+    };
+    match (&d.local, &d.other) {
+        (Some(l), None) => format!("- {}", rule(l)),
+        (None, Some(o)) => format!("+ {}", rule(o)),
+        (Some(l), Some(o)) => format!("~ {}\t{}\t{}", rule(l), rule(o), d.columns.join(",")),
+        (None, None) => String::new(),
+    }
+}
+
+///Show rules differing between the database and another one
+pub fn config_diff(other: &Path) -> Result<()> {
+    let conn = open_db(None)?;
+    let diffs = diff_config_rows(&conn, &open_other_db(other)?)?;
+    diffs.iter().for_each(|d| println!("{}", diff_line(d)));
+    println!("{} rules differ from {:?}", diffs.len(), other);
+
+    Ok(())
+}
+
+///Merge rules of another database:rules only in it added,different ones replaced by its ones if
+///confirmed and rules only in the database kept
+pub fn config_merge(other: &Path) -> Result<()> {
+    let mut conn = open_db(None)?;
+    let other_conn = open_other_db(other)?;
+    let diffs = diff_config_rows(&conn, &other_conn)?;
+
+    //answers taken before writing so that the database is not locked while prompting
+    let mut taken = Vec::new();
+    let mut all = false;
+    for d in diffs
+        .iter()
+        .filter(|d| d.local.is_some() && d.other.is_some())
+    {
+        if !all {
+            println!("{}", diff_line(d));
+            print!("Take the rule of {:?}? [y/N/a/q] ", other);
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            match answer.trim() {
+                "y" | "Y" | "yes" => {}
+                "a" | "A" => all = true,
+                "q" | "Q" => break,
+                _ => continue,
+            }
+        }
+        taken.push(d);
+    }
+
+    let tx = conn.transaction()?;
+    let mut added = 0;
+    for o in diffs
+        .iter()
+        .filter(|d| d.local.is_none())
+        .flat_map(|d| &d.other)
+    {
+        copy_config_row(&other_conn, &tx, &o.kind, o.id, None)?;
+        added += 1;
+    }
+    for d in &taken {
+        let (l, o) = (d.local.as_ref().unwrap(), d.other.as_ref().unwrap());
+        copy_config_row(&other_conn, &tx, &o.kind, o.id, Some(l.id))?;
+    }
+    tx.commit()?;
+    let replaced = taken.len();
+    println!(
+        "Merged {:?}:{} rules added,{} replaced",
+        other, added, replaced
+    );
+
+    Ok(())
+}
+
+///Kind and id of a config row by '<KIND>:<ID>'
+fn config_row_id(s: &str) -> Result<(&str, i32)> {
+    let (kind, id) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Config row must be '<KIND>:<ID>':{:?}", s))?;
+    let id = id
+        .parse()
+        .map_err(|_| anyhow!("Config row must be '<KIND>:<ID>':{:?}", s))?;
+    kind_table(kind)?;
+
+    Ok((kind, id))
+}
+
+///Enable or disable a config row by '<KIND>:<ID>',or all rows with a tag by 'tag:<TAG>'
+pub fn config_enable(target: &str, enabled: bool) -> Result<()> {
+    let conn = open_db(None)?;
+    match target.strip_prefix("tag:") {
+        Some(tag) => {
+            if update_tag_enabled(&conn, tag, enabled)? == 0 {
+                return Err(anyhow!("No config row with tag {:?}", tag));
+            }
+        }
+        None => {
+            let (kind, id) = config_row_id(target)?;
+            if !update_enabled(&conn, kind, id, enabled)? {
+                return Err(anyhow!("No config row {:?}", target));
+            }
+        }
+    }
+    list_toggled_rows(&conn)
+}
+
+///Tag a config row by '<KIND>:<ID>=<TAG>'
+pub fn config_tag(spec: &str) -> Result<()> {
+    let conn = open_db(None)?;
+    let (row, tag) = spec
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Tag must be '<KIND>:<ID>=<TAG>':{:?}", spec))?;
+    let (kind, id) = config_row_id(row)?;
+    if !update_tag(&conn, kind, id, tag.trim())? {
+        return Err(anyhow!("No config row {:?}", row));
+    }
+    list_toggled_rows(&conn)
+}
+
+///Plan paths with and without the candidate rule and print those whose outcome differs
+pub fn config_compare(rule: &str, paths: Vec<PathBuf>, args: &Args) -> Result<()> {
+    let conn = open_db(None)?;
+    let rules = RuleSet::load(&conn)?.with_args(args);
+    let mut candidate = rules.clone();
+    candidate.add_rule(rule)?;
+    let (rules, candidate) = (Renamer::new(rules), Renamer::new(candidate));
+
+    let color = color_enabled(&args.color);
+    let paths = exclude_fdn_own(paths)?;
+
+    paths.iter().try_for_each(|p| -> Result<()> {
+        let without = rules.plan_path(p)?;
+        let with = candidate.plan_path(p)?;
+        if without.to != with.to {
+            let (o_r, e_r) = fname_compare(
+                &os2string(without.to.file_name())?,
+                &os2string(with.to.file_name())?,
+                "",
+                color,
+                &args.compound_ext,
+            )?;
+            println!("{}\n   {}\n-->{}", p.display(), o_r, e_r);
+        }
+        Ok(())
+    })
+}
+
+///Strip duplicate markers such as " (1)"," - Copy" and "Copy of " from a file stem
+fn strip_dup_markers(stem: &str) -> Result<String> {
+    let mut rlt = stem.to_owned();
+    for pattern in [
+        r"(?i)^copy\s+of\s+",
+        r"(?i)(\s+-\s+|_)copy(\s*\(\d+\))?$",
+        r"\s*\(\d+\)$",
+    ] {
+        rlt = Regex::new(pattern)?.replace(&rlt, "").to_string();
+    }
+
+    if rlt.trim().is_empty() {
+        Ok(stem.to_owned())
+    } else {
+        Ok(rlt)
+    }
+}
+
+///Plan numbered targets of files directory by directory,renames ordered so that no file is
+///overwritten
+pub fn number_targets(files: Vec<PathBuf>, args: &Args) -> Result<(Vec<PathBuf>, Vec<String>)> {
+    let rules = RuleSet::load(&open_db(None)?)?.with_args(args);
+    let sep = regex::escape(&rules.sep);
+
+    let mut dirs: BTreeMap<PathBuf, Vec<(PathBuf, fs::Metadata)>> = BTreeMap::new();
+    for f in exclude_fdn_own(files)? {
+        let meta = fs::metadata(&f)?;
+        dirs.entry(f.parent().unwrap_or(Path::new("")).to_path_buf())
+            .or_default()
+            .push((f, meta));
+    }
+
+    let mut renames = Vec::new();
+    for (dir, mut group) in dirs {
+        sort_files(&mut group, &args.sort);
+        let origins: HashSet<_> = group.iter().map(|(f, _)| f.clone()).collect();
+        //only a counter as wide as those written here is an earlier number,so that renumbering
+        //is stable while a trailing year such as 1999 is kept
+        let last = args.start + group.len().saturating_sub(1);
+        let width = args.pad.max(last.to_string().len());
+        let numbered = match args.number_at.as_str() {
+            "prefix" => Regex::new(&format!(r"^\d{{{}}}{}", width, sep))?,
+            _ => Regex::new(&format!(r"{}\d{{{}}}$", sep, width))?,
+        };
+
+        for (i, (f, _)) in group.into_iter().enumerate() {
+            let planned = plan_base(&os2string(f.file_name())?, true, &rules)?;
+            let (stem, ext) = match split_ext(&planned, &rules.compound_exts) {
+                (s, Some(e)) => (s.to_owned(), format!(".{}", e)),
+                (s, None) => (s.to_owned(), String::new()),
+            };
+            let stem = numbered.replace(&stem, "");
+            let num = format!("{:0width$}", args.start + i);
+            let target = match (stem.is_empty(), args.number_at.as_str()) {
+                (true, _) => format!("{}{}", num, ext),
+                (false, "prefix") => format!("{}{}{}{}", num, rules.sep, stem, ext),
+                (false, _) => format!("{}{}{}{}", stem, rules.sep, num, ext),
+            };
+
+            let t_path = dir.join(&target);
+            if t_path != f && t_path.exists() && !origins.contains(&t_path) {
+                warn!("Skip {:?},target {:?} exists", f, target);
+                continue;
+            }
+            renames.push((f, t_path));
+        }
+    }
+
+    Ok(order_renames(renames)?
+        .into_iter()
+        .filter_map(|(f, t)| t.file_name().map(|n| (f, n.to_string_lossy().to_string())))
+        .unzip())
+}
+
+///Sort files with their metadata by a key of SORT_KEYS,ties by path in natural order
+fn sort_files(files: &mut [(PathBuf, fs::Metadata)], key: &str) {
+    files.sort_by(|(a, a_m), (b, b_m)| {
+        let by = match key {
+            "mtime" => a_m.modified().ok().cmp(&b_m.modified().ok()),
+            "size" => a_m.len().cmp(&b_m.len()),
+            _ => Ordering::Equal,
+        };
+        by.then_with(|| path_order(a, b, key))
+    });
+}
+
+///Key of '--sort' of SORT_KEYS,'name' taken as an alias of 'natural'
+fn sort_key(key: &str) -> Result<String, String> {
+    match key {
+        "name" => Ok("natural".to_owned()),
+        k if SORT_KEYS.contains(&k) => Ok(k.to_owned()),
+        k => Err(format!("must be one of {:?}:{:?}", SORT_KEYS, k)),
+    }
+}
+
+///Sort paths by a key of SORT_KEYS,metadata read only for 'mtime' and 'size' and paths whose
+///metadata can not be read such as ones removed meanwhile sorted last
+pub fn sort_paths(paths: &mut [PathBuf], key: &str) {
+    if !matches!(key, "mtime" | "size") {
+        paths.sort_by(|a, b| path_order(a, b, key));
+        return;
+    }
+    let (mut files, mut unread) = (Vec::new(), Vec::new());
+    for p in paths.iter() {
+        match fs::metadata(p) {
+            Ok(meta) => files.push((p.clone(), meta)),
+            Err(_) => unread.push(p.clone()),
+        }
+    }
+    sort_files(&mut files, key);
+    unread.sort_by(|a, b| path_order(a, b, key));
+    let sorted = files.into_iter().map(|(f, _)| f).chain(unread);
+    paths.iter_mut().zip(sorted).for_each(|(p, f)| *p = f);
+}
+
+///Open a storage backend by its URL with the path of the root of files to rename,listed down to
+///the depth where backends list entries up front
+#[cfg_attr(
+    not(any(feature = "sftp", feature = "webdav", feature = "mtp")),
+    allow(unused_variables)
+)]
+pub fn open_backend(url: &str, depth: usize) -> Result<(Box<dyn FsBackend>, PathBuf)> {
+    match url.split_once("://").map(|(scheme, _)| scheme) {
+        #[cfg(feature = "s3")]
+        Some("s3") => {
+            let fs = s3::S3Fs::open(url)?;
+            let root = fs.root();
+            Ok((Box::new(fs), root))
+        }
+        #[cfg(not(feature = "s3"))]
+        Some("s3") => Err(anyhow!("fdn is built without feature 's3'")),
+        #[cfg(feature = "sftp")]
+        Some("sftp") => {
+            let fs = sftp::SftpFs::open(url, depth)?;
+            let root = fs.root();
+            Ok((Box::new(fs), root))
+        }
+        #[cfg(not(feature = "sftp"))]
+        Some("sftp") => Err(anyhow!("fdn is built without feature 'sftp'")),
+        #[cfg(feature = "webdav")]
+        Some("dav" | "davs") => {
+            let fs = webdav::WebDavFs::open(url, depth)?;
+            let root = fs.root();
+            Ok((Box::new(fs), root))
+        }
+        #[cfg(not(feature = "webdav"))]
+        Some("dav" | "davs") => Err(anyhow!("fdn is built without feature 'webdav'")),
+        #[cfg(feature = "mtp")]
+        Some("mtp") => {
+            let fs = mtp::MtpFs::open(url, depth)?;
+            let root = fs.root();
+            Ok((Box::new(fs), root))
+        }
+        #[cfg(not(feature = "mtp"))]
+        Some("mtp") => Err(anyhow!("fdn is built without feature 'mtp'")),
+        _ => Err(anyhow!(
+            "Backend must be an URL such as 's3://bucket/prefix','sftp://user@host/path',\
+             'davs://host/path' or 'mtp://device/storage/dir':{:?}",
+            url
+        )),
+    }
+}
+
+///Storage backend opened once with files under its root as input files of the local filesystem
+///are,sorted by the key of args
+pub fn backend_files(url: &str, args: &Args) -> Result<(Box<dyn FsBackend>, Vec<PathBuf>)> {
+    if args.filetype != "f" {
+        return Err(anyhow!("Backend renames files only,file type must be 'f'"));
+    }
+    let (fs, root) = open_backend(url, args.max_depth)?;
+    let exs = args.excludes_under(&root)?;
+    let skips = args.skips()?;
+    let mut files = fs.files(&root, args.max_depth)?;
+    files.retain(|f| {
+        !exs.excluded(f)
+            && !is_skipped(&f.file_name().unwrap_or_default().to_string_lossy(), &skips)
+            && args.hidden_kept(hidden_in(f, &root))
+    });
+    exs.warn_unmatched();
+
+    let meta = |p: &Path| fs.metadata(p).ok();
+    files.sort_by(|a, b| {
+        let by = match args.sort.as_str() {
+            "mtime" => meta(a)
+                .and_then(|m| m.modified)
+                .cmp(&meta(b).and_then(|m| m.modified)),
+            "size" => meta(a).map(|m| m.len).cmp(&meta(b).map(|m| m.len)),
+            _ => Ordering::Equal,
+        };
+        by.then_with(|| path_order(a, b, &args.sort))
+    });
+
+    Ok((fs, files))
+}
+
+///Order of paths by a key of SORT_KEYS,lexical for 'lexical' and natural for the others
+pub fn path_order(a: &Path, b: &Path, key: &str) -> Ordering {
+    match key {
+        "lexical" => a.cmp(b),
+        _ => natural_path_cmp(a, b),
+    }
+}
+
+///Compare paths component by component in natural order
+pub fn natural_path_cmp(a: &Path, b: &Path) -> Ordering {
+    let names = |p: &Path| {
+        p.components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+    };
+    let (a_names, b_names) = (names(a), names(b));
+    let by = a_names
+        .iter()
+        .zip(&b_names)
+        .map(|(x, y)| natural_cmp(x, y))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal);
+
+    by.then(a_names.len().cmp(&b_names.len())).then(a.cmp(b))
+}
+
+///Compare strings with runs of digits by value,such as "file2" before "file10",ties by text
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let runs = |s: &str| -> Vec<(bool, String)> {
+        let mut runs: Vec<(bool, String)> = Vec::new();
+        for c in s.chars() {
+            let digit = c.is_ascii_digit();
+            match runs.last_mut() {
+                Some((d, run)) if *d == digit => run.push(c),
+                _ => runs.push((digit, c.to_string())),
+            }
+        }
+        runs
+    };
+    let (a_runs, b_runs) = (runs(a), runs(b));
+    for ((a_digit, x), (b_digit, y)) in a_runs.iter().zip(&b_runs) {
+        let by = match (a_digit, b_digit) {
+            (true, true) => {
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x.len().cmp(&y.len()).then(x.cmp(y))
+            }
+            _ => x.cmp(y),
+        };
+        if by.is_ne() {
+            return by;
+        }
+    }
+
+    a_runs.len().cmp(&b_runs.len()).then(a.cmp(b))
+}
+
+///Check a plex preset is enabled
+pub fn plex_enabled(args: &Args) -> bool {
+    args.preset.iter().any(|p| p.starts_with("plex-"))
+}
+
+///Plan targets of regular files in the layouts of plex presets relative to their directories,
+///directories which files are already in not repeated,files fitting no layout left alone
+pub fn plex_targets(files: Vec<PathBuf>, args: &Args) -> Result<(Vec<PathBuf>, Vec<String>)> {
+    let mut planned = HashSet::new();
+    let mut renames = (Vec::new(), Vec::new());
+    for f in exclude_fdn_own(files)? {
+        if !f.is_file() {
+            continue;
+        }
+        let stem = os2string(f.file_stem())?;
+        let Some((dirs, stem)) = args.preset.iter().find_map(|p| plex_layout(p, &stem)) else {
+            continue;
+        };
+        let name = match f.extension() {
+            Some(ext) => format!("{}.{}", stem, ext.to_string_lossy()),
+            None => stem,
+        };
+
+        //names of the parent,grandparent and further directories
+        let dir = f.parent().unwrap_or(Path::new(""));
+        let abs = fs::canonicalize(match dir.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => dir,
+        })?;
+        let ancestors: Vec<_> = abs
+            .ancestors()
+            .filter_map(|a| a.file_name().and_then(OsStr::to_str))
+            .collect();
+        let inside = (0..=dirs.len())
+            .rev()
+            .find(|k| {
+                ancestors.len() >= *k
+                    && dirs[..*k].iter().rev().zip(&ancestors).all(|(d, a)| d == a)
+            })
+            .unwrap_or(0);
+        let target = dirs[inside..]
+            .iter()
+            .fold(PathBuf::new(), |p, d| p.join(d))
+            .join(&name);
+
+        let t_path = dir.join(&target);
+        if t_path == f {
+            continue;
+        }
+        if t_path.exists() || !planned.insert(t_path) {
+            warn!("Skip {:?},target {:?} exists", f, target);
+            continue;
+        }
+        renames.0.push(f);
+        renames.1.push(os2string(Some(target.as_os_str()))?);
+    }
+
+    Ok(renames)
+}
+
+///Groups of regular files with identical contents and not all the same name,paths sorted and
+///the first one of a group kept as the origin
+pub fn find_dupes(files: &[PathBuf]) -> Result<Vec<Vec<PathBuf>>> {
+    //only files of the same size are hashed
+    let mut by_len: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for f in files.iter().filter(|f| f.is_file()) {
+        by_len.entry(fs::metadata(f)?.len()).or_default().push(f);
+    }
+    let candidates: Vec<_> = by_len
+        .into_values()
+        .filter(|g| g.len() > 1)
+        .flatten()
+        .collect();
+    let hashed = candidates
+        .par_iter()
+        .map(|f| Ok((hash_file(f)?, (*f).clone())))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut by_hash: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    hashed
+        .into_iter()
+        .for_each(|(h, f)| by_hash.entry(h).or_default().push(f));
+    let mut groups: Vec<_> = by_hash
+        .into_values()
+        .filter(|g| g.iter().any(|f| f.file_name() != g[0].file_name()))
+        .map(|mut g| {
+            g.sort();
+            g
+        })
+        .collect();
+    groups.sort();
+
+    Ok(groups)
+}
+
+///Report duplicates of files in the mode of '--detect-dupes' and return the files left to rename
+pub fn detect_dupes(files: Vec<PathBuf>, args: &Args) -> Result<Vec<PathBuf>> {
+    let mode = args.detect_dupes.as_deref().unwrap_or("report");
+    let ctx = (args.in_place && mode == "link")
+        .then(|| Context::new(args))
+        .transpose()?;
+
+    dupes_handled(files, mode, ctx.as_ref())
+}
+
+///Report duplicates of files in a mode and return the files left to rename,duplicates linked only
+///in place with the context logging them
+fn dupes_handled(files: Vec<PathBuf>, mode: &str, ctx: Option<&Context>) -> Result<Vec<PathBuf>> {
+    let groups = find_dupes(&files)?;
+    let mut skipped = HashSet::new();
+    for g in groups.iter() {
+        println!("   {}", g[0].display());
+        for dup in g[1..].iter() {
+            println!("==={}", dup.display());
+            match (mode, ctx) {
+                ("skip", _) => {
+                    skipped.insert(dup.clone());
+                }
+                ("link", Some(ctx)) => {
+                    let (Some(dir), Some(base)) = (dup.parent(), dup.file_name()) else {
+                        continue;
+                    };
+                    let d_b = DirBase {
+                        dir: os2string(Some(dir.as_os_str()))?,
+                        base: os2string(Some(base))?,
+                    };
+                    let origin = g[0].canonicalize()?;
+                    let rlt = link_dup(&origin, dup);
+                    log_renamed(ctx, &d_b, &origin.to_string_lossy(), "linked", rlt)?;
+                }
+                _ => {}
+            }
+        }
+    }
+    if mode == "link" && ctx.is_none() && !groups.is_empty() {
+        warn!("Duplicates are linked only in place,use '-i' to link them");
+    }
+    if !groups.is_empty() {
+        warn!(
+            "{} files have duplicates of different names",
+            groups.iter().map(|g| g.len() - 1).sum::<usize>()
+        );
+    }
+
+    Ok(files.into_iter().filter(|f| !skipped.contains(f)).collect())
+}
+
+///Replace a duplicate by a hard link to the origin,the duplicate kept if linking fails
+fn link_dup(origin: &Path, dup: &Path) -> io::Result<()> {
+    let tmp = dup.with_file_name(format!(
+        ".{}.fdn-link",
+        dup.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    fs::hard_link(origin, &tmp)?;
+    fs::rename(&tmp, dup).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+///Replace a hard link to a duplicate by a copy of its own,undoing 'link_dup'
+fn unlink_dup(dup: &Path) -> io::Result<()> {
+    let tmp = dup.with_file_name(format!(
+        ".{}.fdn-unlink",
+        dup.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    fs::copy(dup, &tmp)?;
+    fs::rename(&tmp, dup).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+///Order renames so that every target is free when renamed,error on a cycle of renames
+fn order_renames(mut pending: Vec<(PathBuf, PathBuf)>) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut ordered = Vec::new();
+    while !pending.is_empty() {
+        let origins: HashSet<_> = pending
+            .iter()
+            .filter(|(f, t)| f != t)
+            .map(|(f, _)| f.clone())
+            .collect();
+        let (ready, blocked): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|(f, t)| f == t || !origins.contains(t));
+        if ready.is_empty() {
+            return Err(anyhow!(
+                "Renames form a cycle,rename one of them first:{:?}",
+                blocked.iter().map(|(f, _)| f).collect::<Vec<_>>()
+            ));
+        }
+        ordered.extend(ready);
+        pending = blocked;
+    }
+
+    Ok(ordered)
+}
+
+///Return target or target with a "_N" suffix on stem that is not taken and not existing on disk
+fn unique_target(dir: &Path, origin: &str, target: &str, taken: &HashSet<PathBuf>) -> String {
+    let is_free = |name: &str| {
+        let p = dir.join(name);
+        !taken.contains(&p) && (name == origin || !p.exists())
+    };
+    if is_free(target) {
+        return target.to_owned();
+    }
+
+    let (stem, ext) = match target.rsplit_once('.') {
+        Some((s, e)) if !s.is_empty() => (s, format!(".{}", e)),
+        _ => (target, String::new()),
+    };
+    (1..)
+        .map(|n| format!("{}_{}{}", stem, n, ext))
+        .find(|name| is_free(name))
+        .unwrap_or(target.to_owned())
+}
+
+///Scan a directory,normalize names conservatively,remove duplicate markers,resolve collisions,
+///then apply all changes after a single confirmation and print a summary
+pub fn clean(dir: &Path, args: Args, yes: bool) -> Result<()> {
+    if args.explain {
+        return Err(anyhow!(
+            "'--explain' is not supported by 'fdn clean',whose targets also strip duplicate \
+             markers and resolve collisions"
+        ));
+    }
+    let exs = args.excludes_under(dir)?;
+    let mut files = exclude_fdn_own(regular_files(dir, args.max_depth, &exs, &args.skips()?)?)?;
+    files.retain(|f| args.hidden_kept(hidden_in(f, dir)));
+    files.sort();
+    exs.warn_unmatched();
+
+    let rules = RuleSet::load(&open_db(None)?)?;
+    let color = color_enabled(&args.color);
+
+    let mut taken = HashSet::new();
+    let mut origins = Vec::new();
+    let mut targets = Vec::new();
+    let mut collisions = 0;
+    for f in files.iter() {
+        if let Some(d_b) = dir_base(f) {
+            let stripped = match Path::new(&d_b.base).extension() {
+                Some(ext) => format!(
+                    "{}.{}",
+                    strip_dup_markers(&os2string(Path::new(&d_b.base).file_stem())?)?,
+                    os2string(Some(ext))?
+                ),
+                None => strip_dup_markers(&d_b.base)?,
+            };
+            let planned = plan_base(&stripped, true, &rules)?;
+            let target = unique_target(Path::new(&d_b.dir), &d_b.base, &planned, &taken);
+            if target != planned {
+                collisions += 1;
+            }
+            taken.insert(Path::new(&d_b.dir).join(&target));
+            if target != d_b.base {
+                print_change(&d_b, &target, &args, color, None)?;
+                origins.push(f.clone());
+                targets.push(target);
+            }
+        }
+    }
+
+    println!(
+        "Scanned {} files,{} to rename,{} collisions resolved",
+        files.len(),
+        origins.len(),
+        collisions
+    );
+    if origins.is_empty() {
+        return Ok(());
+    }
+
+    if !yes {
+        print!("Apply {} renames? [y/N] ", origins.len());
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Nothing changed");
+            return Ok(());
+        }
+    }
+
+    let n = origins.len();
+    let args = Args {
+        in_place: true,
+        ..args
+    };
+    let _lock = TreeLock::for_run(dir, &args)?;
+    fdn_fs_post(origins, targets, args)?;
+    println!("Renamed {} files", n);
+
+    Ok(())
+}
+
+///Print latest operations filtered by directory and status,keep printing new ones if follow
+pub fn log_tail(dir: Option<&str>, status: Option<&str>, lines: usize, follow: bool) -> Result<()> {
+    let conn = open_db(None)?;
+    let dir = dir.map(|d| {
+        let p = Path::new(d);
+        p.canonicalize()
+            .unwrap_or(p.to_path_buf())
+            .to_string_lossy()
+            .to_string()
+    });
+
+    //read before the latest lines,so that following starts after them even if none is printed
+    let latest = latest_operation_id(&conn)?;
+    let mut last_id = 0;
+    let mut limit = lines;
+    loop {
+        let ops = retrieve_operations(&conn, last_id, dir.as_deref(), status, limit)?;
+        ops.iter().for_each(|op| {
+            println!(
+                "{}\t{}\t{}\t{}\t{} -> {}",
+                op.created, op.session, op.status, op.dir, op.origin, op.target
+            );
+        });
+        if let Some(op) = ops.last() {
+            last_id = op.id;
+        }
+        last_id = last_id.max(latest);
+        //the first signal stops following,since there is no rename to finish
+        if !follow || aborted() {
+            break;
+        }
+        limit = usize::MAX >> 1;
+        thread::sleep(Duration::from_secs(1));
+    }
+
+    Ok(())
+}
+
+///Print normalized names and optionally the rules which fired
+pub fn try_names(names: &[String], dir: bool, explain: bool, args: &Args) -> Result<()> {
+    let rules = RuleSet::load(&open_db(None)?)?.with_args(args);
+
+    names.iter().try_for_each(|name| -> Result<()> {
+        let (stem, ext) = match dir {
+            true => (name.as_str(), None),
+            false => match name.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+                _ => (name.as_str(), None),
+            },
+        };
+        let (stem, steps) = rules.trace_stem(stem)?;
+        match ext {
+            Some(ext) => println!("{}.{}", stem, ext),
+            None => println!("{}", stem),
+        }
+        if explain {
+            steps
+                .iter()
+                .for_each(|s| println!("   {} {:?}:{} -> {}", s.stage, s.rule, s.before, s.after));
+        }
+        Ok(())
+    })
+}
+
+///List interrupted in-place runs or resume one of them by session id,default the latest one
+pub fn resume(session: Option<&str>, list: bool) -> Result<()> {
+    let conn = open_db(None)?;
+    let cps = retrieve_checkpoints(&conn)?;
+
+    if list {
+        cps.iter().for_each(|cp| {
+            println!(
+                "{} {} {} pending:{}",
+                cp.session,
+                cp.created,
+                cp.argv.join(" "),
+                cp.pending
+            )
+        });
+
+        return Ok(());
+    }
+
+    let cp = match session {
+        Some(s) => cps.into_iter().find(|cp| cp.session == s),
+        None => cps.into_iter().next(),
+    };
+    let cp = cp.ok_or(anyhow!("No interrupted run to resume"))?;
+
+    //paths gone are renamed before interrupted,their records can't be recovered
+    let (pending, gone): (Vec<_>, Vec<_>) = retrieve_pending_paths(&conn, &cp.session)?
+        .into_iter()
+        .partition(|(p, _)| Path::new(p).exists());
+    gone.iter().try_for_each(|(p, _)| -> Result<()> {
+        warn!("Skip {:?},not exists any more", p);
+        update_checkpoint_path(&conn, &cp.session, p)
+    })?;
+    drop(conn);
+
+    let mut args = Args::try_parse_from(&cp.argv)?;
+    args.session = cp.session;
+    args.argv = cp.argv;
+    args.in_place = true;
+    let _locks = TreeLock::for_inputs(&args.input_paths(), &args)?;
+
+    let (files, targets): (Vec<_>, Vec<_>) = pending
+        .into_iter()
+        .map(|(p, t)| (PathBuf::from(p), t))
+        .unzip();
+    if targets.iter().all(Option::is_some) && !targets.is_empty() {
+        fdn_fs_post(files, targets.into_iter().flatten().collect(), args)
+    } else if args.reverse || args.reverse_chainly {
+        fdn_rfs_post(files, args)
+    } else {
+        fdn_fs_post(files, Vec::new(), args)
+    }
+}
+
+///A rule learned from names,in config syntax with the flags of term words
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    ///stage the rule belongs to,'to-sep-words','term-words' or 'regex-rules'
+    pub stage: &'static str,
+    pub rule: String,
+    pub flags: TermFlags,
+    ///number of names the rule is learned from
+    pub count: usize,
+    pub reason: String,
+}
+
+///Learn rules from stems not configured yet:separators other than sep as to sep words,case
+///variants of words as whole word term words and repeated bracketed groups or web sites as regex
+///rules removing them,each found in at least min_count stems
+fn suggest_rules(stems: &[String], rules: &RuleSet, min_count: usize) -> Vec<Suggestion> {
+    static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\p{L}{2,}").unwrap());
+    static JUNK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)[\[({][^\[\](){}]+[\])}]|\bwww\.[a-z0-9-]+(?:\.[a-z0-9-]+)+").unwrap()
+    });
+    static YEAR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^.(19|20)\d{2}.$").unwrap());
+
+    //names each item is found in,counted once per name
+    let count_in = |items: &mut HashMap<String, usize>, found: HashSet<String>| {
+        found
+            .into_iter()
+            .for_each(|k| *items.entry(k).or_insert(0) += 1)
+    };
+    let mut seps = HashMap::new();
+    let mut words = HashMap::new();
+    let mut junks = HashMap::new();
+    for stem in stems {
+        let junk_found = JUNK
+            .find_iter(stem)
+            .map(|m| m.as_str().to_owned())
+            .filter(|j| !YEAR.is_match(j))
+            .collect();
+        //separators and words of junk not counted
+        let stem = &JUNK.replace_all(stem, "");
+        count_in(
+            &mut seps,
+            stem.chars()
+                .filter(|c| !c.is_alphanumeric() && !"()[]{}'".contains(*c))
+                .map(String::from)
+                .filter(|c| *c != rules.sep)
+                .collect(),
+        );
+        count_in(
+            &mut words,
+            WORD.find_iter(stem)
+                .map(|m| m.as_str().to_owned())
+                .collect(),
+        );
+        count_in(&mut junks, junk_found);
+    }
+
+    let mut suggestions: Vec<_> = seps
+        .into_iter()
+        .filter(|(c, n)| *n >= min_count && !rules.to_sep_words.contains(c))
+        .map(|(c, n)| Suggestion {
+            stage: STAGES[12],
+            reason: format!(
+                "{:?} separates words of {} names,{:?} configured",
+                c, n, rules.sep
+            ),
+            rule: c,
+            flags: TermFlags::default(),
+            count: n,
+        })
+        .collect();
+
+    //case variants differing beyond the first letter,the most used one canonical
+    let mut variants: BTreeMap<String, Vec<(String, usize)>> = BTreeMap::new();
+    words
+        .into_iter()
+        .for_each(|(w, n)| variants.entry(w.to_lowercase()).or_default().push((w, n)));
+    for (_, mut forms) in variants {
+        forms.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let (canonical, _) = &forms[0];
+        let total: usize = forms.iter().map(|(_, n)| n).sum();
+        for (form, n) in forms.iter().skip(1) {
+            let tail = |w: &str| w.chars().skip(1).collect::<String>();
+            if total < min_count
+                || tail(form) == tail(canonical)
+                || rules.term_words.iter().any(|(k, _)| k == form)
+            {
+                continue;
+            }
+            suggestions.push(Suggestion {
+                stage: STAGES[13],
+                rule: format!("{}:{}", form, canonical),
+                flags: TermFlags {
+                    ignore_case: false,
+                    whole_word: true,
+                },
+                count: *n,
+                reason: format!("{:?} in {} names but {:?} in more", form, n, canonical),
+            });
+        }
+    }
+
+    suggestions.extend(junks.into_iter().filter_map(|(j, n)| {
+        let pattern = regex::escape(&j);
+        (n >= min_count
+            && !rules
+                .regex_rules
+                .iter()
+                .any(|(re, _)| re.as_str() == pattern))
+        .then(|| Suggestion {
+            stage: STAGES[14],
+            rule: format!("re:{}:", pattern),
+            flags: TermFlags::default(),
+            count: n,
+            reason: format!("{:?} repeated in {} names", j, n),
+        })
+    }));
+
+    let order = |s: &Suggestion| STAGES.iter().position(|stage| *stage == s.stage);
+    suggestions.sort_by(|a, b| {
+        order(a)
+            .cmp(&order(b))
+            .then(b.count.cmp(&a.count))
+            .then(a.rule.cmp(&b.rule))
+    });
+
+    suggestions
+}
+
+///Suggest rules learned from names of regular files under the directory,add the accepted ones
+///into config after asking or all of them if yes
+pub fn suggest(path: &Path, min_count: usize, yes: bool, args: &Args) -> Result<()> {
+    let exs = args.excludes_under(path)?;
+    let mut files = exclude_fdn_own(regular_files(path, args.max_depth, &exs, &args.skips()?)?)?;
+    files.retain(|f| args.hidden_kept(hidden_in(f, path)));
+    exs.warn_unmatched();
+    let stems: Vec<_> = files
+        .iter()
+        .filter_map(|f| f.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .collect();
+
+    let rules = RuleSet::load(&open_db(None)?)?;
+    let suggestions = suggest_rules(&stems, &rules, min_count);
+    println!(
+        "Scanned {} names,{} rules suggested",
+        stems.len(),
+        suggestions.len()
+    );
+
+    let mut added = 0;
+    for s in suggestions {
+        println!("{}\t{:?}\t{}", s.stage, s.rule, s.reason);
+        if !yes {
+            print!("Add {:?}? [y/N/q] ", s.rule);
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            match answer.trim() {
+                "y" | "Y" | "yes" => {}
+                "q" | "Q" => break,
+                _ => continue,
+            }
+        }
+        config_add(&s.rule, s.flags, None)?;
+        added += 1;
+    }
+    println!("Added {} rules", added);
+
+    Ok(())
+}
+
+///Watch a directory by notifications of the OS,or by scanning it every interval of poll,
+///normalize new regular files in place once they stay unchanged for the debounce,until aborted
+///by a signal
+pub fn watch(dir: &Path, poll: Option<Duration>, debounce: Duration, args: &Args) -> Result<()> {
+    let args = Args {
+        in_place: true,
+        ..args.clone()
+    };
+    let mut watched = Watched::new(dir, args, debounce, poll)?;
+    println!("Watching {:?},Ctrl-C to stop", dir);
+
+    while !aborted() {
+        watched.step()?;
+        thread::sleep(WATCH_TICK);
+    }
+
+    Ok(())
+}
+
+///A watched directory with its new files waiting for the debounce
+struct Watched {
+    dir: PathBuf,
+    args: Args,
+    watcher: Watcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    ///kept for as long as the directory is watched
+    _notifier: Box<dyn notify::Watcher>,
+}
+
+impl Watched {
+    ///Watch a directory by notifications of the OS,or by scanning it every interval of poll for
+    ///file systems sending none such as network shares;subdirectories watched if the depth of
+    ///args reaches them
+    fn new(dir: &Path, args: Args, debounce: Duration, poll: Option<Duration>) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(anyhow!("Not a directory"));
+        }
+        let (tx, events) = mpsc::channel();
+        let mut notifier: Box<dyn notify::Watcher> = match poll {
+            Some(interval) => Box::new(notify::PollWatcher::new(
+                tx,
+                notify::Config::default().with_poll_interval(interval),
+            )?),
+            None => Box::new(notify::recommended_watcher(tx)?),
+        };
+        let mode = match args.max_depth > 1 {
+            true => RecursiveMode::Recursive,
+            false => RecursiveMode::NonRecursive,
+        };
+        notifier.watch(dir, mode)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            args,
+            watcher: Watcher::new(debounce),
+            events,
+            _notifier: notifier,
+        })
+    }
+
+    ///Take the notifications so far,normalize new regular files which are ready by args
+    fn step(&mut self) -> Result<()> {
+        if !self.dir.is_dir() {
+            return Err(anyhow!("Not a directory"));
+        }
+        let exs = self.args.excludes_under(&self.dir)?;
+        let skips = self.args.skips()?;
+        let now = Instant::now();
+        while let Ok(event) = self.events.try_recv() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("Notification of {:?} failed:{}", self.dir, e);
+                    continue;
+                }
+            };
+            //a file created or renamed into the directory,such as a download losing its '.part'
+            let new = matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Modify(ModifyKind::Name(
+                        RenameMode::To | RenameMode::Both | RenameMode::Any
+                    ))
+            );
+            let Some(path) = event.paths.last().filter(|_| new) else {
+                continue;
+            };
+            if let (true, Some(stamp)) = (self.listed(path, &exs, &skips), stamp_of(path)) {
+                self.watcher.notice(path.clone(), stamp, now);
+            }
+        }
+
+        let ready = exclude_fdn_own(self.watcher.ready(now, stamp_of))?;
+        if !ready.is_empty() {
+            let _lock = TreeLock::for_run(&self.dir, &self.args)?;
+            if let Err(e) = fdn_fs_post(ready, Vec::new(), self.args.clone()) {
+                warn!("Failed to normalize new files of {:?}:{}", self.dir, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    ///Check a file is one a scan of the directory by args would list,a regular file within the
+    ///depth neither excluded,skipped nor hidden by the policy,and not a temporary one
+    fn listed(&self, path: &Path, exs: &Excludes, skips: &[String]) -> bool {
+        let depth = path
+            .strip_prefix(&self.dir)
+            .map_or(usize::MAX, |rel| rel.components().count());
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        path.is_file()
+            && depth <= self.args.max_depth
+            && !exs.excluded(path)
+            && !is_skipped(&name, skips)
+            && self.args.hidden_kept(hidden_in(path, &self.dir))
+            && !is_temp(path)
+    }
+}
+
+///Size and modified time of a file,None if it is gone
+fn stamp_of(path: &Path) -> Option<Stamp> {
+    let meta = fs::metadata(path).ok()?;
+
+    Some((meta.len(), meta.modified().ok()))
+}
+
+///Start the daemon in the background,stop it,show its status or run it in the foreground
+pub fn daemon(action: &str, config: Option<&Path>) -> Result<()> {
+    let config = match config {
+        Some(c) => c.to_path_buf(),
+        None => daemon::config_path()?,
+    };
+    let pid = daemon::running_pid()?;
+
+    match (action, pid) {
+        ("start", Some(pid)) => Err(anyhow!("Daemon is already running:{}", pid)),
+        ("start", None) => {
+            //roots checked before the daemon goes to the background
+            let roots = daemon::load_config(&config)?;
+            let log = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(daemon::log_path()?)?;
+            let child = process::Command::new(env::current_exe()?)
+                .arg("daemon")
+                .arg("run")
+                .arg("--config")
+                .arg(&config)
+                .stdin(process::Stdio::null())
+                .stdout(log.try_clone()?)
+                .stderr(log)
+                .spawn()?;
+            fs::write(daemon::pid_path()?, child.id().to_string())?;
+            println!(
+                "Daemon started:{},watching {} roots,log {:?}",
+                child.id(),
+                roots.len(),
+                daemon::log_path()?
+            );
+            Ok(())
+        }
+        ("stop", Some(pid)) => {
+            daemon::terminate(pid)?;
+            println!("Daemon stopped:{}", pid);
+            Ok(())
+        }
+        ("status", Some(pid)) => {
+            println!(
+                "Daemon is running:{},config {:?},socket {:?}",
+                pid,
+                config,
+                daemon::socket_path()?
+            );
+            Ok(())
+        }
+        ("stop" | "status", None) => {
+            println!("Daemon is not running");
+            Ok(())
+        }
+        ("run", _) => daemon_run(&config),
+        _ => Err(anyhow!("Unknown daemon action:{:?}", action)),
+    }
+}
+
+///Watch the roots of the config until aborted by a signal,roots reloaded when the config
+///file changes and old ones kept if the new config is invalid
+fn daemon_run(config: &Path) -> Result<()> {
+    let modified = || fs::metadata(config).and_then(|m| m.modified()).ok();
+    let watched = |roots: Vec<daemon::Root>| -> Result<Vec<_>> {
+        roots
+            .into_iter()
+            .map(|r| {
+                let args = Args {
+                    in_place: true,
+                    ..r.args()?
+                };
+                Ok((
+                    r.path,
+                    args,
+                    Duration::from_secs(r.debounce),
+                    None::<Watched>,
+                    None,
+                ))
+            })
+            .collect()
+    };
+
+    let mut loaded = modified();
+    let mut roots = watched(daemon::load_config(config)?)?;
+    fs::write(daemon::pid_path()?, process::id().to_string())?;
+    #[cfg(unix)]
+    rpc_listen(&daemon::socket_path()?)?;
+    #[cfg(not(unix))]
+    warn!("The JSON-RPC socket is only served on unix");
+    println!("Daemon {} watching {} roots", process::id(), roots.len());
+
+    while !aborted() {
+        if modified() != loaded {
+            loaded = modified();
+            match daemon::load_config(config).and_then(watched) {
+                Ok(r) => {
+                    roots = r;
+                    println!("Daemon config reloaded,watching {} roots", roots.len());
+                }
+                Err(e) => warn!("Daemon config not reloaded:{}", e),
+            }
+        }
+        //a root watched again once it is back,a failing root warned once until its error changes
+        for (dir, args, debounce, watching, failed) in roots.iter_mut() {
+            let rlt = match watching {
+                Some(w) => w.step(),
+                None => Watched::new(dir, args.clone(), *debounce, None).map(|w| {
+                    *watching = Some(w);
+                }),
+            };
+            match rlt {
+                Ok(()) => *failed = None,
+                Err(e) => {
+                    *watching = None;
+                    let e = e.to_string();
+                    if failed.as_ref() != Some(&e) {
+                        warn!("Failed to watch {:?}:{}", dir, e);
+                        *failed = Some(e);
+                    }
+                }
+            }
+        }
+        thread::sleep(DAEMON_INTERVAL);
+    }
+    //the pid file may be gone with a config directory removed meanwhile
+    match fs::remove_file(daemon::pid_path()?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    #[cfg(unix)]
+    let _ = fs::remove_file(daemon::socket_path()?);
+
+    Ok(())
+}
+
+///Listen on a unix socket in a thread,serving connections one by one
+#[cfg(unix)]
+fn rpc_listen(path: &Path) -> Result<()> {
+    use std::os::unix::net::UnixListener;
+
+    //a socket left by a daemon which did not stop cleanly
+    let _ = fs::remove_file(path);
+    let listener = UnixListener::bind(path)?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let rlt = stream
+                .map_err(anyhow::Error::from)
+                .and_then(|s| rpc_serve(&s, &s));
+            if let Err(e) = rlt {
+                warn!("JSON-RPC connection failed:{}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+///Serve JSON-RPC requests of a connection,one request and one response per line
+fn rpc_serve(input: impl io::Read, mut output: impl Write) -> Result<()> {
+    for line in io::BufRead::lines(io::BufReader::new(input)) {
+        let line = line?;
+        if !line.trim().is_empty() {
+            writeln!(output, "{}", rpc_response(&line, rpc_call))?;
+        }
+    }
+
+    Ok(())
+}
+
+///Response of a JSON-RPC 2.0 request,the handler returns None for an unknown method
+fn rpc_response(
+    request: &str,
+    handler: impl Fn(&str, &serde_json::Value) -> Result<Option<serde_json::Value>>,
+) -> String {
+    let error = |id: &serde_json::Value, code: i32, message: String| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": code, "message": message},
+        })
+    };
+    let v = match serde_json::from_str::<serde_json::Value>(request) {
+        Ok(v) => v,
+        Err(e) => return error(&json!(null), -32700, e.to_string()).to_string(),
+    };
+    let id = v.get("id").cloned().unwrap_or(json!(null));
+    let Some(method) = v.get("method").and_then(|m| m.as_str()) else {
+        return error(&id, -32600, "Request must have a 'method'".to_owned()).to_string();
+    };
+    let params = v.get("params").cloned().unwrap_or(json!({}));
+
+    match handler(method, &params) {
+        Ok(Some(result)) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Ok(None) => error(&id, -32601, format!("Unknown method:{:?}", method)),
+        Err(e) => error(&id, -32000, e.to_string()),
+    }
+    .to_string()
+}
+
+///String param of a JSON-RPC request
+fn rpc_param<'a>(params: &'a serde_json::Value, key: &str) -> Result<&'a str> {
+    params
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("Missing string param {:?}", key))
+}
+
+///Call a JSON-RPC method of the daemon:'preview' a name,'normalize' a path in place or 'undo'
+///the last rename
+fn rpc_call(method: &str, params: &serde_json::Value) -> Result<Option<serde_json::Value>> {
+    let args = Args::try_parse_from(["fdn", "-i"])?;
+    let rules = RuleSet::load(&open_db(None)?)?;
+
+    match method {
+        "preview" => rpc_preview(params, &rules).map(Some),
+        "normalize" => {
+            let path = PathBuf::from(rpc_param(params, "path")?);
+            //a relative path would resolve against the cwd of the daemon,not of the client
+            if !path.is_absolute() {
+                return Err(anyhow!("Path must be absolute:{:?}", path));
+            }
+            let d_b = dir_base(&path).ok_or_else(|| anyhow!("Invalid path:{:?}", path))?;
+            if !path.exists() {
+                return Err(anyhow!("Path not exists:{:?}", path));
+            }
+            let target = plan_base(&d_b.base, path.is_file(), &rules)?;
+            let to = Path::new(&d_b.dir).join(&target);
+            if target != d_b.base {
+                let _lock = TreeLock::for_run(&path, &args)?;
+                fdn_fs_post(vec![path.clone()], vec![target], args)?;
+                if !to.exists() {
+                    return Err(anyhow!("Path not renamed:{:?}", path));
+                }
+            }
+            Ok(Some(json!({"from": path, "to": to, "changed": path != to})))
+        }
+        "undo" => {
+            let conn = open_db(None)?;
+            let ops = retrieve_operations(&conn, 0, None, None, RPC_UNDO_SCAN)?;
+            let Some(op) = undoable_rename(&ops) else {
+                return Err(anyhow!("Nothing to undo"));
+            };
+            if op.status == "linked" {
+                let dup = Path::new(&op.dir).join(&op.origin);
+                let ctx = Context::with_conn(conn, &args)?;
+                let d_b = DirBase {
+                    dir: op.dir.clone(),
+                    base: op.origin.clone(),
+                };
+                log_renamed(&ctx, &d_b, &op.target, "unlinked", unlink_dup(&dup))?;
+                return Ok(Some(json!({"from": op.target, "to": dup})));
+            }
+            let (from, to) = (
+                Path::new(&op.dir).join(&op.target),
+                Path::new(&op.dir).join(&op.origin),
+            );
+            let _lock = TreeLock::for_run(&from, &args)?;
+            fdn_rfs_post(vec![from.clone()], args)?;
+            if !to.exists() {
+                return Err(anyhow!("Path not restored:{:?}", from));
+            }
+            Ok(Some(json!({"from": from, "to": to})))
+        }
+        _ => Ok(None),
+    }
+}
+
+///Latest rename or link of duplicate of operations in ascending order whose file still exists,
+///those already undone by later operations skipped
+fn undoable_rename(ops: &[Operation]) -> Option<&Operation> {
+    let mut reversed = HashSet::new();
+    ops.iter().rev().find(|op| match op.status.as_str() {
+        "reversed" | "unlinked" => {
+            reversed.insert((&op.dir, &op.target, &op.origin));
+            false
+        }
+        "renamed" => {
+            !reversed.contains(&(&op.dir, &op.origin, &op.target))
+                && Path::new(&op.dir).join(&op.target).exists()
+        }
+        "linked" => {
+            !reversed.contains(&(&op.dir, &op.target, &op.origin))
+                && Path::new(&op.dir).join(&op.origin).exists()
+        }
+        _ => false,
+    })
+}
+
+///Preview the name a base name would be normalized into,'dir' true for a directory name
+fn rpc_preview(params: &serde_json::Value, rules: &RuleSet) -> Result<serde_json::Value> {
+    let name = rpc_param(params, "name")?;
+    let dir = params.get("dir").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    Ok(json!({"name": plan_base(name, !dir, rules)?}))
+}
+
+///Serve the REST API until aborted by a signal,connections served one by one so that renames
+///never run concurrently
+pub fn serve(listen: &str, token: Option<&str>) -> Result<()> {
+    let listener = TcpListener::bind(listen)?;
+    let addr = listener.local_addr()?;
+    if !addr.ip().is_loopback() && token.is_none() {
+        return Err(anyhow!(
+            "Serving on non-loopback address {} needs '--token'",
+            addr
+        ));
+    }
+    listener.set_nonblocking(true)?;
+    println!("Serving the REST API on http://{}", addr);
+
+    while !aborted() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = serve_connection(&stream, token) {
+                    warn!("HTTP connection failed:{}", e);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(SERVE_POLL),
+            Err(e) => warn!("Failed to accept a connection:{}", e),
+        }
+    }
+
+    Ok(())
+}
+
+///Answer one request of a connection
+fn serve_connection(stream: &TcpStream, token: Option<&str>) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    //a client slow to send its request holds up the others
+    stream.set_read_timeout(Some(SERVE_READ_TIMEOUT))?;
+    let (status, body) = match read_request(io::BufReader::new(stream)) {
+        Ok(req) if !req.authorized(token) => (401, json!({"error": "Missing or wrong token"})),
+        Ok(req) => match req.forged(token) {
+            Some((status, reason)) => (status, json!({ "error": reason })),
+            None => serve_route(&req),
+        },
+        Err(e) => (400, json!({"error": e.to_string()})),
+    };
+    write_response(stream, status, &body)?;
+
+    Ok(())
+}
+
+///Status and body answering a request of the REST API:'GET|POST /preview' and 'POST /renames'
+///of a path with fdn arguments,'GET /history' of operations and 'POST /undo' of the last rename
+fn serve_route(req: &Request) -> (u16, serde_json::Value) {
+    let rlt = match (req.method.as_str(), req.path.as_str()) {
+        ("GET" | "POST", "/preview") => serve_batch(req, false),
+        ("POST", "/renames") => serve_batch(req, true),
+        ("GET", "/history") => serve_history(req),
+        ("POST", "/undo") => rpc_call("undo", &json!({})).map(Option::unwrap_or_default),
+        (_, "/preview" | "/renames" | "/history" | "/undo") => {
+            return (
+                405,
+                json!({"error": format!("Method {} not allowed", req.method)}),
+            )
+        }
+        _ => return (404, json!({"error": format!("No endpoint {}", req.path)})),
+    };
+
+    match rlt {
+        Ok(v) => (200, v),
+        Err(e) => (400, json!({"error": e.to_string()})),
+    }
+}
+
+///Changes of a path planned or applied by fdn with the arguments,the path and arguments from a
+///JSON body such as '{"path":"/a","args":["-d","2"]}' or the query 'path=/a&arg=-d&arg=2',fdn
+///run as a child so that its '--format json' lines are the answer
+fn serve_batch(req: &Request, apply: bool) -> Result<serde_json::Value> {
+    let (path, argv) = match req.body.trim().is_empty() {
+        true => (
+            req.param("path")
+                .ok_or_else(|| anyhow!("Missing query param \"path\""))?
+                .to_owned(),
+            req.params("arg"),
+        ),
+        false => {
+            let v: serde_json::Value = serde_json::from_str(&req.body)?;
+            let argv = match v.get("args") {
+                Some(a) => serde_json::from_value(a.clone())
+                    .map_err(|_| anyhow!("'args' must be a list of strings"))?,
+                None => Vec::new(),
+            };
+            (rpc_param(&v, "path")?.to_owned(), argv)
+        }
+    };
+    if !Path::new(&path).is_absolute() {
+        return Err(anyhow!("Path must be absolute:{:?}", path));
+    }
+    let args = alias_args(&argv, &["--".to_owned(), path.clone()])?;
+    if args.command.is_some() {
+        return Err(anyhow!("Arguments of a batch can not run a subcommand"));
+    }
+    if args.in_place && !apply {
+        return Err(anyhow!(
+            "A preview can not apply renames,use 'POST /renames'"
+        ));
+    }
+
+    let mut cmd = process::Command::new(env::current_exe()?);
+    cmd.args(&argv)
+        .args(["--format", "json", "--report", "none"]);
+    if apply && !args.in_place {
+        cmd.arg("-i");
+    }
+    let output = cmd
+        .arg("--")
+        .arg(&path)
+        .env("RUST_BACKTRACE", "0")
+        .env("RUST_LIB_BACKTRACE", "0")
+        .stdin(process::Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let msg = stderr
+            .lines()
+            .find_map(|l| l.strip_prefix("Error: "))
+            .unwrap_or(stderr.trim());
+        return Err(anyhow!("{}", msg));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+        .collect())
+}
+
+///Latest operations in order,filtered by the query 'dir','status' and 'limit' as 'fdn log'
+fn serve_history(req: &Request) -> Result<serde_json::Value> {
+    let limit = match req.param("limit") {
+        Some(l) => l
+            .parse()
+            .map_err(|_| anyhow!("Invalid query param \"limit\":{:?}", l))?,
+        None => 10,
+    };
+    let dir = req.param("dir").map(|d| {
+        let p = Path::new(d);
+        p.canonicalize()
+            .unwrap_or(p.to_path_buf())
+            .to_string_lossy()
+            .to_string()
+    });
+    let conn = open_db(None)?;
+    let ops = retrieve_operations(&conn, 0, dir.as_deref(), req.param("status"), limit)?;
+
+    Ok(ops
+        .iter()
+        .map(|op| {
+            json!({
+                "session": op.session,
+                "dir": op.dir,
+                "origin": op.origin,
+                "target": op.target,
+                "status": op.status,
+                "created": op.created,
+            })
+        })
+        .collect())
+}
+
+///Naming styles of file names
+#[derive(Debug, Default, PartialEq)]
+struct Audit {
+    names: usize,
+    ///names each separator is found in
+    separators: BTreeMap<String, usize>,
+    ///names of each case style
+    cases: BTreeMap<&'static str, usize>,
+    ///names with non-ASCII chars
+    non_ascii: usize,
+    ///names far shorter or longer than the others by Tukey's fences
+    length_outliers: Vec<String>,
+    ///names sharing a stem in any case,by the lowercase stem
+    duplicates: BTreeMap<String, Vec<String>>,
+}
+
+///Case style of a stem,'lower','upper','title','camel','mixed' or 'none' without cased letters
+fn case_style(stem: &str) -> &'static str {
+    let cased: Vec<_> = stem
+        .chars()
+        .filter(|c| c.is_lowercase() || c.is_uppercase())
+        .collect();
+    if cased.is_empty() {
+        return "none";
+    }
+    if cased.iter().all(|c| c.is_lowercase()) {
+        return "lower";
+    }
+    if cased.iter().all(|c| c.is_uppercase()) {
+        return "upper";
+    }
+    let words: Vec<_> = stem
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().any(char::is_alphabetic))
+        .collect();
+    let title = |w: &str| {
+        let mut chars = w.chars().skip_while(|c| !c.is_alphabetic());
+        chars.next().is_some_and(|c| !c.is_lowercase()) && chars.all(|c| !c.is_uppercase())
+    };
+    if words.iter().all(|w| title(w)) {
+        return "title";
+    }
+    let camel = |w: &str| {
+        w.chars()
+            .zip(w.chars().skip(1))
+            .any(|(a, b)| a.is_lowercase() && b.is_uppercase())
+    };
+    match words.len() == 1 && camel(words[0]) {
+        true => "camel",
+        false => "mixed",
+    }
+}
+
+///Audit names given as paths relative to the audited directory
+fn audit_names(names: &[String]) -> Audit {
+    let mut audit = Audit {
+        names: names.len(),
+        ..Default::default()
+    };
+    let stem = |name: &str| {
+        Path::new(name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+
+    for name in names {
+        let stem = stem(name);
+        let seps: HashSet<_> = stem.chars().filter(|c| !c.is_alphanumeric()).collect();
+        seps.into_iter()
+            .for_each(|c| *audit.separators.entry(c.to_string()).or_insert(0) += 1);
+        *audit.cases.entry(case_style(&stem)).or_insert(0) += 1;
+        if !name.is_ascii() {
+            audit.non_ascii += 1;
+        }
+        audit
+            .duplicates
+            .entry(stem.to_lowercase())
+            .or_default()
+            .push(name.clone());
+    }
+    audit.duplicates.retain(|_, names| names.len() > 1);
+
+    let mut lens: Vec<_> = names.iter().map(|n| stem(n).chars().count()).collect();
+    lens.sort_unstable();
+    if let (Some(q1), Some(q3)) = (lens.get(lens.len() / 4), lens.get(lens.len() * 3 / 4)) {
+        let iqr = (q3 - q1) as f64;
+        let (low, high) = (*q1 as f64 - 1.5 * iqr, *q3 as f64 + 1.5 * iqr);
+        audit.length_outliers = names
+            .iter()
+            .filter(|n| {
+                let len = stem(n).chars().count() as f64;
+                len < low || len > high
+            })
+            .cloned()
+            .collect();
+    }
+
+    audit
+}
+
+///Report naming styles of regular files under the directory as tables or JSON
+pub fn audit(path: &Path, json: bool, args: &Args) -> Result<()> {
+    let exs = args.excludes_under(path)?;
+    let mut files = exclude_fdn_own(regular_files(path, args.max_depth, &exs, &args.skips()?)?)?;
+    files.retain(|f| args.hidden_kept(hidden_in(f, path)));
+    files.sort();
+    exs.warn_unmatched();
+    let names: Vec<_> = files
+        .iter()
+        .map(|f| {
+            f.strip_prefix(path)
+                .unwrap_or(f)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    let audit = audit_names(&names);
+
+    if json {
+        let v = json!({
+            "names": audit.names,
+            "separators": audit.separators,
+            "cases": audit.cases,
+            "non_ascii": audit.non_ascii,
+            "length_outliers": audit.length_outliers,
+            "duplicates": audit.duplicates.values().collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&v)?);
+
+        return Ok(());
+    }
+
+    println!("Audited {} names", audit.names);
+    let s = "Separator";
+    println!("{} Char\tNames", s);
+    let mut separators: Vec<_> = audit.separators.iter().collect();
+    separators.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    separators.iter().for_each(|(c, n)| {
+        println!("{} {:?}\t{}", " ".repeat(s.len()), c, n);
+    });
+    let s = "Case";
+    println!("{} Style\tNames", s);
+    audit.cases.iter().for_each(|(style, n)| {
+        println!("{} {}\t{}", " ".repeat(s.len()), style, n);
+    });
+    println!("NonASCII {}", audit.non_ascii);
+    let s = "LengthOutlier";
+    println!("{} Name", s);
+    audit.length_outliers.iter().for_each(|name| {
+        println!("{} {:?}", " ".repeat(s.len()), name);
+    });
+    let s = "DuplicateStem";
+    println!("{} Names", s);
+    audit.duplicates.values().for_each(|names| {
+        println!("{} {:?}", " ".repeat(s.len()), names);
+    });
+
+    Ok(())
+}
+
+///Browse planned renames of regular files of a directory in a terminal UI,apply the selected
+///ones in place
+pub fn tui(dir: &Path, args: &Args) -> Result<()> {
+    let exs = args.excludes_under(dir)?;
+    let mut files = exclude_fdn_own(regular_files(dir, args.max_depth, &exs, &args.skips()?)?)?;
+    files.retain(|f| args.hidden_kept(hidden_in(f, dir)));
+    exs.warn_unmatched();
+    let rules = RuleSet::load(&open_db(None)?)?.with_args(args);
+    let entries = files
+        .into_iter()
+        .filter_map(|f| {
+            let d_b = dir_base(&f)?;
+            let rel = Path::new(&d_b.dir)
+                .strip_prefix(dir)
+                .unwrap_or(Path::new(&d_b.dir));
+            Some((f.clone(), rel.to_string_lossy().into_owned(), d_b.base))
+        })
+        .map(|(path, dir, origin)| {
+            Ok(tui::Entry {
+                target: plan_base(&origin, true, &rules)?,
+                path,
+                dir,
+                origin,
+                selected: false,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut browser = tui::Browser::new(entries);
+
+    if !browse(&mut browser)? {
+        println!("Nothing renamed");
+        return Ok(());
+    }
+    let (origins, targets) = browser.selection();
+    if origins.is_empty() {
+        println!("Nothing selected");
+        return Ok(());
+    }
+    let args = Args {
+        in_place: true,
+        ..args.clone()
+    };
+    let _lock = TreeLock::for_run(dir, &args)?;
+
+    fdn_fs_post(origins, targets, args)
+}
+
+///Pick a subset of paths in a fuzzy finder,shown relative to the input path,nothing picked if
+///cancelled
+pub fn pick(paths: Vec<PathBuf>, root: &Path) -> Result<Vec<PathBuf>> {
+    if paths.is_empty() {
+        return Ok(paths);
+    }
+    let items = paths
+        .iter()
+        .map(|p| {
+            let rel = p
+                .strip_prefix(root)
+                .ok()
+                .filter(|r| !r.as_os_str().is_empty());
+            rel.unwrap_or(p).to_string_lossy().into_owned()
+        })
+        .collect();
+    let mut picker = tui::Picker::new(items);
+
+    if !fuzzy_pick(&mut picker)? {
+        return Ok(Vec::new());
+    }
+
+    Ok(picker
+        .picked()
+        .into_iter()
+        .map(|i| paths[i].clone())
+        .collect())
+}
+
+///Pick in the fuzzy finder,true if accepted
+#[cfg(feature = "tui")]
+fn fuzzy_pick(picker: &mut tui::Picker) -> Result<bool> {
+    tui::app::pick(picker)
+}
+
+///Pick in the fuzzy finder,true if accepted
+#[cfg(not(feature = "tui"))]
+fn fuzzy_pick(_picker: &mut tui::Picker) -> Result<bool> {
+    Err(anyhow!("fdn is built without feature 'tui'"))
+}
+
+///Browse in the terminal UI,true if the selection is applied
+#[cfg(feature = "tui")]
+fn browse(browser: &mut tui::Browser) -> Result<bool> {
+    tui::app::run(browser)
+}
+
+///Browse in the terminal UI,true if the selection is applied
+#[cfg(not(feature = "tui"))]
+fn browse(_browser: &mut tui::Browser) -> Result<bool> {
+    Err(anyhow!("fdn is built without feature 'tui'"))
+}
+
+///Rename regular files of a directory to names pasted from the clipboard or stdin,matched in the
+///order of the sort key
+pub fn paste_names(dir: &Path, stdin: bool, sort: &str, keep_ext: bool, args: &Args) -> Result<()> {
+    let text = match stdin {
+        true => io::read_to_string(io::stdin())?,
+        false => clipboard_text()?,
+    };
+    let names = pasted_names(&text);
+
+    let exs = args.excludes_under(dir)?;
+    let mut files = exclude_fdn_own(regular_files(dir, 1, &exs, &args.skips()?)?)?
+        .into_iter()
+        .filter(|f| args.hidden_kept(hidden_in(f, dir)))
+        .map(|f| {
+            let meta = fs::metadata(&f)?;
+            Ok((f, meta))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    sort_files(&mut files, sort);
+    let files: Vec<_> = files.into_iter().map(|(f, _)| f).collect();
+    exs.warn_unmatched();
+
+    let renames = pasted_targets(&files, &names, keep_ext)?;
+    let origins: HashSet<_> = files.iter().collect();
+    if let Some((_, t)) = renames
+        .iter()
+        .find(|(f, t)| f != t && t.exists() && !origins.contains(t))
+    {
+        return Err(anyhow!("Target exists:{:?}", t));
+    }
+    let (origins, targets): (Vec<_>, Vec<_>) = order_renames(renames)?
+        .into_iter()
+        .filter(|(f, t)| f != t)
+        .filter_map(|(f, t)| t.file_name().map(|n| (f, n.to_string_lossy().to_string())))
+        .unzip();
+    if origins.is_empty() {
+        println!("Nothing renamed");
+        return Ok(());
+    }
+    let _lock = TreeLock::for_run(dir, args)?;
+
+    fdn_fs_post(origins, targets, args.clone())
+}
+
+///Rename paths by rows of a mapping file relative to the root,nothing renamed if any row fails
+///the checks
+pub fn map(file: &Path, root: &Path, args: &Args) -> Result<()> {
+    let text = fs::read_to_string(file).map_err(|e| anyhow!("Failed to read {:?}:{}", file, e))?;
+    let rows = mapping::parse_map(&text, mapping::delimiter(file))?;
+    let renames = mapping::check_map(root, &rows, |p| p.symlink_metadata().is_ok())?;
+
+    let (origins, targets): (Vec<_>, Vec<_>) = order_renames(renames)?
+        .into_iter()
+        .filter_map(|(f, t)| {
+            let rel = t
+                .strip_prefix(f.parent()?)
+                .ok()?
+                .to_string_lossy()
+                .into_owned();
+            Some((f, rel))
+        })
+        .unzip();
+    if origins.is_empty() {
+        println!("Nothing renamed");
+        return Ok(());
+    }
+    let _lock = TreeLock::for_run(root, args)?;
+
+    fdn_fs_post(origins, targets, args.clone())
+}
+
+///Rename a source into a target name,move sources into a directory or rename pairs of source
+///and target name,all checked before any rename and renamed together
+pub fn mv(inputs: &[String], pairs: &[String], parents: bool, args: &Args) -> Result<()> {
+    let mut renames = mv_renames(inputs, pairs)?;
+    if parents {
+        let rules = RuleSet::load(&open_db(None)?)?.with_args(args);
+        let exists = |p: &Path| p.symlink_metadata().is_ok();
+        renames = renames
+            .into_iter()
+            .map(|(f, t)| {
+                let t = parents_normalized(&t, f.is_file(), &rules, exists)?;
+                Ok((f, t))
+            })
+            .collect::<Result<_>>()?;
+    }
+
+    let mut problems = Vec::new();
+    let mut targets = HashSet::new();
+    let origins: HashSet<_> = renames.iter().map(|(f, _)| f.clone()).collect();
+    for (f, t) in &renames {
+        if f.symlink_metadata().is_err() {
+            problems.push(format!("source {:?} not exists", f));
+        }
+        if !parents
+            && t.parent()
+                .is_some_and(|d| !d.as_os_str().is_empty() && !d.is_dir())
+        {
+            problems.push(format!("directory of target {:?} not exists", t));
+        }
+        if !targets.insert(t.clone()) {
+            problems.push(format!("target {:?} repeated", t));
+        }
+        if f != t && t.symlink_metadata().is_ok() && !origins.contains(t) {
+            problems.push(format!("target {:?} exists", t));
+        }
+    }
+    if !problems.is_empty() {
+        return Err(anyhow!("Nothing moved:{}", problems.join(",")));
+    }
+
+    let (origins, targets): (Vec<_>, Vec<_>) = order_renames(renames)?
+        .into_iter()
+        .filter(|(f, t)| f != t)
+        .map(|(f, t)| {
+            let rel = relative_path(f.parent().unwrap_or(Path::new("")), &t)?;
+            Ok((f, os2string(Some(rel.as_os_str()))?))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    let Some(first) = origins.first() else {
+        println!("Nothing renamed");
+        return Ok(());
+    };
+    let _lock = TreeLock::for_run(first, args)?;
+
+    fdn_fs_post(origins, targets, args.clone())
+}
+
+///Path with components not existing yet normalized by rules as new directories and the target
+///name,existing directories kept
+fn parents_normalized(
+    path: &Path,
+    is_file: bool,
+    rules: &RuleSet,
+    exists: impl Fn(&Path) -> bool,
+) -> Result<PathBuf> {
+    let comps: Vec<_> = path.components().collect();
+    let mut normalized = PathBuf::new();
+    for (i, c) in comps.iter().enumerate() {
+        let last = i + 1 == comps.len();
+        match c {
+            Component::Normal(name) if last || !exists(&normalized.join(name)) => {
+                let name = os2string(Some(name))?;
+                normalized.push(plan_base(&name, last && is_file, rules)?);
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    Ok(normalized)
+}
+
+///Renames of mv arguments:a source and a target name,sources and a target directory given with
+///a trailing separator or after more than one source,or pairs of 'source:target name'
+fn mv_renames(inputs: &[String], pairs: &[String]) -> Result<Vec<(PathBuf, PathBuf)>> {
+    if !pairs.is_empty() {
+        return pairs
+            .iter()
+            .map(|p| {
+                let (s, t) = p
+                    .rsplit_once(':')
+                    .filter(|(s, t)| !s.is_empty() && !t.is_empty())
+                    .ok_or_else(|| anyhow!("Pair must be 'source:target':{:?}", p))?;
+                Ok((PathBuf::from(s), Path::new(s).with_file_name(t)))
+            })
+            .collect();
+    }
+
+    match inputs {
+        [] | [_] => Err(anyhow!("At least two arguments are required:{:?}", inputs)),
+        [s, t] if !t.ends_with(['/', MAIN_SEPARATOR]) => {
+            Ok(vec![(PathBuf::from(s), Path::new(s).with_file_name(t))])
+        }
+        [sources @ .., dest] => sources
+            .iter()
+            .map(|s| {
+                let name = Path::new(s)
+                    .file_name()
+                    .ok_or_else(|| anyhow!("Source has no name:{:?}", s))?;
+                Ok((PathBuf::from(s), Path::new(dest).join(name)))
+            })
+            .collect(),
+    }
+}
+
+///Move regular files of subdirectories up into the directory with normalized names,emptied
+///subdirectories removed once moved in place
+pub fn flatten(
+    dir: &Path,
+    depth: Option<usize>,
+    encode_path: Option<&str>,
+    args: &Args,
+) -> Result<()> {
+    let exs = args.excludes_under(dir)?;
+    let walk_depth = depth.map_or(usize::MAX, |d| d + 1);
+    let mut files = exclude_fdn_own(regular_files(dir, walk_depth, &exs, &args.skips()?)?)?;
+    files.retain(|f| args.hidden_kept(hidden_in(f, dir)));
+    files.sort();
+    exs.warn_unmatched();
+    let rules = RuleSet::load(&open_db(None)?)?.with_args(args);
+
+    let mut taken = HashSet::new();
+    let (mut origins, mut targets) = (Vec::new(), Vec::new());
+    for f in files {
+        let rel = f.strip_prefix(dir).unwrap_or(&f);
+        if rel.components().count() < 2 {
+            continue;
+        }
+        let planned = flat_name(rel, encode_path, &rules)?;
+        let name = unique_target(dir, "", &planned, &taken);
+        taken.insert(dir.join(&name));
+        let target = relative_path(f.parent().unwrap_or(dir), &dir.join(&name))?;
+        targets.push(os2string(Some(target.as_os_str()))?);
+        origins.push(f);
+    }
+    if origins.is_empty() {
+        println!("Nothing to flatten");
+        return Ok(());
+    }
+    let dirs: BTreeSet<_> = origins
+        .iter()
+        .flat_map(|f| f.ancestors().skip(1).take_while(|a| *a != dir))
+        .map(Path::to_path_buf)
+        .collect();
+    let _lock = TreeLock::for_run(dir, args)?;
+    fdn_fs_post(origins, targets, args.clone())?;
+
+    if args.in_place {
+        //deepest first,directories still holding anything kept
+        dirs.iter().rev().for_each(|d| {
+            let _ = fs::remove_dir(d);
+        });
+    }
+
+    Ok(())
+}
+
+///Move regular files of a directory into the layout rendered by the template,files with missing
+///values left alone and collisions suffixed by '_N'
+pub fn organize(dir: &Path, template: &Template, args: &Args) -> Result<()> {
+    let exs = args.excludes_under(dir)?;
+    let mut files = exclude_fdn_own(regular_files(dir, args.max_depth, &exs, &args.skips()?)?)?;
+    files.retain(|f| args.hidden_kept(hidden_in(f, dir)));
+    files.sort();
+    exs.warn_unmatched();
+    let rules = RuleSet::load(&open_db(None)?)?.with_args(args);
+    let providers = template::providers();
+    let clean = |v: &str| {
+        let v = v.replace(['/', '\\'], &rules.sep);
+        plan_base(&v, false, &rules).unwrap_or(v)
+    };
+
+    let mut taken = HashSet::new();
+    let (mut origins, mut targets) = (Vec::new(), Vec::new());
+    for f in files {
+        let Some(d_b) = dir_base(&f) else {
+            continue;
+        };
+        let name = plan_base(&d_b.base, true, &rules)?;
+        let (stem, ext) = split_ext(&name, &rules.compound_exts);
+        let Some(rendered) = template.render(&f, stem, ext, &providers, &clean)? else {
+            warn!("Skip {:?},a placeholder has no value", f);
+            continue;
+        };
+        let Some(layout) = organized(&rendered) else {
+            continue;
+        };
+        let t_dir = dir.join(layout.parent().unwrap_or(Path::new("")));
+        let mut target = t_dir.join(layout.file_name().unwrap_or_default());
+        if target != f {
+            let free = unique_target(&t_dir, "", &os2string(layout.file_name())?, &taken);
+            target = t_dir.join(free);
+        }
+        taken.insert(target.clone());
+        if target != f {
+            let rel = relative_path(f.parent().unwrap_or(dir), &target)?;
+            targets.push(os2string(Some(rel.as_os_str()))?);
+            origins.push(f);
+        }
+    }
+    if origins.is_empty() {
+        println!("Nothing to organize");
+        return Ok(());
+    }
+    let _lock = TreeLock::for_run(dir, args)?;
+
+    fdn_fs_post(origins, targets, args.clone())
+}
+
+///Relative path of a rendered layout,empty,'.' and '..' components dropped,None if nothing left
+fn organized(rendered: &str) -> Option<PathBuf> {
+    let layout: PathBuf = rendered
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && *s != "." && *s != "..")
+        .collect();
+
+    (!layout.as_os_str().is_empty()).then_some(layout)
+}
+
+///Name of a file flattened into the root,normalized by rules and prefixed by its former
+///directories joined by the separator if given
+fn flat_name(rel: &Path, encode_path: Option<&str>, rules: &RuleSet) -> Result<String> {
+    let comps: Vec<_> = rel.iter().collect();
+    let Some((name, parents)) = comps.split_last() else {
+        return Err(anyhow!("Empty path"));
+    };
+    let mut parts = Vec::new();
+    if encode_path.is_some() {
+        for p in parents {
+            parts.push(plan_base(&os2string(Some(p))?, false, rules)?);
+        }
+    }
+    parts.push(plan_base(&os2string(Some(name))?, true, rules)?);
+
+    Ok(parts.join(encode_path.unwrap_or_default()))
+}
+
+///Text of the clipboard by the paste command of the platform
+fn clipboard_text() -> Result<String> {
+    let commands: &[&[&str]] = match env::consts::OS {
+        "macos" => &[&["pbpaste"]],
+        "windows" => &[&["powershell", "-NoProfile", "-Command", "Get-Clipboard"]],
+        _ => &[
+            &["wl-paste", "--no-newline"],
+            &["xclip", "-selection", "clipboard", "-o"],
+            &["xsel", "--clipboard", "--output"],
+        ],
+    };
+
+    commands
+        .iter()
+        .find_map(|cmd| {
+            let output = process::Command::new(cmd[0])
+                .args(&cmd[1..])
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Failed to read the clipboard by {:?},pipe names with '--stdin' instead",
+                commands.iter().map(|c| c[0]).collect::<Vec<_>>()
+            )
+        })
+}
+
+///Names pasted one per line,lines trimmed and blank ones skipped
+fn pasted_names(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+///Renames of files into pasted names in order,extensions of files appended if keep_ext
+fn pasted_targets(
+    files: &[PathBuf],
+    names: &[String],
+    keep_ext: bool,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    if files.len() != names.len() {
+        return Err(anyhow!(
+            "{} names pasted for {} files,they must match one by one",
+            names.len(),
+            files.len()
+        ));
+    }
+    let mut taken = HashSet::new();
+
+    files
+        .iter()
+        .zip(names)
+        .map(|(f, n)| {
+            if n.contains(['/', '\\']) || n == "." || n == ".." {
+                return Err(anyhow!("Pasted name is not a file name:{:?}", n));
+            }
+            let name = match (keep_ext, f.extension()) {
+                (true, Some(ext)) => format!("{}.{}", n, ext.to_string_lossy()),
+                _ => n.clone(),
+            };
+            if !taken.insert(name.clone()) {
+                return Err(anyhow!("Pasted name repeated:{:?}", name));
+            }
+            Ok((f.clone(), f.with_file_name(name)))
+        })
+        .collect()
+}
+
+///Rename and reverse sampled files of a directory in memory with a scratch database,error if
+///any name does not come back byte-identical
+pub fn selftest(path: &Path, sample: usize, args: &Args) -> Result<()> {
+    let args = Args {
+        log_file: None,
+        ..args.clone()
+    };
+    let rules = RuleSet::load(&open_db(None)?)?;
+    let mut files = exclude_fdn_own(regular_files(
+        path,
+        args.max_depth,
+        &Excludes::default(),
+        &args.skips()?,
+    )?)?;
+    files.sort();
+    files.truncate(sample);
+
+    let mut mem = MemFs::new();
+    files.iter().for_each(|f| mem.add_file(f, 0));
+    let mut ctx = Context::with_conn(open_db(Some(":memory:"))?, &args)?.with_fs(Box::new(mem));
+    ctx.renamer.rules = rules;
+
+    let (mut passed, mut skipped, mut failed) = (0, 0, 0);
+    for d_b in files.iter().filter_map(|f| dir_base(f)) {
+        let s_path = Path::new(&d_b.dir).join(&d_b.base);
+        let action = ctx.renamer.plan_in(&**ctx.fs.borrow(), &s_path)?;
+        let target = checked_target(&ctx, &d_b, &action, false)?;
+        if target == d_b.base {
+            continue;
+        }
+        //another sampled file already has the target name
+        if ctx.fs.borrow().exists(&Path::new(&d_b.dir).join(&target)) {
+            skipped += 1;
+            continue;
+        }
+
+        fdn_f(&ctx, &d_b, Some(target.clone()), true)?;
+        let back_d_b = DirBase {
+            dir: d_b.dir.clone(),
+            base: target.clone(),
+        };
+        let back = fdn_rf(&ctx, &back_d_b, true, false)?;
+        match back.as_deref().map(str::as_bytes) == Some(d_b.base.as_bytes()) {
+            true => passed += 1,
+            false => {
+                failed += 1;
+                println!(
+                    "   {}\n-->{}\n-->{}",
+                    d_b.base,
+                    target,
+                    back.unwrap_or_default()
+                );
+            }
+        }
+    }
+
+    match failed {
+        0 => {
+            println!(
+                "Self test passed:{} of {} sampled names round tripped,{} skipped for collisions",
+                passed,
+                files.len(),
+                skipped
+            );
+            Ok(())
+        }
+        n => Err(anyhow!("Self test failed:{} names not restored", n)),
+    }
+}
+
+///Print compiled in features,supported stages,template placeholders and schema versions
+pub fn capabilities(json: bool) -> Result<()> {
+    let features = [
+        ("audio-tags", cfg!(feature = "audio-tags")),
+        ("ebook-meta", cfg!(feature = "ebook-meta")),
+        ("s3", cfg!(feature = "s3")),
+        ("sftp", cfg!(feature = "sftp")),
+        ("webdav", cfg!(feature = "webdav")),
+        ("mtp", cfg!(feature = "mtp")),
+        ("archive", cfg!(feature = "archive")),
+        ("script", cfg!(feature = "script")),
+        ("trash", cfg!(feature = "trash")),
+        ("tui", cfg!(feature = "tui")),
+    ];
+    let db_schema = schema_version(&open_db(None)?)?;
+    let placeholders: Vec<_> = template::BUILTINS
+        .into_iter()
+        .chain(template::providers().iter().map(|p| p.namespace()))
+        .collect();
+
+    if json {
+        let features: serde_json::Map<_, _> = features
+            .iter()
+            .map(|(k, v)| (k.to_string(), json!(v)))
+            .collect();
+        let v = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "features": features,
+            "stages": STAGES,
+            "placeholders": placeholders,
+            "schema_version": SCHEMA_VERSION,
+            "db_schema_version": db_schema,
+        });
+        println!("{}", v);
+    } else {
+        println!("Version {}", env!("CARGO_PKG_VERSION"));
+        features.iter().for_each(|(k, v)| {
+            println!("Feature {}\t{}", k, if *v { "yes" } else { "no" });
+        });
+        println!("Stages {}", STAGES.join(","));
+        println!("Placeholders {}", placeholders.join(","));
+        println!("Schema {}\tDatabase {}", SCHEMA_VERSION, db_schema);
+    }
+
+    Ok(())
+}
+
+///compare file stem and file extension separately and return rich text
+fn fname_compare(
+    origin: &str,
+    edit: &str,
+    mode: &str,
+    color: bool,
+    compound: &[String],
+) -> Result<(String, String)> {
+    let (o_stem, o_ext) = stem_ext(origin, compound)?;
+    let (e_stem, e_ext) = stem_ext(edit, compound)?;
+
+    let (o_stem_cmp, e_stem_cmp) = s_compare(&o_stem, &e_stem, mode, color)?;
+    let (o_ext_cmp, e_ext_cmp) = s_compare(&o_ext, &e_ext, mode, color)?;
+    //directories of targets such as "Show/Season 01/" kept
+    let e_dir = match Path::new(edit).parent() {
+        Some(d) if !d.as_os_str().is_empty() => format!("{}{}", d.display(), MAIN_SEPARATOR),
+        _ => String::new(),
+    };
+
+    Ok((
+        o_stem_cmp + if o_ext.is_empty() { "" } else { "." } + o_ext_cmp.as_str(),
+        e_dir + e_stem_cmp.as_str() + if e_ext.is_empty() { "" } else { "." } + e_ext_cmp.as_str(),
+    ))
+}
+
+///return file stem and file extension by file path,extension empty if the name has none
+fn stem_ext<P>(path: P, compound: &[String]) -> Result<(String, String)>
+where
+    P: AsRef<Path> + AsRef<OsStr>,
+{
+    let name = os2string(Path::new(&path).file_name())?;
+    let (stem, ext) = split_ext(&name, compound);
+
+    Ok((stem.to_owned(), ext.unwrap_or_default().to_owned()))
+}
+
+///Split a file name into stem and extension,compound extensions such as "tar.gz" kept whole
+pub(crate) fn split_ext<'a>(name: &'a str, compound: &[String]) -> (&'a str, Option<&'a str>) {
+    for c in compound {
+        let at = name.len().saturating_sub(c.len() + 1);
+        if at > 0
+            && name.is_char_boundary(at)
+            && name[at..].starts_with('.')
+            && name[at + 1..].eq_ignore_ascii_case(c)
+        {
+            return (&name[..at], Some(&name[at + 1..]));
+        }
+    }
+
+    let path = Path::new(name);
+    match (
+        path.file_stem().and_then(OsStr::to_str),
+        path.extension().and_then(OsStr::to_str),
+    ) {
+        (Some(stem), Some(ext)) => (stem, Some(ext)),
+        _ => (name, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        borrow::Cow,
+        cmp::Ordering,
+        fs,
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    use clap::Parser;
+
+    use crate::{
+        alias_args, assert_idempotent, audit_names, brackets_stripped, camel_split, canonical_path,
+        case_insensitive, case_style, checked_target, common_dir, delete_tag_rows, dupes_handled,
+        emoji_replaced, entities_decoded, explain_change, fdn_f, fdn_lossy, fdn_rf, fdn_rfs_in,
+        find_dupes, flat_name, hashed_name, hidden_in, html_decoded, insert_record,
+        insert_term_word, install_pack, is_fdn_own, is_skipped, mv_renames, natural_cmp,
+        natural_path_cmp, normalize_name, number_targets, numbers_padded, open_db, order_renames,
+        organized, os2string, overwritten, parents_normalized, parse_pack, parse_stopword,
+        parse_term_rule, pasted_names, pasted_targets, path_order, percent_decoded,
+        pinyin_converted, plan_base, plex_targets, regular_files, relative_path, release_record,
+        remove_continuous, remove_dup_words, remove_prefix_sep_suffix_sep, rename_into,
+        retrieve_packs, retrieve_pending_paths, retrieve_records_by_hash,
+        retrieve_session_operations, retrieve_term_words, retrieve_toggled_rows, rpc_preview,
+        rpc_response, rpc_serve, second_pass, selftest, serve_route, sort_paths, split_ext,
+        stem_cased, stem_ext, strip_dup_markers, suggest_rules, term_replaced, trace_base,
+        undoable_rename, unique_paths, unsafe_in_name, unsafe_target, url_decoded, url_encoded,
+        versions_normalized, Args, Context, DirBase, Excludes, FsBackend, MemFs, Operation, Record,
+        RenameAction, Request, RuleScopes, RuleSet, TermFlags, TreeLock, DEFAULT_SKIPS,
+        LOCK_FILE_NAME,
+    };
+
+    #[test]
+    fn test_remove_xfix_sep() {
+        let sep = "_";
+        let s = "_PDFScholar_";
+        assert!(s.starts_with(sep));
+        assert!(s.ends_with(sep));
+        let t = "PDFScholar";
+        assert_eq!(remove_prefix_sep_suffix_sep(s, sep), t);
+    }
+
+    #[test]
+    fn test_stem_ext() {
+        let p = "stem.ext";
+        let (s, e) = stem_ext(p, &[]).unwrap();
+        assert!(s.eq("stem"));
+        assert!(e.eq("ext"));
+
+        let compound = ["tar.gz".to_owned(), "d.ts".to_owned()];
+        let (s, e) = stem_ext("dir/archive.v1.TAR.GZ", &compound).unwrap();
+        assert_eq!((s.as_str(), e.as_str()), ("archive.v1", "TAR.GZ"));
+        assert_eq!(stem_ext("README", &compound).unwrap().1, "");
+        assert_eq!(split_ext(".tar.gz", &compound), (".tar", Some("gz")));
+        assert_eq!(split_ext("index.d.ts", &compound), ("index", Some("d.ts")));
+        assert_eq!(split_ext("a.ts", &compound), ("a", Some("ts")));
+    }
+
+    #[test]
+    fn test_date_format() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        rules.add_rule("-").unwrap();
+        let rules = rules.with_date_format(Some("%Y-%m-%d"));
+        assert_eq!(
+            normalize_name("Scan Dec 31 2023 - page.pdf", &rules),
+            "Scan_2023-12-31_page.pdf"
+        );
+        let (_, steps) = trace_base("31.12.2023.txt", true, &rules).unwrap();
+        assert_eq!(steps[0].stage, "dates");
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(versions_normalized("app_v1.2", 3, 1), "app_v1.2.0");
+        assert_eq!(
+            versions_normalized("Doc V02 final", 3, 1),
+            "Doc v2.0.0 final"
+        );
+        assert_eq!(
+            versions_normalized("ver_3 version 4.1", 2, 2),
+            "v03.00 v04.01"
+        );
+        assert_eq!(versions_normalized("v1.2.3.4", 3, 1), "v1.2.3.4");
+        assert_eq!(
+            versions_normalized("dev1 v8engine rev2", 3, 1),
+            "dev1 v8engine rev2"
+        );
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let rules = rules.with_versions(Some((3, 1)));
+        assert_eq!(
+            normalize_name("Report ver 2.pdf", &rules),
+            "Report_v2.0.0.pdf"
+        );
+    }
+
+    #[test]
+    fn test_suggest_rules() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule("-").unwrap();
+        let stems: Vec<_> = [
+            "Song One [www.site.com] (2019)",
+            "usb guide (Official)",
+            "USB cable-set (Official)",
+            "USB hub (Official) (2019)",
+            "The_USB [www.site.com]",
+            "the-end [www.site.com] (2019)",
+        ]
+        .map(str::to_owned)
+        .to_vec();
+
+        let found: Vec<_> = suggest_rules(&stems, &rules, 3)
+            .into_iter()
+            .map(|s| (s.stage, s.rule, s.flags.whole_word, s.count))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("to-sep-words", " ".to_owned(), false, 6),
+                ("term-words", "usb:USB".to_owned(), true, 1),
+                ("regex-rules", r"re:\(Official\):".to_owned(), false, 3),
+                (
+                    "regex-rules",
+                    r"re:\[www\.site\.com\]:".to_owned(),
+                    false,
+                    3
+                ),
+            ]
+        );
+
+        //rules already configured not suggested again
+        rules.add_rule(" ").unwrap();
+        rules.add_rule(r"re:\(Official\):").unwrap();
+        let found = suggest_rules(&stems, &rules, 3);
+        assert_eq!(found.len(), 2);
+        assert!(suggest_rules(&stems, &rules, 7).is_empty());
+    }
+
+    #[test]
+    fn test_serve_route() {
+        let req = |method: &str, path: &str, query: &str, body: &str| Request {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            query: crate::serve::parse_query(query),
+            body: body.to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(serve_route(&req("GET", "/nope", "", "")).0, 404);
+        assert_eq!(serve_route(&req("DELETE", "/undo", "", "")).0, 405);
+        assert_eq!(serve_route(&req("GET", "/renames", "path=.", "")).0, 405);
+        let (status, v) = serve_route(&req("GET", "/preview", "", ""));
+        assert_eq!(status, 400);
+        assert!(v["error"].as_str().unwrap().contains("path"));
+        let (status, v) = serve_route(&req("GET", "/preview", "path=.", ""));
+        assert_eq!(status, 400);
+        assert!(v["error"].as_str().unwrap().contains("absolute"));
+        let (status, v) = serve_route(&req("GET", "/preview", "path=%2F&arg=-i", ""));
+        assert_eq!(status, 400);
+        assert!(v["error"].as_str().unwrap().contains("POST /renames"));
+        let (status, v) = serve_route(&req("POST", "/preview", "", r#"{"path":"/","args":[1]}"#));
+        assert_eq!(status, 400);
+        assert!(v["error"].as_str().unwrap().contains("list of strings"));
+        let body = r#"{"path":"/","args":["log"]}"#;
+        assert_eq!(serve_route(&req("POST", "/renames", "", body)).0, 400);
+    }
+
+    #[test]
+    fn test_rpc_response() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let handler = |method: &str, params: &serde_json::Value| match method {
+            "preview" => rpc_preview(params, &rules).map(Some),
+            _ => Ok(None),
+        };
+        let call = |request: &str| {
+            serde_json::from_str::<serde_json::Value>(&rpc_response(request, handler)).unwrap()
+        };
+
+        let v = call(r#"{"jsonrpc":"2.0","id":1,"method":"preview","params":{"name":"a b.txt"}}"#);
+        assert_eq!(v["id"], 1);
+        assert_eq!(v["result"]["name"], "a_b.txt");
+        let v = call(r#"{"id":"x","method":"preview","params":{"name":"a b.c","dir":true}}"#);
+        assert_eq!(v["result"]["name"], "a_b.c");
+        assert_eq!(call(r#"{"id":2,"method":"nope"}"#)["error"]["code"], -32601);
+        assert_eq!(
+            call(r#"{"id":3,"method":"preview"}"#)["error"]["code"],
+            -32000
+        );
+        assert_eq!(call(r#"{"id":4}"#)["error"]["code"], -32600);
+        assert_eq!(call("{")["error"]["code"], -32700);
+
+        let mut out = Vec::new();
+        rpc_serve(&b"\n{\"id\":5,\"method\":\"nope\"}\n"[..], &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn test_undoable_rename() {
+        let tmp = tempfile::tempdir().unwrap();
+        for name in ["b", "c", "e"] {
+            fs::write(tmp.path().join(name), "").unwrap();
+        }
+        let op = |origin: &str, target: &str, status: &str| {
+            let d_b = DirBase {
+                dir: tmp.path().to_string_lossy().to_string(),
+                base: origin.to_owned(),
+            };
+            Operation::new("s", &d_b, target, status)
+        };
+        let ops = [op("a", "b", "renamed"), op("d", "e", "renamed")];
+        assert_eq!(undoable_rename(&ops).unwrap().target, "e");
+
+        //reversed by 'e'->'d',the older rename is next
+        let ops = [
+            op("a", "b", "renamed"),
+            op("d", "e", "renamed"),
+            op("e", "d", "reversed"),
+        ];
+        assert_eq!(undoable_rename(&ops).unwrap().target, "b");
+        //a target gone is skipped
+        let ops = [op("a", "c", "renamed"), op("x", "y", "renamed")];
+        assert_eq!(undoable_rename(&ops).unwrap().target, "c");
+        assert!(undoable_rename(&[op("x", "y", "reversed")]).is_none());
+    }
+
+    #[test]
+    fn test_checked_target() {
+        let args = Args::try_parse_from(["fdn"]).unwrap();
+        let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args).unwrap();
+        let d_b = DirBase {
+            dir: "/d".to_owned(),
+            base: "a".to_owned(),
+        };
+        let checked = |to: &str, given: bool| {
+            let action = RenameAction {
+                from: PathBuf::from("/d/a"),
+                to: Path::new("/d").join(to),
+                violations: Vec::new(),
+            };
+            checked_target(&ctx, &d_b, &action, given).unwrap()
+        };
+        assert_eq!(checked("b", false), "b");
+        assert_eq!(checked("AC/DC", false), "a");
+        assert_eq!(checked("AC\\DC", false), "a");
+        assert_eq!(checked("..", false), "a");
+        assert_eq!(checked(".", true), "a");
+        assert_eq!(checked("Show/Season 01/b", true), "Show/Season 01/b");
+        assert_eq!(checked("Show/../../b", true), "a");
+
+        assert!(unsafe_target("", false));
+        assert!(!unsafe_target("a..b", false));
+    }
+
+    #[test]
+    fn test_restore_overwritten() {
+        let mut mem = MemFs::new();
+        mem.add_file("/mem/e f.txt", 1);
+        mem.add_file("/mem/e_f.txt", 2);
+        let args = Args::parse_from([
+            "fdn",
+            "--fs-case",
+            "sensitive",
+            "--on-conflict",
+            "overwrite",
+        ]);
+        let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+            .unwrap()
+            .with_fs(Box::new(mem));
+        let d_b = DirBase {
+            dir: "/mem".to_owned(),
+            base: "e f.txt".to_owned(),
+        };
+        fdn_f(&ctx, &d_b, Some("e_f.txt".to_owned()), true).unwrap();
+        let len = |p: &str| ctx.fs.borrow().metadata(Path::new(p)).map(|m| m.len).ok();
+        assert_eq!((len("/mem/e f.txt"), len("/mem/e_f.txt")), (None, Some(1)));
+
+        //reversing the rename restores the file it overwrote
+        let d_b = DirBase {
+            dir: "/mem".to_owned(),
+            base: "e_f.txt".to_owned(),
+        };
+        assert_eq!(
+            fdn_rf(&ctx, &d_b, true, false).unwrap().as_deref(),
+            Some("e f.txt")
+        );
+        assert_eq!(
+            (len("/mem/e f.txt"), len("/mem/e_f.txt")),
+            (Some(1), Some(2))
+        );
+        let ops = retrieve_session_operations(&ctx.conn, &ctx.session.id).unwrap();
+        let statuses: Vec<_> = ops.iter().map(|op| op.status.as_str()).collect();
+        assert_eq!(statuses, ["trashed", "renamed", "reversed", "restored"]);
+        assert!(overwritten(&ops, "/mem", "e f.txt", "e_f.txt").is_none());
+        assert!(overwritten(&ops[..2], "/mem", "e f.txt", "e_f.txt").is_some());
+    }
+
+    #[test]
+    fn test_processed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join("fdn.db");
+        let db = db.to_str().unwrap();
+        let args = Args::try_parse_from(["fdn", "-i", "--commit-every", "2"]).unwrap();
+        let ctx = Context::with_conn(open_db(Some(db)).unwrap(), &args).unwrap();
+        let paths: Vec<_> = ["a", "b", "c"].iter().map(PathBuf::from).collect();
+        let items: Vec<_> = paths.iter().map(|p| (p, None)).collect();
+        ctx.checkpoint(&args.argv, &items).unwrap();
+        ctx.begin().unwrap();
+
+        //marks seen by another connection once a batch is committed
+        let pending = || retrieve_pending_paths(&open_db(Some(db)).unwrap(), &ctx.session.id);
+        ctx.processed(&paths[0]).unwrap();
+        assert_eq!(pending().unwrap().len(), 3);
+        ctx.processed(&paths[1]).unwrap();
+        assert_eq!(pending().unwrap(), [("c".to_owned(), None)]);
+    }
+
+    #[test]
+    fn test_pasted_names() {
+        assert_eq!(
+            pasted_names("Intro.mp3\r\n\n  Outro.mp3  \n"),
+            ["Intro.mp3", "Outro.mp3"]
+        );
+
+        let files = [PathBuf::from("d/01.mp3"), PathBuf::from("d/02")];
+        let names = ["Intro".to_owned(), "Outro".to_owned()];
+        assert_eq!(
+            pasted_targets(&files, &names, true).unwrap(),
+            [
+                (files[0].clone(), PathBuf::from("d/Intro.mp3")),
+                (files[1].clone(), PathBuf::from("d/Outro")),
+            ]
+        );
+        assert_eq!(
+            pasted_targets(&files, &names, false).unwrap()[0].1,
+            PathBuf::from("d/Intro")
+        );
+        assert!(pasted_targets(&files, &names[..1], false).is_err());
+        let repeated = ["Intro".to_owned(), "Intro".to_owned()];
+        assert!(pasted_targets(&files, &repeated, false).is_err());
+        let nested = ["a/b".to_owned(), "c".to_owned()];
+        assert!(pasted_targets(&files, &nested, false).is_err());
+    }
+
+    #[test]
+    fn test_mv_renames() {
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let pb = PathBuf::from;
+
+        assert_eq!(
+            mv_renames(&strings(&["d/a.txt", "b.txt"]), &[]).unwrap(),
+            [(pb("d/a.txt"), pb("d/b.txt"))]
+        );
+        assert_eq!(
+            mv_renames(&strings(&["a.txt", "d/c.txt", "dest/"]), &[]).unwrap(),
+            [
+                (pb("a.txt"), pb("dest/a.txt")),
+                (pb("d/c.txt"), pb("dest/c.txt"))
+            ]
+        );
+        assert_eq!(
+            mv_renames(&strings(&["a.txt", "b.txt", "dest"]), &[]).unwrap()[1],
+            (pb("b.txt"), pb("dest/b.txt"))
+        );
+        assert_eq!(
+            mv_renames(&[], &strings(&["d/a:b.txt:c.txt", "e:f"])).unwrap(),
+            [(pb("d/a:b.txt"), pb("d/c.txt")), (pb("e"), pb("f"))]
+        );
+        assert!(mv_renames(&strings(&["a.txt"]), &[]).is_err());
+        assert!(mv_renames(&[], &strings(&["a.txt:"])).is_err());
+    }
+
+    #[test]
+    fn test_parents_normalized() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let exists = |p: &Path| p == Path::new("d/Old Dir");
+
+        assert_eq!(
+            parents_normalized(Path::new("d/New Folder/My File.txt"), true, &rules, exists)
+                .unwrap(),
+            PathBuf::from("d/New_Folder/My_File.txt")
+        );
+        assert_eq!(
+            parents_normalized(Path::new("d/Old Dir/a b.c"), false, &rules, exists).unwrap(),
+            PathBuf::from("d/Old Dir/a_b.c")
+        );
+        assert_eq!(
+            parents_normalized(Path::new("../x y/z"), true, &rules, exists).unwrap(),
+            PathBuf::from("../x_y/z")
+        );
+    }
+
+    #[test]
+    fn test_flat_name() {
+        let mut rules = RuleSet {
+            sep: "-".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+
+        let rel = Path::new("sub dir/in/my file.txt");
+        assert_eq!(flat_name(rel, None, &rules).unwrap(), "my-file.txt");
+        assert_eq!(
+            flat_name(rel, Some("__"), &rules).unwrap(),
+            "sub-dir__in__my-file.txt"
+        );
+    }
+
+    #[test]
+    fn test_organized() {
+        assert_eq!(
+            organized("mp3/2024/a.mp3"),
+            Some(PathBuf::from("mp3/2024/a.mp3"))
+        );
+        assert_eq!(organized("/2024/../a"), Some(PathBuf::from("2024/a")));
+        assert_eq!(organized(" / "), None);
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("a/b"), Path::new("a/c.txt")).unwrap(),
+            PathBuf::from("../c.txt")
+        );
+        assert_eq!(
+            relative_path(Path::new("a/./b/.."), Path::new("a/x/../c.txt")).unwrap(),
+            PathBuf::from("c.txt")
+        );
+        assert_eq!(
+            relative_path(Path::new("a"), Path::new("d/e/f")).unwrap(),
+            PathBuf::from("../d/e/f")
+        );
+        assert_eq!(
+            relative_path(Path::new(""), Path::new("c.txt")).unwrap(),
+            PathBuf::from("c.txt")
+        );
+    }
+
+    #[test]
+    fn test_audit_names() {
+        assert_eq!(case_style("my_file"), "lower");
+        assert_eq!(case_style("README"), "upper");
+        assert_eq!(case_style("My File 2"), "title");
+        assert_eq!(case_style("myFile"), "camel");
+        assert_eq!(case_style("my File"), "mixed");
+        assert_eq!(case_style("2024 文件"), "none");
+
+        let names: Vec<_> = [
+            "a b.txt",
+            "c_d.txt",
+            "Report.pdf",
+            "report.txt",
+            "sub/文件.txt",
+            "e_f.md",
+            "a very very long name of a file indeed.txt",
+        ]
+        .map(str::to_owned)
+        .to_vec();
+        let audit = audit_names(&names);
+        assert_eq!(audit.names, 7);
+        assert_eq!(audit.separators.get("_"), Some(&2));
+        assert_eq!(audit.separators.get(" "), Some(&2));
+        assert_eq!(audit.cases.get("lower"), Some(&5));
+        assert_eq!(audit.cases.get("title"), Some(&1));
+        assert_eq!(audit.non_ascii, 1);
+        assert_eq!(
+            audit.length_outliers,
+            ["a very very long name of a file indeed.txt"]
+        );
+        assert_eq!(
+            audit.duplicates.get("report"),
+            Some(&vec!["Report.pdf".to_owned(), "report.txt".to_owned()])
+        );
+        assert_eq!(audit.duplicates.len(), 1);
+    }
+
+    #[test]
+    fn test_case() {
+        let small = ["a", "of", "the"].map(str::to_owned);
+        let acronyms = ["PDF", "USB"].map(str::to_owned);
+        let cased = |s, case| stem_cased(s, case, &small, &acronyms);
+        assert_eq!(
+            cased("the_lord_of_the_rings", "title-smart"),
+            "The_Lord_of_the_Rings"
+        );
+        assert_eq!(
+            cased("the_lord_of_the_rings", "title"),
+            "The_Lord_Of_The_Rings"
+        );
+        assert_eq!(cased("a tale of", "title-smart"), "A Tale Of");
+        assert_eq!(
+            cased("USB_GUIDE_for_iPhone.pdf notes", "title-smart"),
+            "USB_Guide_For_iPhone.PDF Notes"
+        );
+        assert_eq!(cased("don't STOP", "title-smart"), "Don't Stop");
+        assert_eq!(cased("Mixed Case", "lower"), "mixed case");
+        assert_eq!(cased("Mixed Case", "upper"), "MIXED CASE");
+        assert_eq!(cased("Mixed case", "keep"), "Mixed case");
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            small_words: small.to_vec(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let rules = rules.with_case("title-smart");
+        assert_eq!(
+            normalize_name("the lord of the rings.MKV", &rules),
+            "The_Lord_of_the_Rings.MKV"
+        );
+        let (_, steps) = trace_base("a b.txt", true, &rules).unwrap();
+        assert_eq!(steps.last().unwrap().stage, "case");
+    }
+
+    #[test]
+    fn test_pad_numbers() {
+        assert_eq!(numbers_padded("track 3", 3), "track 003");
+        assert_eq!(
+            numbers_padded("(7) chapter_12_end", 2),
+            "(07) chapter_12_end"
+        );
+        assert_eq!(numbers_padded("12345 1", 3), "12345 001");
+        assert_eq!(
+            numbers_padded("v1.2.0 2023-12-31 mp3 3.5 1,000", 3),
+            "v1.2.0 2023-12-31 mp3 3.5 1,000"
+        );
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let rules = rules.with_pad_numbers(Some(3));
+        assert_eq!(normalize_name("Track 3.mp3", &rules), "Track_003.mp3");
+    }
+
+    #[test]
+    fn test_remove_dup_words() {
+        assert_eq!(remove_dup_words("final_final_report", "_"), "final_report");
+        assert_eq!(remove_dup_words("Final_final_FINAL", "_"), "Final");
+        assert_eq!(remove_dup_words("vol_1_1_a_b_a", "_"), "vol_1_1_a_b_a");
+        assert_eq!(remove_dup_words("_a_a_", "_"), "_a_");
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        assert_eq!(
+            normalize_name("Bora Bora final final.txt", &rules),
+            "Bora_Bora_final_final.txt"
+        );
+        let rules = rules.with_dedup_words(true);
+        assert_eq!(
+            normalize_name("final  final report.txt", &rules),
+            "final_report.txt"
+        );
+    }
+
+    #[test]
+    fn test_remove_continuous() {
+        let src = "A_B__C___D_.txt";
+        let sep = "_";
+        let tgt = "A_B_C_D_.txt";
+        assert_eq!(remove_continuous(src, sep).unwrap(), tgt);
+    }
+
+    #[test]
+    fn test_is_fdn_own() {
+        let db_path = Path::new("/home/user/.fdn/fdn.db");
+        assert!(is_fdn_own(Path::new("/home/user/.fdn"), db_path));
+        assert!(is_fdn_own(Path::new("/data/.fdn/fdn.db"), db_path));
+        assert!(!is_fdn_own(Path::new("/data/fdn.db"), db_path));
+        assert!(!is_fdn_own(Path::new("/data/notes.txt"), db_path));
+    }
+
+    #[test]
+    fn test_rule_set() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            to_sep_words: vec![" ".to_owned(), "(".to_owned(), ")".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            rules.normalize_stem(" Movie (2020) ").unwrap(),
+            "Movie_2020"
+        );
+
+        rules.add_rule("Movie:Film").unwrap();
+        rules.add_rule(r"re:_(\d{4}):_[$1]").unwrap();
+        assert_eq!(rules.normalize_stem("Movie (2020)").unwrap(), "Film_[2020]");
+        assert!(rules.add_rule("re:(:x").is_err());
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            to_sep_words: vec![" ".to_owned(), "(".to_owned(), ")".to_owned()],
+            ..Default::default()
+        };
+        rules.add_rule("Movie:Film").unwrap();
+        assert_eq!(
+            normalize_name("Some Movie (2020).mkv", &rules),
+            "Some_Film_2020.mkv"
+        );
+
+        let (stem, steps) = rules.trace_stem("Movie (1)").unwrap();
+        assert_eq!(stem, "Film_1");
+        let stages: Vec<_> = steps.iter().map(|s| s.stage).collect();
+        assert_eq!(
+            stages,
+            vec![
+                "to-sep-words",
+                "to-sep-words",
+                "to-sep-words",
+                "term-words",
+                "remove-continuous-sep",
+                "trim-sep"
+            ]
+        );
+        assert_eq!(steps[3].before, "Movie__1_");
+    }
+
+    #[test]
+    fn test_second_pass() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        assert_idempotent(&rules, &["a b.txt", "a__b", "x"]);
+
+        //oscillating term words stop and are reported instead of looping forever
+        ["a:c", "b:a", "c:b"]
+            .iter()
+            .for_each(|r| rules.add_rule(r).unwrap());
+        assert_eq!(
+            second_pass("a.txt", &rules),
+            Some(("b.txt".to_owned(), "a.txt".to_owned()))
+        );
+        //growing term words stop after limited passes
+        rules.add_rule("x:xy").unwrap();
+        assert!(second_pass("x", &rules).is_some());
+    }
+
+    #[test]
+    fn test_explain_change() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let args = Args::parse_from(["fdn"]);
+        let d_b = DirBase {
+            dir: "d".to_owned(),
+            base: "a b.txt".to_owned(),
+        };
+
+        let steps = explain_change(&rules, &d_b, "a_b.txt", &args).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            (steps[0].rule.as_str(), steps[0].after.as_str()),
+            (" ", "a_b")
+        );
+        assert!(explain_change(&rules, &d_b, "other.txt", &args).is_none());
+    }
+
+    #[test]
+    fn test_ext_normalization() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule("ext:JPEG:jpg").unwrap();
+        assert!(rules.add_rule("ext:jpeg").is_err());
+        assert_eq!(normalize_name("a.Jpeg", &rules), "a.jpg");
+        assert_eq!(normalize_name("a.PNG", &rules), "a.PNG");
+
+        let rules = rules.with_ext_case("upper");
+        assert_eq!(normalize_name("a.jpeg", &rules), "a.JPG");
+        let (name, steps) = trace_base("a.jpeg", true, &rules).unwrap();
+        assert_eq!(name, "a.JPG");
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].rule, "jpeg:jpg");
+        //directories have no extensions
+        assert_eq!(plan_base("d.jpeg", false, &rules).unwrap(), "d.jpeg");
+
+        let rules = rules.with_ext_case("lower");
+        assert_eq!(normalize_name("B.TXT", &rules), "B.txt");
+    }
+
+    #[test]
+    fn test_unicode_normalize() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        //composed "é" of the term word,decomposed one of the name
+        rules.add_rule("caf\u{e9}:coffee").unwrap();
+        let name = "cafe\u{301} menu.txt";
+        assert_eq!(normalize_name(name, &rules), name);
+
+        let nfc = rules.clone().with_unicode_form(Some("nfc"));
+        assert_eq!(normalize_name(name, &nfc), "coffee menu.txt");
+        let nfd = rules.clone().with_unicode_form(Some("nfd"));
+        assert_eq!(
+            normalize_name("caf\u{e9} menu.txt", &nfd),
+            "coffee menu.txt"
+        );
+        let nfkc = rules.with_unicode_form(Some("nfkc"));
+        assert_eq!(normalize_name("\u{ff21}1.txt", &nfkc), "A1.txt");
+        //separators folded by NFKC replaced after normalizing
+        assert_eq!(normalize_name("AC\u{ff0f}DC.txt", &nfkc), "AC_DC.txt");
+    }
+
+    #[test]
+    fn test_ascii() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let rules = rules.with_ascii(true);
+        assert_eq!(
+            normalize_name("Café Straße.txt", &rules),
+            "Cafe_Strasse.txt"
+        );
+        assert_eq!(normalize_name("Жук Ωmega.md", &rules), "Zhuk_Omega.md");
+        assert_eq!(normalize_name("AC／DC∕Live.mp3", &rules), "AC_DC_Live.mp3");
+        let (_, steps) = trace_base("é.txt", true, &rules).unwrap();
+        assert_eq!(steps[0].stage, "ascii");
+    }
+
+    #[test]
+    fn test_strip_brackets() {
+        assert_eq!(
+            brackets_stripped("Movie [1080p] (proper) [x]", "[]", false),
+            "Movie  (proper) "
+        );
+        assert_eq!(brackets_stripped("【字幕组】Show", "【】", false), "Show");
+        assert_eq!(brackets_stripped("a [b [c] d] e", "[]", false), "a  e");
+        assert_eq!(
+            brackets_stripped("Film (2019) (proper)", "()", true),
+            "Film (2019) "
+        );
+        assert_eq!(brackets_stripped("Film (2019)", "()", false), "Film ");
+        assert_eq!(brackets_stripped("a (b", "()", false), "a (b");
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let rules = rules.with_strip_brackets(&["[]".to_owned(), "()".to_owned()], true);
+        assert_eq!(
+            normalize_name("[Group] Film (2019) [1080p] (proper).mkv", &rules),
+            "Film_(2019).mkv"
+        );
+    }
+
+    #[test]
+    fn test_split_camel() {
+        let acronyms = ["PDF".to_owned(), "ID".to_owned()];
+        assert_eq!(
+            camel_split("MyProjectReport", "_", &[]),
+            "My_Project_Report"
+        );
+        assert_eq!(camel_split("PDFScholar", "_", &acronyms), "PDF_Scholar");
+        assert_eq!(camel_split("PDFScholar", "_", &[]), "PDFScholar");
+        assert_eq!(
+            camel_split("myPDFFile PDFs", "-", &acronyms),
+            "my-PDF-File PDFs"
+        );
+        assert_eq!(camel_split("IDCard Idea", "_", &acronyms), "ID_Card Idea");
+        assert_eq!(camel_split("IDEAProject", "_", &acronyms), "IDEAProject");
+        assert_eq!(camel_split("IDLE PDFA", "_", &acronyms), "IDLE PDFA");
+        assert_eq!(camel_split("myID", "_", &acronyms), "my_ID");
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        }
+        .with_split_camel(true);
+        rules.add_rule("acronym:HTML").unwrap();
+        assert!(rules.add_rule("acronym:A B").is_err());
+        assert_eq!(
+            normalize_name("HTMLGuideDraft.txt", &rules),
+            "HTML_Guide_Draft.txt"
+        );
+    }
+
+    #[test]
+    fn test_split_words_stage() {
+        let rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        }
+        .with_split_camel(true)
+        .with_split_words(true);
+        assert_eq!(
+            normalize_name("annualreportFinalVersion.pdf", &rules),
+            "annual_report_Final_Version.pdf"
+        );
+        let (_, steps) = trace_base("weeklymeetingnotes.txt", true, &rules).unwrap();
+        assert_eq!(steps[0].stage, "split-words");
+    }
+
+    #[test]
+    fn test_term_flags() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule("cat:dog").unwrap();
+        assert_eq!(
+            normalize_name("concatenate_Cat.txt", &rules),
+            "condogenate_Cat.txt"
+        );
+
+        let flags = TermFlags {
+            ignore_case: true,
+            whole_word: true,
+        };
+        rules.term_matchers.insert(
+            "cat".to_owned(),
+            (flags.matcher("cat").unwrap().unwrap(), flags),
+        );
+        assert_eq!(
+            normalize_name("concatenate_Cat.CAT.txt", &rules),
+            "concatenate_dog.dog.txt"
+        );
+        assert_eq!(normalize_name("cat cat-cat.txt", &rules), "dog dog-dog.txt");
+        let re = flags.matcher("a a").unwrap().unwrap();
+        assert_eq!(term_replaced(&re, true, "aa a a", "b"), "aa b");
+
+        rules.add_rule("café:bar").unwrap();
+        rules.term_matchers.insert(
+            "café".to_owned(),
+            (flags.matcher("café").unwrap().unwrap(), flags),
+        );
+        let rules = rules.with_unicode_form(Some("nfd"));
+        assert_eq!(normalize_name("my CAFÉ.txt", &rules), "my bar.txt");
+        assert_eq!(flags.short(), "iw");
+        assert!(TermFlags::default().matcher("cat").unwrap().is_none());
+
+        let ignore_case = TermFlags {
+            ignore_case: true,
+            whole_word: false,
+        };
+        let re = ignore_case.matcher("usb").unwrap().unwrap();
+        assert_eq!(term_replaced(&re, false, "myUsbDisk", "USB"), "myUSBDisk");
+        assert_eq!(term_replaced(&re, false, "usb", "$0"), "$0");
+    }
+
+    #[test]
+    fn test_parse_term_rule() {
+        let term = |k: &str, v: &str| (k.to_owned(), Some(v.to_owned()));
+        assert_eq!(parse_term_rule("ft.:feat."), term("ft.", "feat."));
+        assert_eq!(parse_term_rule("a:b:c"), term("a", "b:c"));
+        assert_eq!(parse_term_rule(r"10\:30:10h30"), term("10:30", "10h30"));
+        assert_eq!(parse_term_rule(r"a\\:b"), term(r"a\", "b"));
+        assert_eq!(parse_term_rule(r"\:"), (":".to_owned(), None));
+        assert_eq!(parse_term_rule(r"a\b"), (r"a\b".to_owned(), None));
+        assert_eq!(parse_term_rule("-"), ("-".to_owned(), None));
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(r"\:").unwrap();
+        assert_eq!(rules.normalize_stem("a:b").unwrap(), "a_b");
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let removed = |s: &str, words: &[&str]| {
+            let mut rules = RuleSet::default();
+            for word in words {
+                rules.add_rule(&format!("stop:{}", word)).unwrap();
+            }
+            let mut steps = Vec::new();
+            let s = rules.stopwords_removed(s.to_owned(), &mut steps);
+            (s, steps.len())
+        };
+        assert_eq!(
+            removed("Copy of report", &["copy of"]),
+            (" report".into(), 1)
+        );
+        assert_eq!(removed("finally final", &["final"]), ("finally ".into(), 1));
+        assert_eq!(removed("photo(1)", &["(1)"]), ("photo".into(), 1));
+        assert_eq!(
+            removed("copy of copy", &["copy", "copy of"]),
+            (" ".into(), 2)
+        );
+        assert_eq!(
+            removed("finally new", &["final", "new"]),
+            ("finally ".into(), 1)
+        );
+        assert_eq!(parse_stopword("de:kopie von").unwrap(), ("de", "kopie von"));
+        assert_eq!(parse_stopword("(1)").unwrap(), ("", "(1)"));
+        assert!(parse_stopword("de: ").is_err());
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        for rule in [" ", "stop:copy of", "stop:new", "stop:de:kopie von"] {
+            rules.add_rule(rule).unwrap();
+        }
+        assert_eq!(
+            normalize_name("Copy of new newsletter.txt", &rules),
+            "newsletter.txt"
+        );
+        assert_eq!(
+            normalize_name("Kopie von Bericht.txt", &rules),
+            "Kopie_von_Bericht.txt"
+        );
+        let rules = rules.with_stopword_langs(&["de".to_owned()]);
+        assert_eq!(
+            normalize_name("Kopie von Bericht.txt", &rules),
+            "Bericht.txt"
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(url_decoded("My%20Caf%C3%A9%2"), "My Café%2");
+        assert_eq!(url_decoded("bad%FF%FEbytes"), "bad%FF%FEbytes");
+        assert_eq!(url_encoded("a b/ü+c.txt"), "a%20b/%C3%BC%2Bc.txt");
+        assert_eq!(url_decoded(&url_encoded("a b/ü+c.txt")), "a b/ü+c.txt");
+        assert_eq!(
+            percent_decoded("a%2fb%5C%00%20%C3%A9", unsafe_in_name),
+            "a%2fb%5C%00 é"
+        );
+        assert_eq!(
+            entities_decoded("a&#47;b&#x5c;&#0;&amp;", unsafe_in_name),
+            "a&#47;b&#x5c;&#0;&"
+        );
+        assert_eq!(
+            html_decoded("Tom &amp; Jerry &#8211; &#x2014;&bogus;"),
+            "Tom & Jerry – —&bogus;"
+        );
+
+        let rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        }
+        .with_decode(&["url".to_owned(), "html".to_owned()]);
+        assert_eq!(normalize_name("a%26amp%3Bb.txt", &rules), "a&b.txt");
+        let (_, steps) = trace_base("a%20b.txt", true, &rules).unwrap();
+        assert_eq!((steps[0].stage, steps[0].rule.as_str()), ("decode", "url"));
+    }
+
+    #[test]
+    fn test_invisibles() {
+        let rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_name("\u{FEFF}a\u{200B}b\u{202E}c\u{7}.txt", &rules),
+            "abc.txt"
+        );
+        let (_, steps) = trace_base("a\u{2060}b.txt", true, &rules).unwrap();
+        assert_eq!(steps[0].stage, "invisibles");
+        assert_eq!(
+            normalize_name("\u{645}\u{200C}\u{634}\u{200D}.txt", &rules),
+            "\u{645}\u{200C}\u{634}\u{200D}.txt"
+        );
+
+        let rules = rules.with_keep_invisibles(true);
+        assert_eq!(normalize_name("a\u{200B}b.txt", &rules), "a\u{200B}b.txt");
+    }
+
+    #[test]
+    fn test_emoji() {
+        assert_eq!(emoji_replaced("a🎉b 🇯🇵.", "strip", "_"), "ab .");
+        assert_eq!(emoji_replaced("a🎉b", "name", "_"), "a_PARTY POPPER_b");
+        assert_eq!(
+            emoji_replaced("👍\u{1F3FD} ❤\u{FE0F}", "name", "-"),
+            "THUMBS UP SIGN-EMOJI MODIFIER FITZPATRICK TYPE-4 HEAVY BLACK HEART"
+        );
+        assert_eq!(
+            emoji_replaced("1\u{FE0F}\u{20E3}", "strip", "_"),
+            "1\u{FE0F}\u{20E3}"
+        );
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let rules = rules.with_emoji("name").with_ascii(true);
+        assert_eq!(
+            normalize_name("Party🎉Time.txt", &rules),
+            "Party_PARTY_POPPER_Time.txt"
+        );
+        assert_eq!(
+            normalize_name("🎉 fun.txt", &rules.clone().with_emoji("strip")),
+            "fun.txt"
+        );
+    }
+
+    #[test]
+    fn test_punctuation_preset() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        rules.add_rule("-").unwrap();
+        let rules = rules.with_presets(&["punctuation".to_owned()], "keep");
+        assert_eq!(
+            normalize_name("It’s\u{A0}a — “Test”….txt", &rules),
+            "It's_a_\"Test\"....txt"
+        );
+        let (_, steps) = trace_base("a–b.txt", true, &rules).unwrap();
+        assert_eq!(
+            (steps[0].stage, steps[0].rule.as_str()),
+            ("presets", "punctuation")
+        );
+    }
+
+    #[test]
+    fn test_strip_ids_preset() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let rules = rules.with_presets(&["strip-ids".to_owned()], "keep");
+        assert_eq!(
+            normalize_name("report_f81d4fae-7dec-11d0-a765-00a0c91e6bf6.pdf", &rules),
+            "report.pdf"
+        );
+        assert_eq!(
+            normalize_name("Holiday 2023 (aB3xK9qZ).jpg", &rules),
+            "Holiday_2023.jpg"
+        );
+        let (_, steps) = trace_base("a d41d8cd98f00b204.txt", true, &rules).unwrap();
+        assert_eq!(
+            (steps[0].stage, steps[0].rule.as_str()),
+            ("presets", "strip-ids")
+        );
+    }
+
+    #[test]
+    fn test_cjk_preset() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        rules.add_rule(",").unwrap();
+        let rules = rules.with_presets(&["cjk".to_owned()], "keep");
+        assert_eq!(
+            normalize_name("第１集，ＡＢＣ\u{3000}(2024).mp4", &rules),
+            "第1集_ABC_(2024).mp4"
+        );
+        let (_, steps) = trace_base("Ａ.txt", true, &rules).unwrap();
+        assert_eq!(steps[0].stage, "half-width");
+    }
+
+    #[test]
+    fn test_pinyin() {
+        assert_eq!(pinyin_converted("北京2024", "lower", "_"), "bei_jing_2024");
+        assert_eq!(
+            pinyin_converted("去北京 trip", "title", ""),
+            "QuBeiJing trip"
+        );
+        assert_eq!(pinyin_converted("a中b", "lower", "-"), "a-zhong-b");
+        assert_eq!(pinyin_converted("《中》", "lower", "_"), "《zhong》");
+
+        let rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        }
+        .with_pinyin(Some(("lower".to_owned(), "_".to_owned())))
+        .with_ascii(true);
+        assert_eq!(normalize_name("北京Café.txt", &rules), "bei_jing_Cafe.txt");
+    }
+
+    #[test]
+    fn test_strip_dup_markers() {
+        assert_eq!(strip_dup_markers("report (1)").unwrap(), "report");
+        assert_eq!(strip_dup_markers("report - Copy (2)").unwrap(), "report");
+        assert_eq!(strip_dup_markers("Copy of report").unwrap(), "report");
+        assert_eq!(strip_dup_markers("Carbon Copy").unwrap(), "Carbon Copy");
+        assert_eq!(strip_dup_markers("(1)").unwrap(), "(1)");
+    }
+
+    #[test]
+    fn test_tree_lock() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (locks, tree) = (tmp.path().join("locks"), tmp.path().join("tree"));
+        fs::create_dir_all(tree.join("a/b")).unwrap();
+        fs::create_dir(tree.join("c")).unwrap();
+        let acquire = |d: &str| TreeLock::acquire_in(&locks, &tree.join(d), Duration::ZERO);
+        let lock = acquire("a").unwrap();
+        assert!(tree.join("a").join(LOCK_FILE_NAME).exists());
+        assert!(acquire("a").is_err());
+        assert!(acquire("a/b").is_err());
+        assert!(acquire("").is_err());
+        assert!(!tree.join(LOCK_FILE_NAME).exists());
+        assert!(acquire("c").is_ok());
+
+        drop(lock);
+        assert!(!tree.join("a").join(LOCK_FILE_NAME).exists());
+        let lock = acquire("a/b").unwrap();
+        assert!(acquire("a").is_err());
+        assert!(TreeLock::acquire_in(&locks, &tree.join("a"), Duration::from_millis(300)).is_err());
+        drop(lock);
+        assert!(acquire("a").is_ok());
+    }
+
+    #[test]
+    fn test_rename_reverse_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let conn = open_db(tmp.path().join("fdn.db").to_str()).unwrap();
+        let args = Args::parse_from(["fdn"]);
+        let names = [
+            "a b.txt",
+            "x  y  z.md",
+            "ok.txt",
+            "(1) Copy,of it!.rs",
+            "日本 語？.txt",
+            "dir name",
+        ];
+        let mut mem = MemFs::new();
+        for n in names {
+            match n.contains('.') {
+                true => mem.add_file(Path::new("/mem").join(n), 1),
+                false => mem.add_dir(Path::new("/mem").join(n)),
+            }
+        }
+        let ctx = Context::with_conn(conn, &args)
+            .unwrap()
+            .with_fs(Box::new(mem));
+
+        for n in names {
+            let d_b = DirBase {
+                dir: "/mem".to_owned(),
+                base: n.to_owned(),
+            };
+            let target = fdn_f(&ctx, &d_b, None, true).unwrap();
+            assert!(ctx.fs.borrow().exists(&Path::new("/mem").join(&target)));
+
+            let d_b = DirBase {
+                dir: "/mem".to_owned(),
+                base: target.clone(),
+            };
+            let back = fdn_rf(&ctx, &d_b, true, false).unwrap();
+            if target != n {
+                assert_eq!(back.as_deref(), Some(n));
+            }
+            assert!(ctx.fs.borrow().exists(&Path::new("/mem").join(n)));
+        }
+    }
+
+    #[test]
+    fn test_hidden_in() {
+        let root = Path::new("./root");
+        assert!(hidden_in(Path::new("./root/.a"), root));
+        assert!(hidden_in(Path::new("./root/.git/sub/a"), root));
+        assert!(!hidden_in(Path::new("./root/sub/a"), root));
+        assert!(!hidden_in(Path::new(".root"), Path::new(".root")));
+        assert!(!hidden_in(Path::new(".hidden/a"), root));
+
+        let kept = |argv: &[&str]| {
+            let args = Args::parse_from(argv);
+            (args.hidden_kept(true), args.hidden_kept(false))
+        };
+        assert_eq!(kept(&["fdn"]), (false, true));
+        assert_eq!(kept(&["fdn", "--hidden", "include"]), (true, true));
+        assert_eq!(kept(&["fdn", "--hidden", "only"]), (true, false));
+        assert_eq!(kept(&["fdn", "-I"]), (true, true));
     }
-}
 
-///Remove continuouse "word" in "source"
-fn remove_continuous(source: &str, word: &str) -> Result<String> {
-    let re = Regex::new(&format!(r"(?i){}{}+", word, word))?;
-    Ok(re.replace_all(source, word).to_string())
-}
+    #[test]
+    fn test_alias_args() {
+        let argv = ["-f", "dl", "-d", "2", "--preset", "tv", "-i"].map(str::to_owned);
+        let args = alias_args(&argv, &["-t".to_owned(), "d".to_owned()]).unwrap();
+        assert_eq!((args.max_depth, args.in_place), (2, true));
+        assert_eq!(args.filetype, "d");
+        assert_eq!(args.preset, ["tv"]);
+        assert!(alias_args(&["--nope".to_owned()], &[]).is_err());
+        assert!(alias_args(&["alias".to_owned(), "list".to_owned()], &[]).is_err());
+    }
 
-///Remove prefix separator and suffix separator
-fn remove_prefix_sep_suffix_sep<'a>(s: &'a str, sep: &'a str) -> &'a str {
-    let s = s.strip_prefix(sep).unwrap_or(s);
-    s.strip_suffix(&sep).unwrap_or(s)
-}
+    #[test]
+    fn test_install_pack() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join("fdn.db");
+        let mut conn = open_db(db.to_str()).unwrap();
+        insert_term_word(&conn, "ft.", "ft", TermFlags::default(), "").unwrap();
+        let mut pack = parse_pack(
+            r#"{"name":"mine","version":"1","rules":["acronym:NASA","acronym:PDF","ft.:feat.",
+                {"rule":"vol:Volume","scope":"audio","whole_word":true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(install_pack(&mut conn, &pack, "mine.json").unwrap(), (2, 1));
+        let tagged: Vec<_> = retrieve_toggled_rows(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.rule)
+            .collect();
+        assert_eq!(tagged, ["vol:Volume", "acronym:NASA"]);
+        let vol = retrieve_term_words(&conn).unwrap();
+        assert_eq!(
+            (vol[1].scope.as_str(), vol[1].flags.whole_word),
+            ("audio", true)
+        );
 
-///Rename a file or directory's name into specific target or by default
-fn fdn_f(dir_base: &DirBase, target: Option<String>, in_place: bool) -> Result<String> {
-    let conn = open_db(None)?;
+        pack.version = "2".to_owned();
+        pack.rules.truncate(1);
+        assert_eq!(install_pack(&mut conn, &pack, "mine.json").unwrap(), (1, 0));
+        assert_eq!(retrieve_term_words(&conn).unwrap().len(), 1);
+        assert_eq!(
+            retrieve_packs(&conn).unwrap(),
+            [("mine".to_owned(), "2".to_owned(), "mine.json".to_owned())]
+        );
+        assert_eq!(delete_tag_rows(&conn, &pack.tag()).unwrap(), 1);
+        assert!(retrieve_toggled_rows(&conn).unwrap().is_empty());
+        let mut bad = pack.clone();
+        bad.rules[0].scope = "video".to_owned();
+        assert!(install_pack(&mut conn, &bad, "mine.json").is_err());
+    }
 
-    let sep = retrieve_separators(&conn)?;
-    let sep = {
-        if !sep.is_empty() {
-            sep[0].clone().value
-        } else {
-            Separator::default().value
+    #[test]
+    fn test_scoped_rules() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        for rule in [" ", "ft.:feat.", "vol:Volume", "re:^\\d+-:"] {
+            rules.add_rule(rule).unwrap();
         }
-    };
-
-    let mut base_name = dir_base.base.to_owned();
+        rules.scopes = RuleScopes {
+            to_sep_words: vec![String::new()],
+            term_words: vec!["audio".to_owned(), "document,txt".to_owned()],
+            regex_rules: vec!["mp3".to_owned()],
+        };
+        assert_eq!(
+            plan_base("01-a ft. b vol 2.mp3", true, &rules).unwrap(),
+            "a_feat._b_vol_2.mp3"
+        );
+        assert_eq!(
+            plan_base("01-a ft. b vol 2.pdf", true, &rules).unwrap(),
+            "01-a_ft._b_Volume_2.pdf"
+        );
+        assert_eq!(
+            plan_base("01-a ft. b vol 2", false, &rules).unwrap(),
+            "01-a_ft._b_vol_2"
+        );
+        assert!(matches!(rules.scoped(Some("mp3")), Cow::Owned(_)));
+        rules.scopes = RuleScopes::default();
+        assert!(matches!(rules.scoped(None), Cow::Borrowed(_)));
+    }
 
-    let s_path = Path::new(&dir_base.dir).join(dir_base.base.clone());
+    #[test]
+    fn test_is_skipped() {
+        let skips = DEFAULT_SKIPS.map(str::to_owned);
+        assert!(is_skipped(".DS_Store", &skips));
+        assert!(is_skipped("thumbs.db", &skips));
+        assert!(is_skipped("._a b.txt", &skips));
+        assert!(!is_skipped("a.DS_Store", &skips));
+        assert!(!is_skipped("_.txt", &skips));
+        assert!(is_skipped("a.tmp", &["*.tmp".to_owned()]));
+        assert!(!is_skipped("a.tmp", &[]));
 
-    let t_path = match target {
-        Some(tn) => {
-            base_name.clone_from(&tn);
-            Path::new(&dir_base.dir).join(tn)
+        let tmp = tempfile::tempdir().unwrap();
+        for n in [".DS_Store", "._a.txt", "a.txt"] {
+            fs::write(tmp.path().join(n), "").unwrap();
         }
-        None => {
-            let (f_stem, f_ext) = match s_path.is_file() {
-                true => (
-                    Path::new(&base_name).file_stem(),
-                    Path::new(&base_name).extension().and_then(OsStr::to_str),
-                ),
-                false => (Some(Path::new(&base_name).as_os_str()), None),
-            };
+        let names = |skips: &[String]| -> Vec<_> {
+            let mut files = regular_files(tmp.path(), 1, &Excludes::default(), skips).unwrap();
+            files.sort();
+            files
+                .iter()
+                .map(|f| os2string(f.file_name()).unwrap())
+                .collect()
+        };
+        assert_eq!(names(&skips), ["a.txt"]);
+        assert_eq!(names(&[]), [".DS_Store", "._a.txt", "a.txt"]);
+    }
 
-            let mut f_stem = os2string(f_stem)?;
+    #[test]
+    #[cfg(unix)]
+    fn test_fdn_lossy() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
 
-            //replace to sep words
-            let to_sep_words = retrieve_to_sep_words(&conn)?;
-            let replacements_map: HashMap<_, _> = to_sep_words
-                .iter()
-                .map(|e| (e.value.clone(), sep.clone()))
-                .collect();
+        let path = Path::new("/mem").join(OsStr::from_bytes(b"a \xff b.txt"));
+        let mut mem = MemFs::new();
+        mem.add_file(&path, 1);
+        let args = Args::parse_from(["fdn"]);
+        let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+            .unwrap()
+            .with_fs(Box::new(mem));
+        let (d_b, rlt) = fdn_lossy(&ctx, &path, None, true).unwrap();
+        assert_eq!(d_b.base, "a \u{FFFD} b.txt");
+        assert_eq!(rlt, "a_b.txt");
+        assert!(ctx.fs.borrow().exists(Path::new("/mem/a_b.txt")));
+        assert!(!ctx.fs.borrow().exists(&path));
 
-            let mut old_f_stem = f_stem.clone();
-            loop {
-                replacements_map.iter().for_each(|(k, v)| {
-                    f_stem = f_stem.replace(k, v);
-                });
-                if old_f_stem.eq(&f_stem) {
-                    break;
-                }
-                old_f_stem.clone_from(&f_stem);
-            }
+        let path = Path::new("/mem").join(OsStr::from_bytes(b"\xfe\xff"));
+        assert!(fdn_lossy(&ctx, &path, None, false).is_err());
 
-            //term words
-            let term_words = retrieve_term_words(&conn)?;
-            let replacements_map: HashMap<_, _> = term_words
-                .iter()
-                .map(|e| (e.key.clone(), e.value.clone()))
-                .collect();
-            let mut old_f_stem = f_stem.clone();
-            loop {
-                replacements_map.iter().for_each(|(k, v)| {
-                    f_stem = f_stem.replace(k, v);
-                });
-                if old_f_stem.eq(&f_stem) {
-                    break;
-                }
-                old_f_stem.clone_from(&f_stem);
+        let path = Path::new("/mem").join(OsStr::from_bytes(b"b \xff.txt"));
+        let reverse = |policy: &str, keep_going: bool| {
+            let mut mem = MemFs::new();
+            mem.add_file(&path, 1);
+            let mut argv = vec!["fdn", "-r", "--non-utf8", policy];
+            if keep_going {
+                argv.push("--keep-going");
             }
+            let args = Args::parse_from(argv);
+            let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+                .unwrap()
+                .with_fs(Box::new(mem));
+            fdn_rfs_in(ctx, vec![path.clone()], args)
+        };
+        assert!(reverse("skip", false).is_ok());
+        assert!(reverse("lossy", false).is_ok());
+        assert!(reverse("error", false).is_err());
+        //a failed file still fails a run going on after it
+        assert!(reverse("error", true).is_err());
+    }
 
-            //remove continuous
-            f_stem = remove_continuous(&f_stem, &sep)?;
-
-            //remove prefix and suffix sep
-            let rlt = remove_prefix_sep_suffix_sep(&f_stem, &sep).to_owned();
-            f_stem = rlt;
+    #[test]
+    fn test_canonical_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(tmp.path()).unwrap();
+        fs::create_dir_all(root.join("a/sub")).unwrap();
+        fs::write(root.join("a/b.txt"), "").unwrap();
+        fs::write(root.join("a/sub/c.txt"), "").unwrap();
 
-            base_name = match f_ext {
-                Some(f_ext) => format!("{}.{}", f_stem, f_ext),
-                None => f_stem.to_owned(),
-            };
-            Path::new(&dir_base.dir).join(base_name.clone())
+        let b = root.join("a/b.txt");
+        assert_eq!(canonical_path(&tmp.path().join("a/./sub/../b.txt")), b);
+        assert_eq!(canonical_path(&tmp.path().join("a/sub/..")), root.join("a"));
+        assert_eq!(
+            canonical_path(&root.join("a/new.txt")),
+            root.join("a/new.txt")
+        );
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&b, root.join("a/link.txt")).unwrap();
+            assert_eq!(
+                canonical_path(&root.join("a/link.txt")),
+                root.join("a/link.txt")
+            );
         }
-    };
+        assert_eq!(
+            unique_paths(vec![b.clone(), root.join("a"), b.clone()]),
+            [b, root.join("a")]
+        );
 
-    //take effect
-    if base_name != dir_base.base && in_place {
-        fs::rename(s_path, t_path)?;
-        let rd = Record::new(&dir_base.clone().base, &base_name)?;
-        insert_record(&conn, rd)?;
+        let unnormalized = root.join("a/sub/../../a");
+        let args = Args::parse_from([
+            "fdn",
+            "-X",
+            root.join("a/sub/.").to_str().unwrap(),
+            "-X",
+            "/elsewhere",
+        ]);
+        let exs = args.excludes_under(&unnormalized).unwrap();
+        assert!(exs.excluded(&unnormalized.join("sub/c.txt")));
+        assert_eq!(exs.unmatched(), ["/elsewhere"]);
+        //a file input excluded by its directory
+        let args = Args::parse_from(["fdn", "-X", root.join("a").to_str().unwrap()]);
+        let exs = args.excludes_under(&root.join("a/b.txt")).unwrap();
+        assert!(exs.excluded(&root.join("a/b.txt")));
+        let args = Args::parse_from(["fdn", "-X", root.join("a/sub").to_str().unwrap()]);
+        let exs = args.excludes_under(&unnormalized).unwrap();
+        let listed = regular_files(&unnormalized, 9, &exs, &[]);
+        assert_eq!(listed.unwrap(), [unnormalized.join("b.txt")]);
     }
 
-    Ok(base_name)
-}
-
-///Firstly rename files or directories's name into targets or by default,then do post-processing work
-pub fn fdn_fs_post(origins: Vec<PathBuf>, targets: Vec<String>, args: Args) -> Result<()> {
-    let mut tgts: Vec<Option<String>> = vec![None];
+    #[test]
+    fn test_record_dir() {
+        let mut mem = MemFs::new();
+        mem.add_file("/mem/a b.txt", 1);
+        mem.add_dir("/other");
+        let args = Args::parse_from(["fdn", "--record-dir"]);
+        let mut ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+            .unwrap()
+            .with_fs(Box::new(mem));
+        let d_b = DirBase {
+            dir: "/mem".to_owned(),
+            base: "a b.txt".to_owned(),
+        };
+        assert_eq!(fdn_f(&ctx, &d_b, None, true).unwrap(), "a_b.txt");
+        ctx.fs
+            .borrow_mut()
+            .rename(Path::new("/mem/a_b.txt"), Path::new("/other/a_b.txt"))
+            .unwrap();
 
-    if targets.is_empty() {
-        tgts.resize(origins.len(), None);
-    } else if origins.len() != targets.len() {
-        return Err(anyhow!(
-            "origins length {:?} must equal to targets length {:?}",
-            origins.len(),
-            targets.len()
-        ));
-    } else {
-        tgts = targets.into_iter().map(Some).collect();
+        let moved = DirBase {
+            dir: "/other".to_owned(),
+            base: "a_b.txt".to_owned(),
+        };
+        assert_eq!(fdn_rf(&ctx, &moved, false, false).unwrap(), None);
+        assert!(ctx.report.borrow().text().contains("--accept-relocation"));
+        ctx.accept_relocation = true;
+        assert_eq!(
+            fdn_rf(&ctx, &moved, false, false).unwrap().as_deref(),
+            Some("a b.txt")
+        );
+        ctx.accept_relocation = false;
+        let stayed = DirBase {
+            dir: "/mem".to_owned(),
+            ..moved
+        };
+        assert!(fdn_rf(&ctx, &stayed, false, false).unwrap().is_some());
     }
 
-    origins
-        .iter()
-        .zip(tgts.iter())
-        .filter(|(of, _tn)| !(is_hidden(of) && args.not_ignore_hidden))
-        .try_for_each(|(of, tn)| -> Result<()> {
-            if let Some(d_b) = dir_base(of) {
-                let rlt = fdn_f(&d_b, tn.clone(), args.in_place)?;
-
-                let (o_r, e_r) = match args.align {
-                    true => fname_compare(&d_b.base, &rlt, "a")?,
-                    false => fname_compare(&d_b.base, &rlt, "")?,
-                };
-                if !o_r.eq(&e_r) {
-                    if args.in_place {
-                        println!("   {}\n==>{}", o_r, e_r);
-                    } else {
-                        println!("   {}\n-->{}", o_r, e_r);
-                    }
-                }
-            }
-            Ok(())
-        })?;
-
-    Ok(())
-}
+    #[test]
+    fn test_chosen_record() {
+        let args = Args::parse_from(["fdn"]);
+        let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args).unwrap();
+        let hashed = hashed_name("ab.txt");
+        let d_b = |dir: &str| DirBase {
+            dir: dir.to_owned(),
+            base: "ab.txt".to_owned(),
+        };
 
-///Revertly rename a file or directory's name
-fn fdn_rf(dir_base: &DirBase, in_place: bool) -> Result<Option<String>> {
-    let conn = open_db(None)?;
+        for _ in 0..2 {
+            insert_record(&ctx.conn, Record::new("a b.txt", "ab.txt").unwrap()).unwrap();
+        }
+        let rds = retrieve_records_by_hash(&ctx.conn, &hashed).unwrap();
+        assert_eq!(rds.len(), 1);
+        assert_eq!(rds[0].count, 2);
+        release_record(&ctx.conn, &rds[0]).unwrap();
+        assert_eq!(
+            retrieve_records_by_hash(&ctx.conn, &hashed).unwrap()[0].count,
+            1
+        );
+        assert_eq!(
+            fdn_rf(&ctx, &d_b("/mem"), false, false).unwrap().as_deref(),
+            Some("a b.txt")
+        );
 
-    let base_name = &dir_base.base;
-    let rds = retrieve_records(&conn)?;
+        insert_record(&ctx.conn, Record::new("a-b.txt", "ab.txt").unwrap()).unwrap();
+        assert_eq!(fdn_rf(&ctx, &d_b("/mem"), false, false).unwrap(), None);
+        assert!(ctx.report.borrow().text().contains("different names"));
 
-    let map: HashMap<_, _> = rds
-        .iter()
-        .map(|rd| (rd.clone().hashed_current_name, rd.clone()))
-        .collect();
-    let rd = map.get(&hashed_name(base_name));
-
-    match rd {
-        Some(rd) => match decrypted(&rd.encrypted_pre_name, base_name) {
-            Ok(v) => {
-                let rt = v.from_hex()?;
-                let base_name = String::from_utf8(rt)?;
-                //take effect
-                if in_place {
-                    let s_path = Path::new(&dir_base.dir).join(dir_base.base.clone());
-                    let t_path = Path::new(&dir_base.dir).join(base_name.clone());
-                    fs::rename(s_path, t_path)?; //Only rename successfully then ...
-                    if rd.count == 1 {
-                        delete_records(&conn, rd.id)?;
-                    }
-                }
-                Ok(Some(base_name))
-            }
-            Err(err) => Err(err),
-        },
-        None => Ok(None),
+        let rd = Record::new("x.txt", "ab.txt").unwrap();
+        insert_record(
+            &ctx.conn,
+            rd.with_dir(Path::new("/mem2"), "ab.txt").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            fdn_rf(&ctx, &d_b("/mem2"), false, false)
+                .unwrap()
+                .as_deref(),
+            Some("x.txt")
+        );
+        assert_eq!(fdn_rf(&ctx, &d_b("/mem3"), false, false).unwrap(), None);
     }
-}
 
-///Firstly revertly rename files or directories's name,then do post-processing work
-pub fn fdn_rfs_post(files: Vec<PathBuf>, args: Args) -> Result<()> {
-    files
-        .iter()
-        .filter(|f| args.not_ignore_hidden || !is_hidden(f))
-        .try_for_each(|f| -> Result<()> {
-            let mut frc = Some(f.clone());
-            while let Some(ref f) = frc {
-                if let Some(dir_base) = dir_base(f) {
-                    match fdn_rf(&dir_base, args.in_place) {
-                        Ok(Some(rf_base)) => {
-                            if args.reverse_chainly {
-                                frc = Some(Path::new(&dir_base.dir).join(rf_base.clone()));
-                            } else {
-                                frc = None;
-                            }
-                            let (o_r, e_r) = match args.align {
-                                true => fname_compare(&dir_base.base, &rf_base, "a")?,
-                                false => fname_compare(&dir_base.base, &rf_base, "")?,
-                            };
-                            if !o_r.eq(&e_r) {
-                                if args.in_place {
-                                    println!("   {}\n==>{}", o_r, e_r);
-                                } else {
-                                    println!("   {}\n-->{}", o_r, e_r);
-                                }
-                            }
-                        }
-                        Ok(None) => break,
-                        Err(err) => return Err(err),
-                    }
-                }
-            }
-
-            Ok(())
-        })?;
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = [
+            "file10.txt",
+            "file2.txt",
+            "File1.txt",
+            "file02.txt",
+            "file1.txt",
+            "a",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            [
+                "File1.txt",
+                "a",
+                "file1.txt",
+                "file02.txt",
+                "file2.txt",
+                "file10.txt"
+            ]
+        );
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("x", "x1"), Ordering::Less);
 
-    Ok(())
-}
+        let mut paths = ["s10/a", "s2/b", "s2", "s10"].map(PathBuf::from);
+        paths.sort_by(|a, b| natural_path_cmp(a, b));
+        assert_eq!(paths, ["s2", "s2/b", "s10", "s10/a"].map(PathBuf::from));
+        paths.sort_by(|a, b| path_order(a, b, "lexical"));
+        assert_eq!(paths, ["s10", "s10/a", "s2", "s2/b"].map(PathBuf::from));
+        sort_paths(&mut paths, "natural");
+        assert_eq!(paths[0], PathBuf::from("s2"));
 
-fn os2string(input: Option<&OsStr>) -> Result<String> {
-    match input {
-        Some(os_str) => match os_str.to_str() {
-            Some(valid_str) => Ok(valid_str.to_string()),
-            None => Err(anyhow!("Invalid UTF-8 sequence")),
-        },
-        None => Err(anyhow!("Option is None")),
+        let tmp = tempfile::tempdir().unwrap();
+        let (big, small) = (tmp.path().join("big"), tmp.path().join("small"));
+        fs::write(&big, "big").unwrap();
+        fs::write(&small, "s").unwrap();
+        let mut paths = [tmp.path().join("gone"), big.clone(), small.clone()];
+        sort_paths(&mut paths, "size");
+        assert_eq!(paths, [small, big, tmp.path().join("gone")]);
+        let args = Args::parse_from(["fdn", "--sort", "name"]);
+        assert_eq!(args.sort, "natural");
+        assert!(Args::try_parse_from(["fdn", "--sort", "random"]).is_err());
     }
-}
 
-///return unicode names of every character of the string and name separated by "," sign
-fn unames(s: &str) -> String {
-    s.chars()
-        .filter_map(|c| unicode_names2::name(c).map(|n| n.to_string()))
-        .collect::<Vec<_>>()
-        .join(",")
-}
+    #[test]
+    fn test_common_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::create_dir_all(root.join("a/c")).unwrap();
+        fs::write(root.join("a/c/d.txt"), "").unwrap();
 
-///list all separators stored in database via database connection
-fn list_separators(conn: &Connection) -> Result<()> {
-    let mut rlts = retrieve_separators(conn)?;
-    let s = "Separator";
-    println!("{} ID\tValue\tDescription", s);
-    rlts.sort_by_key(|sep| sep.id);
-    rlts.iter().for_each(|sep| {
-        println!(
-            "{} {}\t{}\t{}",
-            " ".repeat(s.len()),
-            sep.id,
-            sep.value,
-            unames(&sep.value)
+        assert_eq!(common_dir(&[root.join("a/b")]), root.join("a/b"));
+        assert_eq!(
+            common_dir(&[root.join("a/b"), root.join("a/c/d.txt")]),
+            root.join("a")
         );
-    });
+        assert_eq!(common_dir(&[root.join("a/c/d.txt")]), root.join("a/c"));
+        assert_eq!(common_dir(&[]), PathBuf::new());
+    }
 
-    Ok(())
-}
+    #[test]
+    fn test_uncollided() {
+        let rename = |fs_case: &str| {
+            let args = Args::parse_from(["fdn", "--fs-case", fs_case]);
+            let mut mem = MemFs::new();
+            let names = ["a b.txt", "A B.txt", "c d.txt"];
+            names
+                .iter()
+                .for_each(|n| mem.add_file(Path::new("/mem").join(n), 1));
+            let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+                .unwrap()
+                .with_fs(Box::new(mem));
+            names.map(|n| {
+                let d_b = DirBase {
+                    dir: "/mem".to_owned(),
+                    base: n.to_owned(),
+                };
+                fdn_f(&ctx, &d_b, Some(n.replace(' ', "_")), false).unwrap()
+            })
+        };
 
-///list all to separator words stored in database via database connection
-fn list_to_sep_words(conn: &Connection) -> Result<()> {
-    let mut rlts = retrieve_to_sep_words(conn)?;
-    let s = "ToSepWord";
-    println!("{} ID\tValue\tDescription", s);
-    rlts.sort_by_key(|tsw| tsw.id);
-    rlts.iter().for_each(|tsw| {
-        println!(
-            "{} {}\t{}\t{}",
-            " ".repeat(s.len()),
-            tsw.id,
-            tsw.value.replace('\r', "\\r").replace('\n', "\\n"),
-            unames(&tsw.value)
+        assert_eq!(rename("sensitive"), ["a_b.txt", "A_B.txt", "c_d.txt"]);
+        assert_eq!(rename("insensitive"), ["a_b.txt", "A B.txt", "c_d.txt"]);
+
+        let mut mem = MemFs::new();
+        ["e f.txt", "e_f.txt"]
+            .iter()
+            .for_each(|n| mem.add_file(Path::new("/mem").join(n), 1));
+        let args = Args::parse_from(["fdn", "--fs-case", "sensitive"]);
+        let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+            .unwrap()
+            .with_fs(Box::new(mem));
+        let d_b = DirBase {
+            dir: "/mem".to_owned(),
+            base: "e f.txt".to_owned(),
+        };
+        assert_eq!(
+            fdn_f(&ctx, &d_b, Some("e_f.txt".to_owned()), true).unwrap(),
+            "e f.txt"
+        );
+        assert_eq!(
+            ctx.report.borrow().text(),
+            "skipped \"/mem/e f.txt\":target \"e_f.txt\" exists\n1 skipped"
         );
-    });
 
-    Ok(())
-}
+        //a chain 'b'->'c' before 'a'->'b',planned or applied
+        for in_place in [false, true] {
+            let mut mem = MemFs::new();
+            mem.add_file("/mem/a.txt", 1);
+            mem.add_file("/mem/b.txt", 2);
+            let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+                .unwrap()
+                .with_fs(Box::new(mem));
+            let chained: Vec<_> = [("b.txt", "c.txt"), ("a.txt", "b.txt")]
+                .iter()
+                .map(|(base, target)| {
+                    let d_b = DirBase {
+                        dir: "/mem".to_owned(),
+                        base: base.to_string(),
+                    };
+                    fdn_f(&ctx, &d_b, Some(target.to_string()), in_place).unwrap()
+                })
+                .collect();
+            assert_eq!(chained, ["c.txt", "b.txt"]);
+            assert_eq!(ctx.fs.borrow().exists(Path::new("/mem/c.txt")), in_place);
+        }
 
-///list all term words stored in database via database connection
-fn list_term_words(conn: &Connection) -> Result<()> {
-    let mut rlts = retrieve_term_words(conn)?;
-    let s = "TermWord";
-    println!("{} ID\tKey\tValue", s);
-    rlts.sort_by_key(|tw| tw.id);
-    rlts.iter().for_each(|tw| {
-        println!(
-            "{} {}\t{}\t{}",
-            " ".repeat(s.len()),
-            tw.id,
-            tw.key,
-            tw.value.replace('\r', "\\r").replace('\n', "\\n")
+        let mut mem = MemFs::new();
+        mem.add_file("/mem/e f.txt", 1);
+        mem.add_file("/mem/e_f.txt", 2);
+        let args = Args::parse_from([
+            "fdn",
+            "--fs-case",
+            "sensitive",
+            "--on-conflict",
+            "overwrite",
+        ]);
+        let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+            .unwrap()
+            .with_fs(Box::new(mem));
+        assert_eq!(
+            fdn_f(&ctx, &d_b, Some("e_f.txt".to_owned()), true).unwrap(),
+            "e_f.txt"
+        );
+        let len = |p: &str| ctx.fs.borrow().metadata(Path::new(p)).map(|m| m.len).ok();
+        assert_eq!((len("/mem/e f.txt"), len("/mem/e_f.txt")), (None, Some(1)));
+        assert_eq!(ctx.rollback().unwrap(), 1);
+        assert_eq!(
+            (len("/mem/e f.txt"), len("/mem/e_f.txt")),
+            (Some(1), Some(2))
         );
-    });
 
-    Ok(())
-}
+        //the record bumped by the run released,not a newer one of the same target
+        let mut mem = MemFs::new();
+        mem.add_file("/mem/a.txt", 1);
+        let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args)
+            .unwrap()
+            .with_fs(Box::new(mem));
+        insert_record(&ctx.conn, Record::new("a.txt", "x.txt").unwrap()).unwrap();
+        insert_record(&ctx.conn, Record::new("c.txt", "x.txt").unwrap()).unwrap();
+        let d_b = DirBase {
+            dir: "/mem".to_owned(),
+            base: "a.txt".to_owned(),
+        };
+        fdn_f(&ctx, &d_b, Some("x.txt".to_owned()), true).unwrap();
+        let counts = || {
+            let rds = retrieve_records_by_hash(&ctx.conn, &hashed_name("x.txt")).unwrap();
+            rds.iter().map(|rd| rd.count).collect::<Vec<_>>()
+        };
+        assert_eq!(counts(), [1, 2]);
+        ctx.rollback().unwrap();
+        assert_eq!(counts(), [1, 1]);
 
-///List all configurations
-pub fn config_list() -> Result<()> {
-    let conn = open_db(None)?;
-    list_separators(&conn)?;
-    list_to_sep_words(&conn)?;
-    list_term_words(&conn)?;
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("Probe"), "").unwrap();
+        assert_eq!(
+            case_insensitive(tmp.path()),
+            tmp.path().join("pROBE").exists()
+        );
+    }
 
-    Ok(())
-}
+    #[test]
+    fn test_number_targets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let files: Vec<_> = ["b b.jpg", "a.jpg", "c_007.jpg"]
+            .iter()
+            .map(|n| tmp.path().join(n))
+            .collect();
+        files.iter().for_each(|f| fs::write(f, "").unwrap());
+        let args = Args::parse_from(["fdn", "--number"]);
 
-///Add configuration into database
-pub fn config_add(word: &str) -> Result<()> {
-    let conn = open_db(None)?;
-    match word.split_once(':') {
-        Some((key, value)) => {
-            insert_term_word(&conn, key, value)?;
-            list_term_words(&conn)?;
-        }
-        None => {
-            insert_to_sep_word(&conn, word)?;
-            list_to_sep_words(&conn)?;
-        }
-    }
+        let (origins, targets) = number_targets(files.clone(), &args).unwrap();
+        assert_eq!(origins[0], files[1]);
+        assert_eq!(targets, vec!["a_001.jpg", "b_b_002.jpg", "c_003.jpg"]);
 
-    Ok(())
-}
+        let live = tmp.path().join("live");
+        fs::create_dir(&live).unwrap();
+        let files: Vec<_> = ["live 1999.jpg", "live_0042.jpg"]
+            .iter()
+            .map(|n| live.join(n))
+            .collect();
+        files.iter().for_each(|f| fs::write(f, "").unwrap());
+        let (_, targets) = number_targets(files, &args).unwrap();
+        assert_eq!(targets, vec!["live_1999_001.jpg", "live_0042_002.jpg"]);
 
-///Delete configuration in the database
-pub fn config_delete(word: &str) -> Result<()> {
-    let conn = open_db(None)?;
-    match word.split_once(':') {
-        Some((key, value)) => {
-            let rlts = retrieve_term_words(&conn)?;
-            let the_word = rlts.iter().find(|&w| w.key == key && w.value == value);
-            if let Some(w) = the_word {
-                delete_term_word(&conn, w.id)?;
-                list_term_words(&conn)?;
-            }
-        }
-        None => {
-            let rlts = retrieve_to_sep_words(&conn)?;
-            let the_word = rlts.iter().find(|&w| w.value == word);
-            if let Some(w) = the_word {
-                delete_to_sep_word(&conn, w.id)?;
-                list_to_sep_words(&conn)?;
+        //numbering again a directory numbered by the same options keeps every name
+        let renumbered = |dir: &str, n: usize, args: &Args| {
+            let dir = tmp.path().join(dir);
+            fs::create_dir(&dir).unwrap();
+            let files: Vec<_> = (0..n).map(|i| dir.join(format!("p{}.jpg", i))).collect();
+            files.iter().for_each(|f| fs::write(f, "").unwrap());
+            let (origins, targets) = number_targets(files, args).unwrap();
+            for (o, t) in origins.iter().zip(&targets) {
+                fs::rename(o, dir.join(t)).unwrap();
             }
-        }
+            let files: Vec<_> = targets.iter().map(|t| dir.join(t)).collect();
+            let (_, again) = number_targets(files, args).unwrap();
+            (targets, again)
+        };
+        let (targets, again) = renumbered("many", 102, &args);
+        assert_eq!(targets[99], "p99_100.jpg");
+        assert_eq!(again[99], "p99_100.jpg");
+        assert_eq!(again[101], "p101_102.jpg");
+        let args = Args::parse_from(["fdn", "--number", "--pad", "1"]);
+        let (targets, again) = renumbered("short", 3, &args);
+        assert_eq!(targets, vec!["p0_1.jpg", "p1_2.jpg", "p2_3.jpg"]);
+        assert_eq!(again, targets);
+        let args = Args::parse_from(["fdn", "--number", "--pad", "1", "--number-at", "prefix"]);
+        let (targets, again) = renumbered("prefixed", 12, &args);
+        assert_eq!(
+            (&targets[0][..], &targets[11][..]),
+            ("01_p0.jpg", "12_p11.jpg")
+        );
+        assert_eq!(again, targets);
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_plex_targets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let show = tmp.path().join("Show");
+        fs::create_dir(&show).unwrap();
+        let files = vec![
+            tmp.path().join("The.Matrix.1999.1080p.mkv"),
+            show.join("Show.S01E02.Pilot.mkv"),
+            tmp.path().join("notes.txt"),
+        ];
+        files.iter().for_each(|f| fs::write(f, "").unwrap());
+        let args = Args::parse_from(["fdn", "--preset", "plex-movie", "--preset", "plex-tv"]);
 
-///compare file stem and file extension separately and return rich text
-fn fname_compare(origin: &str, edit: &str, mode: &str) -> Result<(String, String)> {
-    let (o_stem, o_ext) = stem_ext(origin)?;
-    let (e_stem, e_ext) = stem_ext(edit)?;
+        let (origins, targets) = plex_targets(files.clone(), &args).unwrap();
+        assert_eq!(origins, files[..2]);
+        let target = |p: &str| Path::new(p).to_string_lossy().to_string();
+        assert_eq!(
+            targets,
+            vec![
+                target("The Matrix (1999)/The Matrix (1999).mkv"),
+                target("Season 01/Show - S01E02 - Pilot.mkv")
+            ]
+        );
+    }
 
-    let (o_stem_cmp, e_stem_cmp) = s_compare(&o_stem, &e_stem, mode)?;
-    let (o_ext_cmp, e_ext_cmp) = s_compare(&o_ext, &e_ext, mode)?;
+    #[test]
+    fn test_detect_dupes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sub = tmp.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        let files: Vec<_> = ["a.txt", "b.txt", "c.txt", "sub/a.txt"]
+            .iter()
+            .map(|n| tmp.path().join(n))
+            .collect();
+        for (f, content) in files.iter().zip(["same", "same", "other", "same"]) {
+            fs::write(f, content).unwrap();
+        }
 
-    Ok((
-        o_stem_cmp + if o_ext.is_empty() { "" } else { "." } + &o_ext_cmp,
-        e_stem_cmp + if e_ext.is_empty() { "" } else { "." } + &e_ext_cmp,
-    ))
-}
+        let groups = find_dupes(&files).unwrap();
+        assert_eq!(
+            groups,
+            vec![vec![files[0].clone(), files[1].clone(), files[3].clone()]]
+        );
+        //same names only are not duplicates to report
+        assert!(find_dupes(&[files[0].clone(), files[3].clone()])
+            .unwrap()
+            .is_empty());
 
-///return file stem and file extension by file path
-fn stem_ext<P>(path: P) -> Result<(String, String)>
-where
-    P: AsRef<Path> + AsRef<OsStr>,
-{
-    let p = Path::new(&path);
-    let stem = os2string(p.file_stem())?;
-    let ext = os2string(p.extension())?;
+        let left = dupes_handled(files.clone(), "skip", None).unwrap();
+        assert_eq!(left, vec![files[0].clone(), files[2].clone()]);
+        //a dry run leaves duplicates untouched
+        let left = dupes_handled(files.clone(), "link", None).unwrap();
+        assert_eq!(left, files);
+        fs::write(&files[0], "changed").unwrap();
+        assert_eq!(fs::read_to_string(&files[1]).unwrap(), "same");
+        fs::write(&files[0], "same").unwrap();
 
-    Ok((stem, ext))
-}
+        let args = Args::try_parse_from(["fdn", "-i"]).unwrap();
+        let ctx = Context::with_conn(open_db(Some(":memory:")).unwrap(), &args).unwrap();
+        let left = dupes_handled(files.clone(), "link", Some(&ctx)).unwrap();
+        assert_eq!(left, files);
+        let ops = retrieve_session_operations(&ctx.conn, &ctx.session.id).unwrap();
+        assert_eq!(ops.len(), 2);
+        assert!(ops.iter().all(|op| op.status == "linked"));
+        assert_eq!(undoable_rename(&ops).unwrap().origin, "a.txt");
+        fs::write(&files[0], "changed").unwrap();
+        assert_eq!(fs::read_to_string(&files[1]).unwrap(), "changed");
 
-#[cfg(test)]
-mod tests {
-    use crate::{remove_continuous, remove_prefix_sep_suffix_sep, stem_ext};
+        //rolled back by copies,contents kept but no longer shared
+        ctx.rollback().unwrap();
+        fs::write(&files[0], "again").unwrap();
+        assert_eq!(fs::read_to_string(&files[1]).unwrap(), "changed");
+        assert_eq!(fs::read_to_string(&files[3]).unwrap(), "changed");
+    }
 
     #[test]
-    fn test_remove_xfix_sep() {
-        let sep = "_";
-        let s = "_PDFScholar_";
-        assert!(s.starts_with(sep));
-        assert!(s.ends_with(sep));
-        let t = "PDFScholar";
-        assert_eq!(remove_prefix_sep_suffix_sep(s, sep), t);
+    fn test_rename_into() {
+        let mut mfs = MemFs::new();
+        mfs.add_file("/d/a.mkv", 1);
+        rename_into(&mut mfs, Path::new("/d"), "a.mkv", "A (2000)/A (2000).mkv").unwrap();
+        assert!(mfs.exists(Path::new("/d/A (2000)/A (2000).mkv")));
+        assert!(!mfs.exists(Path::new("/d/a.mkv")));
     }
 
     #[test]
-    fn test_stem_ext() {
-        let p = "stem.ext";
-        let (s, e) = stem_ext(p).unwrap();
-        assert!(s.eq("stem"));
-        assert!(e.eq("ext"));
+    fn test_order_renames() {
+        let p = |n: &str| PathBuf::from(n);
+        let ordered =
+            order_renames(vec![(p("a"), p("b")), (p("b"), p("c")), (p("d"), p("d"))]).unwrap();
+        assert_eq!(ordered[0], (p("b"), p("c")));
+        assert_eq!(ordered[2], (p("a"), p("b")));
+        assert!(order_renames(vec![(p("a"), p("b")), (p("b"), p("a"))]).is_err());
     }
 
     #[test]
-    fn test_remove_continuous() {
-        let src = "A_B__C___D_.txt";
-        let sep = "_";
-        let tgt = "A_B_C_D_.txt";
-        assert_eq!(remove_continuous(src, sep).unwrap(), tgt);
+    fn test_selftest() {
+        let tmp = tempfile::tempdir().unwrap();
+        for n in ["a b.txt", "a_b.txt", "c  d.md"] {
+            fs::write(tmp.path().join(n), "").unwrap();
+        }
+        let args = Args::parse_from(["fdn"]);
+        selftest(tmp.path(), 10, &args).unwrap();
+        assert!(tmp.path().join("a b.txt").exists());
+        assert!(!tmp.path().join("c_d.md").exists());
     }
 }
@@ -0,0 +1,137 @@
+use std::{fs::File, io::Read, path::Path};
+
+///Bytes read from the start of files to detect their types
+const HEAD_LEN: usize = 64;
+
+///Extensions accepted for zip based formats,office documents,e-books and packages sharing the
+///zip container
+const ZIP_EXTS: [&str; 37] = [
+    "zip", "docx", "docm", "dotx", "xlsx", "xlsm", "xltx", "pptx", "pptm", "potx", "ppsx", "vsdx",
+    "odt", "ods", "odp", "odg", "odf", "ott", "epub", "jar", "war", "ear", "apk", "aab", "ipa",
+    "appx", "msix", "cbz", "xpi", "whl", "aar", "nupkg", "vsix", "kmz", "3mf", "xps", "idml",
+];
+
+///Extensions accepted for TIFF based formats,camera RAW images sharing the TIFF container
+const TIFF_EXTS: [&str; 20] = [
+    "tif", "tiff", "dng", "cr2", "nef", "nrw", "arw", "srf", "sr2", "orf", "pef", "rw2", "raw",
+    "3fr", "erf", "kdc", "dcr", "mef", "mos", "srw",
+];
+
+///Extensions accepted for the type detected from the head of a file,the first one canonical,
+///None for unknown types such as plain text
+pub fn sniff(head: &[u8]) -> Option<&'static [&'static str]> {
+    let at = |i: usize, magic: &[u8]| head.get(i..i + magic.len()) == Some(magic);
+
+    let exts: &'static [&'static str] = if at(0, b"\x89PNG\r\n\x1a\n") {
+        &["png"]
+    } else if at(0, b"\xff\xd8\xff") {
+        &["jpg", "jpeg", "jpe", "jfif"]
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        &["gif"]
+    } else if at(0, b"RIFF") && at(8, b"WEBP") {
+        &["webp"]
+    } else if at(0, b"RIFF") && at(8, b"WAVE") {
+        &["wav"]
+    } else if at(0, b"RIFF") && at(8, b"AVI ") {
+        &["avi"]
+    } else if at(0, b"BM") && head.len() >= 14 && at(6, b"\0\0\0\0") {
+        &["bmp"]
+    } else if at(0, b"II*\0") || at(0, b"MM\0*") || at(0, b"IIRO") || at(0, b"IIU\0") {
+        &TIFF_EXTS
+    } else if at(0, b"%PDF-") {
+        &["pdf"]
+    } else if at(0, b"PK\x03\x04") {
+        &ZIP_EXTS
+    } else if at(0, b"\x1f\x8b") {
+        &["gz", "tgz"]
+    } else if at(0, b"BZh") {
+        &["bz2", "tbz2"]
+    } else if at(0, b"\xfd7zXZ\0") {
+        &["xz", "txz"]
+    } else if at(0, b"7z\xbc\xaf\x27\x1c") {
+        &["7z"]
+    } else if at(0, b"Rar!\x1a\x07") {
+        &["rar"]
+    } else if at(0, b"fLaC") {
+        &["flac"]
+    } else if at(0, b"OggS") {
+        &["ogg", "oga", "ogv", "opus"]
+    } else if at(0, b"ID3") || at(0, b"\xff\xfb") {
+        &["mp3"]
+    } else if at(4, b"ftyp") {
+        match head.get(8..12) {
+            Some(b"qt  ") => &["mov", "qt"],
+            Some(b"M4A ") | Some(b"M4B ") => &["m4a", "m4b", "mp4"],
+            Some(b"heic") | Some(b"heix") | Some(b"mif1") => &["heic", "heif"],
+            Some(b"3gp4") | Some(b"3gp5") => &["3gp", "mp4"],
+            _ => &["mp4", "m4v", "m4a", "mov"],
+        }
+    } else if at(0, b"\x1a\x45\xdf\xa3") {
+        match head.windows(4).any(|w| w == b"webm") {
+            true => &["webm", "mkv"],
+            false => &["mkv", "mka", "mks", "mk3d"],
+        }
+    } else {
+        return None;
+    };
+
+    Some(exts)
+}
+
+///Name with the extension corrected or added by the detected type of the file,name kept if the
+///type is unknown or the extension is accepted
+pub fn fixed_ext(path: &Path, name: &str) -> String {
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    let read = File::open(path).and_then(|f| f.take(HEAD_LEN as u64).read_to_end(&mut head));
+    let Some(exts) = read.ok().and_then(|_| sniff(&head)) else {
+        return name.to_owned();
+    };
+
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => {
+            match exts.contains(&ext.to_lowercase().as_str()) {
+                true => name.to_owned(),
+                false => format!("{}.{}", stem, exts[0]),
+            }
+        }
+        _ => format!("{}.{}", name, exts[0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::magic::{fixed_ext, sniff};
+
+    #[test]
+    fn test_fixed_ext() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n....").unwrap()[0], "png");
+        assert_eq!(sniff(b"\0\0\0\x20ftypqt  ").unwrap()[0], "mov");
+        assert!(sniff(b"plain text").is_none());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let png = tmp.path().join("a.jpg");
+        fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR").unwrap();
+        assert_eq!(fixed_ext(&png, "a.jpg"), "a.png");
+        assert_eq!(fixed_ext(&png, "a"), "a.png");
+        assert_eq!(fixed_ext(&png, "a.PNG"), "a.PNG");
+
+        let docx = tmp.path().join("b.docx");
+        fs::write(&docx, b"PK\x03\x04rest").unwrap();
+        assert_eq!(fixed_ext(&docx, "b.docx"), "b.docx");
+        assert_eq!(fixed_ext(&docx, "b.xlsm"), "b.xlsm");
+        assert_eq!(fixed_ext(&docx, "b.jpg"), "b.zip");
+
+        let raw = tmp.path().join("e.cr2");
+        fs::write(&raw, b"II*\0\x10\0\0\0CR").unwrap();
+        assert_eq!(fixed_ext(&raw, "e.CR2"), "e.CR2");
+        assert_eq!(fixed_ext(&raw, "e.nef"), "e.nef");
+        assert_eq!(fixed_ext(&raw, "e.png"), "e.tif");
+
+        let txt = tmp.path().join("c.txt");
+        fs::write(&txt, "text").unwrap();
+        assert_eq!(fixed_ext(&txt, "c.txt"), "c.txt");
+        assert_eq!(fixed_ext(&tmp.path().join("missing"), "d.jpg"), "d.jpg");
+    }
+}
@@ -1,21 +1,29 @@
 use std::{
-    cmp::Ordering,
+    io,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use anyhow::{anyhow, Result};
 use clap::Parser;
-use tracing::warn;
-
 use fdn::{
-    config_add, config_delete, config_list, directories, fdn_fs_post, fdn_rfs_post, regular_files,
-    Args, Commands,
+    aborted, alias, archive_names, audit, backend_files, capabilities, check_idempotent, clean,
+    common_dir, config_add, config_backup, config_compare, config_delete, config_delete_id,
+    config_diff, config_enable, config_list, config_merge, config_restore, config_restore_backup,
+    config_script, config_set, config_stats, config_tag, config_unset, config_update_id, daemon,
+    detect_dupes, directories,
+    exclude::{warn_unmatched, Excludes},
+    expand_alias, fdn_fs_in, fdn_rfs_in, flatten, handle_signals, hidden_in, integrate, log_tail,
+    map, mv, number_targets, organize, paste_names, path_order, pick, plex_enabled, plex_targets,
+    preset, regular_files, resume, selftest, serve, sort_paths, suggest, try_names, tui,
+    unique_paths, watch, Args, Commands, Context, TermFlags, TreeLock,
 };
 
 fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    tracing_subscriber::fmt().with_writer(io::stderr).init();
+    handle_signals()?;
 
-    let args = Args::parse();
+    let args = expand_alias(Args::parse())?;
 
     //process version
     if args.version {
@@ -35,9 +43,107 @@ fn main() -> Result<()> {
                 list: ls,
                 add: cfg,
                 delete: dlt,
+                restore,
+                compare,
+                with,
+                set,
+                unset,
+                script,
+                stats,
+                ignore_case,
+                whole_word,
+                scope,
+                rows,
+                db,
             } => {
+                if *stats {
+                    config_stats()?;
+
+                    return Ok(());
+                }
+                if let Some(id) = restore {
+                    config_restore(*id)?;
+
+                    return Ok(());
+                }
+                if let Some(path) = &db.diff {
+                    config_diff(Path::new(path))?;
+
+                    return Ok(());
+                }
+                if let Some(path) = &db.merge {
+                    config_merge(Path::new(path))?;
+
+                    return Ok(());
+                }
+                if let Some(path) = &db.backup {
+                    config_backup(Path::new(path), db.part())?;
+
+                    return Ok(());
+                }
+                if let Some(path) = &db.restore_backup {
+                    config_restore_backup(Path::new(path), db.part())?;
+
+                    return Ok(());
+                }
+                if let Some(target) = &rows.enable {
+                    config_enable(target, true)?;
+
+                    return Ok(());
+                }
+                if let Some(target) = &rows.disable {
+                    config_enable(target, false)?;
+
+                    return Ok(());
+                }
+                if let Some(spec) = &rows.tag {
+                    config_tag(spec)?;
+
+                    return Ok(());
+                }
+                if let Some(row) = &rows.delete_id {
+                    config_delete_id(row)?;
+
+                    return Ok(());
+                }
+                if let Some(spec) = &rows.update_id {
+                    config_update_id(spec)?;
+
+                    return Ok(());
+                }
+                if let Some(kv) = set {
+                    config_set(kv)?;
+
+                    return Ok(());
+                }
+                if let Some(key) = unset {
+                    config_unset(key)?;
+
+                    return Ok(());
+                }
+                if let Some(file) = script {
+                    config_script(Path::new(file))?;
+
+                    return Ok(());
+                }
+                if let (true, Some(rule)) = (compare, with) {
+                    let (mut files, mut excludes) = (Vec::new(), Vec::new());
+                    for f_path in args.input_paths() {
+                        let (input, exs, _) = input_files(&args, &f_path)?;
+                        files.extend(input);
+                        excludes.push(exs);
+                    }
+                    warn_unmatched(&excludes);
+                    config_compare(rule, unique_paths(files), &args)?;
+
+                    return Ok(());
+                }
                 if let Some(word) = cfg {
-                    config_add(word)?;
+                    let flags = TermFlags {
+                        ignore_case: *ignore_case,
+                        whole_word: *whole_word,
+                    };
+                    config_add(word, flags, scope.as_deref())?;
 
                     return Ok(());
                 }
@@ -52,67 +158,298 @@ fn main() -> Result<()> {
                     return Ok(());
                 }
             }
-            Commands::Mv { inputs } => {
-                match inputs.len().cmp(&2) {
-                    Ordering::Less => {
-                        return Err(anyhow!("At least two arguments are required:{:?}", inputs));
-                    }
-                    Ordering::Equal => {}
-                    Ordering::Greater => {
-                        warn!("Only the first two arguments are valid.")
+            Commands::Log {
+                follow,
+                dir,
+                status,
+                lines,
+            } => {
+                log_tail(dir.as_deref(), status.as_deref(), *lines, *follow)?;
+
+                return Ok(());
+            }
+            Commands::Clean { dir, yes } => {
+                clean(Path::new(dir), args.clone(), *yes)?;
+
+                return Ok(());
+            }
+            Commands::Try {
+                names,
+                dir,
+                explain,
+            } => {
+                try_names(names, *dir, *explain, &args)?;
+
+                return Ok(());
+            }
+            Commands::Resume { session, list } => {
+                resume(session.as_deref(), *list)?;
+
+                return Ok(());
+            }
+            Commands::Archive { file } => {
+                archive_names(Path::new(file), &args)?;
+
+                return Ok(());
+            }
+            Commands::Check { names, idempotent } => {
+                if !idempotent {
+                    return Err(anyhow!("No check selected,such as '--idempotent'"));
+                }
+                let (mut files, mut excludes) = (Vec::new(), Vec::new());
+                if names.is_empty() {
+                    for f_path in args.input_paths() {
+                        let (input, exs, _) = input_files(&args, &f_path)?;
+                        files.extend(input);
+                        excludes.push(exs);
                     }
+                    warn_unmatched(&excludes);
                 }
+                check_idempotent(unique_paths(files), names, &args)?;
 
-                let sfs = vec![PathBuf::from(inputs[0].clone())];
-                if sfs.iter().all(|f| f.is_dir() || f.is_file()) {
-                    let tns = vec![inputs[1].clone()];
-                    fdn_fs_post(sfs, tns, args)?;
-                } else {
-                    return Err(anyhow!("The paths not exist:{:?}", sfs));
-                }
+                return Ok(());
+            }
+            Commands::Suggest {
+                path,
+                min_count,
+                yes,
+            } => {
+                suggest(Path::new(path), *min_count, *yes, &args)?;
 
                 return Ok(());
             }
-        }
-    }
+            Commands::Watch {
+                dir,
+                poll,
+                interval,
+                debounce,
+            } => {
+                watch(
+                    Path::new(dir),
+                    poll.then(|| Duration::from_millis(*interval)),
+                    Duration::from_secs(*debounce),
+                    &args,
+                )?;
 
-    //process fdn with no subcommands
-    let input_paths = match args.files {
-        Some(ref vs) => vs.iter().map(Path::new).collect(),
-        None => vec![Path::new(&args.file_path)],
-    };
+                return Ok(());
+            }
+            Commands::Alias { action, name, argv } => {
+                alias(action, name.as_deref(), argv)?;
+
+                return Ok(());
+            }
+            Commands::Integrate {
+                action,
+                target,
+                argv,
+            } => {
+                integrate(action, target, argv)?;
 
-    let exs: Vec<_> = args.exclude_path.iter().map(Path::new).collect();
+                return Ok(());
+            }
+            Commands::Preset { action, name } => {
+                preset(action, name.as_deref())?;
 
-    input_paths.iter().try_for_each(|f_path| -> Result<()> {
-        let args = args.clone();
+                return Ok(());
+            }
+            Commands::Daemon { action, config } => {
+                daemon(action, config.as_deref().map(Path::new))?;
 
-        if args.filetype == "f" {
-            let files = match f_path.is_dir() {
-                true => regular_files(f_path, args.max_depth, exs.clone())?,
-                false => vec![PathBuf::from(f_path)],
-            };
+                return Ok(());
+            }
+            Commands::Serve { listen, token } => {
+                serve(listen, token.as_deref())?;
 
-            if (args.reverse) || (args.reverse_chainly) {
-                let _ = fdn_rfs_post(files, args);
-            } else {
-                let _ = fdn_fs_post(files, Vec::new(), args);
+                return Ok(());
             }
-        } else if args.filetype == "d" {
-            let files = match f_path.is_dir() {
-                true => directories(f_path, args.max_depth, exs.clone())?,
-                false => panic!("input path not match filetype"),
-            };
+            Commands::Audit { path, json } => {
+                audit(Path::new(path), *json, &args)?;
 
-            if (args.reverse) || (args.reverse_chainly) {
-                let _ = fdn_rfs_post(files, args);
-            } else {
-                let _ = fdn_fs_post(files, Vec::new(), args);
+                return Ok(());
+            }
+            Commands::Tui { dir } => {
+                tui(Path::new(dir), &args)?;
+
+                return Ok(());
+            }
+            Commands::PasteNames {
+                dir,
+                stdin,
+                sort,
+                keep_ext,
+            } => {
+                paste_names(Path::new(dir), *stdin, sort, *keep_ext, &args)?;
+
+                return Ok(());
+            }
+            Commands::Map { file, root } => {
+                map(Path::new(file), Path::new(root), &args)?;
+
+                return Ok(());
+            }
+            Commands::Flatten {
+                path,
+                depth,
+                encode_path,
+            } => {
+                flatten(Path::new(path), *depth, encode_path.as_deref(), &args)?;
+
+                return Ok(());
+            }
+            Commands::Organize { template, path } => {
+                organize(Path::new(path), template, &args)?;
+
+                return Ok(());
+            }
+            Commands::Selftest { path, sample } => {
+                selftest(Path::new(path), *sample, &args)?;
+
+                return Ok(());
+            }
+            Commands::Capabilities { json } => {
+                capabilities(*json)?;
+
+                return Ok(());
+            }
+            Commands::Mv {
+                inputs,
+                pairs,
+                parents,
+            } => {
+                mv(inputs, pairs, *parents, &args)?;
+
+                return Ok(());
             }
         }
+    }
 
-        Ok(())
-    })?;
+    //process fdn with no subcommands,files of all inputs in one plan and a file under
+    //overlapping inputs processed once
+    if args.on_conflict == "overwrite" && !cfg!(feature = "trash") {
+        return Err(anyhow!(
+            "fdn is built without feature 'trash',needed by '--on-conflict overwrite'"
+        ));
+    }
+    if args.preset.iter().any(|p| p == "ebook")
+        && args.template.is_none()
+        && !cfg!(feature = "ebook-meta")
+    {
+        return Err(anyhow!(
+            "fdn is built without feature 'ebook-meta',needed by '--preset ebook'"
+        ));
+    }
+    if let Some(ref url) = args.backend {
+        if args.number
+            || plex_enabled(&args)
+            || args.detect_dupes.is_some()
+            || args.record_dir
+            || args.on_conflict == "overwrite"
+        {
+            return Err(anyhow!(
+                "Backend can not be used with numbering,plex,duplicate detection,'--record-dir' \
+                 or '--on-conflict overwrite'"
+            ));
+        }
+        let (fs, files) = backend_files(url, &args)?;
+        let ctx = Context::on_fs(&args, fs)?;
+        return match args.reverse || args.reverse_chainly {
+            true => fdn_rfs_in(ctx, files, args),
+            false => fdn_fs_in(ctx, files, Vec::new(), args),
+        };
+    }
+    let inputs = args.input_paths();
+    let _locks = TreeLock::for_inputs(&inputs, &args)?;
+    let (mut files, mut excludes, mut skipped) = (Vec::new(), Vec::new(), Vec::new());
+    for f_path in &inputs {
+        let (input, exs, skips) = input_files(&args, f_path)?;
+        files.extend(input);
+        excludes.push(exs);
+        skipped.extend(skips);
+    }
+    let mut files = unique_paths(files);
+    match args.filetype.as_str() {
+        //subdirectories renamed before their parents
+        "d" => files.sort_by(|a, b| path_order(b, a, &args.sort)),
+        _ => sort_paths(&mut files, &args.sort),
+    }
+    if args.detect_dupes.is_some() && !(args.reverse || args.reverse_chainly) {
+        files = detect_dupes(files, &args)?;
+    }
+    if args.pick {
+        files = pick(files, &common_dir(&inputs))?;
+    }
+    warn_unmatched(&excludes);
+
+    let (origins, targets) = if args.reverse || args.reverse_chainly {
+        (files, Vec::new())
+    } else if args.number {
+        number_targets(files, &args)?
+    } else if plex_enabled(&args) {
+        plex_targets(files, &args)?
+    } else {
+        (files, Vec::new())
+    };
+    //hidden and excluded files reported as skipped,though they are left out before the run
+    let ctx = Context::new(&args)?;
+    for (f, reason) in &skipped {
+        ctx.report.borrow_mut().skip(f, reason);
+    }
+    let rlt = match args.reverse || args.reverse_chainly {
+        true => fdn_rfs_in(ctx, origins, args),
+        false => fdn_fs_in(ctx, origins, targets, args),
+    };
+    if aborted() {
+        return Err(anyhow!("Aborted by signal"));
+    }
+
+    rlt
+}
+
+///Files left out of a run with the reasons
+type Skipped = Vec<(PathBuf, String)>;
+
+///Files or directories of an input path by file type,filtered by the hidden policy,with the
+///excludes which matched them and the files left out with the reasons
+fn input_files(args: &Args, f_path: &Path) -> Result<(Vec<PathBuf>, Excludes, Skipped)> {
+    let exs = args.excludes_under(f_path)?;
+    let mut files = if args.filetype == "f" {
+        match f_path.is_dir() {
+            true => regular_files(f_path, args.max_depth, &Excludes::default(), &args.skips()?)?,
+            false => vec![PathBuf::from(f_path)],
+        }
+    } else if args.filetype == "d" {
+        match f_path.is_dir() {
+            true => directories(f_path, args.max_depth, &Excludes::default())?,
+            false => {
+                return Err(anyhow!(
+                    "Input path must be a directory for file type 'd':{:?}",
+                    f_path
+                ))
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let mut skipped = Vec::new();
+    files.retain(|f| match exs.excluded_by(f) {
+        Some(e) => {
+            skipped.push((f.clone(), format!("excluded by {}", e)));
+            false
+        }
+        None => true,
+    });
+    files.retain(|f| {
+        let hidden = hidden_in(f, f_path);
+        let kept = args.hidden_kept(hidden);
+        if !kept {
+            let reason = match hidden {
+                true => "hidden",
+                false => "not hidden",
+            };
+            skipped.push((f.clone(), reason.to_owned()));
+        }
+        kept
+    });
 
-    Ok(())
+    Ok((files, exs, skipped))
 }
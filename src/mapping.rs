@@ -0,0 +1,247 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+///A row of a mapping file,source and target relative to the root
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapRow {
+    ///line number in the file,from 1
+    pub line: usize,
+    pub source: String,
+    pub target: String,
+}
+
+///Delimiter of a mapping file by its extension,tab for '.tsv' and '.tab',otherwise comma
+pub fn delimiter(path: &Path) -> char {
+    match path.extension().map(|e| e.to_string_lossy().to_lowercase()) {
+        Some(e) if e == "tsv" || e == "tab" => '\t',
+        _ => ',',
+    }
+}
+
+///Split a line into fields,a field quoted by '"' may contain the delimiter and '""' for a quote
+fn fields(line: &str, delim: char) -> Result<Vec<String>> {
+    let mut fields = vec![String::new()];
+    //in a quoted field,right after a quoted field
+    let (mut quoted, mut closed) = (false, false);
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => (quoted, closed) = (false, true),
+            (true, c) => field.push(c),
+            (false, c) if c == delim => {
+                fields.push(String::new());
+                closed = false;
+            }
+            (false, c) if closed => return Err(anyhow!("Unexpected {:?} after a quoted field", c)),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, c) => field.push(c),
+        }
+    }
+
+    match quoted {
+        true => Err(anyhow!("Unclosed quote")),
+        false => Ok(fields),
+    }
+}
+
+///Parse rows of 'source,target' of a mapping file,blank lines,lines starting with '#' and a
+///header row 'source,target' skipped
+pub fn parse_map(text: &str, delim: char) -> Result<Vec<MapRow>> {
+    let mut rows = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fs = fields(line, delim).map_err(|e| anyhow!("Line {}:{}", i + 1, e))?;
+        let [source, target] = fs.as_slice() else {
+            return Err(anyhow!("Line {}:expected 2 fields,got {}", i + 1, fs.len()));
+        };
+        if rows.is_empty() && source == "source" && target == "target" {
+            continue;
+        }
+        rows.push(MapRow {
+            line: i + 1,
+            source: source.trim().to_owned(),
+            target: target.trim().to_owned(),
+        });
+    }
+
+    Ok(rows)
+}
+
+///Check a path is relative and stays under the root
+fn is_inside(path: &str) -> bool {
+    let p = Path::new(path);
+    !path.is_empty()
+        && p.components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+///Check rows against the filesystem with all problems reported at once:sources exist and are
+///unique,targets are unique,under the directory of their sources and either free or a source
+///of another row;renames returned as paths under the root
+pub fn check_map(
+    root: &Path,
+    rows: &[MapRow],
+    exists: impl Fn(&Path) -> bool,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut problems = Vec::new();
+    let mut sources = HashMap::new();
+    let mut targets = HashMap::new();
+
+    for r in rows {
+        for p in [&r.source, &r.target] {
+            if !is_inside(p) {
+                problems.push(format!(
+                    "line {}:{:?} is not a path under the root",
+                    r.line, p
+                ));
+            }
+        }
+        let (src, dst) = (root.join(&r.source), root.join(&r.target));
+        if !exists(&src) {
+            problems.push(format!("line {}:source {:?} not exists", r.line, r.source));
+        }
+        if !dst.starts_with(src.parent().unwrap_or(root)) {
+            problems.push(format!(
+                "line {}:target {:?} is not under the directory of its source",
+                r.line, r.target
+            ));
+        }
+        if let Some(l) = sources.insert(src, r.line) {
+            problems.push(format!(
+                "line {}:source {:?} repeats line {}",
+                r.line, r.source, l
+            ));
+        }
+        if let Some(l) = targets.insert(dst, r.line) {
+            problems.push(format!(
+                "line {}:target {:?} repeats line {}",
+                r.line, r.target, l
+            ));
+        }
+    }
+    let moved: HashSet<_> = rows
+        .iter()
+        .filter(|r| r.source != r.target)
+        .map(|r| root.join(&r.source))
+        .collect();
+    for r in rows {
+        let dst = root.join(&r.target);
+        if r.source != r.target && exists(&dst) && !moved.contains(&dst) {
+            problems.push(format!("line {}:target {:?} exists", r.line, r.target));
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(anyhow!("Invalid mapping:\n{}", problems.join("\n")));
+    }
+
+    Ok(rows
+        .iter()
+        .filter(|r| r.source != r.target)
+        .map(|r| (root.join(&r.source), root.join(&r.target)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::mapping::{check_map, delimiter, parse_map, MapRow};
+
+    #[test]
+    fn test_parse_map() {
+        assert_eq!(delimiter(Path::new("a.TSV")), '\t');
+        assert_eq!(delimiter(Path::new("a.csv")), ',');
+
+        let rows = parse_map(
+            "source,target\n# comment\n\na b.txt,a_b.txt\r\n\"c,d.txt\",\"say \"\"hi\"\".txt\"\n",
+            ',',
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            [
+                MapRow {
+                    line: 4,
+                    source: "a b.txt".to_owned(),
+                    target: "a_b.txt".to_owned(),
+                },
+                MapRow {
+                    line: 5,
+                    source: "c,d.txt".to_owned(),
+                    target: "say \"hi\".txt".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(parse_map("a\tsub/b", '\t').unwrap()[0].target, "sub/b");
+        assert_eq!(parse_map("a,", ',').unwrap()[0].target, "");
+        assert!(parse_map("a,b,c", ',').is_err());
+        assert!(parse_map("a", ',').is_err());
+        assert!(parse_map("\"a,b", ',').is_err());
+    }
+
+    #[test]
+    fn test_check_map() {
+        let row = |line: usize, source: &str, target: &str| MapRow {
+            line,
+            source: source.to_owned(),
+            target: target.to_owned(),
+        };
+        let existing = ["r/a", "r/b", "r/c", "r/d/e"].map(PathBuf::from);
+        let exists = |p: &Path| existing.iter().any(|e| e == p);
+        let root = Path::new("r");
+
+        let renames = check_map(
+            root,
+            &[
+                row(1, "a", "b"),
+                row(2, "b", "x"),
+                row(3, "c", "c"),
+                row(4, "d/e", "d/f/e"),
+            ],
+            exists,
+        )
+        .unwrap();
+        assert_eq!(
+            renames,
+            [
+                (PathBuf::from("r/a"), PathBuf::from("r/b")),
+                (PathBuf::from("r/b"), PathBuf::from("r/x")),
+                (PathBuf::from("r/d/e"), PathBuf::from("r/d/f/e")),
+            ]
+        );
+
+        let err = check_map(
+            root,
+            &[
+                row(1, "a", "c"),
+                row(2, "z", "y"),
+                row(3, "b", "y"),
+                row(4, "b", "w"),
+                row(5, "d/e", "e"),
+                row(6, "../a", "a2"),
+            ],
+            exists,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("line 1:target \"c\" exists"));
+        assert!(err.contains("line 2:source \"z\" not exists"));
+        assert!(err.contains("line 3:target \"y\" repeats line 2"));
+        assert!(err.contains("line 4:source \"b\" repeats line 3"));
+        assert!(err.contains("line 5:target \"e\" is not under"));
+        assert!(err.contains("line 6:\"../a\" is not a path under the root"));
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    backend::{FsBackend, FsMeta, MemFs},
+    url_decoded, url_encoded,
+};
+
+///Attributes asked by 'gio list'
+const LIST_ATTRS: &str = "standard::type,standard::size,time::modified";
+
+///Files of a folder on a phone or camera as a filesystem,listed once and renamed over MTP by the
+///gio tool talking to the device directly instead of through a FUSE mount,'/device/storage/dir'
+///for 'mtp://device/storage/dir';gio rather than mtp-tools since those have no command to rename
+///or move an object and binding libmtp would need it at build time,every command checked so that
+///a dropped gvfs session fails the rename instead of passing silently
+#[derive(Debug)]
+pub struct MtpFs {
+    root: PathBuf,
+    listing: MemFs,
+}
+
+impl MtpFs {
+    ///List files of a URL such as 'mtp://Pixel_7/Internal shared storage/DCIM' down to the depth
+    ///where 1 is the folder itself
+    pub fn open(url: &str, depth: usize) -> Result<Self> {
+        let root = parse_mtp_url(url)?;
+        let mut fs = Self {
+            root: root.clone(),
+            listing: MemFs::new(),
+        };
+        fs.listing.add_dir(&root);
+        let mut dirs = vec![root];
+        for level in 1..=depth {
+            for dir in std::mem::take(&mut dirs) {
+                let listed = gio(&["list", "-l", "-a", LIST_ATTRS, &mtp_uri(&dir)])?;
+                for (name, meta) in listed.lines().filter_map(parse_list_line) {
+                    let path = dir.join(name);
+                    match meta.is_dir {
+                        true => {
+                            fs.listing.add_dir(&path);
+                            if level < depth {
+                                dirs.push(path);
+                            }
+                        }
+                        false => fs.listing.add_file_at(&path, meta.len, meta.modified),
+                    }
+                }
+            }
+        }
+
+        Ok(fs)
+    }
+
+    ///Path of the folder,the root of files to rename
+    pub fn root(&self) -> PathBuf {
+        self.root.clone()
+    }
+}
+
+impl FsBackend for MtpFs {
+    ///Rename in place on the device or move to another folder,a taken target refused
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        self.listing.metadata(from)?;
+        if from == to {
+            return Ok(());
+        }
+        if self.listing.exists(to) || gio(&["info", &mtp_uri(to)]).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} exists", to),
+            ));
+        }
+        match (from.parent() == to.parent(), to.file_name()) {
+            (true, Some(name)) => gio(&["rename", &mtp_uri(from), &name.to_string_lossy()])?,
+            _ => gio(&["move", "-T", &mtp_uri(from), &mtp_uri(to)])?,
+        };
+
+        self.listing.rename(from, to)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMeta> {
+        self.listing.metadata(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.listing.read_dir(path)
+    }
+
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        if !self.listing.exists(path) {
+            gio(&["mkdir", "-p", &mtp_uri(path)])?;
+        }
+
+        self.listing.create_dir_all(path)
+    }
+}
+
+///Path of a URL such as 'mtp://device/storage/dir',percent-encoded chars decoded
+pub fn parse_mtp_url(url: &str) -> Result<PathBuf> {
+    let rest = url
+        .strip_prefix("mtp://")
+        .ok_or_else(|| anyhow!("MTP URL must start with 'mtp://':{:?}", url))?;
+    let rest = rest.trim_end_matches('/');
+    if rest.is_empty() || rest.starts_with('/') {
+        return Err(anyhow!("MTP URL must have a device:{:?}", url));
+    }
+
+    Ok(Path::new("/").join(url_decoded(rest)))
+}
+
+///URI of a path for gio
+fn mtp_uri(path: &Path) -> String {
+    format!("mtp:/{}", url_encoded(&path.to_string_lossy()))
+}
+
+///Name and metadata of an entry listed by 'gio list -l' such as
+///'a b.jpg\t2048\t(regular)\tstandard::type=1 time::modified=1714557600',entries other than
+///files and directories left out
+fn parse_list_line(line: &str) -> Option<(&str, FsMeta)> {
+    let mut fields = line.split('\t');
+    let name = fields.next().filter(|n| !n.is_empty())?;
+    let len = fields.next()?.parse().ok()?;
+    let is_dir = match fields.next()? {
+        "(directory)" => true,
+        "(regular)" => false,
+        _ => return None,
+    };
+    let modified = fields
+        .next()
+        .unwrap_or_default()
+        .split(' ')
+        .find_map(|a| a.strip_prefix("time::modified="))
+        .and_then(|s| s.parse().ok())
+        .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+    Some((
+        name,
+        FsMeta {
+            is_dir,
+            len,
+            modified,
+            created: None,
+        },
+    ))
+}
+
+///Run the gio tool and return its output
+fn gio(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("gio")
+        .args(args)
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to run gio:{}", e)))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "gio {} failed:{}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::mtp::{mtp_uri, parse_list_line, parse_mtp_url};
+
+    #[test]
+    fn test_mtp_fs() {
+        let root = parse_mtp_url("mtp://Pixel_7/Internal%20shared%20storage/DCIM/").unwrap();
+        assert_eq!(root, PathBuf::from("/Pixel_7/Internal shared storage/DCIM"));
+        assert_eq!(
+            mtp_uri(&root),
+            "mtp://Pixel_7/Internal%20shared%20storage/DCIM"
+        );
+        assert!(parse_mtp_url("mtp://").is_err());
+        assert!(parse_mtp_url("file:///a").is_err());
+
+        let (name, meta) =
+            parse_list_line("IMG 1.jpg\t2048\t(regular)\tstandard::type=1 time::modified=60")
+                .unwrap();
+        assert_eq!(name, "IMG 1.jpg");
+        assert_eq!(meta.len, 2048);
+        assert!(!meta.is_dir);
+        assert_eq!(meta.modified, Some(UNIX_EPOCH + Duration::from_secs(60)));
+        assert!(parse_list_line("Camera\t0\t(directory)").unwrap().1.is_dir);
+        assert!(parse_list_line("link\t0\t(symbolic-link)").is_none());
+    }
+}
@@ -0,0 +1,167 @@
+use std::{fs, path::Path, process::Command};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::TermFlags;
+
+///Actions of 'fdn preset'
+pub const PACK_ACTIONS: [&str; 4] = ["list", "install", "update", "remove"];
+
+///Built-in rule packs by name with their manifests
+pub const BUILTIN_PACKS: [(&str, &str); 3] = [
+    ("scene", include_str!("scene.json")),
+    ("cjk-punctuation", include_str!("cjk-punctuation.json")),
+    ("academic", include_str!("academic.json")),
+];
+
+///A rule of a pack in config syntax with its term flags and scope
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackRule {
+    pub rule: String,
+    pub flags: TermFlags,
+    ///extensions or MIME classes separated by ',',empty for all files
+    pub scope: String,
+}
+
+///A pack of rules installed and removed as a whole,updated when its version changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pack {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub rules: Vec<PackRule>,
+}
+
+impl Pack {
+    ///Tag of config rows installed by the pack
+    pub fn tag(&self) -> String {
+        pack_tag(&self.name)
+    }
+}
+
+///Tag of config rows installed by a pack of the name
+pub fn pack_tag(name: &str) -> String {
+    format!("pack:{}", name)
+}
+
+///Parse a pack manifest such as {"name":"scene","version":"1","description":"...","rules":
+///["acronym:PDF",{"rule":"ft.:feat.","scope":"video","ignore_case":true,"whole_word":true}]},
+///rules in config syntax and name of lowercase letters,digits and '-'
+pub fn parse_pack(s: &str) -> Result<Pack> {
+    let v: Value = serde_json::from_str(s)?;
+    let string = |key: &str| -> Result<String> {
+        v.get(key)
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow!("Pack manifest must have a string '{}'", key))
+    };
+    let name = string("name")?;
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(anyhow!(
+            "Pack name must be lowercase letters,digits or '-':{:?}",
+            name
+        ));
+    }
+
+    let rules = v
+        .get("rules")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("Pack manifest must have a 'rules' array"))?
+        .iter()
+        .map(|r| {
+            let flag = |key: &str| r.get(key).and_then(Value::as_bool).unwrap_or(false);
+            let rule = match r {
+                Value::String(s) => Some(s.as_str()),
+                _ => r.get("rule").and_then(Value::as_str),
+            };
+            Ok(PackRule {
+                rule: rule
+                    .ok_or_else(|| {
+                        anyhow!("Rule of a pack must be a string or have a 'rule':{}", r)
+                    })?
+                    .to_owned(),
+                flags: TermFlags {
+                    ignore_case: flag("ignore_case"),
+                    whole_word: flag("whole_word"),
+                },
+                scope: r
+                    .get("scope")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Pack {
+        name,
+        version: string("version")?,
+        description: string("description").unwrap_or_default(),
+        rules,
+    })
+}
+
+///Manifest of a pack from a built-in name,an URL fetched by curl or a file
+pub fn load_manifest(source: &str) -> Result<String> {
+    if let Some((_, manifest)) = BUILTIN_PACKS.iter().find(|(name, _)| *name == source) {
+        return Ok((*manifest).to_owned());
+    }
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let output = Command::new("curl")
+            .args(["-fsSL", source])
+            .output()
+            .map_err(|e| anyhow!("Failed to run curl for {:?}:{}", source, e))?;
+        return match output.status.success() {
+            true => Ok(String::from_utf8(output.stdout)?),
+            false => Err(anyhow!(
+                "Failed to fetch {:?}:{}",
+                source,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+        };
+    }
+
+    fs::read_to_string(Path::new(source)).map_err(|e| {
+        anyhow!(
+            "Pack must be one of {:?},an URL or a manifest file:{:?},{}",
+            BUILTIN_PACKS.map(|(name, _)| name),
+            source,
+            e
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packs::{load_manifest, parse_pack, BUILTIN_PACKS};
+
+    #[test]
+    fn test_parse_pack() {
+        let pack = parse_pack(
+            r#"{"name":"my-pack","version":"2","rules":["acronym:PDF",
+                {"rule":"ft.:feat.","scope":"video","ignore_case":true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(pack.tag(), "pack:my-pack");
+        assert_eq!(pack.version, "2");
+        assert_eq!(pack.rules[0].rule, "acronym:PDF");
+        assert_eq!(pack.rules[1].scope, "video");
+        assert!(pack.rules[1].flags.ignore_case);
+        assert!(!pack.rules[1].flags.whole_word);
+
+        assert!(parse_pack(r#"{"name":"My Pack","version":"1","rules":[]}"#).is_err());
+        assert!(parse_pack(r#"{"name":"a","rules":[]}"#).is_err());
+        assert!(parse_pack(r#"{"name":"a","version":"1","rules":[1]}"#).is_err());
+
+        for (name, _) in BUILTIN_PACKS {
+            let pack = parse_pack(&load_manifest(name).unwrap()).unwrap();
+            assert_eq!(pack.name, name);
+        }
+        assert!(load_manifest("no-such-pack").is_err());
+    }
+}
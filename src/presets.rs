@@ -0,0 +1,384 @@
+use std::{ops::Range, sync::LazyLock};
+
+use regex::Regex;
+
+///Built-in rule packs which can be enabled by '--preset'
+pub const PRESETS: [&str; 7] = [
+    "tv",
+    "plex-movie",
+    "plex-tv",
+    "ebook",
+    "cjk",
+    "punctuation",
+    "strip-ids",
+];
+
+///Template of preset 'ebook',values normalized by rules
+pub const EBOOK_TEMPLATE: &str = "{book.author} - {book.title} ({book.year}).{ext}";
+
+///Places of canonical episode tags in stems
+pub const EPISODE_AT: [&str; 3] = ["keep", "start", "end"];
+
+///S01E02,s1.e2 or S01E02E03
+static SXXEXX: LazyLock<Regex> =
+    LazyLock::new(|| episode_regex(r"s(\d{1,2})[ ._-]?e(\d{1,3})(?:[ ._-]?e(\d{1,3}))?"));
+
+///1x02
+static NXNN: LazyLock<Regex> = LazyLock::new(|| episode_regex(r"(\d{1,2})x(\d{2,3})"));
+
+///Season 1 Episode 2
+static SEASON_EPISODE: LazyLock<Regex> =
+    LazyLock::new(|| episode_regex(r"season[ ._-]*(\d{1,2})[ ._-]*episode[ ._-]*(\d{1,3})"));
+
+///Offset from full-width ASCII variants '！'..'～' to '!'..'~'
+const FULL_WIDTH_OFFSET: u32 = 0xFEE0;
+
+///Words of release names which end titles,such as resolutions,sources and codecs
+static RELEASE_WORD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"(?i)^(\d{3,4}[pi]|4k|uhd|hdr|10bit|x26[45]|h\.?26[45]|hevc|avc|xvid|divx|",
+        r"web(-?dl|-?rip)?|blu-?ray|bdrip|brrip|dvdrip|hdtv|hdrip|remux|aac|ac3|dts|",
+        r"proper|repack|extended|unrated)$"
+    ))
+    .unwrap()
+});
+
+///2010 or (2010)
+static YEAR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\(?((?:19|20)\d{2})\)?$").unwrap());
+
+///Trailing UUID with or without dashes such as "_f81d4fae-7dec-11d0-a765-00a0c91e6bf6",the char
+///before it in group 1
+static TRAILING_UUID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(^|[^0-9a-z])[\[(]?[0-9a-f]{8}(?:-?[0-9a-f]{4}){3}-?[0-9a-f]{12}[\])]?$")
+        .unwrap()
+});
+
+///Trailing alphanumeric token after a separator such as "_aB3xK9qZ" or " (d41d8cd98f00)",the
+///token in group 1
+static TRAILING_TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[ ._~-][\[(]?([0-9A-Za-z]+)[\])]?$").unwrap());
+
+///Separators left before stripped ids
+const ID_SEPS: [char; 5] = [' ', '.', '_', '~', '-'];
+
+///Least length of hex hashes such as short commit ids
+const MIN_HASH_LEN: usize = 12;
+
+///Least length of random suffixes
+const MIN_RANDOM_LEN: usize = 8;
+
+///Least Shannon entropy in bits per char of random suffixes
+const MIN_RANDOM_ENTROPY: f64 = 2.75;
+
+///Least transitions between letters and digits of random suffixes
+const MIN_RANDOM_TRANSITIONS: usize = 3;
+
+///Bracketed groups such as "[1080p]" or "{tags}"
+static BRACKETED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[[^\]]*\]|\{[^}]*\}").unwrap());
+
+///Case insensitive pattern not joined to letters or digits,the whole pattern in group 1 and
+///season,episode and the optional next episode in groups 2,3 and 4
+fn episode_regex(pattern: &str) -> Regex {
+    Regex::new(&format!(
+        r"(?i)(?:^|[^\p{{L}}\d])({})(?:$|[^\p{{L}}\d])",
+        pattern
+    ))
+    .unwrap()
+}
+
+///Find the first TV episode pattern in a stem,return its range and canonical tag such as S01E02
+pub fn find_episode(stem: &str) -> Option<(Range<usize>, String)> {
+    [&*SXXEXX, &*NXNN, &*SEASON_EPISODE]
+        .iter()
+        .filter_map(|re| re.captures(stem))
+        .min_by_key(|caps| caps.get(1).map(|m| m.start()))
+        .and_then(|caps| {
+            let num = |i| caps.get(i).and_then(|m| m.as_str().parse::<u32>().ok());
+            let mut tag = format!("S{:02}E{:02}", num(2)?, num(3)?);
+            if let Some(e) = num(4) {
+                tag.push_str(&format!("E{:02}", e));
+            }
+            Some((caps.get(1)?.range(), tag))
+        })
+}
+
+///Normalize the TV episode pattern of a stem to a canonical tag,kept in place or moved to the
+///start or end of the stem joined by sep
+pub fn tv_episode(stem: &str, at: &str, sep: &str) -> String {
+    let Some((range, tag)) = find_episode(stem) else {
+        return stem.to_owned();
+    };
+    let (head, tail) = (&stem[..range.start], &stem[range.end..]);
+
+    match at {
+        "start" => format!("{}{}{}{}", tag, sep, head, tail),
+        "end" => format!("{}{}{}{}", head, tail, sep, tag),
+        _ => format!("{}{}{}", head, tag, tail),
+    }
+}
+
+///Words of a release name up to the first release word,bracketed groups dropped
+fn title_words(s: &str) -> Vec<String> {
+    BRACKETED
+        .replace_all(s, " ")
+        .split([' ', '.', '_'])
+        .filter(|w| !w.is_empty() && !w.chars().all(|c| c == '-'))
+        .take_while(|w| !RELEASE_WORD.is_match(w))
+        .map(str::to_owned)
+        .collect()
+}
+
+///Directories and stem of a movie as "Title (Year)/Title (Year)",None without a year after
+///the title
+fn plex_movie(stem: &str) -> Option<(Vec<String>, String)> {
+    let words = title_words(stem);
+    let i = words
+        .iter()
+        .rposition(|w| YEAR.is_match(w))
+        .filter(|i| *i > 0)?;
+    let year = &YEAR.captures(&words[i])?[1];
+    let name = format!("{} ({})", words[..i].join(" "), year);
+
+    Some((vec![name.clone()], name))
+}
+
+///Directories and stem of an episode as "Show/Season 01/Show - S01E02 - Episode",None without
+///an episode pattern after the show
+fn plex_tv(stem: &str) -> Option<(Vec<String>, String)> {
+    let (range, tag) = find_episode(stem)?;
+    let show: Vec<_> = title_words(&stem[..range.start])
+        .into_iter()
+        .map(|w| match YEAR.captures(&w) {
+            Some(caps) => format!("({})", &caps[1]),
+            None => w,
+        })
+        .collect();
+    if show.is_empty() {
+        return None;
+    }
+    let show = show.join(" ");
+    let season: u32 = tag[1..3].parse().ok()?;
+    let episode: Vec<_> = title_words(&stem[range.end..])
+        .into_iter()
+        .take_while(|w| !YEAR.is_match(w))
+        .collect();
+
+    let name = match episode.is_empty() {
+        true => format!("{} - {}", show, tag),
+        false => format!("{} - {} - {}", show, tag, episode.join(" ")),
+    };
+    Some((vec![show, format!("Season {:02}", season)], name))
+}
+
+///Convert full-width ASCII variants such as 'Ａ','１' and '，' and ideographic spaces into their
+///half-width chars,full-width solidus and reverse solidus kept as they would be path separators
+pub fn half_width(stem: &str) -> String {
+    stem.chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{FF0F}' | '\u{FF3C}' => c,
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - FULL_WIDTH_OFFSET).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+///Shannon entropy in bits per char of a string
+fn entropy(s: &str) -> f64 {
+    let mut counts = std::collections::HashMap::new();
+    s.chars()
+        .for_each(|c| *counts.entry(c).or_insert(0usize) += 1);
+    let len = s.chars().count() as f64;
+
+    counts
+        .values()
+        .map(|&n| n as f64 / len)
+        .map(|p| -p * p.log2())
+        .sum()
+}
+
+///Check a token looks like a hex hash or a random suffix rather than a word,a number or a tag
+///such as "1080p","x264","S01E02" or "H264AAC51"
+fn is_id(token: &str) -> bool {
+    let digits = token.chars().filter(char::is_ascii_digit).count();
+    if digits == 0 || digits == token.len() {
+        return false;
+    }
+    if token.len() >= MIN_HASH_LEN && token.chars().all(|c| c.is_ascii_hexdigit()) {
+        return true;
+    }
+    let transitions = token
+        .as_bytes()
+        .windows(2)
+        .filter(|w| w[0].is_ascii_digit() != w[1].is_ascii_digit())
+        .count();
+
+    token.len() >= MIN_RANDOM_LEN
+        && transitions >= MIN_RANDOM_TRANSITIONS
+        && token.chars().any(|c| c.is_ascii_lowercase())
+        && entropy(token) >= MIN_RANDOM_ENTROPY
+}
+
+///Remove trailing UUIDs,hex hashes and random download suffixes of a stem with the separators
+///before them,the stem kept if nothing else would be left
+pub fn ids_stripped(stem: &str) -> String {
+    let mut out = stem.to_owned();
+    loop {
+        let start = match TRAILING_UUID.captures(&out) {
+            Some(caps) => caps.get(1).map(|m| m.end()),
+            None => TRAILING_TOKEN
+                .captures(&out)
+                .filter(|caps| is_id(&caps[1]))
+                .and_then(|caps| caps.get(0))
+                .map(|m| m.start()),
+        };
+        let Some(rest) = start.map(|i| out[..i].trim_end_matches(ID_SEPS)) else {
+            break;
+        };
+        if rest.is_empty() {
+            break;
+        }
+        out = rest.to_owned();
+    }
+
+    out
+}
+
+///Convert typographic quotes,dashes,ellipses and non-breaking or sized spaces into their plain
+///ASCII chars
+pub fn plain_punctuation(stem: &str) -> String {
+    let mut out = String::with_capacity(stem.len());
+    for c in stem.chars() {
+        match c {
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' | '«' | '»' => {
+                out.push('"')
+            }
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' | '‹' | '›' => {
+                out.push('\'')
+            }
+            '\u{2010}'..='\u{2015}' | '\u{2212}' => out.push('-'),
+            '\u{2026}' => out.push_str("..."),
+            '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' => out.push(' '),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+///Directories and stem of a file in the layout of a plex preset,None if the stem does not fit
+pub fn plex_layout(preset: &str, stem: &str) -> Option<(Vec<String>, String)> {
+    match preset {
+        "plex-movie" => plex_movie(stem),
+        "plex-tv" => plex_tv(stem),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::presets::{
+        find_episode, half_width, ids_stripped, plain_punctuation, plex_layout, tv_episode,
+    };
+
+    #[test]
+    fn test_tv_episode() {
+        let tag = |s: &str| find_episode(s).map(|(_, t)| t);
+        assert_eq!(tag("Show.s1e2.720p").as_deref(), Some("S01E02"));
+        assert_eq!(tag("Show S01.E02-E03").as_deref(), Some("S01E02E03"));
+        assert_eq!(tag("Show 1x02 Pilot").as_deref(), Some("S01E02"));
+        assert_eq!(tag("Show Season 1 Episode 12").as_deref(), Some("S01E12"));
+        assert_eq!(tag("Movie 1920x1080"), None);
+        assert_eq!(tag("Plain name"), None);
+        assert_eq!(tag("Show_s01e02_x").as_deref(), Some("S01E02"));
+        assert_eq!(tag("Shows01e02"), None);
+
+        assert_eq!(
+            tv_episode("Show 1x02 Pilot", "keep", "_"),
+            "Show S01E02 Pilot"
+        );
+        assert_eq!(
+            tv_episode("Show 1x02 Pilot", "start", "_"),
+            "S01E02_Show  Pilot"
+        );
+        assert_eq!(
+            tv_episode("Show 1x02 Pilot", "end", "_"),
+            "Show  Pilot_S01E02"
+        );
+        assert_eq!(tv_episode("Plain", "end", "_"), "Plain");
+    }
+
+    #[test]
+    fn test_half_width() {
+        assert_eq!(half_width("ＡＢＣ１２３，：（）"), "ABC123,:()");
+        assert_eq!(half_width("ＡＣ／ＤＣ＼"), "AC／DC＼");
+        assert_eq!(half_width("第１話\u{3000}ｆｉｎａｌ～"), "第1話 final~");
+        assert_eq!(half_width("中文、。"), "中文、。");
+    }
+
+    #[test]
+    fn test_plain_punctuation() {
+        assert_eq!(
+            plain_punctuation("“a” ‘b’ c–d—e…\u{A0}f\u{2009}g"),
+            "\"a\" 'b' c-d-e... f g"
+        );
+        assert_eq!(plain_punctuation("«x» − y"), "\"x\" - y");
+        assert_eq!(plain_punctuation("plain-'name'"), "plain-'name'");
+    }
+
+    #[test]
+    fn test_ids_stripped() {
+        assert_eq!(
+            ids_stripped("report_f81d4fae-7dec-11d0-a765-00a0c91e6bf6"),
+            "report"
+        );
+        assert_eq!(
+            ids_stripped("report (F81D4FAE7DEC11D0A76500A0C91E6BF6)"),
+            "report"
+        );
+        assert_eq!(
+            ids_stripped("photo-d41d8cd98f00b204e9800998ecf8427e"),
+            "photo"
+        );
+        assert_eq!(ids_stripped("invoice_aB3xK9qZ_7f3e2a9c1b4d"), "invoice");
+        assert_eq!(ids_stripped("video.dQw4w9WgXcQ"), "video");
+        //words,numbers and tags kept
+        for stem in [
+            "Show.S01E02E03",
+            "clip_1920x1080",
+            "movie.H264AAC51",
+            "notes_20231231",
+            "Part2019 draft",
+            "track.x264",
+            "f81d4fae-7dec-11d0-a765-00a0c91e6bf6",
+        ] {
+            assert_eq!(ids_stripped(stem), stem);
+        }
+    }
+
+    #[test]
+    fn test_plex_layout() {
+        let layout = |p, s| plex_layout(p, s).map(|(d, n)| format!("{}/{}", d.join("/"), n));
+        assert_eq!(
+            layout("plex-movie", "The.Matrix.1999.1080p.BluRay.x264").as_deref(),
+            Some("The Matrix (1999)/The Matrix (1999)")
+        );
+        assert_eq!(
+            layout("plex-movie", "[Group] Blade Runner 2049 (2017) [2160p]").as_deref(),
+            Some("Blade Runner 2049 (2017)/Blade Runner 2049 (2017)")
+        );
+        assert_eq!(layout("plex-movie", "2012"), None);
+        assert_eq!(layout("plex-movie", "No year"), None);
+
+        assert_eq!(
+            layout("plex-tv", "Mr.Robot.S01E02.Ones.and.Zer0es.720p.WEB-DL").as_deref(),
+            Some("Mr Robot/Season 01/Mr Robot - S01E02 - Ones and Zer0es")
+        );
+        assert_eq!(
+            layout("plex-tv", "Doctor_Who_2005_2x03_720p").as_deref(),
+            Some("Doctor Who (2005)/Season 02/Doctor Who (2005) - S02E03")
+        );
+        assert_eq!(layout("plex-tv", "S01E02 Pilot"), None);
+        assert_eq!(layout("tv", "Show S01E02"), None);
+    }
+}
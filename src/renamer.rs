@@ -0,0 +1,421 @@
+use std::{
+    ffi::OsStr,
+    fmt::Write,
+    io,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+use regex::Regex;
+use tracing::warn;
+
+use crate::{
+    backend::{FsBackend, FsMeta, RealFs},
+    magic::fixed_ext,
+    os2string, plan_base, split_ext,
+    template::{Provider, Template},
+    trace_entry,
+    utils::{violations, Validator},
+    RuleSet,
+};
+
+///A planned rename of a file or directory,not applied if the target violates any validator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameAction {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub violations: Vec<String>,
+}
+
+impl RenameAction {
+    ///Check the action changes the name and passes all validators
+    pub fn is_effective(&self) -> bool {
+        self.from != self.to && self.violations.is_empty()
+    }
+}
+
+///Planned rename actions in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenamePlan {
+    actions: Vec<RenameAction>,
+}
+
+impl RenamePlan {
+    ///Apply effective actions in order through the filesystem,one result for every action
+    pub fn apply<F: FsBackend + ?Sized>(&self, fs: &mut F) -> Vec<io::Result<()>> {
+        self.actions
+            .iter()
+            .map(|a| match a.is_effective() {
+                true => fs.rename(&a.from, &a.to),
+                false => Ok(()),
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<RenameAction>> for RenamePlan {
+    fn from(actions: Vec<RenameAction>) -> Self {
+        Self { actions }
+    }
+}
+
+impl Deref for RenamePlan {
+    type Target = [RenameAction];
+
+    fn deref(&self) -> &Self::Target {
+        &self.actions
+    }
+}
+
+impl IntoIterator for RenamePlan {
+    type Item = RenameAction;
+    type IntoIter = std::vec::IntoIter<RenameAction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.actions.into_iter()
+    }
+}
+
+///Stems starting with a date such as 2024-01-31,2024_01_31 or 20240131
+static DATED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(19|20)\d{2}[-_.]?(0[1-9]|1[0-2])[-_.]?(0[1-9]|[12]\d|3[01])").unwrap()
+});
+
+///Date from file metadata prefixed to stems of regular files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatePrefix {
+    ///strftime format of the date
+    pub format: String,
+    ///date of creation instead of modification
+    pub created: bool,
+}
+
+impl DatePrefix {
+    ///Check the strftime format
+    pub fn new(format: &str, created: bool) -> Result<Self> {
+        if StrftimeItems::new(format).any(|i| i == Item::Error) {
+            return Err(anyhow!("Invalid date format:{:?}", format));
+        }
+
+        Ok(Self {
+            format: format.to_owned(),
+            created,
+        })
+    }
+
+    ///Prepend the date to the stem of name,kept if the stem already starts with a date or the
+    ///filesystem has no such date
+    pub fn prefixed(&self, name: &str, meta: &FsMeta, sep: &str) -> Result<String> {
+        let time = match self.created {
+            true => meta.created,
+            false => meta.modified,
+        };
+        let Some(time) = time else {
+            return Ok(name.to_owned());
+        };
+        if DATED.is_match(name) {
+            return Ok(name.to_owned());
+        }
+
+        let mut date = String::new();
+        write!(
+            date,
+            "{}",
+            DateTime::<Local>::from(time).format(&self.format)
+        )?;
+        if date.is_empty() || name.starts_with(&date) {
+            return Ok(name.to_owned());
+        }
+
+        Ok(format!("{}{}{}", date, sep, name))
+    }
+}
+
+///Plan renames by rules and validators,nothing printed and nothing renamed
+#[derive(Debug, Default)]
+pub struct Renamer {
+    pub rules: RuleSet,
+    pub validators: Vec<Box<dyn Validator>>,
+    pub date_prefix: Option<DatePrefix>,
+    pub template: Option<Template>,
+    pub providers: Vec<Box<dyn Provider>>,
+    ///correct extensions of regular files by types detected from contents
+    pub fix_ext: bool,
+}
+
+impl Renamer {
+    pub fn new(rules: RuleSet) -> Self {
+        Self {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    ///Check planned targets by validators
+    pub fn with_validators(self, validators: Vec<Box<dyn Validator>>) -> Self {
+        Self { validators, ..self }
+    }
+
+    ///Prepend dates from metadata to names of regular files
+    pub fn with_date_prefix(self, date_prefix: Option<DatePrefix>) -> Self {
+        Self {
+            date_prefix,
+            ..self
+        }
+    }
+
+    ///Render names of regular files by template with values from providers
+    pub fn with_template(
+        self,
+        template: Option<Template>,
+        providers: Vec<Box<dyn Provider>>,
+    ) -> Self {
+        Self {
+            template,
+            providers,
+            ..self
+        }
+    }
+
+    ///Correct wrong or missing extensions of regular files by types detected from contents
+    pub fn with_fix_ext(self, fix_ext: bool) -> Self {
+        Self { fix_ext, ..self }
+    }
+
+    ///Plan the rename of a file or directory on local filesystem by rules
+    pub fn plan_path(&self, path: &Path) -> Result<RenameAction> {
+        self.plan_in(&RealFs, path)
+    }
+
+    ///Plan the rename of a file or directory on filesystem by rules
+    pub fn plan_in<F: FsBackend + ?Sized>(&self, fs: &F, path: &Path) -> Result<RenameAction> {
+        let meta = fs.metadata(path).ok();
+        let is_file = meta.is_some_and(|m| m.is_file());
+        let action = self.plan_entry(path, meta.as_ref())?;
+        if !is_file || (self.template.is_none() && self.date_prefix.is_none() && !self.fix_ext) {
+            return Ok(action);
+        }
+
+        let mut target = os2string(action.to.file_name())?;
+        if self.fix_ext {
+            target = fixed_ext(path, &target);
+        }
+        if let Some(template) = &self.template {
+            target = self.rendered(template, path, target);
+        }
+        if let (Some(dp), Some(meta)) = (&self.date_prefix, meta) {
+            target = dp.prefixed(&target, &meta, &self.rules.sep)?;
+        }
+
+        Ok(self.action(path, &target))
+    }
+
+    ///Read values of placeholders of the template ahead for paths in parallel
+    pub fn prefetch(&self, paths: &[PathBuf]) {
+        if let Some(template) = &self.template {
+            self.providers
+                .iter()
+                .filter(|p| template.uses(p.namespace()))
+                .for_each(|p| p.prefetch(paths));
+        }
+    }
+
+    ///Render the name of a file by template,the normalized name kept if any value is missing or
+    ///rendering fails
+    fn rendered(&self, template: &Template, path: &Path, name: String) -> String {
+        let (stem, ext) = split_ext(&name, &self.rules.compound_exts);
+
+        let sep = &self.rules.sep;
+        let clean = |v: &str| {
+            let v = v.replace(['/', '\\'], sep);
+            match template.is_sanitized() {
+                true => plan_base(&v, false, &self.rules).unwrap_or(v),
+                false => v,
+            }
+        };
+
+        match template.render(path, stem, ext, &self.providers, &clean) {
+            Ok(Some(rendered)) => rendered,
+            Ok(None) => name,
+            Err(err) => {
+                warn!("Keep normalized name of {:?},{}", path, err);
+                name
+            }
+        }
+    }
+
+    ///Plan the rename of an entry by rules,extension kept for regular file by its metadata
+    pub fn plan_entry(&self, path: &Path, meta: Option<&FsMeta>) -> Result<RenameAction> {
+        let name = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .ok_or(anyhow!("Invalid file name:{:?}", path))?;
+
+        Ok(self.action(path, &trace_entry(path, name, meta, &self.rules)?.0))
+    }
+
+    ///Rename action of a file or directory into target name checked by validators
+    pub fn action(&self, path: &Path, target: &str) -> RenameAction {
+        let to = path.with_file_name(target);
+        let violations = match to == path {
+            true => Vec::new(),
+            false => violations(
+                &self.validators,
+                &to.file_name().unwrap_or_default().to_string_lossy(),
+            ),
+        };
+
+        RenameAction {
+            from: path.to_path_buf(),
+            to,
+            violations,
+        }
+    }
+
+    ///Plan renames of files or directories in order
+    pub fn plan<P: AsRef<Path>>(&self, paths: &[P]) -> Result<RenamePlan> {
+        paths
+            .iter()
+            .map(|p| self.plan_path(p.as_ref()))
+            .collect::<Result<Vec<_>>>()
+            .map(RenamePlan::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs, io,
+        path::{Path, PathBuf},
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::{
+        backend::{FsBackend, FsMeta, MemFs, RealFs},
+        renamer::{DatePrefix, Renamer},
+        utils::validate::MaxLength,
+        RuleSet,
+    };
+
+    #[derive(Default)]
+    struct Recorded(Vec<(PathBuf, PathBuf)>);
+
+    impl FsBackend for Recorded {
+        fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+            self.0.push((from.to_path_buf(), to.to_path_buf()));
+            Ok(())
+        }
+
+        fn metadata(&self, _path: &Path) -> io::Result<FsMeta> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+
+        fn read_dir(&self, _path: &Path) -> io::Result<Vec<PathBuf>> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn test_renamer() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["a b.txt", "ok.txt", "long name.txt"]
+            .iter()
+            .map(|n| tmp.path().join(n))
+            .collect();
+        paths.iter().for_each(|p| fs::write(p, "").unwrap());
+
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let renamer = Renamer::new(rules).with_validators(vec![Box::new(MaxLength(8))]);
+        let plan = renamer.plan(&paths).unwrap();
+        assert_eq!(plan.len(), 3);
+        assert_eq!(plan[0].to, tmp.path().join("a_b.txt"));
+        assert!(!plan[1].is_effective());
+        assert_eq!(plan[2].violations.len(), 1);
+
+        let mut fs = Recorded::default();
+        assert!(plan.apply(&mut fs).iter().all(|r| r.is_ok()));
+        assert_eq!(fs.0, vec![(paths[0].clone(), tmp.path().join("a_b.txt"))]);
+
+        assert!(plan.apply(&mut RealFs).iter().all(|r| r.is_ok()));
+        assert!(tmp.path().join("a_b.txt").exists());
+    }
+
+    #[test]
+    fn test_date_prefix() {
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        let dp = DatePrefix::new("%Y%m%d", false).unwrap();
+        let renamer = Renamer::new(rules).with_date_prefix(Some(dp));
+        assert!(DatePrefix::new("%Q", false).is_err());
+
+        //noon of 2021-03-04 in UTC,the same date in all common time zones
+        let time = UNIX_EPOCH + Duration::from_secs(1_614_859_200);
+        let mut fs = MemFs::new();
+        fs.add_file_at("/d/a b.txt", 0, Some(time));
+        fs.add_file_at("/d/2020-01-02 c.txt", 0, Some(time));
+        fs.add_file("/d/no time.txt", 0);
+        fs.add_dir("/d/sub dir");
+
+        let plan = |p: &str| renamer.plan_in(&fs, Path::new(p)).unwrap().to;
+        assert_eq!(plan("/d/a b.txt"), Path::new("/d/20210304_a_b.txt"));
+        assert_eq!(
+            plan("/d/2020-01-02 c.txt"),
+            Path::new("/d/2020-01-02_c.txt")
+        );
+        assert_eq!(plan("/d/no time.txt"), Path::new("/d/no_time.txt"));
+        assert_eq!(plan("/d/sub dir"), Path::new("/d/sub_dir"));
+    }
+
+    #[cfg(feature = "script")]
+    #[test]
+    fn test_scripted() {
+        use std::sync::Arc;
+
+        use crate::{plan_base, script::Script};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("s.rhai");
+        fs::write(
+            &path,
+            r#"fn rename(stem, ext, path, meta) {
+                if meta.is_dir { return; }
+                if ext == "bad" { throw "no"; }
+                stem.to_upper()
+            }"#,
+        )
+        .unwrap();
+        let mut rules = RuleSet {
+            sep: "_".to_owned(),
+            ..Default::default()
+        };
+        rules.add_rule(" ").unwrap();
+        rules.script = Some(Arc::new(Script::load(&path).unwrap()));
+        let renamer = Renamer::new(rules);
+        let mut fs = MemFs::new();
+        fs.add_file("/d/a b.txt", 0);
+        fs.add_file("/d/c d.bad", 0);
+        fs.add_dir("/d/sub dir");
+
+        let plan = |p: &str| renamer.plan_in(&fs, Path::new(p)).unwrap().to;
+        assert_eq!(plan("/d/a b.txt"), Path::new("/d/A_B.txt"));
+        assert_eq!(plan("/d/c d.bad"), Path::new("/d/c_d.bad"));
+        assert_eq!(plan("/d/sub dir"), Path::new("/d/sub_dir"));
+        assert_eq!(
+            plan_base("a b.txt", true, &renamer.rules).unwrap(),
+            "A_B.txt"
+        );
+    }
+}
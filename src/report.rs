@@ -0,0 +1,150 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+///Outcomes of a file in a run
+pub const OUTCOMES: [&str; 5] = ["renamed", "planned", "unchanged", "skipped", "failed"];
+
+///What happened to a file in a run,with the reason if it was skipped or failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub path: PathBuf,
+    ///one of OUTCOMES
+    pub status: &'static str,
+    pub reason: String,
+}
+
+///Outcomes of files of a run in order,the first one of a file kept
+#[derive(Debug, Default)]
+pub struct Report {
+    pub outcomes: Vec<Outcome>,
+    settled: HashSet<PathBuf>,
+}
+
+impl Report {
+    fn push(&mut self, path: &Path, status: &'static str, reason: &str) {
+        if self.settled.insert(path.to_path_buf()) {
+            self.outcomes.push(Outcome {
+                path: path.to_path_buf(),
+                status,
+                reason: reason.to_owned(),
+            });
+        }
+    }
+
+    ///Record a file skipped for the reason
+    pub fn skip(&mut self, path: &Path, reason: &str) {
+        self.push(path, "skipped", reason);
+    }
+
+    ///Record a file failed with the error
+    pub fn fail(&mut self, path: &Path, error: &str) {
+        self.push(path, "failed", error);
+    }
+
+    ///Record a file renamed,planned or unchanged unless it has been skipped already
+    pub fn settle(&mut self, path: &Path, status: &'static str) {
+        self.push(path, status, "");
+    }
+
+    ///Number of files by outcome in the order of OUTCOMES
+    pub fn counts(&self) -> Vec<(&'static str, usize)> {
+        OUTCOMES
+            .iter()
+            .map(|s| (*s, self.outcomes.iter().filter(|o| o.status == *s).count()))
+            .collect()
+    }
+
+    ///Number of files failed
+    pub fn failed(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|o| o.status == "failed")
+            .count()
+    }
+
+    ///Check any file is skipped or failed
+    pub fn has_problems(&self) -> bool {
+        self.outcomes
+            .iter()
+            .any(|o| matches!(o.status, "skipped" | "failed"))
+    }
+
+    ///Skipped and failed files with their reasons,then numbers of files by outcome
+    pub fn text(&self) -> String {
+        let mut lines: Vec<_> = self
+            .outcomes
+            .iter()
+            .filter(|o| matches!(o.status, "skipped" | "failed"))
+            .map(|o| format!("{} {:?}:{}", o.status, o.path, o.reason))
+            .collect();
+        let counts: Vec<_> = self
+            .counts()
+            .into_iter()
+            .filter(|(_, n)| *n > 0)
+            .map(|(s, n)| format!("{} {}", n, s))
+            .collect();
+        lines.push(counts.join(","));
+
+        lines.join("\n")
+    }
+
+    ///Outcomes of all files with numbers of files by outcome in JSON
+    pub fn json(&self) -> Value {
+        let outcomes: Vec<_> = self
+            .outcomes
+            .iter()
+            .map(|o| {
+                json!({
+                    "path": o.path.to_string_lossy(),
+                    "status": o.status,
+                    "reason": o.reason,
+                })
+            })
+            .collect();
+        let counts: serde_json::Map<_, _> = self
+            .counts()
+            .into_iter()
+            .map(|(s, n)| (s.to_owned(), json!(n)))
+            .collect();
+
+        json!({"outcomes": outcomes, "counts": counts})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::report::Report;
+
+    #[test]
+    fn test_report() {
+        let mut r = Report::default();
+        r.settle(Path::new("a"), "renamed");
+        r.skip(Path::new("b"), "target \"x\" collides with another rename");
+        r.settle(Path::new("b"), "unchanged");
+        r.fail(Path::new("c"), "Permission denied");
+        r.settle(Path::new("d"), "unchanged");
+        assert_eq!(r.outcomes.len(), 4);
+        assert!(r.has_problems());
+        assert_eq!(r.failed(), 1);
+        assert_eq!(
+            r.text(),
+            "skipped \"b\":target \"x\" collides with another rename\n\
+             failed \"c\":Permission denied\n\
+             1 renamed,1 unchanged,1 skipped,1 failed"
+        );
+        let v = r.json();
+        assert_eq!(v["counts"]["failed"], 1);
+        assert_eq!(v["outcomes"][1]["status"], "skipped");
+
+        let mut r = Report::default();
+        r.settle(Path::new("a"), "planned");
+        assert!(!r.has_problems());
+        assert_eq!(r.text(), "1 planned");
+    }
+}
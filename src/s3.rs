@@ -0,0 +1,231 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::DateTime;
+use serde_json::Value;
+
+use crate::backend::{FsBackend, FsMeta, MemFs};
+
+///Object keys of a bucket under a prefix as a filesystem,key 'a/b c.txt' at path '/a/b c.txt';
+///keys listed once and renames applied by moving objects by the aws CLI,which takes credentials,
+///region and the endpoint of S3-compatible storage from its own configuration
+#[derive(Debug)]
+pub struct S3Fs {
+    bucket: String,
+    prefix: String,
+    listing: MemFs,
+}
+
+impl S3Fs {
+    ///List keys of a URL such as 's3://bucket/prefix'
+    pub fn open(url: &str) -> Result<Self> {
+        let (bucket, prefix) = parse_s3_url(url)?;
+        let listed = aws(&[
+            "s3api",
+            "list-objects-v2",
+            "--bucket",
+            &bucket,
+            "--prefix",
+            &prefix,
+            "--output",
+            "json",
+        ])?;
+
+        Self::from_listing(&bucket, &prefix, &listed)
+    }
+
+    ///Keys from the JSON output of 'aws s3api list-objects-v2',folder markers ending with '/'
+    ///left out
+    pub fn from_listing(bucket: &str, prefix: &str, listed: &str) -> Result<Self> {
+        let mut listing = MemFs::new();
+        let prefix = prefix.trim_end_matches('/');
+        listing.add_dir(key_path(prefix));
+        if !listed.trim().is_empty() {
+            let v: Value = serde_json::from_str(listed)?;
+            for obj in v["Contents"].as_array().into_iter().flatten() {
+                let key = obj["Key"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Object without a key:{}", obj))?;
+                if key.ends_with('/') {
+                    continue;
+                }
+                let modified = obj["LastModified"]
+                    .as_str()
+                    .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                    .map(SystemTime::from);
+                let len = obj["Size"].as_u64().unwrap_or_default();
+                listing.add_file_at(key_path(key), len, modified);
+            }
+        }
+
+        Ok(Self {
+            bucket: bucket.to_owned(),
+            prefix: prefix.to_owned(),
+            listing,
+        })
+    }
+
+    ///Path of the prefix,the root of files to rename
+    pub fn root(&self) -> PathBuf {
+        key_path(&self.prefix)
+    }
+
+    ///Move an object to a new key by 'aws s3 mv',which copies objects larger than 5 GB in parts
+    ///before deleting the old one
+    fn move_object(&self, from: &str, to: &str) -> io::Result<()> {
+        let uri = |key: &str| format!("s3://{}/{}", self.bucket, key);
+        aws(&["s3", "mv", "--only-show-errors", &uri(from), &uri(to)])?;
+
+        Ok(())
+    }
+}
+
+impl FsBackend for S3Fs {
+    ///Move the object or all objects under a prefix,a taken target refused since copying an
+    ///object overwrites it silently
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let meta = self.listing.metadata(from)?;
+        if from == to {
+            return Ok(());
+        }
+        if self.listing.exists(to) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} exists", to),
+            ));
+        }
+        let moved = match meta.is_dir {
+            true => self
+                .listing
+                .paths()
+                .into_iter()
+                .filter(|p| {
+                    p.starts_with(from) && self.listing.metadata(p).is_ok_and(|m| m.is_file())
+                })
+                .collect(),
+            false => vec![from.to_path_buf()],
+        };
+        for p in moved {
+            let rest = p.strip_prefix(from).unwrap_or(Path::new(""));
+            let t = match rest.as_os_str().is_empty() {
+                true => to.to_path_buf(),
+                false => to.join(rest),
+            };
+            self.move_object(&path_key(&p), &path_key(&t))?;
+        }
+
+        self.listing.rename(from, to)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMeta> {
+        self.listing.metadata(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.listing.read_dir(path)
+    }
+
+    ///Prefixes exist as soon as a key has them,so only remembered
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        self.listing.create_dir_all(path)
+    }
+
+    ///Keys are compared byte by byte
+    fn case_insensitive(&self, _dir: &Path) -> Option<bool> {
+        Some(false)
+    }
+}
+
+///Bucket and key prefix of a URL such as 's3://bucket/prefix',the prefix possibly empty
+pub fn parse_s3_url(url: &str) -> Result<(String, String)> {
+    let rest = url
+        .strip_prefix("s3://")
+        .ok_or_else(|| anyhow!("S3 URL must start with 's3://':{:?}", url))?;
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        return Err(anyhow!("S3 URL must have a bucket:{:?}", url));
+    }
+
+    Ok((bucket.to_owned(), prefix.to_owned()))
+}
+
+///Path of a key
+fn key_path(key: &str) -> PathBuf {
+    Path::new("/").join(key)
+}
+
+///Key of a path
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().trim_start_matches('/').to_owned()
+}
+
+///Run the aws CLI and return its output
+fn aws(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("aws")
+        .args(args)
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to run aws:{}", e)))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "aws {} failed:{}",
+            args[1],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        backend::FsBackend,
+        s3::{parse_s3_url, S3Fs},
+    };
+
+    #[test]
+    fn test_s3_fs() {
+        assert_eq!(
+            parse_s3_url("s3://bucket/a/b/").unwrap(),
+            ("bucket".to_owned(), "a/b/".to_owned())
+        );
+        assert_eq!(
+            parse_s3_url("s3://bucket").unwrap(),
+            ("bucket".to_owned(), String::new())
+        );
+        assert!(parse_s3_url("s3:///a").is_err());
+        assert!(parse_s3_url("gs://bucket").is_err());
+
+        let fs = S3Fs::from_listing(
+            "bucket",
+            "media/",
+            r#"{"Contents":[
+                {"Key":"media/","Size":0},
+                {"Key":"media/a b.mp4","Size":7,"LastModified":"2024-05-01T10:00:00+00:00"},
+                {"Key":"media/sub/c d.mp4","Size":9}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(fs.root(), Path::new("/media"));
+        let meta = fs.metadata(Path::new("/media/a b.mp4")).unwrap();
+        assert_eq!(meta.len, 7);
+        assert!(meta.modified.is_some());
+        assert!(fs.metadata(Path::new("/media/sub")).unwrap().is_dir);
+        assert_eq!(
+            fs.files(&fs.root(), 9).unwrap(),
+            [Path::new("/media/a b.mp4"), Path::new("/media/sub/c d.mp4")]
+        );
+
+        assert_eq!(fs.case_insensitive(&fs.root()), Some(false));
+
+        let empty = S3Fs::from_listing("bucket", "", "").unwrap();
+        assert!(empty.files(&empty.root(), 1).unwrap().is_empty());
+    }
+}
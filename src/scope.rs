@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+
+///Classes of files by MIME type with their extensions,rules scoped by a class applied to files
+///of these extensions only
+pub const MIME_CLASSES: [(&str, &[&str]); 6] = [
+    (
+        "video",
+        &[
+            "mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "m4v", "mpg", "mpeg", "ts", "3gp",
+        ],
+    ),
+    (
+        "audio",
+        &[
+            "mp3", "flac", "wav", "aac", "m4a", "ogg", "opus", "wma", "aiff", "ape", "alac",
+        ],
+    ),
+    (
+        "image",
+        &[
+            "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "svg", "raw", "cr2",
+            "nef", "arw", "dng",
+        ],
+    ),
+    (
+        "text",
+        &[
+            "txt", "md", "csv", "tsv", "log", "json", "xml", "yaml", "yml", "ini", "srt",
+        ],
+    ),
+    (
+        "document",
+        &[
+            "pdf", "doc", "docx", "odt", "rtf", "xls", "xlsx", "ods", "ppt", "pptx", "odp", "epub",
+            "mobi", "djvu",
+        ],
+    ),
+    (
+        "archive",
+        &[
+            "zip", "rar", "7z", "tar", "gz", "bz2", "xz", "zst", "tar.gz", "tar.bz2", "tar.xz",
+            "tar.zst", "iso",
+        ],
+    ),
+];
+
+///Parse a scope of extensions or MIME classes separated by ',' such as 'video,srt',extensions
+///lowercased and leading '.' removed
+pub fn parse_scope(scope: &str) -> Result<String> {
+    let items = scope
+        .split(',')
+        .map(|s| s.trim().trim_start_matches('.').to_lowercase())
+        .map(|s| {
+            let valid = !s.is_empty()
+                && !s.starts_with('.')
+                && !s.ends_with('.')
+                && s.chars().all(|c| c.is_alphanumeric() || c == '.');
+            match valid {
+                true => Ok(s),
+                false => Err(anyhow!(
+                    "Scope must be extensions or MIME classes {:?} separated by ',':{:?}",
+                    MIME_CLASSES.map(|(c, _)| c),
+                    scope
+                )),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(items.join(","))
+}
+
+///Check a file of the extension is in the scope,an empty scope for all files and no extension
+///in no other scope
+pub fn in_scope(scope: &str, ext: Option<&str>) -> bool {
+    if scope.is_empty() {
+        return true;
+    }
+    let Some(ext) = ext.map(str::to_lowercase) else {
+        return false;
+    };
+
+    scope.split(',').any(|item| {
+        item == ext
+            || MIME_CLASSES
+                .iter()
+                .any(|(class, exts)| *class == item && exts.contains(&ext.as_str()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scope::{in_scope, parse_scope};
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(parse_scope("Video, .SRT").unwrap(), "video,srt");
+        assert_eq!(parse_scope("tar.gz").unwrap(), "tar.gz");
+        assert!(parse_scope("").is_err());
+        assert!(parse_scope("mp4,").is_err());
+        assert!(parse_scope("a/b").is_err());
+
+        assert!(in_scope("", None));
+        assert!(in_scope("", Some("mp4")));
+        assert!(in_scope("video", Some("MKV")));
+        assert!(in_scope("video,srt", Some("srt")));
+        assert!(!in_scope("video", Some("mp3")));
+        assert!(!in_scope("video", None));
+        assert!(in_scope("audio", Some("flac")));
+        assert!(in_scope("document", Some("pdf")));
+    }
+}
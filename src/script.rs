@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::backend::FsMeta;
+
+///Function a script must define
+pub const SCRIPT_FN: &str = "rename";
+
+///Operations a script may run for one file,so that an endless loop fails instead of hanging
+#[cfg(feature = "script")]
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+///Bytes of a string a script may build,far more than any name needs
+#[cfg(feature = "script")]
+const MAX_STRING_SIZE: usize = 1 << 16;
+
+///Items of an array or map a script may build,enough for lookup tables of names
+#[cfg(feature = "script")]
+const MAX_COLLECTION_SIZE: usize = 1 << 16;
+
+///A Rhai script rewriting stems after the rules by 'fn rename(stem,ext,path,meta)',given the
+///extension without '.' or "" and metadata as a map of 'is_dir','len','modified' and 'created'
+///in Unix seconds or (),returning the new stem or () to keep it
+#[derive(Debug)]
+pub struct Script {
+    pub path: PathBuf,
+    #[cfg(feature = "script")]
+    engine: rhai::Engine,
+    #[cfg(feature = "script")]
+    ast: rhai::AST,
+}
+
+impl Script {
+    ///Compile a script file,which must define the function
+    #[cfg(feature = "script")]
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut engine = rhai::Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE);
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| anyhow!("Failed to compile script {:?}:{}", path, e))?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == SCRIPT_FN && f.params.len() == 4)
+        {
+            return Err(anyhow!(
+                "Script {:?} must define 'fn {}(stem,ext,path,meta)'",
+                path,
+                SCRIPT_FN
+            ));
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            engine,
+            ast,
+        })
+    }
+
+    ///Compile a script file,which must define the function
+    #[cfg(not(feature = "script"))]
+    pub fn load(_path: &Path) -> Result<Self> {
+        Err(anyhow!("fdn is built without feature 'script'"))
+    }
+
+    ///New stem of a file by the script
+    #[cfg(feature = "script")]
+    pub fn stem(
+        &self,
+        stem: &str,
+        ext: Option<&str>,
+        path: &Path,
+        meta: Option<&FsMeta>,
+    ) -> Result<String> {
+        use rhai::{Dynamic, Map, Scope};
+
+        let secs = |t: Option<std::time::SystemTime>| {
+            t.and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(Dynamic::UNIT, |d| Dynamic::from(d.as_secs() as i64))
+        };
+        let mut m = Map::new();
+        m.insert("is_dir".into(), meta.is_some_and(|m| m.is_dir).into());
+        m.insert("len".into(), (meta.map_or(0, |m| m.len) as i64).into());
+        m.insert("modified".into(), secs(meta.and_then(|m| m.modified)));
+        m.insert("created".into(), secs(meta.and_then(|m| m.created)));
+
+        let args = (
+            stem.to_owned(),
+            ext.unwrap_or_default().to_owned(),
+            path.to_string_lossy().into_owned(),
+            m,
+        );
+        let rlt: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, SCRIPT_FN, args)
+            .map_err(|e| anyhow!("Script {:?} failed:{}", self.path, e))?;
+        if rlt.is_unit() {
+            return Ok(stem.to_owned());
+        }
+        let new = rlt.into_string().map_err(|t| {
+            anyhow!(
+                "Script {:?} must return a string or (),not {}",
+                self.path,
+                t
+            )
+        })?;
+        if new.is_empty() || new.contains(['/', '\\']) {
+            return Err(anyhow!(
+                "Script {:?} returned an invalid stem:{:?}",
+                self.path,
+                new
+            ));
+        }
+
+        Ok(new)
+    }
+
+    ///New stem of a file by the script
+    #[cfg(not(feature = "script"))]
+    pub fn stem(
+        &self,
+        stem: &str,
+        _ext: Option<&str>,
+        _path: &Path,
+        _meta: Option<&FsMeta>,
+    ) -> Result<String> {
+        Ok(stem.to_owned())
+    }
+}
+
+#[cfg(all(test, feature = "script"))]
+mod tests {
+    use std::{
+        fs,
+        path::Path,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::{backend::FsMeta, script::Script};
+
+    #[test]
+    fn test_script() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("clean.rhai");
+        fs::write(
+            &path,
+            r#"
+            fn rename(stem, ext, path, meta) {
+                if ext == "jpg" && meta.len > 100 { return stem + "_big"; }
+                if stem == "loop" { loop {} }
+                if stem == "num" { return 1; }
+                if stem == "grow" { let s = "x"; loop { s += s; } }
+                if stem == "keep" { return; }
+                if path.contains("/lookup/") { return #{ a: "alpha" }[stem] ?? stem; }
+                stem.to_upper()
+            }
+            "#,
+        )
+        .unwrap();
+        let script = Script::load(&path).unwrap();
+        let meta = FsMeta {
+            is_dir: false,
+            len: 200,
+            modified: Some(UNIX_EPOCH + Duration::from_secs(60)),
+            created: None,
+        };
+        let stem =
+            |s: &str, ext: Option<&str>, p: &str| script.stem(s, ext, Path::new(p), Some(&meta));
+        assert_eq!(stem("a", Some("jpg"), "/d/a.jpg").unwrap(), "a_big");
+        assert_eq!(stem("a", None, "/d/a").unwrap(), "A");
+        assert_eq!(stem("a", None, "/lookup/a").unwrap(), "alpha");
+        assert_eq!(stem("keep", None, "/d/keep").unwrap(), "keep");
+        assert!(stem("num", None, "/d/num").is_err());
+        assert!(stem("loop", None, "/d/loop").is_err());
+        assert!(stem("grow", None, "/d/grow").is_err());
+
+        fs::write(&path, "fn other(x) { x }").unwrap();
+        assert!(Script::load(&path).is_err());
+        fs::write(&path, "fn rename(").unwrap();
+        assert!(Script::load(&path).is_err());
+    }
+}
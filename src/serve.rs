@@ -0,0 +1,256 @@
+use std::io::{self, BufRead, Write};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::url_decoded;
+
+///Default address of 'fdn serve'
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:7777";
+
+///Largest body of a request accepted
+const MAX_BODY: usize = 1 << 20;
+
+///An HTTP request of the REST API
+#[derive(Debug, Default, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    ///decoded query parameters in order,repeated keys kept
+    pub query: Vec<(String, String)>,
+    ///headers with lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    ///First query parameter of a key
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    ///All query parameters of a key in order
+    pub fn params(&self, key: &str) -> Vec<String> {
+        self.query
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+            .collect()
+    }
+
+    ///Value of a header by its name in any case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    ///Check the request carries the bearer token,any request allowed if no token is set
+    pub fn authorized(&self, token: Option<&str>) -> bool {
+        match token {
+            Some(token) => self.header("authorization") == Some(&format!("Bearer {}", token)),
+            None => true,
+        }
+    }
+
+    ///Status and reason refusing a request a web page could forge:one from a browser by its
+    ///'Origin',a POST whose body is not 'application/json' or,without a token,one whose 'Host'
+    ///is not loopback as after DNS rebinding
+    pub fn forged(&self, token: Option<&str>) -> Option<(u16, String)> {
+        if let Some(origin) = self.header("origin") {
+            return Some((
+                403,
+                format!("Requests from origin {:?} are refused", origin),
+            ));
+        }
+        let host = self.header("host").unwrap_or_default();
+        if token.is_none() && !loopback_host(host) {
+            return Some((403, format!("Host {:?} is not a loopback address", host)));
+        }
+        let json = self
+            .header("content-type")
+            .and_then(|t| t.split(';').next())
+            .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/json"));
+        if self.method == "POST" && !json {
+            return Some((
+                415,
+                "POST needs 'Content-Type: application/json'".to_owned(),
+            ));
+        }
+
+        None
+    }
+}
+
+///Check a 'Host' header names a loopback address such as 'localhost:7777' or '[::1]'
+fn loopback_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+
+    name.eq_ignore_ascii_case("localhost")
+        || name
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+///Read one HTTP/1.1 request,the body by its 'Content-Length'
+pub fn read_request(mut reader: impl BufRead) -> Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(anyhow!("Invalid request line:{:?}", line.trim_end()));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut req = Request {
+        method: method.to_owned(),
+        path: url_decoded(path),
+        query: parse_query(query),
+        ..Default::default()
+    };
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid header:{:?}", line))?;
+        req.headers
+            .push((name.trim().to_lowercase(), value.trim().to_owned()));
+    }
+    let len: usize = match req.header("content-length") {
+        Some(l) => l
+            .parse()
+            .map_err(|_| anyhow!("Invalid Content-Length:{:?}", l))?,
+        None => 0,
+    };
+    if len > MAX_BODY {
+        return Err(anyhow!("Request body is larger than {} bytes", MAX_BODY));
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    req.body = String::from_utf8(body).map_err(|_| anyhow!("Request body is not UTF-8"))?;
+
+    Ok(req)
+}
+
+///Decoded parameters of a query string such as 'path=a%20b&arg=-d&arg=2','+' read as a space
+pub fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (k, v) = p.split_once('=').unwrap_or((p, ""));
+            let decoded = |s: &str| url_decoded(&s.replace('+', " "));
+            (decoded(k), decoded(v))
+        })
+        .collect()
+}
+
+///Write a JSON response and close the connection
+pub fn write_response(mut writer: impl Write, status: u16, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    )?;
+
+    writer.flush()
+}
+
+///Reason phrase of a status code
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        415 => "Unsupported Media Type",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::serve::{parse_query, read_request, write_response};
+
+    #[test]
+    fn test_serve_http() {
+        let raw = "POST /renames?path=%2Ftmp%2Fa+b&arg=-d&arg=2 HTTP/1.1\r\nHost: x\r\n\
+                   Authorization: Bearer s3cret\r\nContent-Length: 7\r\n\r\n{\"a\":1}tail";
+        let req = read_request(raw.as_bytes()).unwrap();
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/renames");
+        assert_eq!(req.param("path"), Some("/tmp/a b"));
+        assert_eq!(req.params("arg"), vec!["-d", "2"]);
+        assert_eq!(req.header("Host"), Some("x"));
+        assert_eq!(req.body, "{\"a\":1}");
+        assert!(req.authorized(Some("s3cret")));
+        assert!(!req.authorized(Some("other")));
+        assert!(req.authorized(None));
+        assert_eq!(req.forged(Some("s3cret")).unwrap().0, 415);
+
+        let forged = |headers: &str| {
+            let raw = format!("POST /undo HTTP/1.1\r\n{}\r\n", headers);
+            read_request(raw.as_bytes())
+                .unwrap()
+                .forged(None)
+                .map(|(s, _)| s)
+        };
+        let json = "Content-Type: application/json; charset=utf-8\r\n";
+        assert_eq!(forged(&format!("Host: 127.0.0.1:7777\r\n{}", json)), None);
+        assert_eq!(forged(&format!("Host: [::1]:7777\r\n{}", json)), None);
+        assert_eq!(forged(&format!("Host: localhost\r\n{}", json)), None);
+        assert_eq!(
+            forged(&format!("Host: evil.com:7777\r\n{}", json)),
+            Some(403)
+        );
+        assert_eq!(forged(json), Some(403));
+        assert_eq!(
+            forged(&format!(
+                "Host: localhost\r\nOrigin: http://evil.com\r\n{}",
+                json
+            )),
+            Some(403)
+        );
+        assert_eq!(
+            forged("Host: localhost\r\nContent-Type: text/plain\r\n"),
+            Some(415)
+        );
+
+        assert!(read_request(&b"garbage\r\n\r\n"[..]).is_err());
+        assert!(read_request(&b"GET / HTTP/1.1\r\nContent-Length: 9\r\n\r\nshort"[..]).is_err());
+        assert_eq!(
+            parse_query("k&v=%E4%B8%AD"),
+            vec![("k".into(), "".into()), ("v".into(), "中".into())]
+        );
+
+        let mut out = Vec::new();
+        write_response(&mut out, 404, &json!({"error": "no"})).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(out.ends_with("Content-Length: 14\r\nConnection: close\r\n\r\n{\"error\":\"no\"}"));
+    }
+}
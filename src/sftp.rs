@@ -0,0 +1,309 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::backend::{FsBackend, FsMeta, MemFs};
+
+///Files of a remote directory as a filesystem,listed once and renamed over the SFTP protocol by
+///the sftp client in batch mode,which takes keys,known hosts and ssh config from its own setup
+#[derive(Debug)]
+pub struct SftpFs {
+    ///'user@host' or 'host'
+    dest: String,
+    port: Option<u16>,
+    root: PathBuf,
+    listing: MemFs,
+}
+
+impl SftpFs {
+    ///List files of a URL such as 'sftp://user@host:22/path' down to the depth where 1 is the
+    ///remote directory itself
+    pub fn open(url: &str, depth: usize) -> Result<Self> {
+        let (dest, port, root) = parse_sftp_url(url)?;
+        let mut fs = Self {
+            dest,
+            port,
+            root: root.clone(),
+            listing: MemFs::new(),
+        };
+        fs.listing.add_dir(&root);
+        let mut dirs = vec![root];
+        for level in 1..=depth {
+            if dirs.is_empty() {
+                break;
+            }
+            let cmds: Vec<_> = dirs
+                .iter()
+                .map(|d| format!("ls -aln {}", quoted(d)))
+                .collect();
+            let listed = fs.batch(&cmds)?;
+            dirs.clear();
+            for (path, meta) in listed.lines().filter_map(parse_ls_line) {
+                match meta.is_dir {
+                    true => {
+                        fs.listing.add_dir(&path);
+                        if level < depth {
+                            dirs.push(path);
+                        }
+                    }
+                    false => fs.listing.add_file_at(&path, meta.len, meta.modified),
+                }
+            }
+        }
+
+        Ok(fs)
+    }
+
+    ///Path of the remote directory,the root of files to rename
+    pub fn root(&self) -> PathBuf {
+        self.root.clone()
+    }
+
+    ///Run sftp commands in one batch,aborted at the first failure
+    fn batch(&self, cmds: &[String]) -> io::Result<String> {
+        let mut sftp = Command::new("sftp");
+        sftp.args(["-q", "-b", "-"]);
+        if let Some(port) = self.port {
+            sftp.args(["-P", &port.to_string()]);
+        }
+        let mut child = sftp
+            .arg(&self.dest)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to run sftp:{}", e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(cmds.join("\n").as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "sftp to {} failed:{}",
+                self.dest,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl FsBackend for SftpFs {
+    ///Rename on the server,a taken target refused since servers may overwrite it silently
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        self.listing.metadata(from)?;
+        if from == to {
+            return Ok(());
+        }
+        if self.listing.exists(to) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} exists", to),
+            ));
+        }
+        self.batch(&[format!("rename {} {}", quoted(from), quoted(to))])?;
+
+        self.listing.rename(from, to)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMeta> {
+        self.listing.metadata(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.listing.read_dir(path)
+    }
+
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        let missing: Vec<_> = path
+            .ancestors()
+            .take_while(|a| !self.listing.exists(a))
+            .collect();
+        let cmds: Vec<_> = missing
+            .iter()
+            .rev()
+            .map(|d| format!("mkdir {}", quoted(d)))
+            .collect();
+        if !cmds.is_empty() {
+            self.batch(&cmds)?;
+        }
+
+        self.listing.create_dir_all(path)
+    }
+}
+
+///Destination,port and absolute remote path of a URL such as 'sftp://user@host:22/path',the
+///path '/' if empty
+pub fn parse_sftp_url(url: &str) -> Result<(String, Option<u16>, PathBuf)> {
+    let rest = url
+        .strip_prefix("sftp://")
+        .ok_or_else(|| anyhow!("SFTP URL must start with 'sftp://':{:?}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let (dest, port) = match authority.rsplit_once(':') {
+        Some((dest, port)) => (
+            dest,
+            Some(
+                port.parse()
+                    .map_err(|_| anyhow!("SFTP URL must have a valid port:{:?}", url))?,
+            ),
+        ),
+        None => (authority, None),
+    };
+    if dest.is_empty() || dest.ends_with('@') {
+        return Err(anyhow!("SFTP URL must have a host:{:?}", url));
+    }
+    let path = match path.trim_end_matches('/') {
+        "" => "/",
+        p => p,
+    };
+
+    Ok((dest.to_owned(), port, PathBuf::from(path)))
+}
+
+///Path and metadata of an entry listed by 'ls -aln' of sftp such as
+///'-rw-r--r--    1 1000     1000         12 May  1 10:00 /d/a b.txt',entries other than files
+///and directories,'.' and '..' left out;the modification time to the minute in local time
+fn parse_ls_line(line: &str) -> Option<(PathBuf, FsMeta)> {
+    let mut rest = line.trim_start();
+    let mut fields = Vec::new();
+    for _ in 0..8 {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    let is_dir = match fields[0].chars().next()? {
+        'd' => true,
+        '-' => false,
+        _ => return None,
+    };
+    let path = Path::new(rest);
+    if !path.is_absolute() || matches!(rest.rsplit('/').next(), Some("." | "..")) {
+        return None;
+    }
+
+    let modified = ls_time(fields[5], fields[6], fields[7], Local::now().naive_local())
+        .and_then(|t| t.and_local_timezone(Local).earliest())
+        .map(SystemTime::from);
+
+    Some((
+        path.to_path_buf(),
+        FsMeta {
+            is_dir,
+            len: fields[4].parse().ok()?,
+            modified,
+            created: None,
+        },
+    ))
+}
+
+///Time of a date listed by 'ls -l' such as 'May  1 10:00' within the last year before now or
+///'Jan  3  2023'
+fn ls_time(month: &str, day: &str, time: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let date =
+        |year: i32| NaiveDate::parse_from_str(&format!("{} {} {}", year, month, day), "%Y %b %d");
+    match time.split_once(':') {
+        Some(_) => {
+            let hm = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+            let t = date(now.year()).ok()?.and_time(hm);
+            //a date later than now is of the last year
+            match t > now + Duration::days(1) {
+                true => Some(date(now.year() - 1).ok()?.and_time(hm)),
+                false => Some(t),
+            }
+        }
+        None => Some(date(time.parse().ok()?).ok()?.and_time(NaiveTime::MIN)),
+    }
+}
+
+///Path double-quoted for an sftp batch command,glob chars escaped since sftp expands them in
+///quotes too
+fn quoted(path: &Path) -> String {
+    let mut escaped = String::new();
+    for c in path.to_string_lossy().chars() {
+        if matches!(c, '\\' | '"' | '*' | '?' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use chrono::NaiveDate;
+
+    use crate::sftp::{ls_time, parse_ls_line, parse_sftp_url, quoted};
+
+    #[test]
+    fn test_sftp_fs() {
+        assert_eq!(
+            parse_sftp_url("sftp://me@host:2222/srv/media/").unwrap(),
+            (
+                "me@host".to_owned(),
+                Some(2222),
+                PathBuf::from("/srv/media")
+            )
+        );
+        assert_eq!(
+            parse_sftp_url("sftp://host").unwrap(),
+            ("host".to_owned(), None, PathBuf::from("/"))
+        );
+        assert!(parse_sftp_url("sftp://me@/a").is_err());
+        assert!(parse_sftp_url("sftp://host:port/a").is_err());
+        assert!(parse_sftp_url("ssh://host/a").is_err());
+
+        let (path, meta) =
+            parse_ls_line("-rw-r--r--    1 1000     1000           12 May  1 10:00 /d/a  b.txt")
+                .unwrap();
+        assert_eq!(path, Path::new("/d/a  b.txt"));
+        assert_eq!(meta.len, 12);
+        assert!(!meta.is_dir);
+        assert!(meta.modified.is_some());
+        let (path, meta) =
+            parse_ls_line("drwxr-xr-x    2 0        0            4096 Jan  3  2023 /d/sub")
+                .unwrap();
+        assert_eq!(path, Path::new("/d/sub"));
+        assert!(meta.is_dir);
+        assert!(parse_ls_line("drwxr-xr-x    2 0   0   4096 Jan  3  2023 /d/.").is_none());
+        assert!(parse_ls_line("drwxr-xr-x    2 0   0   4096 Jan  3  2023 /d/..").is_none());
+        assert!(parse_ls_line("lrwxrwxrwx    1 0   0      4 Jan  3  2023 /d/link").is_none());
+        assert!(parse_ls_line("sftp> ls -aln \"/d\"").is_none());
+
+        assert_eq!(quoted(Path::new(r#"/d/a "b"\c"#)), r#""/d/a \"b\"\\c""#);
+        assert_eq!(quoted(Path::new("/d/[a]*?.txt")), r#""/d/\[a\]\*\?.txt""#);
+
+        let now = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let at = |y, m, d, h, mi| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(h, mi, 0)
+                .unwrap()
+        };
+        assert_eq!(
+            ls_time("Feb", "28", "10:05", now),
+            Some(at(2024, 2, 28, 10, 5))
+        );
+        assert_eq!(
+            ls_time("May", "1", "10:00", now),
+            Some(at(2023, 5, 1, 10, 0))
+        );
+        assert_eq!(ls_time("Jan", "3", "2023", now), Some(at(2023, 1, 3, 0, 0)));
+        assert_eq!(ls_time("Foo", "3", "2023", now), None);
+    }
+}
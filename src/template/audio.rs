@@ -0,0 +1,123 @@
+use std::{collections::HashMap, ffi::OsStr, fs::File, path::Path};
+
+use anyhow::{anyhow, Result};
+use id3::{Tag, TagLike};
+
+use crate::template::Provider;
+
+///Tags of mp3(ID3),flac and ogg(Vorbis comment) files as '{tag.artist}','{tag.album}',
+///'{tag.albumartist}','{tag.title}','{tag.track}','{tag.year}' and '{tag.genre}'
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AudioTags;
+
+impl Provider for AudioTags {
+    fn namespace(&self) -> &'static str {
+        "tag"
+    }
+
+    fn values(&self, path: &Path) -> Result<HashMap<String, String>> {
+        let ext = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_lowercase);
+
+        match ext.as_deref() {
+            Some("mp3") => id3_values(path),
+            Some("flac") => {
+                let reader = claxon::FlacReader::open(path)?;
+                Ok(vorbis_values(reader.tags()))
+            }
+            Some("ogg") | Some("oga") => {
+                let reader = lewton::inside_ogg::OggStreamReader::new(File::open(path)?)
+                    .map_err(|err| anyhow!("{:?}:{}", path, err))?;
+                Ok(vorbis_values(
+                    reader
+                        .comment_hdr
+                        .comment_list
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.as_str())),
+                ))
+            }
+            _ => Ok(HashMap::new()),
+        }
+    }
+}
+
+///Values of ID3 tag,empty if the file has no tag
+fn id3_values(path: &Path) -> Result<HashMap<String, String>> {
+    let Some(tag) = id3::no_tag_ok(Tag::read_from_path(path))? else {
+        return Ok(HashMap::new());
+    };
+
+    let values = [
+        ("artist", tag.artist().map(str::to_owned)),
+        ("album", tag.album().map(str::to_owned)),
+        ("albumartist", tag.album_artist().map(str::to_owned)),
+        ("title", tag.title().map(str::to_owned)),
+        ("track", tag.track().map(|n| n.to_string())),
+        ("year", tag.year().map(|n| n.to_string())),
+        ("genre", tag.genre_parsed().map(|g| g.into_owned())),
+    ];
+
+    Ok(values
+        .into_iter()
+        .filter_map(|(k, v)| v.map(|v| (k.to_owned(), v)))
+        .collect())
+}
+
+///Values of Vorbis comments,the first one kept for repeated names
+fn vorbis_values<'a, I: Iterator<Item = (&'a str, &'a str)>>(
+    comments: I,
+) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    for (k, v) in comments {
+        let key = match k.to_uppercase().as_str() {
+            "ARTIST" => "artist",
+            "ALBUM" => "album",
+            "ALBUMARTIST" => "albumartist",
+            "TITLE" => "title",
+            "TRACKNUMBER" => "track",
+            "DATE" => "year",
+            "GENRE" => "genre",
+            _ => continue,
+        };
+        values.entry(key.to_owned()).or_insert_with(|| match key {
+            //"2024-01-31" of DATE
+            "year" => v.chars().take(4).collect(),
+            _ => v.to_owned(),
+        });
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use id3::{Tag, TagLike, Version};
+
+    use crate::template::{audio::AudioTags, Provider};
+
+    #[test]
+    fn test_audio_tags() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mp3 = tmp.path().join("a.mp3");
+        fs::write(&mp3, "").unwrap();
+        assert!(AudioTags.values(&mp3).unwrap().is_empty());
+
+        let mut tag = Tag::new();
+        tag.set_artist("Artist");
+        tag.set_track(3);
+        tag.write_to_path(&mp3, Version::Id3v24).unwrap();
+        let values = AudioTags.values(&mp3).unwrap();
+        assert_eq!(values["artist"], "Artist");
+        assert_eq!(values["track"], "3");
+        assert!(!values.contains_key("title"));
+
+        //broken flac fails without panic
+        let flac = tmp.path().join("b.flac");
+        fs::write(&flac, "not flac").unwrap();
+        assert!(AudioTags.values(&flac).is_err());
+    }
+}
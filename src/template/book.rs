@@ -0,0 +1,314 @@
+use std::{collections::HashMap, ffi::OsStr, fs, fs::File, io::Read, path::Path, sync::LazyLock};
+
+use anyhow::{anyhow, Result};
+use regex::{bytes, Regex};
+
+use crate::template::Provider;
+
+///Metadata of epub(OPF) and pdf(Info dictionary) files as '{book.title}','{book.author}' and
+///'{book.year}'
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BookMeta;
+
+impl Provider for BookMeta {
+    fn namespace(&self) -> &'static str {
+        "book"
+    }
+
+    fn values(&self, path: &Path) -> Result<HashMap<String, String>> {
+        let ext = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_lowercase);
+
+        let values = match ext.as_deref() {
+            Some("epub") => epub_values(path)?,
+            Some("pdf") => pdf_values(&fs::read(path)?),
+            _ => Vec::new(),
+        };
+
+        Ok(values
+            .into_iter()
+            .filter(|(_, v)| !v.trim().is_empty())
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect())
+    }
+}
+
+///'full-path' of the package document in META-INF/container.xml
+static ROOTFILE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<(?:\w+:)?rootfile[^>]*\sfull-path\s*=\s*["']([^"']+)["']"#).unwrap()
+});
+
+///Dublin Core elements of the package document such as <dc:title id="t">Title</dc:title>
+static DC: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<dc:(title|creator|date)(?:\s[^>]*)?>(.*?)</dc:(?:title|creator|date)>")
+        .unwrap()
+});
+
+///Values of the package document of an epub,the first one kept for repeated elements
+fn epub_values(path: &Path) -> Result<Vec<(&'static str, String)>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let read = |archive: &mut zip::ZipArchive<File>, name: &str| -> Result<String> {
+        let mut s = String::new();
+        archive.by_name(name)?.read_to_string(&mut s)?;
+        Ok(s)
+    };
+
+    let container = read(&mut archive, "META-INF/container.xml")?;
+    let opf = ROOTFILE
+        .captures(&container)
+        .map(|caps| caps[1].to_owned())
+        .ok_or(anyhow!("No package document in {:?}", path))?;
+    let opf = read(&mut archive, &opf)?;
+
+    let mut values: Vec<(&'static str, String)> = Vec::new();
+    for caps in DC.captures_iter(&opf) {
+        let key = match &caps[1] {
+            "title" => "title",
+            "creator" => "author",
+            _ => "year",
+        };
+        if values.iter().any(|(k, _)| *k == key) {
+            continue;
+        }
+        let v = xml_unescaped(caps[2].trim());
+        values.push((key, if key == "year" { year_of(&v) } else { v }));
+    }
+
+    Ok(values)
+}
+
+///Text of XML character data with entities and character references replaced
+fn xml_unescaped(s: &str) -> String {
+    static ENTITY: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"&(#x[0-9a-fA-F]+|#\d+|amp|lt|gt|quot|apos);").unwrap());
+
+    ENTITY
+        .replace_all(s, |caps: &regex::Captures| {
+            let e = &caps[1];
+            let c = match e {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => match e.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => e[1..].parse().ok(),
+                }
+                .and_then(char::from_u32),
+            };
+            c.map(String::from).unwrap_or_else(|| caps[0].to_owned())
+        })
+        .into_owned()
+}
+
+///First four digits of a date such as "2024-01-31" or "D:20240131"
+fn year_of(date: &str) -> String {
+    date.trim_start_matches("D:")
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .take(4)
+        .collect()
+}
+
+///'/Info' reference of the trailer or cross-reference stream
+static INFO_REF: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"/Info\s+(\d+)\s+(\d+)\s+R").unwrap());
+
+///Start of an object holding a dictionary,such as '12 0 obj <<'
+static OBJ_DICT: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"(?:^|\s)(\d+)\s+(\d+)\s+obj\s*<<").unwrap());
+
+///Values of the Info dictionary of a pdf,empty if it is missing or compressed
+fn pdf_values(data: &[u8]) -> Vec<(&'static str, String)> {
+    //the last trailer and the last object of the number win for incrementally updated files
+    let Some(caps) = INFO_REF.captures_iter(data).last() else {
+        return Vec::new();
+    };
+    let Some(start) = OBJ_DICT
+        .captures_iter(data)
+        .filter(|c| c[1] == caps[1] && c[2] == caps[2])
+        .last()
+        .and_then(|c| c.get(0))
+        .map(|m| m.end())
+    else {
+        return Vec::new();
+    };
+
+    [
+        ("title", "/Title"),
+        ("author", "/Author"),
+        ("year", "/CreationDate"),
+    ]
+    .into_iter()
+    .filter_map(|(key, name)| {
+        let v = pdf_string(&data[start..], name)?;
+        Some((key, if key == "year" { year_of(&v) } else { v }))
+    })
+    .collect()
+}
+
+///Value of a string entry of the dictionary at the start of data,literal such as "(Title)" or
+///hex such as "<FEFF0054>"
+fn pdf_string(data: &[u8], name: &str) -> Option<String> {
+    let end = data
+        .windows(2)
+        .position(|w| w == b">>")
+        .unwrap_or(data.len());
+    let dict = &data[..end];
+    let at = dict
+        .windows(name.len())
+        .position(|w| w == name.as_bytes())
+        .map(|i| i + name.len())?;
+    let rest = dict[at..].trim_ascii_start();
+
+    let raw = match rest.first()? {
+        b'(' => literal_bytes(&data[end - rest.len() + 1..]),
+        b'<' => {
+            let hex: Vec<u8> = rest[1..]
+                .iter()
+                .take_while(|b| **b != b'>')
+                .filter(|b| b.is_ascii_hexdigit())
+                .copied()
+                .collect();
+            hex.chunks(2)
+                .map(|c| {
+                    let s = std::str::from_utf8(c).unwrap_or("0");
+                    u8::from_str_radix(&format!("{:0<2}", s), 16).unwrap_or(0)
+                })
+                .collect()
+        }
+        _ => return None,
+    };
+
+    Some(text_string(&raw))
+}
+
+///Bytes of a literal string after its opening parenthesis,escapes and nested parentheses
+///resolved
+fn literal_bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut iter = data.iter().copied().peekable();
+    while let Some(b) = iter.next() {
+        match b {
+            b'\\' => match iter.next() {
+                Some(b'n') => out.push(b'\n'),
+                Some(b'r') => out.push(b'\r'),
+                Some(b't') => out.push(b'\t'),
+                Some(b'b') => out.push(8),
+                Some(b'f') => out.push(12),
+                Some(d @ b'0'..=b'7') => {
+                    let mut n = u32::from(d - b'0');
+                    for _ in 0..2 {
+                        match iter.peek() {
+                            Some(d @ b'0'..=b'7') => {
+                                n = n * 8 + u32::from(d - b'0');
+                                iter.next();
+                            }
+                            _ => break,
+                        }
+                    }
+                    out.push(n as u8);
+                }
+                //line continuation
+                Some(b'\n') | Some(b'\r') => {}
+                Some(c) => out.push(c),
+                None => break,
+            },
+            b'(' => {
+                depth += 1;
+                out.push(b);
+            }
+            b')' if depth == 0 => break,
+            b')' => {
+                depth -= 1;
+                out.push(b);
+            }
+            b => out.push(b),
+        }
+    }
+
+    out
+}
+
+///Text of a pdf text string,UTF-16BE with byte order mark or PDFDocEncoding read as Latin-1
+fn text_string(raw: &[u8]) -> String {
+    match raw.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => raw.iter().map(|b| char::from(*b)).collect(),
+    }
+    .trim()
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::template::{book::BookMeta, Provider};
+
+    #[test]
+    fn test_epub_meta() {
+        let tmp = tempfile::tempdir().unwrap();
+        let epub = tmp.path().join("a.epub");
+        let mut zip = ZipWriter::new(File::create(&epub).unwrap());
+        let files = [
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                r#"<package><metadata><dc:title id="t">Pride &amp; Prejudice</dc:title>
+                <dc:creator>Jane Austen</dc:creator><dc:creator>Other</dc:creator>
+                <dc:date>1813-01-28</dc:date></metadata></package>"#,
+            ),
+        ];
+        for (name, content) in files {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let values = BookMeta.values(&epub).unwrap();
+        assert_eq!(values["title"], "Pride & Prejudice");
+        assert_eq!(values["author"], "Jane Austen");
+        assert_eq!(values["year"], "1813");
+
+        let broken = tmp.path().join("b.epub");
+        std::fs::write(&broken, "not zip").unwrap();
+        assert!(BookMeta.values(&broken).is_err());
+    }
+
+    #[test]
+    fn test_pdf_meta() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pdf = tmp.path().join("a.pdf");
+        std::fs::write(
+            &pdf,
+            b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n\
+              7 0 obj\n<< /Title (Moby\\040Dick \\(1st\\)) /Author <FEFF004D0065006C00760069006C006C0065>\n\
+              /CreationDate (D:18511018120000Z) >>\nendobj\n\
+              trailer\n<< /Root 1 0 R /Info 7 0 R >>\n%%EOF\n",
+        )
+        .unwrap();
+        let values = BookMeta.values(&pdf).unwrap();
+        assert_eq!(values["title"], "Moby Dick (1st)");
+        assert_eq!(values["author"], "Melville");
+        assert_eq!(values["year"], "1851");
+
+        std::fs::write(&pdf, b"%PDF-1.4\ntrailer\n<< /Root 1 0 R >>\n").unwrap();
+        assert!(BookMeta.values(&pdf).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+use rayon::prelude::*;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::template::Provider;
+
+///Size of chunks files are streamed in
+const CHUNK: usize = 1 << 16;
+
+///XXH3 hash of file contents in hex as '{hash}',cached by path for the run
+#[derive(Debug, Default)]
+pub struct ContentHash {
+    cache: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl ContentHash {
+    ///Hash of a file from cache or contents
+    fn hash(&self, path: &Path) -> Result<String> {
+        if let Some(h) = self.cache.lock().unwrap().get(path) {
+            return Ok(h.clone());
+        }
+        let h = hash_file(path)?;
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), h.clone());
+
+        Ok(h)
+    }
+}
+
+impl Provider for ContentHash {
+    fn namespace(&self) -> &'static str {
+        "hash"
+    }
+
+    fn values(&self, path: &Path) -> Result<HashMap<String, String>> {
+        Ok(HashMap::from([(String::new(), self.hash(path)?)]))
+    }
+
+    fn prefetch(&self, paths: &[PathBuf]) {
+        let missing: Vec<_> = {
+            let cache = self.cache.lock().unwrap();
+            paths
+                .iter()
+                .filter(|p| p.is_file() && !cache.contains_key(*p))
+                .collect()
+        };
+        //failures are left to be reported when names are rendered
+        let hashed: Vec<_> = missing
+            .par_iter()
+            .filter_map(|p| hash_file(p).ok().map(|h| ((*p).clone(), h)))
+            .collect();
+        self.cache.lock().unwrap().extend(hashed);
+    }
+}
+
+///Stream a file through XXH3 and return the 64 bits hash in hex
+pub(crate) fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Xxh3::new();
+    let mut buf = vec![0; CHUNK];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+
+    Ok(format!("{:016x}", hasher.digest()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::template::{hash::ContentHash, Provider};
+
+    #[test]
+    fn test_content_hash() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+
+        let hash = ContentHash::default();
+        hash.prefetch(&[a.clone(), tmp.path().to_path_buf()]);
+        let h = hash.values(&a).unwrap()[""].clone();
+        assert_eq!(h.len(), 16);
+        assert_eq!(hash.values(&b).unwrap()[""], h);
+
+        //cached within the run
+        fs::write(&a, "changed").unwrap();
+        assert_eq!(hash.values(&a).unwrap()[""], h);
+        assert_ne!(ContentHash::default().values(&a).unwrap()[""], h);
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+
+#[cfg(feature = "audio-tags")]
+pub mod audio;
+#[cfg(feature = "ebook-meta")]
+pub mod book;
+pub mod hash;
+pub mod video;
+
+///Placeholders of every file:'{stem}' and '{ext}' of the normalized name,'{name}' the whole
+///name,'{initial}' the uppercased first char of the stem and '{year}','{month}' and '{day}' of
+///the modified time
+pub const BUILTINS: [&str; 7] = ["stem", "ext", "name", "initial", "year", "month", "day"];
+
+///Values of placeholders in a namespace,such as "artist" of "{tag.artist}",read from a file
+pub trait Provider: fmt::Debug + Send + Sync {
+    ///Namespace before the dot of placeholders
+    fn namespace(&self) -> &'static str;
+
+    ///All values of the file by key,empty if the file has none,the value of key "" put as the
+    ///bare namespace such as '{hash}'
+    fn values(&self, path: &Path) -> Result<HashMap<String, String>>;
+
+    ///Read values of files ahead in parallel,nothing by default
+    fn prefetch(&self, _paths: &[PathBuf]) {}
+}
+
+///Providers compiled in
+pub fn providers() -> Vec<Box<dyn Provider>> {
+    vec![
+        #[cfg(feature = "audio-tags")]
+        Box::new(audio::AudioTags),
+        #[cfg(feature = "ebook-meta")]
+        Box::new(book::BookMeta),
+        Box::new(hash::ContentHash::default()),
+        Box::new(video::VideoMeta),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Spec {
+    ///width of zero padded number,such as '{tag.track:02}'
+    Pad(usize),
+    ///max chars of value,such as '{hash:8}'
+    Width(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(String, Option<Spec>),
+}
+
+///A name template such as "{tag.artist} - {tag.track:02} - {tag.title}.{ext}",placeholders of
+///BUILTINS valued for every file,braces escaped by doubling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+    ///values of providers normalized by rules
+    sanitized: bool,
+}
+
+impl Template {
+    ///Parse a template
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => {
+                                return Err(anyhow!("Unclosed '{{' in template:{:?}", template))
+                            }
+                        }
+                    }
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    parts.push(parse_field(&field, template)?);
+                }
+                '}' => return Err(anyhow!("Unmatched '}}' in template:{:?}", template)),
+                c => literal.push(c),
+            }
+        }
+        parts.push(Part::Literal(literal));
+        parts.retain(|p| *p != Part::Literal(String::new()));
+
+        Ok(Self {
+            parts,
+            sanitized: false,
+        })
+    }
+
+    ///Normalize values of providers by rules before they are put into names
+    pub fn sanitized(self) -> Self {
+        Self {
+            sanitized: true,
+            ..self
+        }
+    }
+
+    ///Check values of providers are normalized by rules
+    pub fn is_sanitized(&self) -> bool {
+        self.sanitized
+    }
+
+    ///Namespaces of placeholders in the template,bare names other than BUILTINS are namespaces
+    ///too
+    fn namespaces(&self) -> Vec<&str> {
+        self.parts
+            .iter()
+            .filter_map(|p| match p {
+                Part::Field(name, _) => match name.split_once('.') {
+                    Some((ns, _)) => Some(ns),
+                    None => (!BUILTINS.contains(&name.as_str())).then_some(name.as_str()),
+                },
+                Part::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    ///Check the template has placeholders of the namespace
+    pub fn uses(&self, namespace: &str) -> bool {
+        self.namespaces().contains(&namespace)
+    }
+
+    ///Render the name of a file,None if any placeholder has no value,values of providers passed
+    ///through clean which must replace path separators
+    pub fn render(
+        &self,
+        path: &Path,
+        stem: &str,
+        ext: Option<&str>,
+        providers: &[Box<dyn Provider>],
+        clean: &dyn Fn(&str) -> String,
+    ) -> Result<Option<String>> {
+        let mut values = HashMap::new();
+        values.insert("stem".to_owned(), stem.to_owned());
+        values.insert("ext".to_owned(), ext.unwrap_or_default().to_owned());
+        let name = match ext {
+            Some(ext) => format!("{}.{}", stem, ext),
+            None => stem.to_owned(),
+        };
+        values.insert("name".to_owned(), name);
+        let initial = match stem.chars().next() {
+            Some(c) if c.is_alphanumeric() => c.to_uppercase().collect(),
+            _ => "#".to_owned(),
+        };
+        values.insert("initial".to_owned(), initial);
+        let dated = ["year", "month", "day"].iter().any(|f| {
+            self.parts
+                .iter()
+                .any(|p| matches!(p, Part::Field(n, _) if n == f))
+        });
+        if dated {
+            if let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) {
+                let date = DateTime::<Local>::from(modified);
+                values.insert("year".to_owned(), date.format("%Y").to_string());
+                values.insert("month".to_owned(), date.format("%m").to_string());
+                values.insert("day".to_owned(), date.format("%d").to_string());
+            }
+        }
+        for ns in self.namespaces() {
+            let provider = providers
+                .iter()
+                .find(|p| p.namespace() == ns)
+                .ok_or(anyhow!("No provider of '{{{}.*}}' compiled in", ns))?;
+            provider.values(path)?.into_iter().for_each(|(k, v)| {
+                match k.is_empty() {
+                    true => values.insert(ns.to_owned(), v),
+                    false => values.insert(format!("{}.{}", ns, k), v),
+                };
+            });
+        }
+
+        let mut name = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Literal(s) => name.push_str(s),
+                Part::Field(field, spec) => {
+                    let Some(v) = values.get(field).map(|v| v.trim()) else {
+                        return Ok(None);
+                    };
+                    //empty tags are missing but a file may have no extension
+                    if v.is_empty() && field != "ext" {
+                        return Ok(None);
+                    }
+                    match spec {
+                        Some(Spec::Pad(width)) => {
+                            //leading number of values such as "3/12" of track
+                            let digits: String =
+                                v.chars().take_while(|c| c.is_ascii_digit()).collect();
+                            let n: u64 = digits
+                                .parse()
+                                .map_err(|_| anyhow!("{:?} of {:?} is not a number", v, field))?;
+                            name.push_str(&format!("{:0width$}", n, width = width));
+                        }
+                        Some(Spec::Width(width)) => {
+                            let v: String = v.chars().take(*width).collect();
+                            name.push_str(&clean(&v));
+                        }
+                        None if BUILTINS.contains(&field.as_str()) => name.push_str(v),
+                        None => name.push_str(&clean(v)),
+                    }
+                }
+            }
+        }
+
+        //no extension for '.{ext}'
+        Ok(Some(name.trim_end_matches('.').to_owned()))
+    }
+}
+
+///Parse a placeholder such as "tag.track:02"
+fn parse_field(field: &str, template: &str) -> Result<Part> {
+    let (name, spec) = match field.split_once(':') {
+        Some((name, spec)) => (name, Some(spec)),
+        None => (field, None),
+    };
+    if name.is_empty() {
+        return Err(anyhow!("Empty placeholder in template:{:?}", template));
+    }
+    let spec = match spec {
+        Some(spec) => match (spec.strip_prefix('0'), spec.parse::<usize>()) {
+            (Some(width), _) if width.parse::<usize>().is_ok() => Some(Spec::Pad(width.parse()?)),
+            (None, Ok(width)) if width > 0 => Some(Spec::Width(width)),
+            _ => {
+                return Err(anyhow!(
+                    "Format of placeholder must be '0N' such as '{{tag.track:02}}' or 'N' such as \
+                     '{{hash:8}}':{:?}",
+                    field
+                ))
+            }
+        },
+        None => None,
+    };
+
+    Ok(Part::Field(name.to_owned(), spec))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::Path};
+
+    use anyhow::Result;
+
+    use crate::template::{Provider, Template};
+
+    #[derive(Debug)]
+    struct Fixed;
+
+    impl Provider for Fixed {
+        fn namespace(&self) -> &'static str {
+            "tag"
+        }
+
+        fn values(&self, path: &Path) -> Result<HashMap<String, String>> {
+            Ok(match path == Path::new("tagged.mp3") {
+                true => HashMap::from([
+                    ("artist".to_owned(), "AC/DC".to_owned()),
+                    ("track".to_owned(), "3/12".to_owned()),
+                ]),
+                false => HashMap::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_template() {
+        let providers: Vec<Box<dyn Provider>> = vec![Box::new(Fixed)];
+        let clean = |v: &str| v.replace('/', "_");
+        let t = Template::parse("{tag.artist} - {tag.track:02} {{{stem}}}.{ext}").unwrap();
+        let render = |p: &str, ext| {
+            t.render(Path::new(p), "a_b", ext, &providers, &clean)
+                .unwrap()
+        };
+        assert_eq!(
+            render("tagged.mp3", Some("mp3")).as_deref(),
+            Some("AC_DC - 03 {a_b}.mp3")
+        );
+        assert_eq!(render("untagged.mp3", Some("mp3")), None);
+
+        let t = Template::parse("{stem}.{ext}").unwrap();
+        assert_eq!(
+            t.render(Path::new("x"), "x", None, &[], &clean).unwrap(),
+            Some("x".to_owned())
+        );
+        assert!(Template::parse("{tag.track:x}").is_err());
+        assert!(Template::parse("{hash:0}").is_err());
+        let t = Template::parse("{stem}_{tag.artist:2}.{ext}").unwrap();
+        assert!(t.uses("tag") && !t.uses("stem"));
+        assert_eq!(
+            t.render(
+                Path::new("tagged.mp3"),
+                "a",
+                Some("mp3"),
+                &providers,
+                &clean
+            )
+            .unwrap(),
+            Some("a_AC.mp3".to_owned())
+        );
+        let t = Template::parse("{initial}/{name}").unwrap();
+        assert!(!t.uses("name"));
+        assert_eq!(
+            t.render(Path::new("x"), "song", Some("mp3"), &[], &clean)
+                .unwrap()
+                .as_deref(),
+            Some("S/song.mp3")
+        );
+        assert_eq!(
+            t.render(Path::new("x"), "_x", None, &[], &clean)
+                .unwrap()
+                .as_deref(),
+            Some("#/_x")
+        );
+        let t = Template::parse("{year}/{month}/{stem}").unwrap();
+        assert_eq!(
+            t.render(Path::new("missing"), "a", None, &[], &clean)
+                .unwrap(),
+            None
+        );
+        assert!(Template::parse("a}").is_err());
+        assert!(Template::parse("{stem").is_err());
+        assert!(Template::parse("{video.height}")
+            .unwrap()
+            .render(Path::new("x"), "x", None, &providers, &clean)
+            .is_err());
+    }
+}
@@ -0,0 +1,474 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::template::Provider;
+
+///Max size of metadata read into memory,such as 'moov' box of mp4 or 'Tracks' of matroska
+const MAX_META_SIZE: u64 = 64 * 1024 * 1024;
+
+///Stream data of mp4/mov and matroska/webm files as '{video.width}','{video.height}',
+///'{video.resolution}' such as 1080p,'{video.codec}','{video.duration}' such as 1h32m05s and
+///'{video.seconds}'
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VideoMeta;
+
+impl Provider for VideoMeta {
+    fn namespace(&self) -> &'static str {
+        "video"
+    }
+
+    fn values(&self, path: &Path) -> Result<HashMap<String, String>> {
+        let ext = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_lowercase);
+        let info = match ext.as_deref() {
+            Some("mp4") | Some("m4v") | Some("mov") => mp4_info(&mut File::open(path)?)?,
+            Some("mkv") | Some("webm") => mkv_info(&mut File::open(path)?)?,
+            _ => return Ok(HashMap::new()),
+        };
+
+        Ok(info.values())
+    }
+}
+
+///Stream data of the first video track
+#[derive(Debug, Default, Clone, PartialEq)]
+struct VideoInfo {
+    width: Option<u64>,
+    height: Option<u64>,
+    codec: Option<String>,
+    seconds: Option<f64>,
+}
+
+impl VideoInfo {
+    fn values(&self) -> HashMap<String, String> {
+        let mut values = HashMap::new();
+        if let Some(w) = self.width {
+            values.insert("width".to_owned(), w.to_string());
+        }
+        if let Some(h) = self.height {
+            values.insert("height".to_owned(), h.to_string());
+            values.insert("resolution".to_owned(), format!("{}p", h));
+        }
+        if let Some(c) = &self.codec {
+            values.insert("codec".to_owned(), codec_name(c));
+        }
+        if let Some(s) = self.seconds {
+            let s = s.round() as u64;
+            let d = match s / 3600 {
+                0 => format!("{}m{:02}s", s / 60, s % 60),
+                h => format!("{}h{:02}m{:02}s", h, s / 60 % 60, s % 60),
+            };
+            values.insert("duration".to_owned(), d);
+            values.insert("seconds".to_owned(), s.to_string());
+        }
+
+        values
+    }
+}
+
+///Common name of a mp4 sample entry type or matroska codec id
+fn codec_name(codec: &str) -> String {
+    match codec {
+        "avc1" | "avc3" | "V_MPEG4/ISO/AVC" => "h264",
+        "hvc1" | "hev1" | "V_MPEGH/ISO/HEVC" => "hevc",
+        "av01" | "V_AV1" => "av1",
+        "vp09" | "V_VP9" => "vp9",
+        "vp08" | "V_VP8" => "vp8",
+        "mp4v" | "V_MPEG4/ISO/SP" | "V_MPEG4/ISO/ASP" => "mpeg4",
+        c => c,
+    }
+    .to_owned()
+}
+
+fn u16_at(b: &[u8], i: usize) -> Option<u64> {
+    Some(u16::from_be_bytes(b.get(i..i + 2)?.try_into().ok()?) as u64)
+}
+
+fn u32_at(b: &[u8], i: usize) -> Option<u64> {
+    Some(u32::from_be_bytes(b.get(i..i + 4)?.try_into().ok()?) as u64)
+}
+
+fn u64_at(b: &[u8], i: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(b.get(i..i + 8)?.try_into().ok()?))
+}
+
+///Read an exact number of bytes limited by MAX_META_SIZE
+fn read_meta<R: Read>(r: &mut R, size: u64) -> Result<Vec<u8>> {
+    if size > MAX_META_SIZE {
+        return Err(anyhow!("Metadata of {} bytes is too large", size));
+    }
+    let mut buf = vec![0; size as usize];
+    r.read_exact(&mut buf)?;
+
+    Ok(buf)
+}
+
+//////////mp4
+///Boxes in a slice as (type,body)
+fn mp4_boxes(b: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut boxes = Vec::new();
+    let mut i = 0;
+    while let (Some(size), Some(kind)) = (u32_at(b, i), b.get(i + 4..i + 8)) {
+        let (start, size) = match size {
+            0 => (i + 8, (b.len() - i) as u64),
+            1 => match u64_at(b, i + 8) {
+                Some(size) => (i + 16, size),
+                None => break,
+            },
+            size => (i + 8, size),
+        };
+        let end = i.saturating_add(size as usize);
+        match b.get(start..end) {
+            Some(body) if end > start => boxes.push((kind, body)),
+            _ => break,
+        }
+        i = end;
+    }
+
+    boxes
+}
+
+fn mp4_child<'a>(b: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    mp4_boxes(b)
+        .into_iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, body)| body)
+}
+
+///Find 'moov' box among top level boxes by seeking over others such as 'mdat'
+fn mp4_info<R: Read + Seek>(r: &mut R) -> Result<VideoInfo> {
+    let len = r.seek(SeekFrom::End(0))?;
+    let mut pos = r.seek(SeekFrom::Start(0))?;
+    while pos.saturating_add(8) <= len {
+        let mut head = [0; 16];
+        r.read_exact(&mut head[..8])?;
+        let kind: [u8; 4] = [head[4], head[5], head[6], head[7]];
+        let (mut size, mut head_len) = (u32_at(&head, 0).unwrap_or(0), 8);
+        if size == 1 {
+            r.read_exact(&mut head[8..16])?;
+            size = u64_at(&head, 8).unwrap_or(0);
+            head_len = 16;
+        } else if size == 0 {
+            size = len - pos;
+        }
+        if size < head_len {
+            break;
+        }
+        if &kind == b"moov" {
+            let moov = read_meta(r, size - head_len)?;
+            return Ok(moov_info(&moov));
+        }
+        //a size past the end of any file stops parsing,rather than overflowing
+        let Some(next) = pos.checked_add(size) else {
+            break;
+        };
+        pos = r.seek(SeekFrom::Start(next))?;
+    }
+
+    Ok(VideoInfo::default())
+}
+
+fn moov_info(moov: &[u8]) -> VideoInfo {
+    let mut info = VideoInfo::default();
+    if let Some(mvhd) = mp4_child(moov, b"mvhd") {
+        //version 1 has 64-bit times and duration
+        let (scale, duration) = match mvhd.first() {
+            Some(1) => (u32_at(mvhd, 20), u64_at(mvhd, 24)),
+            _ => (u32_at(mvhd, 12), u32_at(mvhd, 16)),
+        };
+        if let (Some(scale), Some(duration)) = (scale, duration) {
+            if scale > 0 {
+                info.seconds = Some(duration as f64 / scale as f64);
+            }
+        }
+    }
+
+    let video = mp4_boxes(moov)
+        .into_iter()
+        .filter(|(k, _)| *k == b"trak")
+        .map(|(_, trak)| trak)
+        .find(|trak| {
+            mp4_child(trak, b"mdia")
+                .and_then(|mdia| mp4_child(mdia, b"hdlr"))
+                .and_then(|hdlr| hdlr.get(8..12))
+                == Some(b"vide")
+        });
+    if let Some(trak) = video {
+        if let Some(tkhd) = mp4_child(trak, b"tkhd") {
+            //width and height in 16.16 fixed point after the matrix
+            let at = match tkhd.first() {
+                Some(1) => 88,
+                _ => 76,
+            };
+            info.width = u16_at(tkhd, at).filter(|w| *w > 0);
+            info.height = u16_at(tkhd, at + 4).filter(|h| *h > 0);
+        }
+        info.codec = mp4_child(trak, b"mdia")
+            .and_then(|b| mp4_child(b, b"minf"))
+            .and_then(|b| mp4_child(b, b"stbl"))
+            .and_then(|b| mp4_child(b, b"stsd"))
+            .and_then(|stsd| stsd.get(12..16))
+            .map(|c| String::from_utf8_lossy(c).to_string());
+    }
+
+    info
+}
+
+//////////matroska
+const EBML_HEADER: u64 = 0x1A45DFA3;
+const SEGMENT: u64 = 0x18538067;
+const INFO: u64 = 0x1549A966;
+const TIMECODE_SCALE: u64 = 0x2AD7B1;
+const DURATION: u64 = 0x4489;
+const TRACKS: u64 = 0x1654AE6B;
+const TRACK_ENTRY: u64 = 0xAE;
+const TRACK_TYPE: u64 = 0x83;
+const CODEC_ID: u64 = 0x86;
+const VIDEO: u64 = 0xE0;
+const PIXEL_WIDTH: u64 = 0xB0;
+const PIXEL_HEIGHT: u64 = 0xBA;
+const CLUSTER: u64 = 0x1F43B675;
+
+///Read a variable length integer,the length marker kept for element ids,None for unknown size
+fn read_vint<R: Read>(r: &mut R, keep_marker: bool) -> io::Result<Option<u64>> {
+    let mut first = [0];
+    r.read_exact(&mut first)?;
+    let len = first[0].leading_zeros() as usize + 1;
+    if len > 8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid EBML vint",
+        ));
+    }
+    let mut rest = [0; 7];
+    r.read_exact(&mut rest[..len - 1])?;
+
+    let marker = 1u64 << (7 * len);
+    let mut v = first[0] as u64;
+    rest[..len - 1]
+        .iter()
+        .for_each(|b| v = (v << 8) | *b as u64);
+    if keep_marker {
+        return Ok(Some(v));
+    }
+    v &= marker - 1;
+
+    Ok((v != marker - 1).then_some(v))
+}
+
+///Elements in a slice as (id,body)
+fn ebml_elements(mut b: &[u8]) -> Vec<(u64, &[u8])> {
+    let mut elements = Vec::new();
+    while !b.is_empty() {
+        let (Ok(Some(id)), Ok(Some(size))) = (read_vint(&mut b, true), read_vint(&mut b, false))
+        else {
+            break;
+        };
+        let Some(body) = b.get(..size as usize) else {
+            break;
+        };
+        elements.push((id, body));
+        b = &b[size as usize..];
+    }
+
+    elements
+}
+
+fn ebml_uint(b: &[u8]) -> u64 {
+    b.iter().take(8).fold(0, |v, x| (v << 8) | *x as u64)
+}
+
+fn ebml_float(b: &[u8]) -> Option<f64> {
+    match b.len() {
+        4 => Some(f32::from_be_bytes(b.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(b.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+///Read 'Info' and 'Tracks' of the segment,stop at the first cluster
+fn mkv_info<R: Read + Seek>(r: &mut R) -> Result<VideoInfo> {
+    let mut info = VideoInfo::default();
+    if read_vint(r, true)? != Some(EBML_HEADER) {
+        return Err(anyhow!("Not a matroska file"));
+    }
+    let size = read_vint(r, false)?.ok_or(anyhow!("Unknown size of EBML header"))?;
+    r.seek(SeekFrom::Current(size as i64))?;
+    if read_vint(r, true)? != Some(SEGMENT) {
+        return Err(anyhow!("No segment in matroska file"));
+    }
+    read_vint(r, false)?;
+
+    let (mut has_info, mut has_tracks) = (false, false);
+    while !(has_info && has_tracks) {
+        let (Ok(Some(id)), Ok(size)) = (read_vint(r, true), read_vint(r, false)) else {
+            break;
+        };
+        let Some(size) = size else {
+            break;
+        };
+        match id {
+            INFO => {
+                has_info = true;
+                let body = read_meta(r, size)?;
+                let es = ebml_elements(&body);
+                let scale = es
+                    .iter()
+                    .find(|(id, _)| *id == TIMECODE_SCALE)
+                    .map(|(_, b)| ebml_uint(b))
+                    .unwrap_or(1_000_000);
+                info.seconds = es
+                    .iter()
+                    .find(|(id, _)| *id == DURATION)
+                    .and_then(|(_, b)| ebml_float(b))
+                    .map(|d| d * scale as f64 / 1e9);
+            }
+            TRACKS => {
+                has_tracks = true;
+                let body = read_meta(r, size)?;
+                let video = ebml_elements(&body)
+                    .into_iter()
+                    .filter(|(id, _)| *id == TRACK_ENTRY)
+                    .map(|(_, b)| ebml_elements(b))
+                    .find(|es| {
+                        es.iter()
+                            .any(|(id, b)| *id == TRACK_TYPE && ebml_uint(b) == 1)
+                    });
+                if let Some(es) = video {
+                    info.codec = es
+                        .iter()
+                        .find(|(id, _)| *id == CODEC_ID)
+                        .map(|(_, b)| String::from_utf8_lossy(b).trim_end_matches('\0').to_owned());
+                    if let Some((_, v)) = es.iter().find(|(id, _)| *id == VIDEO) {
+                        let vs = ebml_elements(v);
+                        let dim = |want| {
+                            vs.iter()
+                                .find(|(id, _)| *id == want)
+                                .map(|(_, b)| ebml_uint(b))
+                        };
+                        info.width = dim(PIXEL_WIDTH);
+                        info.height = dim(PIXEL_HEIGHT);
+                    }
+                }
+            }
+            CLUSTER => break,
+            _ => {
+                r.seek(SeekFrom::Current(size as i64))?;
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::template::video::{mkv_info, mp4_info, VideoInfo};
+
+    fn mp4_box(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut b = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(kind);
+        b.extend_from_slice(body);
+        b
+    }
+
+    fn ebml(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut b = id.to_vec();
+        //8 bytes size
+        b.push(0x01);
+        b.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+        b.extend_from_slice(body);
+        b
+    }
+
+    #[test]
+    fn test_mp4_info() {
+        let mut mvhd = vec![0; 20];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&5_525_000u32.to_be_bytes());
+        let mut tkhd = vec![0; 84];
+        tkhd[76..78].copy_from_slice(&1920u16.to_be_bytes());
+        tkhd[80..82].copy_from_slice(&1080u16.to_be_bytes());
+        let mut hdlr = vec![0; 12];
+        hdlr[8..12].copy_from_slice(b"vide");
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 16];
+        stsd.extend_from_slice(b"avc1");
+        stsd.extend_from_slice(&[0; 8]);
+
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+        let minf = mp4_box(b"minf", &stbl);
+        let mdia = mp4_box(b"mdia", &[mp4_box(b"hdlr", &hdlr), minf].concat());
+        let trak = mp4_box(b"trak", &[mp4_box(b"tkhd", &tkhd), mdia].concat());
+        let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", &mvhd), trak].concat());
+        let file = [mp4_box(b"ftyp", b"isom"), mp4_box(b"mdat", &[0; 32]), moov].concat();
+
+        let info = mp4_info(&mut Cursor::new(file)).unwrap();
+        assert_eq!(
+            (info.width, info.height, info.codec.as_deref()),
+            (Some(1920), Some(1080), Some("avc1"))
+        );
+        let values = info.values();
+        assert_eq!(values["resolution"], "1080p");
+        assert_eq!(values["codec"], "h264");
+        assert_eq!(values["duration"], "1h32m05s");
+    }
+
+    #[test]
+    fn test_mkv_info() {
+        let info = ebml(
+            &[0x15, 0x49, 0xA9, 0x66],
+            &ebml(&[0x44, 0x89], &90_500.0f64.to_be_bytes()),
+        );
+        let video = ebml(
+            &[0xE0],
+            &[ebml(&[0xB0], &[0x05, 0x00]), ebml(&[0xBA], &[0x02, 0xD0])].concat(),
+        );
+        let audio = ebml(&[0xAE], &ebml(&[0x83], &[2]));
+        let entry = ebml(
+            &[0xAE],
+            &[
+                ebml(&[0x83], &[1]),
+                ebml(&[0x86], b"V_MPEGH/ISO/HEVC"),
+                video,
+            ]
+            .concat(),
+        );
+        let tracks = ebml(&[0x16, 0x54, 0xAE, 0x6B], &[audio, entry].concat());
+        let segment = ebml(&[0x18, 0x53, 0x80, 0x67], &[info, tracks].concat());
+        let file = [
+            ebml(&[0x1A, 0x45, 0xDF, 0xA3], &[0x42, 0x86, 0x81, 0x01]),
+            segment,
+        ]
+        .concat();
+
+        let info = mkv_info(&mut Cursor::new(file)).unwrap();
+        let values = info.values();
+        assert_eq!(values["resolution"], "720p");
+        assert_eq!(values["width"], "1280");
+        assert_eq!(values["codec"], "hevc");
+        assert_eq!(values["duration"], "1m31s");
+        assert!(mkv_info(&mut Cursor::new(vec![0x42; 8])).is_err());
+        assert_eq!(
+            mp4_info(&mut Cursor::new(Vec::new())).unwrap(),
+            VideoInfo::default()
+        );
+        let mut huge = vec![0, 0, 0, 16, b'f', b't', b'y', b'p', 0, 0, 0, 0, 0, 0, 0, 0];
+        huge.extend_from_slice(&[0, 0, 0, 1, b'm', b'd', b'a', b't']);
+        huge.extend_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(
+            mp4_info(&mut Cursor::new(huge)).unwrap(),
+            VideoInfo::default()
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, List, ListState, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+use crate::tui::{Browser, Picker};
+
+///What keys are typed into
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Browse,
+    ///editing the target of the entry under the cursor
+    Edit(String),
+    Filter,
+}
+
+///Keys shown at the bottom by mode
+fn help(mode: &Mode) -> &'static str {
+    match mode {
+        Mode::Browse => {
+            "↑/↓ move  space toggle  a toggle all  e edit  / filter  w apply selected  q quit"
+        }
+        Mode::Edit(_) => "type the target  enter confirm  esc cancel",
+        Mode::Filter => "type to filter  enter/esc done",
+    }
+}
+
+///Draw proposed names side by side with the origins
+fn draw(frame: &mut Frame, browser: &Browser, mode: &Mode, state: &mut TableState) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(2)]).areas(frame.area());
+
+    let rows: Vec<_> = browser
+        .visible()
+        .into_iter()
+        .map(|i| {
+            let e = &browser.entries[i];
+            let mark = match (e.selected, e.origin == e.target) {
+                (_, true) => " ",
+                (true, false) => "✔",
+                (false, false) => "·",
+            };
+            let row = Row::new([
+                mark.to_owned(),
+                e.dir.clone(),
+                e.origin.clone(),
+                e.target.clone(),
+            ]);
+            match e.selected && e.origin != e.target {
+                true => row,
+                false => row.dim(),
+            }
+        })
+        .collect();
+    let (n, total) = (browser.selection().0.len(), browser.entries.len());
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(1),
+            Constraint::Percentage(20),
+            Constraint::Percentage(40),
+            Constraint::Percentage(40),
+        ],
+    )
+    .header(Row::new(["", "Dir", "Name", "Proposed"]).bold())
+    .block(Block::bordered().title(format!(" fdn {} of {} selected ", n, total)))
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    state.select(Some(browser.cursor));
+    frame.render_stateful_widget(table, main, state);
+
+    let input = match mode {
+        Mode::Edit(s) => format!("target> {}", s),
+        Mode::Filter => format!("filter> {}", browser.filter),
+        Mode::Browse if !browser.filter.is_empty() => format!("filter: {}", browser.filter),
+        Mode::Browse => String::new(),
+    };
+    frame.render_widget(
+        Paragraph::new(vec![Line::from(input), Line::from(help(mode)).dim()]),
+        status,
+    );
+}
+
+///Browse planned renames until the selection is applied by 'w' or dropped by 'q',true if
+///applied
+pub fn run(browser: &mut Browser) -> Result<bool> {
+    in_terminal(|terminal| browse(terminal, browser))
+}
+
+///Pick items in a fuzzy finder until accepted by enter or cancelled by esc,true if accepted
+pub fn pick(picker: &mut Picker) -> Result<bool> {
+    in_terminal(|terminal| picking(terminal, picker))
+}
+
+///Run in the terminal switched to the alternate screen,restored even if failed
+fn in_terminal<T>(f: impl FnOnce(&mut DefaultTerminal) -> Result<T>) -> Result<T> {
+    let mut terminal = ratatui::init();
+    let rlt = f(&mut terminal);
+    ratatui::restore();
+
+    rlt
+}
+
+///Draw matching items above the query like skim
+fn draw_picker(frame: &mut Frame, picker: &Picker, state: &mut ListState) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(2)]).areas(frame.area());
+
+    let matches = picker.matches();
+    let items: Vec<_> = matches
+        .iter()
+        .map(|i| {
+            let mark = if picker.marked[*i] { ">" } else { " " };
+            format!("{} {}", mark, picker.items[*i])
+        })
+        .collect();
+    let marked = picker.marked.iter().filter(|m| **m).count();
+    let list = List::new(items)
+        .block(Block::bordered().title(format!(
+            " {}/{} matched,{} marked ",
+            matches.len(),
+            picker.items.len(),
+            marked
+        )))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    state.select((!matches.is_empty()).then_some(picker.cursor));
+    frame.render_stateful_widget(list, main, state);
+
+    let help = "type to filter  ↑/↓ move  tab mark  ctrl-a mark all  enter pick  esc cancel";
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::from(format!("> {}", picker.query)),
+            Line::from(help).dim(),
+        ]),
+        status,
+    );
+}
+
+///Handle keys until accepted or cancelled,true if accepted
+fn picking(terminal: &mut DefaultTerminal, picker: &mut Picker) -> Result<bool> {
+    let mut state = ListState::default();
+
+    loop {
+        terminal.draw(|frame| draw_picker(frame, picker, &mut state))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if ctrl => return Ok(false),
+            KeyCode::Enter => return Ok(true),
+            KeyCode::Char('a') if ctrl => picker.toggle_all(),
+            KeyCode::Up => picker.move_cursor(-1),
+            KeyCode::Down => picker.move_cursor(1),
+            KeyCode::PageUp => picker.move_cursor(-10),
+            KeyCode::PageDown => picker.move_cursor(10),
+            KeyCode::Tab => {
+                picker.toggle();
+                picker.move_cursor(1);
+            }
+            KeyCode::BackTab => {
+                picker.toggle();
+                picker.move_cursor(-1);
+            }
+            KeyCode::Backspace => {
+                let mut q = picker.query.clone();
+                q.pop();
+                picker.set_query(&q);
+            }
+            KeyCode::Char(c) if !ctrl => picker.set_query(&format!("{}{}", picker.query, c)),
+            _ => {}
+        }
+    }
+}
+
+///Handle keys until applied or quit,true if applied
+fn browse(terminal: &mut DefaultTerminal, browser: &mut Browser) -> Result<bool> {
+    let mut mode = Mode::Browse;
+    let mut state = TableState::default();
+
+    loop {
+        terminal.draw(|frame| draw(frame, browser, &mode, &mut state))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        mode = match (mode, key.code) {
+            (Mode::Browse, KeyCode::Char('q') | KeyCode::Esc) => return Ok(false),
+            (Mode::Browse, KeyCode::Char('w')) => return Ok(true),
+            (Mode::Browse, KeyCode::Up | KeyCode::Char('k')) => {
+                browser.move_cursor(-1);
+                Mode::Browse
+            }
+            (Mode::Browse, KeyCode::Down | KeyCode::Char('j')) => {
+                browser.move_cursor(1);
+                Mode::Browse
+            }
+            (Mode::Browse, KeyCode::PageUp) => {
+                browser.move_cursor(-10);
+                Mode::Browse
+            }
+            (Mode::Browse, KeyCode::PageDown) => {
+                browser.move_cursor(10);
+                Mode::Browse
+            }
+            (Mode::Browse, KeyCode::Char(' ')) => {
+                browser.toggle();
+                Mode::Browse
+            }
+            (Mode::Browse, KeyCode::Char('a')) => {
+                browser.toggle_all();
+                Mode::Browse
+            }
+            (Mode::Browse, KeyCode::Char('e') | KeyCode::Enter) => match browser.current() {
+                Some(i) => Mode::Edit(browser.entries[i].target.clone()),
+                None => Mode::Browse,
+            },
+            (Mode::Browse, KeyCode::Char('/')) => Mode::Filter,
+            //editing goes on until the target is a single name
+            (Mode::Edit(s), KeyCode::Enter) => match browser.set_target(&s) {
+                true => Mode::Browse,
+                false => Mode::Edit(s),
+            },
+            (Mode::Edit(_), KeyCode::Esc) => Mode::Browse,
+            (Mode::Edit(mut s), KeyCode::Backspace) => {
+                s.pop();
+                Mode::Edit(s)
+            }
+            (Mode::Edit(mut s), KeyCode::Char(c)) => {
+                s.push(c);
+                Mode::Edit(s)
+            }
+            (Mode::Filter, KeyCode::Enter | KeyCode::Esc) => Mode::Browse,
+            (Mode::Filter, KeyCode::Backspace) => {
+                let mut f = browser.filter.clone();
+                f.pop();
+                browser.set_filter(&f);
+                Mode::Filter
+            }
+            (Mode::Filter, KeyCode::Char(c)) => {
+                browser.set_filter(&format!("{}{}", browser.filter, c));
+                Mode::Filter
+            }
+            (mode, _) => mode,
+        };
+    }
+}
@@ -0,0 +1,316 @@
+use std::path::PathBuf;
+
+#[cfg(feature = "tui")]
+pub mod app;
+
+///A file with its proposed name,applied if selected and the name changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    ///directory of the file relative to the browsed one,empty for the browsed one itself
+    pub dir: String,
+    pub origin: String,
+    pub target: String,
+    pub selected: bool,
+}
+
+///Score of a pattern fuzzily matching a text,each whitespace separated term a subsequence of
+///the text,higher for consecutive chars and chars at word starts,None if any term not matching;
+///case insensitive unless the pattern has uppercase chars
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    //folded char by char so that indexes of folded chars match those of the text
+    let sensitive = pattern.chars().any(char::is_uppercase);
+    let smart = |s: &str| -> Vec<char> {
+        s.chars()
+            .map(|c| match sensitive {
+                true => c,
+                false => c.to_lowercase().next().unwrap_or(c),
+            })
+            .collect()
+    };
+    let chars: Vec<_> = text.chars().collect();
+    let folded = smart(text);
+
+    pattern.split_whitespace().try_fold(0, |total, term| {
+        let (mut score, mut at, mut prev) = (0, 0, None::<usize>);
+        for c in smart(term) {
+            let i = at + folded.get(at..)?.iter().position(|f| *f == c)?;
+            let word_start = i == 0
+                || !chars[i - 1].is_alphanumeric()
+                || (chars[i - 1].is_lowercase() && chars[i].is_uppercase());
+            let consecutive = prev.is_some_and(|p| p + 1 == i);
+            score += FUZZY_MATCH - prev.map_or(0, |p| (i - p - 1) as i64);
+            score += if consecutive { FUZZY_CONSECUTIVE } else { 0 };
+            score += if word_start { FUZZY_WORD_START } else { 0 };
+            (at, prev) = (i + 1, Some(i));
+        }
+        Some(total + score)
+    })
+}
+
+const FUZZY_MATCH: i64 = 16;
+const FUZZY_CONSECUTIVE: i64 = 8;
+const FUZZY_WORD_START: i64 = 16;
+
+///Items picked interactively:filtered by a fuzzy query,marked and moved through
+#[derive(Debug, Default)]
+pub struct Picker {
+    pub items: Vec<String>,
+    pub query: String,
+    pub marked: Vec<bool>,
+    ///position in matching items
+    pub cursor: usize,
+}
+
+impl Picker {
+    pub fn new(items: Vec<String>) -> Self {
+        Self {
+            marked: vec![false; items.len()],
+            items,
+            ..Default::default()
+        }
+    }
+
+    ///Indexes of items matching the query,best matches first
+    pub fn matches(&self) -> Vec<usize> {
+        let mut scored: Vec<_> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some((fuzzy_score(&self.query, s)?, i)))
+            .collect();
+        scored.sort_by_key(|(score, i)| (-score, *i));
+
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    ///Index of the item under the cursor
+    pub fn current(&self) -> Option<usize> {
+        self.matches().get(self.cursor).copied()
+    }
+
+    ///Move the cursor by delta within matching items
+    pub fn move_cursor(&mut self, delta: isize) {
+        let n = self.matches().len();
+        self.cursor = match n {
+            0 => 0,
+            n => self.cursor.saturating_add_signed(delta).min(n - 1),
+        };
+    }
+
+    ///Query items,the cursor back to the best match
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_owned();
+        self.cursor = 0;
+    }
+
+    ///Mark or unmark the item under the cursor
+    pub fn toggle(&mut self) {
+        if let Some(i) = self.current() {
+            self.marked[i] = !self.marked[i];
+        }
+    }
+
+    ///Unmark all matching items if all of them are marked,otherwise mark them all
+    pub fn toggle_all(&mut self) {
+        let matches = self.matches();
+        let all = matches.iter().all(|i| self.marked[*i]);
+        matches.into_iter().for_each(|i| self.marked[i] = !all);
+    }
+
+    ///Indexes of marked items in order,the one under the cursor if none marked
+    pub fn picked(&self) -> Vec<usize> {
+        let marked: Vec<_> = (0..self.items.len()).filter(|i| self.marked[*i]).collect();
+        match marked.is_empty() {
+            true => self.current().into_iter().collect(),
+            false => marked,
+        }
+    }
+}
+
+///Planned renames browsed interactively:moved through,toggled,edited and filtered
+#[derive(Debug, Default)]
+pub struct Browser {
+    pub entries: Vec<Entry>,
+    ///case insensitive text the origin or target of visible entries contains
+    pub filter: String,
+    ///position in visible entries
+    pub cursor: usize,
+}
+
+impl Browser {
+    ///Browse entries in the order of directories then names,renames selected at first
+    pub fn new(mut entries: Vec<Entry>) -> Self {
+        entries.sort_by(|a, b| (&a.dir, &a.origin).cmp(&(&b.dir, &b.origin)));
+        entries
+            .iter_mut()
+            .for_each(|e| e.selected = e.origin != e.target);
+
+        Self {
+            entries,
+            ..Default::default()
+        }
+    }
+
+    ///Indexes of entries matching the filter
+    pub fn visible(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| {
+                filter.is_empty()
+                    || e.origin.to_lowercase().contains(&filter)
+                    || e.target.to_lowercase().contains(&filter)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    ///Index of the entry under the cursor
+    pub fn current(&self) -> Option<usize> {
+        self.visible().get(self.cursor).copied()
+    }
+
+    ///Move the cursor by delta within visible entries
+    pub fn move_cursor(&mut self, delta: isize) {
+        let n = self.visible().len();
+        self.cursor = match n {
+            0 => 0,
+            n => self.cursor.saturating_add_signed(delta).min(n - 1),
+        };
+    }
+
+    ///Select or unselect the entry under the cursor
+    pub fn toggle(&mut self) {
+        if let Some(i) = self.current() {
+            self.entries[i].selected = !self.entries[i].selected;
+        }
+    }
+
+    ///Unselect all visible entries if all of them are selected,otherwise select them all
+    pub fn toggle_all(&mut self) {
+        let visible = self.visible();
+        let all = visible.iter().all(|i| self.entries[*i].selected);
+        visible
+            .into_iter()
+            .for_each(|i| self.entries[i].selected = !all);
+    }
+
+    ///Edit the target of the entry under the cursor,selected if it changes the name;false and
+    ///left unchanged if the target is not a single name
+    pub fn set_target(&mut self, target: &str) -> bool {
+        if target.is_empty() || target.contains(['/', '\\']) || target == "." || target == ".." {
+            return false;
+        }
+        if let Some(i) = self.current() {
+            let e = &mut self.entries[i];
+            e.target = target.to_owned();
+            e.selected = e.target != e.origin;
+        }
+
+        true
+    }
+
+    ///Filter entries,the cursor kept within visible ones
+    pub fn set_filter(&mut self, filter: &str) {
+        self.filter = filter.to_owned();
+        self.move_cursor(0);
+    }
+
+    ///Paths and targets of selected entries which change names
+    pub fn selection(&self) -> (Vec<PathBuf>, Vec<String>) {
+        self.entries
+            .iter()
+            .filter(|e| e.selected && !e.target.is_empty() && e.target != e.origin)
+            .map(|e| (e.path.clone(), e.target.clone()))
+            .unzip()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::tui::{fuzzy_score, Browser, Entry, Picker};
+
+    fn entry(dir: &str, origin: &str, target: &str) -> Entry {
+        Entry {
+            path: PathBuf::from(dir).join(origin),
+            dir: dir.to_owned(),
+            origin: origin.to_owned(),
+            target: target.to_owned(),
+            selected: false,
+        }
+    }
+
+    #[test]
+    fn test_browser() {
+        let mut b = Browser::new(vec![
+            entry("sub", "c d.txt", "c_d.txt"),
+            entry("", "a b.txt", "a_b.txt"),
+            entry("", "same.txt", "same.txt"),
+        ]);
+        assert_eq!(b.entries[0].origin, "a b.txt");
+        assert_eq!(b.selection().1, ["a_b.txt", "c_d.txt"]);
+
+        b.toggle();
+        assert_eq!(b.selection().1, ["c_d.txt"]);
+        b.move_cursor(5);
+        assert_eq!(b.current(), Some(2));
+        b.move_cursor(-1);
+        assert!(b.set_target("Same Renamed.txt"));
+        assert_eq!(b.selection().1, ["Same Renamed.txt", "c_d.txt"]);
+        for bad in ["", ".", "..", "../x.txt", "a\\b.txt"] {
+            assert!(!b.set_target(bad));
+        }
+        assert_eq!(b.selection().1, ["Same Renamed.txt", "c_d.txt"]);
+
+        b.set_filter("C_D");
+        assert_eq!(b.visible(), [2]);
+        assert_eq!(b.cursor, 0);
+        b.toggle_all();
+        assert_eq!(b.selection().1, ["Same Renamed.txt"]);
+        b.set_filter("");
+        b.toggle_all();
+        assert_eq!(b.selection().1.len(), 3);
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "abc"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "abc"), None);
+        assert_eq!(fuzzy_score("ba", "abc"), None);
+        assert!(fuzzy_score("abc", "abc.txt") > fuzzy_score("abc", "axbxc.txt"));
+        assert!(fuzzy_score("mf", "my_file") > fuzzy_score("mf", "amfile"));
+        assert!(fuzzy_score("MF", "myFile").is_none());
+        assert!(fuzzy_score("mF", "myFile").is_some());
+        assert!(fuzzy_score("tx ab", "ab.txt").is_some());
+        assert!(fuzzy_score("tx zz", "ab.txt").is_none());
+        assert!(fuzzy_score("ix", "İx").is_some());
+    }
+
+    #[test]
+    fn test_picker() {
+        let mut p = Picker::new(
+            ["a/readme.md", "b/report.txt", "b/notes.txt"]
+                .map(str::to_owned)
+                .to_vec(),
+        );
+        assert_eq!(p.matches(), [0, 1, 2]);
+        p.move_cursor(1);
+        assert_eq!(p.picked(), [1]);
+
+        p.set_query("txt");
+        assert_eq!(p.matches(), [1, 2]);
+        p.toggle_all();
+        assert_eq!(p.picked(), [1, 2]);
+        p.set_query("rep");
+        p.toggle();
+        assert_eq!(p.picked(), [2]);
+        p.set_query("zzz");
+        assert_eq!(p.current(), None);
+        p.toggle_all();
+        assert_eq!(p.picked(), [2]);
+    }
+}
@@ -1,21 +1,86 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
+    thread,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Result};
 use directories::UserDirs;
-use rusqlite::{params, Connection};
+use rusqlite::{params, params_from_iter, types::Value, Connection, DatabaseName, ErrorCode};
 
-use crate::{Record, Separator, TermWord, ToSepWord};
+use crate::{
+    Checkpoint, ConfigRow, Operation, Record, RegexRule, RuleDiff, Separator, TermFlags, TermWord,
+    ToSepWord,
+};
 
+pub const CONFIG_DIR_NAME: &str = ".fdn";
+///Schema version written into database,increase it when tables change
+pub const SCHEMA_VERSION: i32 = 9;
 const DEFAULT_DB_NAME: &str = "fdn.db";
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(50);
+const BUSY_RETRY_TIMES: usize = 5;
 const SEP_WORD: &str = "_";
+///Tables of what runs did,the others of rules and settings
+pub const RECORD_TABLES: [&str; 5] = [
+    "records",
+    "operations",
+    "checkpoints",
+    "checkpoint_paths",
+    "rule_stats",
+];
+///Parts of the database backed up or restored
+pub const DB_PARTS: [&str; 3] = ["all", "rules", "records"];
+///Table of a backup of a part other than all,keeping the part
+const BACKUP_PART_TABLE: &str = "backup_part";
+///Columns of config rows not compared between two databases
+const UNCOMPARED_COLUMNS: [&str; 3] = ["id", "created", "deleted_at"];
+///Kinds of config rows addressed as 'kind:id',with their tables,rules in config syntax and
+///columns changed by update
+pub const CONFIG_KINDS: [(&str, &str, &str, &str); 8] = [
+    ("sep", "separators", "value", "value"),
+    ("to-sep", "to_sep_words", "value", "value"),
+    ("term", "term_words", "key || ':' || value", "value"),
+    (
+        "re",
+        "regex_rules",
+        "'re:' || pattern || ':' || replacement",
+        "replacement",
+    ),
+    (
+        "ext",
+        "ext_aliases",
+        "'ext:' || ext || ':' || alias",
+        "alias",
+    ),
+    ("acronym", "acronyms", "'acronym:' || word", "word"),
+    ("small", "small_words", "'small:' || word", "word"),
+    (
+        "stop",
+        "stopwords",
+        "'stop:' || CASE lang WHEN '' THEN word ELSE lang || ':' || word END",
+        "word",
+    ),
+];
 const TOBE_SEP_S: [&str; 24] = [
     "：", ":", "，", ",", "！", "!", "？", "?", "（", "(", ")", "【", "[", "】", "]", "~", "》",
     "《", "▯", "“", "”", "\"", " ", "-",
 ];
+const EXT_ALIASES: [(&str, &str); 4] = [
+    ("jpeg", "jpg"),
+    ("jpe", "jpg"),
+    ("htm", "html"),
+    ("tiff", "tif"),
+];
+const ACRONYMS: [&str; 12] = [
+    "PDF", "HTML", "HTTP", "URL", "API", "JSON", "XML", "SQL", "USB", "DVD", "GPS", "ID",
+];
+const SMALL_WORDS: [&str; 18] = [
+    "a", "an", "and", "as", "at", "but", "by", "for", "from", "in", "nor", "of", "on", "or", "the",
+    "to", "vs", "with",
+];
 
 //////////separators
 ///Create separators table via database connection
@@ -24,7 +89,9 @@ pub fn create_separators_table(conn: &Connection) -> Result<()> {
         "CREATE TABLE IF NOT EXISTS separators (
                     id      INTEGER PRIMARY KEY,
                     value   TEXT NOT NULL UNIQUE,
-                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    enabled INTEGER NOT NULL DEFAULT 1,
+                    tag     TEXT NOT NULL DEFAULT ''
                 )",
         (),
     )?;
@@ -40,7 +107,7 @@ pub fn insert_separator(conn: &Connection, sep: &str) -> Result<()> {
 
 ///Retrieve from separators via database connection
 pub fn retrieve_separators(conn: &Connection) -> Result<Vec<Separator>> {
-    let mut stmt = conn.prepare("SELECT id,value FROM separators")?;
+    let mut stmt = conn.prepare("SELECT id,value FROM separators WHERE enabled = 1")?;
     let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
 
     let mut results = Vec::new();
@@ -76,7 +143,10 @@ pub fn create_to_sep_words_table(conn: &Connection) -> Result<()> {
         "CREATE TABLE IF NOT EXISTS to_sep_words (
                     id      INTEGER PRIMARY KEY,
                     value   TEXT NOT NULL UNIQUE,
-                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    enabled INTEGER NOT NULL DEFAULT 1,
+                    tag     TEXT NOT NULL DEFAULT '',
+                    scope   TEXT NOT NULL DEFAULT ''
                 )",
         (),
     )?;
@@ -84,24 +154,24 @@ pub fn create_to_sep_words_table(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-///Create from to_sep_words via database connection
-pub fn insert_to_sep_word(conn: &Connection, word: &str) -> Result<()> {
+///Create from to_sep_words with scope via database connection,empty scope for all files
+pub fn insert_to_sep_word(conn: &Connection, word: &str, scope: &str) -> Result<()> {
     conn.execute(
-        "INSERT INTO to_sep_words (value) VALUES (?1)",
-        params![word],
+        "INSERT INTO to_sep_words (value,scope) VALUES (?1,?2)",
+        params![word, scope],
     )?;
     Ok(())
 }
 
 ///Retrieve from to_sep_words via database connection
 pub fn retrieve_to_sep_words(conn: &Connection) -> Result<Vec<ToSepWord>> {
-    let mut stmt = conn.prepare("SELECT id,value FROM to_sep_words")?;
-    let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let mut stmt = conn.prepare("SELECT id,value,scope FROM to_sep_words WHERE enabled = 1")?;
+    let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
 
     let mut results = Vec::new();
     for row_rlt in rows {
-        let (id, value) = row_rlt?;
-        results.push(ToSepWord { id, value });
+        let (id, value, scope) = row_rlt?;
+        results.push(ToSepWord { id, value, scope });
     }
 
     Ok(results)
@@ -132,7 +202,13 @@ pub fn create_term_words_table(conn: &Connection) -> Result<()> {
                     id      INTEGER PRIMARY KEY,
                     key     TEXT NOT NULL UNIQUE,
                     value   TEXT,
-                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    enabled INTEGER NOT NULL DEFAULT 1,
+                    tag     TEXT NOT NULL DEFAULT '',
+                    deleted_at TIMESTAMP,
+                    ignore_case INTEGER NOT NULL DEFAULT 0,
+                    whole_word  INTEGER NOT NULL DEFAULT 0,
+                    scope       TEXT NOT NULL DEFAULT ''
                 )",
         (),
     )?;
@@ -140,24 +216,57 @@ pub fn create_term_words_table(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-///Insert into term_words via database connection
-pub fn insert_term_word(conn: &Connection, key: &str, value: &str) -> Result<()> {
+///Insert into term_words with matching flags and scope via database connection,a soft deleted
+///one with same key is dropped
+pub fn insert_term_word(
+    conn: &Connection,
+    key: &str,
+    value: &str,
+    flags: TermFlags,
+    scope: &str,
+) -> Result<()> {
     conn.execute(
-        "INSERT INTO term_words (key,value) VALUES (?1,?2)",
-        params![key, value],
+        "DELETE FROM term_words WHERE key = ?1 AND deleted_at IS NOT NULL",
+        params![key],
+    )?;
+    conn.execute(
+        "INSERT INTO term_words (key,value,ignore_case,whole_word,scope) VALUES (?1,?2,?3,?4,?5)",
+        params![key, value, flags.ignore_case, flags.whole_word, scope],
     )?;
     Ok(())
 }
 
-///Retrieve from term_words via database connection
+///Retrieve not deleted from term_words via database connection
 pub fn retrieve_term_words(conn: &Connection) -> Result<Vec<TermWord>> {
-    let mut stmt = conn.prepare("SELECT id,key,value FROM term_words")?;
-    let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+    retrieve_term_words_where(conn, "deleted_at IS NULL AND enabled = 1")
+}
+
+///Retrieve soft deleted from term_words via database connection
+pub fn retrieve_deleted_term_words(conn: &Connection) -> Result<Vec<TermWord>> {
+    retrieve_term_words_where(conn, "deleted_at IS NOT NULL")
+}
+
+fn retrieve_term_words_where(conn: &Connection, cond: &str) -> Result<Vec<TermWord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id,key,value,ignore_case,whole_word,scope FROM term_words WHERE {}",
+        cond
+    ))?;
+    let rows = stmt.query_map(params![], |row| {
+        Ok(TermWord {
+            id: row.get(0)?,
+            key: row.get(1)?,
+            value: row.get(2)?,
+            flags: TermFlags {
+                ignore_case: row.get(3)?,
+                whole_word: row.get(4)?,
+            },
+            scope: row.get(5)?,
+        })
+    })?;
 
     let mut results = Vec::new();
     for row_rlt in rows {
-        let (id, key, value) = row_rlt?;
-        results.push(TermWord { id, key, value });
+        results.push(row_rlt?);
     }
 
     Ok(results)
@@ -173,9 +282,96 @@ pub fn update_term_word(conn: &Connection, id: i32, new_value: &str) -> Result<(
     Ok(())
 }
 
-///Delete from term_words via database connection
+///Soft delete from term_words via database connection
 pub fn delete_term_word(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM term_words WHERE id = ?", params![id])?;
+    conn.execute(
+        "UPDATE term_words SET deleted_at = STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW') WHERE id = ?",
+        params![id],
+    )?;
+
+    Ok(())
+}
+
+///Restore soft deleted from term_words via database connection,return false if not found
+pub fn restore_term_word(conn: &Connection, id: i32) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE term_words SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL",
+        params![id],
+    )?;
+
+    Ok(n > 0)
+}
+
+///Purge term_words soft deleted more than days ago via database connection
+pub fn purge_term_words(conn: &Connection, days: u32) -> Result<usize> {
+    let n = conn.execute(
+        "DELETE FROM term_words WHERE deleted_at < DATETIME('NOW', ?1)",
+        params![format!("-{} days", days)],
+    )?;
+
+    Ok(n)
+}
+
+//////////regex_rules
+///Create regex_rules table via database connection
+pub fn create_regex_rules_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS regex_rules (
+                    id          INTEGER PRIMARY KEY,
+                    pattern     TEXT NOT NULL,
+                    replacement TEXT NOT NULL,
+                    created     TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    enabled     INTEGER NOT NULL DEFAULT 1,
+                    tag         TEXT NOT NULL DEFAULT '',
+                    scope       TEXT NOT NULL DEFAULT '',
+                    UNIQUE (pattern, replacement)
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert into regex_rules with scope via database connection
+pub fn insert_regex_rule(
+    conn: &Connection,
+    pattern: &str,
+    replacement: &str,
+    scope: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO regex_rules (pattern,replacement,scope) VALUES (?1,?2,?3)",
+        params![pattern, replacement, scope],
+    )?;
+    Ok(())
+}
+
+///Retrieve from regex_rules via database connection
+pub fn retrieve_regex_rules(conn: &Connection) -> Result<Vec<RegexRule>> {
+    let mut stmt = conn.prepare(
+        "SELECT id,pattern,replacement,scope FROM regex_rules WHERE enabled = 1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    })?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        let (id, pattern, replacement, scope) = row_rlt?;
+        results.push(RegexRule {
+            id,
+            pattern,
+            replacement,
+            scope,
+        });
+    }
+
+    Ok(results)
+}
+
+///Delete from regex_rules via database connection
+pub fn delete_regex_rule(conn: &Connection, id: i32) -> Result<()> {
+    conn.execute("DELETE FROM regex_rules WHERE id = ?", params![id])?;
 
     Ok(())
 }
@@ -189,7 +385,8 @@ pub fn create_records_table(conn: &Connection) -> Result<()> {
                     hashed_current_name     TEXT NOT NULL,
                     encrypted_previous_name TEXT NOT NULL,
                     count                   INTEGER,
-                    created     TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                    created     TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    encrypted_dir           TEXT
                 )",
         (),
     )?;
@@ -197,35 +394,143 @@ pub fn create_records_table(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-///Create from records via database connection
+///Create from records via database connection,the count of the same rename in the same
+///directory increased instead
 pub fn insert_record(conn: &Connection, record: Record) -> Result<()> {
-    conn.execute("INSERT INTO records (hashed_current_name, encrypted_previous_name, count) VALUES (?1, ?2, ?3)", params![record.hashed_current_name,record.encrypted_pre_name,record.count])?;
+    let bumped = conn
+        .prepare_cached(
+            "UPDATE records SET count = count + 1 WHERE id = (SELECT id FROM records
+                WHERE hashed_current_name = ?1 AND encrypted_previous_name = ?2
+                    AND encrypted_dir IS ?3 ORDER BY id DESC LIMIT 1)",
+        )?
+        .execute(params![
+            record.hashed_current_name,
+            record.encrypted_pre_name,
+            record.encrypted_dir
+        ])?;
+    if bumped > 0 {
+        return Ok(());
+    }
+    let mut stmt = conn.prepare_cached("INSERT INTO records (hashed_current_name, encrypted_previous_name, count, encrypted_dir) VALUES (?1, ?2, ?3, ?4)")?;
+    stmt.execute(params![
+        record.hashed_current_name,
+        record.encrypted_pre_name,
+        record.count,
+        record.encrypted_dir
+    ])?;
 
     Ok(())
 }
 
 ///Retrieve from records via database connection
 pub fn retrieve_records(conn: &Connection) -> Result<Vec<Record>> {
-    let mut stmt =
-        conn.prepare("SELECT id,hashed_current_name,encrypted_previous_name,count FROM records")?;
+    let mut stmt = conn.prepare(
+        "SELECT id,hashed_current_name,encrypted_previous_name,count,encrypted_dir FROM records",
+    )?;
     let rows = stmt.query_map(params![], |row| {
-        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        Ok((
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            row.get(3)?,
+            row.get(4)?,
+        ))
     })?;
 
     let mut results = Vec::new();
     for row_rlt in rows {
-        let (id, hashed_current_name, encrypted_pre_name, count) = row_rlt?;
+        let (id, hashed_current_name, encrypted_pre_name, count, encrypted_dir) = row_rlt?;
         results.push(Record {
             id,
             hashed_current_name,
             encrypted_pre_name,
             count,
+            encrypted_dir,
         });
     }
 
     Ok(results)
 }
 
+///Retrieve the latest record by hashed current name via database connection
+pub fn retrieve_record_by_hash(conn: &Connection, hashed: &str) -> Result<Option<Record>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id,hashed_current_name,encrypted_previous_name,count,encrypted_dir FROM records
+            WHERE hashed_current_name = ?1 ORDER BY id DESC LIMIT 1",
+    )?;
+    let mut rows = stmt.query_map(params![hashed], |row| {
+        Ok(Record {
+            id: row.get(0)?,
+            hashed_current_name: row.get(1)?,
+            encrypted_pre_name: row.get(2)?,
+            count: row.get(3)?,
+            encrypted_dir: row.get(4)?,
+        })
+    })?;
+
+    Ok(rows.next().transpose()?)
+}
+
+///Retrieve records by hashed current name newest first via database connection
+pub fn retrieve_records_by_hash(conn: &Connection, hashed: &str) -> Result<Vec<Record>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id,hashed_current_name,encrypted_previous_name,count,encrypted_dir FROM records
+            WHERE hashed_current_name = ?1 ORDER BY id DESC",
+    )?;
+    let rows = stmt.query_map(params![hashed], |row| {
+        Ok(Record {
+            id: row.get(0)?,
+            hashed_current_name: row.get(1)?,
+            encrypted_pre_name: row.get(2)?,
+            count: row.get(3)?,
+            encrypted_dir: row.get(4)?,
+        })
+    })?;
+
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+///Retrieve the newest record of the same current name,previous name and directory as a record
+///via database connection,the one inserting it bumps
+pub fn retrieve_same_record(conn: &Connection, record: &Record) -> Result<Option<Record>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id,hashed_current_name,encrypted_previous_name,count,encrypted_dir FROM records
+            WHERE hashed_current_name = ?1 AND encrypted_previous_name = ?2
+                AND encrypted_dir IS ?3 ORDER BY id DESC LIMIT 1",
+    )?;
+    let mut rows = stmt.query_map(
+        params![
+            record.hashed_current_name,
+            record.encrypted_pre_name,
+            record.encrypted_dir
+        ],
+        |row| {
+            Ok(Record {
+                id: row.get(0)?,
+                hashed_current_name: row.get(1)?,
+                encrypted_pre_name: row.get(2)?,
+                count: row.get(3)?,
+                encrypted_dir: row.get(4)?,
+            })
+        },
+    )?;
+
+    Ok(rows.next().transpose()?)
+}
+
+///Release a record reversed once,its count decreased or it deleted at the last one
+pub fn release_record(conn: &Connection, record: &Record) -> Result<()> {
+    if record.count <= 1 {
+        return delete_records(conn, record.id);
+    }
+    conn.execute(
+        "UPDATE records SET count = count - 1 WHERE id = ?1",
+        params![record.id],
+    )?;
+
+    Ok(())
+}
+
 ///Update from records via database connection
 pub fn update_records(conn: &Connection, id: i32, origin: &str, target: &str) -> Result<()> {
     conn.execute(
@@ -243,108 +548,1831 @@ pub fn delete_records(conn: &Connection, id: i32) -> Result<()> {
     Ok(())
 }
 
-//
-fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
-    let sql = format!(
-        "SELECT name FROM sqlite_master WHERE type='table' AND name='{}';",
-        name
-    );
-    let mut stmt = conn.prepare(&sql)?;
-    let exists = stmt.exists([])?;
+//////////settings
+///Create settings table via database connection
+pub fn create_settings_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+                    id      INTEGER PRIMARY KEY,
+                    key     TEXT NOT NULL UNIQUE,
+                    value   TEXT NOT NULL,
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                )",
+        (),
+    )?;
 
-    Ok(exists)
+    Ok(())
 }
 
-///Open database and return database connection via Result
-pub fn open_db(db_path: Option<&str>) -> Result<Connection> {
-    let mut t_c_map: HashMap<String, fn(&Connection) -> Result<()>> = HashMap::new();
-    t_c_map.insert(String::from("separators"), create_separators_table);
-    t_c_map.insert(String::from("to_sep_words"), create_to_sep_words_table);
-    t_c_map.insert(String::from("term_words"), create_term_words_table);
-    t_c_map.insert(String::from("records"), create_records_table);
+///Insert or update a setting via database connection
+pub fn upsert_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key,value) VALUES (?1,?2)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
 
-    let db_path = match db_path {
-        Some(v) => Path::new(v),
-        None => {
-            let db_dir = match UserDirs::new() {
-                Some(v) => {
-                    let path = v.home_dir().to_path_buf().join(".fdn");
-                    if !path.exists() {
-                        match fs::create_dir_all(path.clone()) {
-                            Ok(()) => path,
-                            Err(err) => panic!("{}", err),
-                        }
-                    } else {
-                        path
-                    }
-                }
-                None => PathBuf::from("."),
-            };
-            &db_dir.join(DEFAULT_DB_NAME)
-        }
-    };
+    Ok(())
+}
 
-    if !db_path.exists() {
-        match Connection::open(db_path) {
-            core::result::Result::Ok(conn) => {
-                //Create separators table and initial it with default value
-                create_separators_table(&conn)?;
-                let sep = Separator {
-                    id: 0,
-                    value: SEP_WORD.to_owned(),
-                };
-                insert_separator(&conn, &sep.value)?;
+///Retrieve a setting value by key via database connection
+pub fn retrieve_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
+    let mut rows = stmt.query_map(params![key], |row| row.get(0))?;
 
-                //Create to_sep_words table and initial it with default value
-                create_to_sep_words_table(&conn)?;
-                for w in TOBE_SEP_S {
-                    let to_sep_word = ToSepWord {
-                        id: 0,
-                        value: w.to_owned(),
-                    };
-                    insert_to_sep_word(&conn, &to_sep_word.value)?;
-                }
+    Ok(rows.next().transpose()?)
+}
 
-                //Create term words table
-                create_term_words_table(&conn)?;
+///Retrieve all settings via database connection
+pub fn retrieve_settings(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT key,value FROM settings ORDER BY key")?;
+    let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
 
-                //Create records table
-                create_records_table(&conn)?;
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
 
-                Ok(conn)
-            }
-            Err(err) => Err(anyhow!(format!("{}", err))),
-        }
-    } else {
-        match Connection::open(db_path) {
-            core::result::Result::Ok(conn) => {
-                for (tb, c) in t_c_map {
-                    match table_exists(&conn, &tb) {
-                        Ok(true) => {}
-                        Ok(false) => c(&conn)?,
-                        Err(err) => return Err(anyhow!(format!("{}", err))),
-                    }
-                }
-                Ok(conn)
-            }
-            Err(err) => Err(anyhow!(format!("{}", err))),
-        }
+    Ok(results)
+}
+
+///Delete a setting by key via database connection
+pub fn delete_setting(conn: &Connection, key: &str) -> Result<()> {
+    conn.execute("DELETE FROM settings WHERE key = ?", params![key])?;
+
+    Ok(())
+}
+
+//////////ext_aliases
+///Create ext_aliases table via database connection
+pub fn create_ext_aliases_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ext_aliases (
+                    id      INTEGER PRIMARY KEY,
+                    ext     TEXT NOT NULL UNIQUE,
+                    alias   TEXT NOT NULL,
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    enabled INTEGER NOT NULL DEFAULT 1,
+                    tag     TEXT NOT NULL DEFAULT ''
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert or update the alias of an extension via database connection,extension compared in
+///lower case
+pub fn upsert_ext_alias(conn: &Connection, ext: &str, alias: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO ext_aliases (ext,alias) VALUES (?1,?2)
+            ON CONFLICT(ext) DO UPDATE SET alias = excluded.alias",
+        params![ext.to_lowercase(), alias],
+    )?;
+
+    Ok(())
+}
+
+///Insert the alias of an extension via database connection,an alias of the extension already
+///there kept
+fn insert_ext_alias(conn: &Connection, ext: &str, alias: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO ext_aliases (ext,alias) VALUES (?1,?2)",
+        params![ext.to_lowercase(), alias],
+    )?;
+
+    Ok(())
+}
+
+///Retrieve all extension aliases via database connection
+pub fn retrieve_ext_aliases(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt =
+        conn.prepare("SELECT ext,alias FROM ext_aliases WHERE enabled = 1 ORDER BY ext")?;
+    let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
     }
+
+    Ok(results)
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{open_db, utils::db::DEFAULT_DB_NAME};
-    use std::fs;
+///Delete the alias of an extension via database connection,true if it existed
+pub fn delete_ext_alias(conn: &Connection, ext: &str) -> Result<bool> {
+    let n = conn.execute(
+        "DELETE FROM ext_aliases WHERE ext = ?",
+        params![ext.to_lowercase()],
+    )?;
 
-    #[test]
-    fn test_initial_db() {
-        let exist = fs::metadata(DEFAULT_DB_NAME).is_ok();
-        let rlt = open_db(Some(DEFAULT_DB_NAME));
-        assert!(rlt.is_ok());
-        assert!(fs::metadata(DEFAULT_DB_NAME).is_ok());
-        if !exist {
-            assert!(fs::remove_file(DEFAULT_DB_NAME).is_ok());
-        }
+    Ok(n > 0)
+}
+
+//////////acronyms
+///Create acronyms table via database connection
+pub fn create_acronyms_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS acronyms (
+                    id      INTEGER PRIMARY KEY,
+                    word    TEXT NOT NULL UNIQUE,
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    enabled INTEGER NOT NULL DEFAULT 1,
+                    tag     TEXT NOT NULL DEFAULT ''
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert an acronym via database connection,ignored if it exists
+pub fn insert_acronym(conn: &Connection, word: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO acronyms (word) VALUES (?)",
+        params![word],
+    )?;
+
+    Ok(())
+}
+
+///Retrieve all acronyms via database connection
+pub fn retrieve_acronyms(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT word FROM acronyms WHERE enabled = 1 ORDER BY word")?;
+    let rows = stmt.query_map(params![], |row| row.get(0))?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+///Delete an acronym via database connection,true if it existed
+pub fn delete_acronym(conn: &Connection, word: &str) -> Result<bool> {
+    let n = conn.execute("DELETE FROM acronyms WHERE word = ?", params![word])?;
+
+    Ok(n > 0)
+}
+
+//////////stopwords
+///Create stopwords table via database connection,language empty for all languages
+pub fn create_stopwords_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stopwords (
+                    id      INTEGER PRIMARY KEY,
+                    word    TEXT NOT NULL,
+                    lang    TEXT NOT NULL DEFAULT '',
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    enabled INTEGER NOT NULL DEFAULT 1,
+                    tag     TEXT NOT NULL DEFAULT '',
+                    UNIQUE(word, lang)
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert a stopword of a language via database connection,ignored if it exists
+pub fn insert_stopword(conn: &Connection, word: &str, lang: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO stopwords (word,lang) VALUES (?1,?2)",
+        params![word, lang],
+    )?;
+
+    Ok(())
+}
+
+///Retrieve all stopwords with their languages via database connection
+pub fn retrieve_stopwords(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt =
+        conn.prepare("SELECT word,lang FROM stopwords WHERE enabled = 1 ORDER BY lang,id")?;
+    let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+///Delete a stopword of a language via database connection,true if it existed
+pub fn delete_stopword(conn: &Connection, word: &str, lang: &str) -> Result<bool> {
+    let n = conn.execute(
+        "DELETE FROM stopwords WHERE word = ?1 AND lang = ?2",
+        params![word, lang],
+    )?;
+
+    Ok(n > 0)
+}
+
+//////////small words
+///Create small words table via database connection
+pub fn create_small_words_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS small_words (
+                    id      INTEGER PRIMARY KEY,
+                    word    TEXT NOT NULL UNIQUE,
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    enabled INTEGER NOT NULL DEFAULT 1,
+                    tag     TEXT NOT NULL DEFAULT ''
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert a small word in lowercase via database connection,ignored if it exists
+pub fn insert_small_word(conn: &Connection, word: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO small_words (word) VALUES (?)",
+        params![word.to_lowercase()],
+    )?;
+
+    Ok(())
+}
+
+///Retrieve all small words via database connection
+pub fn retrieve_small_words(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT word FROM small_words WHERE enabled = 1 ORDER BY word")?;
+    let rows = stmt.query_map(params![], |row| row.get(0))?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+///Delete a small word via database connection,true if it existed
+pub fn delete_small_word(conn: &Connection, word: &str) -> Result<bool> {
+    let n = conn.execute(
+        "DELETE FROM small_words WHERE word = ?",
+        params![word.to_lowercase()],
+    )?;
+
+    Ok(n > 0)
+}
+
+//////////operations
+///Create operations table via database connection
+pub fn create_operations_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operations (
+                    id      INTEGER PRIMARY KEY,
+                    session TEXT NOT NULL,
+                    dir     TEXT NOT NULL,
+                    origin  TEXT NOT NULL,
+                    target  TEXT NOT NULL,
+                    status  TEXT NOT NULL,
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert into operations via database connection
+pub fn insert_operation(conn: &Connection, op: &Operation) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO operations (session, dir, origin, target, status) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    stmt.execute(params![op.session, op.dir, op.origin, op.target, op.status])?;
+
+    Ok(())
+}
+
+///Retrieve operations after id via database connection,optionally filtered by directory
+///prefix and status,only the latest "limit" operations are returned in ascending order
+pub fn retrieve_operations(
+    conn: &Connection,
+    after_id: i32,
+    dir: Option<&str>,
+    status: Option<&str>,
+    limit: usize,
+) -> Result<Vec<Operation>> {
+    let mut stmt = conn.prepare(
+        "SELECT id,session,dir,origin,target,status,created FROM operations
+            WHERE id > ?1
+                AND (?2 IS NULL OR dir = ?2 OR dir LIKE ?2 || '/%')
+                AND (?3 IS NULL OR status = ?3)
+            ORDER BY id DESC LIMIT ?4",
+    )?;
+    let rows = stmt.query_map(params![after_id, dir, status, limit as i64], |row| {
+        Ok(Operation {
+            id: row.get(0)?,
+            session: row.get(1)?,
+            dir: row.get(2)?,
+            origin: row.get(3)?,
+            target: row.get(4)?,
+            status: row.get(5)?,
+            created: row.get(6)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+    results.reverse();
+
+    Ok(results)
+}
+
+///Retrieve id of the latest operation via database connection,0 if there is none
+pub fn latest_operation_id(conn: &Connection) -> Result<i32> {
+    Ok(
+        conn.query_row("SELECT COALESCE(MAX(id), 0) FROM operations", [], |row| {
+            row.get(0)
+        })?,
+    )
+}
+
+///Retrieve all operations of a session via database connection in ascending order
+pub fn retrieve_session_operations(conn: &Connection, session: &str) -> Result<Vec<Operation>> {
+    let mut stmt = conn.prepare(
+        "SELECT id,session,dir,origin,target,status,created FROM operations
+            WHERE session = ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![session], |row| {
+        Ok(Operation {
+            id: row.get(0)?,
+            session: row.get(1)?,
+            dir: row.get(2)?,
+            origin: row.get(3)?,
+            target: row.get(4)?,
+            status: row.get(5)?,
+            created: row.get(6)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+//////////checkpoints
+///Create checkpoints table via database connection
+pub fn create_checkpoints_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS checkpoints (
+                    id      INTEGER PRIMARY KEY,
+                    session TEXT NOT NULL UNIQUE,
+                    argv    TEXT NOT NULL,
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Create checkpoint paths table via database connection
+pub fn create_checkpoint_paths_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS checkpoint_paths (
+                    id      INTEGER PRIMARY KEY,
+                    session TEXT NOT NULL,
+                    path    TEXT NOT NULL,
+                    target  TEXT,
+                    done    INTEGER NOT NULL DEFAULT 0,
+                    UNIQUE(session, path)
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert checkpoint of session and its pending paths with optional targets via database
+///connection,paths already in checkpoint are kept as they are
+pub fn insert_checkpoint(
+    conn: &Connection,
+    session: &str,
+    argv: &[String],
+    paths: &[(String, Option<String>)],
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT OR IGNORE INTO checkpoints (session,argv) VALUES (?1,?2)",
+        params![session, serde_json::to_string(argv)?],
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR IGNORE INTO checkpoint_paths (session,path,target) VALUES (?1,?2,?3)",
+        )?;
+        for (path, target) in paths {
+            stmt.execute(params![session, path, target])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
+///Mark a path of session checkpoint processed via database connection
+pub fn update_checkpoint_path(conn: &Connection, session: &str, path: &str) -> Result<()> {
+    let mut stmt = conn
+        .prepare_cached("UPDATE checkpoint_paths SET done = 1 WHERE session = ?1 AND path = ?2")?;
+    stmt.execute(params![session, path])?;
+
+    Ok(())
+}
+
+///Retrieve checkpoints with their pending paths count via database connection,latest first
+pub fn retrieve_checkpoints(conn: &Connection) -> Result<Vec<Checkpoint>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.session,c.argv,c.created,
+                (SELECT COUNT(*) FROM checkpoint_paths p WHERE p.session = c.session AND p.done = 0)
+            FROM checkpoints c ORDER BY c.id DESC",
+    )?;
+    let rows = stmt.query_map(params![], |row| {
+        Ok((
+            row.get(0)?,
+            row.get::<_, String>(1)?,
+            row.get(2)?,
+            row.get(3)?,
+        ))
+    })?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        let (session, argv, created, pending) = row_rlt?;
+        results.push(Checkpoint {
+            session,
+            argv: serde_json::from_str(&argv)?,
+            pending,
+            created,
+        });
+    }
+
+    Ok(results)
+}
+
+///Retrieve pending paths and their targets of session checkpoint via database connection
+pub fn retrieve_pending_paths(
+    conn: &Connection,
+    session: &str,
+) -> Result<Vec<(String, Option<String>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT path,target FROM checkpoint_paths WHERE session = ?1 AND done = 0 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![session], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+///Delete processed paths of session checkpoint via database connection,and the checkpoint
+///itself if no path pending
+pub fn delete_checkpoint_done(conn: &Connection, session: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM checkpoint_paths WHERE session = ?1 AND done = 1",
+        params![session],
+    )?;
+    conn.execute(
+        "DELETE FROM checkpoints WHERE session = ?1
+            AND NOT EXISTS (SELECT 1 FROM checkpoint_paths WHERE session = ?1)",
+        params![session],
+    )?;
+
+    Ok(())
+}
+
+///Delete checkpoint of session and all its paths via database connection
+pub fn delete_checkpoint(conn: &Connection, session: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM checkpoint_paths WHERE session = ?1",
+        params![session],
+    )?;
+    conn.execute(
+        "DELETE FROM checkpoints WHERE session = ?1",
+        params![session],
+    )?;
+
+    Ok(())
+}
+
+//////////rule_stats
+///Create rule stats table via database connection
+pub fn create_rule_stats_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rule_stats (
+                    id      INTEGER PRIMARY KEY,
+                    stage   TEXT NOT NULL,
+                    rule    TEXT NOT NULL,
+                    hits    INTEGER NOT NULL DEFAULT 0,
+                    updated TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+                    UNIQUE(stage, rule)
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Add hits of a rule via database connection
+pub fn upsert_rule_hits(conn: &Connection, stage: &str, rule: &str, hits: usize) -> Result<()> {
+    conn.execute(
+        "INSERT INTO rule_stats (stage,rule,hits) VALUES (?1,?2,?3)
+            ON CONFLICT(stage,rule) DO UPDATE SET hits = hits + excluded.hits,
+            updated = STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')",
+        params![stage, rule, hits],
+    )?;
+
+    Ok(())
+}
+
+///Retrieve hits of rules by stage and rule via database connection
+pub fn retrieve_rule_stats(conn: &Connection) -> Result<HashMap<(String, String), usize>> {
+    let mut stmt = conn.prepare("SELECT stage,rule,hits FROM rule_stats")?;
+    let rows = stmt.query_map(params![], |row| {
+        Ok(((row.get(0)?, row.get(1)?), row.get(2)?))
+    })?;
+
+    let mut results = HashMap::new();
+    for row_rlt in rows {
+        let (k, v) = row_rlt?;
+        results.insert(k, v);
+    }
+
+    Ok(results)
+}
+
+///Delete all rule stats via database connection
+pub fn delete_rule_stats(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM rule_stats", ())?;
+
+    Ok(())
+}
+
+//////////config rows
+///Table of a kind of config rows
+pub fn kind_table(kind: &str) -> Result<&'static str> {
+    Ok(config_kind(kind)?.1)
+}
+
+//
+fn config_kind(kind: &str) -> Result<(&'static str, &'static str, &'static str, &'static str)> {
+    CONFIG_KINDS
+        .iter()
+        .find(|(k, ..)| *k == kind)
+        .copied()
+        .ok_or_else(|| {
+            anyhow!(
+                "Kind must be one of {:?}:{:?}",
+                CONFIG_KINDS.map(|(k, ..)| k),
+                kind
+            )
+        })
+}
+
+///Delete a config row of a kind by id via database connection,term words soft deleted,false
+///if not found
+pub fn delete_config_row(conn: &Connection, kind: &str, id: i32) -> Result<bool> {
+    let sql = match kind {
+        "term" => "UPDATE term_words SET deleted_at = STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')
+            WHERE id = ?1 AND deleted_at IS NULL"
+            .to_owned(),
+        _ => format!("DELETE FROM {} WHERE id = ?1", kind_table(kind)?),
+    };
+    let n = conn.execute(&sql, params![id])?;
+
+    Ok(n > 0)
+}
+
+///Update the value of a config row of a kind by id via database connection,such as the value
+///of a term word or the replacement of a regex rule,false if not found
+pub fn update_config_row(conn: &Connection, kind: &str, id: i32, value: &str) -> Result<bool> {
+    let (_, table, _, column) = config_kind(kind)?;
+    let n = conn.execute(
+        &format!("UPDATE {} SET {} = ?1 WHERE id = ?2", table, column),
+        params![value, id],
+    )?;
+
+    Ok(n > 0)
+}
+
+///Enable or disable a config row of a kind by id via database connection,false if not found
+pub fn update_enabled(conn: &Connection, kind: &str, id: i32, enabled: bool) -> Result<bool> {
+    let n = conn.execute(
+        &format!(
+            "UPDATE {} SET enabled = ?1 WHERE id = ?2",
+            kind_table(kind)?
+        ),
+        params![enabled, id],
+    )?;
+
+    Ok(n > 0)
+}
+
+///Enable or disable config rows of all kinds with the tag via database connection,return the
+///number of rows
+pub fn update_tag_enabled(conn: &Connection, tag: &str, enabled: bool) -> Result<usize> {
+    let mut n = 0;
+    for (_, table, ..) in CONFIG_KINDS {
+        n += conn.execute(
+            &format!("UPDATE {} SET enabled = ?1 WHERE tag = ?2", table),
+            params![enabled, tag],
+        )?;
+    }
+
+    Ok(n)
+}
+
+///Tag a config row of a kind by id via database connection,empty tag to untag,false if not
+///found
+pub fn update_tag(conn: &Connection, kind: &str, id: i32, tag: &str) -> Result<bool> {
+    let n = conn.execute(
+        &format!("UPDATE {} SET tag = ?1 WHERE id = ?2", kind_table(kind)?),
+        params![tag, id],
+    )?;
+
+    Ok(n > 0)
+}
+
+///Retrieve config rows of all kinds which are disabled or tagged via database connection,soft
+///deleted term words left out
+pub fn retrieve_toggled_rows(conn: &Connection) -> Result<Vec<ConfigRow>> {
+    let mut results = Vec::new();
+    for (kind, ..) in CONFIG_KINDS {
+        results.extend(retrieve_config_rows_where(
+            conn,
+            kind,
+            "enabled = 0 OR tag != ''",
+        )?);
+    }
+
+    Ok(results)
+}
+
+///Retrieve enabled config rows of a kind via database connection,soft deleted term words left
+///out
+pub fn retrieve_config_rows(conn: &Connection, kind: &str) -> Result<Vec<ConfigRow>> {
+    retrieve_config_rows_where(conn, kind, "enabled = 1")
+}
+
+fn retrieve_config_rows_where(conn: &Connection, kind: &str, cond: &str) -> Result<Vec<ConfigRow>> {
+    let (kind, table, rule, _) = config_kind(kind)?;
+    let deleted = match table {
+        "term_words" => " AND deleted_at IS NULL",
+        _ => "",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id,{},tag,enabled FROM {} WHERE ({}){} ORDER BY id",
+        rule, table, cond, deleted
+    ))?;
+    let rows = stmt.query_map(params![], |row| {
+        Ok(ConfigRow {
+            kind: kind.to_owned(),
+            id: row.get(0)?,
+            rule: row.get(1)?,
+            tag: row.get(2)?,
+            enabled: row.get(3)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+//
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+///Migrate tables created by older versions
+fn migrate(conn: &Connection) -> Result<()> {
+    if table_exists(conn, "records")? && !column_exists(conn, "records", "encrypted_dir")? {
+        conn.execute("ALTER TABLE records ADD COLUMN encrypted_dir TEXT", ())?;
+    }
+    if !column_exists(conn, "term_words", "deleted_at")? {
+        conn.execute("ALTER TABLE term_words ADD COLUMN deleted_at TIMESTAMP", ())?;
+    }
+    for column in ["ignore_case", "whole_word"] {
+        if !column_exists(conn, "term_words", column)? {
+            conn.execute(
+                &format!(
+                    "ALTER TABLE term_words ADD COLUMN {} INTEGER NOT NULL DEFAULT 0",
+                    column
+                ),
+                (),
+            )?;
+        }
+    }
+
+    for table in ["to_sep_words", "term_words", "regex_rules"] {
+        if !column_exists(conn, table, "scope")? {
+            conn.execute(
+                &format!(
+                    "ALTER TABLE {} ADD COLUMN scope TEXT NOT NULL DEFAULT ''",
+                    table
+                ),
+                (),
+            )?;
+        }
+    }
+
+    for (_, table, ..) in CONFIG_KINDS {
+        for (column, def) in [
+            ("enabled", "INTEGER NOT NULL DEFAULT 1"),
+            ("tag", "TEXT NOT NULL DEFAULT ''"),
+        ] {
+            if !column_exists(conn, table, column)? {
+                conn.execute(
+                    &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, def),
+                    (),
+                )?;
+            }
+        }
+    }
+
+    //acronyms came with version 3,seeded once so that deleted ones stay deleted
+    if schema_version(conn)? < 3 {
+        for word in ACRONYMS {
+            insert_acronym(conn, word)?;
+        }
+    }
+
+    //extension aliases came with version 3,seeded once and without overwriting changed ones
+    if schema_version(conn)? < 3 {
+        for (ext, alias) in EXT_ALIASES {
+            insert_ext_alias(conn, ext, alias)?;
+        }
+    }
+
+    //small words came with version 4,seeded once so that deleted ones stay deleted
+    if schema_version(conn)? < 4 {
+        for word in SMALL_WORDS {
+            insert_small_word(conn, word)?;
+        }
+    }
+
+    if schema_version(conn)? < SCHEMA_VERSION {
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+
+    Ok(())
+}
+
+//////////backup
+///Tables of the database of a part,one of DB_PARTS
+fn part_tables(conn: &Connection, schema: &str, part: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name FROM {}.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        schema
+    ))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+    let mut tables = Vec::new();
+    for name in names {
+        let name = name?;
+        if name == BACKUP_PART_TABLE {
+            continue;
+        }
+        let record = RECORD_TABLES.contains(&name.as_str());
+        if part == "all" || (part == "records") == record {
+            tables.push(name);
+        }
+    }
+
+    Ok(tables)
+}
+
+///Backup a part of the database into a new file by the backup API via database connection,
+///tables of other parts left empty
+pub fn backup_db(conn: &Connection, path: &Path, part: &str) -> Result<()> {
+    if path.exists() {
+        return Err(anyhow!("Backup file exists:{:?}", path));
+    }
+    conn.backup(DatabaseName::Main, path, None)?;
+
+    if part != "all" {
+        let bak = Connection::open(path)?;
+        let kept = part_tables(&bak, "main", part)?;
+        for table in part_tables(&bak, "main", "all")? {
+            if !kept.contains(&table) {
+                bak.execute(&format!("DELETE FROM {}", table), ())?;
+            }
+        }
+        bak.execute(
+            &format!("CREATE TABLE {} (part TEXT NOT NULL)", BACKUP_PART_TABLE),
+            (),
+        )?;
+        bak.execute(
+            &format!("INSERT INTO {} (part) VALUES (?1)", BACKUP_PART_TABLE),
+            params![part],
+        )?;
+        bak.execute("VACUUM", ())?;
+    }
+
+    Ok(())
+}
+
+///Restore a part of the database from a backup file via database connection,the whole
+///database replaced by the backup API for all parts,otherwise only tables of the part
+///replaced by their rows in the backup;all parts of a backup of one part are that part
+pub fn restore_db(conn: &mut Connection, path: &Path, part: &str) -> Result<()> {
+    if !path.is_file() {
+        return Err(anyhow!("Backup file not exists:{:?}", path));
+    }
+    let bak = Connection::open(path)?;
+    if !table_exists(&bak, "separators")? {
+        return Err(anyhow!("Not a backup of fdn database:{:?}", path));
+    }
+    let backed: String = match table_exists(&bak, BACKUP_PART_TABLE)? {
+        true => bak.query_row(
+            &format!("SELECT part FROM {}", BACKUP_PART_TABLE),
+            [],
+            |row| row.get(0),
+        )?,
+        false => "all".to_owned(),
+    };
+    drop(bak);
+    let part = match (part, backed.as_str()) {
+        ("all", backed) => backed,
+        (part, "all") => part,
+        (part, backed) if part == backed => part,
+        (part, backed) => return Err(anyhow!("Backup of {} has no {}:{:?}", backed, part, path)),
+    };
+
+    if part == "all" {
+        conn.restore(DatabaseName::Main, path, None::<fn(_)>)?;
+        return migrate(conn);
+    }
+
+    conn.execute("ATTACH DATABASE ?1 AS bak", params![path.to_string_lossy()])?;
+    let rlt = (|| -> Result<()> {
+        let bak_tables = part_tables(conn, "bak", part)?;
+        let tx = conn.transaction()?;
+        for table in part_tables(&tx, "main", part)? {
+            tx.execute(&format!("DELETE FROM main.{}", table), ())?;
+            if !bak_tables.contains(&table) {
+                continue;
+            }
+            //columns of both so that backups of older schemas are restored too
+            let columns = table_columns(&tx, "bak", &table)?;
+            let main_columns = table_columns(&tx, "main", &table)?;
+            let columns: Vec<_> = columns
+                .into_iter()
+                .filter(|c| main_columns.contains(c))
+                .collect();
+            tx.execute(
+                &format!(
+                    "INSERT INTO main.{0} ({1}) SELECT {1} FROM bak.{0}",
+                    table,
+                    columns.join(",")
+                ),
+                (),
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    })();
+    conn.execute("DETACH DATABASE bak", ())?;
+
+    rlt
+}
+
+//
+fn table_columns(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA {}.table_info({})", schema, table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+
+    let mut columns = Vec::new();
+    for name in names {
+        columns.push(name?);
+    }
+
+    Ok(columns)
+}
+
+//////////packs
+///Create packs table of installed rule packs via database connection
+pub fn create_packs_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS packs (
+                    id      INTEGER PRIMARY KEY,
+                    name    TEXT NOT NULL UNIQUE,
+                    version TEXT NOT NULL,
+                    source  TEXT NOT NULL,
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert or update an installed pack with its version and source via database connection
+pub fn upsert_pack(conn: &Connection, name: &str, version: &str, source: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO packs (name,version,source) VALUES (?1,?2,?3)
+            ON CONFLICT(name) DO UPDATE SET version = excluded.version,source = excluded.source",
+        params![name, version, source],
+    )?;
+
+    Ok(())
+}
+
+///Retrieve installed packs as (name,version,source) via database connection
+pub fn retrieve_packs(conn: &Connection) -> Result<Vec<(String, String, String)>> {
+    let mut stmt = conn.prepare("SELECT name,version,source FROM packs ORDER BY name")?;
+    let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+///Delete an installed pack by name via database connection,false if not found
+pub fn delete_pack(conn: &Connection, name: &str) -> Result<bool> {
+    let n = conn.execute("DELETE FROM packs WHERE name = ?1", params![name])?;
+
+    Ok(n > 0)
+}
+
+///Delete config rows of all kinds of a tag via database connection,number of rows deleted
+pub fn delete_tag_rows(conn: &Connection, tag: &str) -> Result<usize> {
+    let mut n = 0;
+    for (_, table, ..) in CONFIG_KINDS {
+        n += conn.execute(
+            &format!("DELETE FROM {} WHERE tag = ?1", table),
+            params![tag],
+        )?;
+    }
+
+    Ok(n)
+}
+
+///Open an empty database in memory with tables of rules only,such as for rules of a pack
+///compared with those of the database before installed
+pub fn open_rules_db() -> Result<Connection> {
+    let conn = Connection::open_in_memory()?;
+    for create in [
+        create_separators_table,
+        create_to_sep_words_table,
+        create_term_words_table,
+        create_regex_rules_table,
+        create_ext_aliases_table,
+        create_acronyms_table,
+        create_stopwords_table,
+        create_small_words_table,
+    ] {
+        create(&conn)?;
+    }
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
+    Ok(conn)
+}
+
+//////////aliases
+///Create aliases table of saved invocations via database connection
+pub fn create_aliases_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS aliases (
+                    id      INTEGER PRIMARY KEY,
+                    name    TEXT NOT NULL UNIQUE,
+                    argv    TEXT NOT NULL,
+                    created TIMESTAMP DEFAULT (STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+                )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+///Insert or update an alias with its arguments in JSON via database connection
+pub fn upsert_alias(conn: &Connection, name: &str, argv: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO aliases (name,argv) VALUES (?1,?2)
+            ON CONFLICT(name) DO UPDATE SET argv = excluded.argv",
+        params![name, argv],
+    )?;
+
+    Ok(())
+}
+
+///Retrieve arguments in JSON of an alias by name via database connection
+pub fn retrieve_alias(conn: &Connection, name: &str) -> Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT argv FROM aliases WHERE name = ?1")?;
+    let mut rows = stmt.query_map(params![name], |row| row.get(0))?;
+
+    Ok(rows.next().transpose()?)
+}
+
+///Retrieve all aliases with their arguments in JSON via database connection
+pub fn retrieve_aliases(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT name,argv FROM aliases ORDER BY name")?;
+    let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+///Delete an alias by name via database connection,false if not found
+pub fn delete_alias(conn: &Connection, name: &str) -> Result<bool> {
+    let n = conn.execute("DELETE FROM aliases WHERE name = ?1", params![name])?;
+
+    Ok(n > 0)
+}
+
+//////////diff and merge
+///Key of config rows of a kind,so that rows of the same key in two databases are the same rule,
+///a regex rule by its pattern so that a changed replacement differs rather than being another rule
+fn kind_key(kind: &str) -> &'static str {
+    match kind {
+        "term" => "key",
+        "re" => "pattern",
+        "ext" => "ext",
+        "stop" => "lang || ':' || word",
+        "sep" | "to-sep" => "value",
+        _ => "word",
+    }
+}
+
+///Open another fdn database copied into memory and migrated to the current schema,the file left
+///untouched
+pub fn open_other_db(path: &Path) -> Result<Connection> {
+    if !path.is_file() {
+        return Err(anyhow!("Database not exists:{:?}", path));
+    }
+    let mut conn = Connection::open_in_memory()?;
+    conn.restore(DatabaseName::Main, path, None::<fn(_)>)?;
+    if !table_exists(&conn, "separators")? {
+        return Err(anyhow!("Not an fdn database:{:?}", path));
+    }
+    for create in [
+        create_to_sep_words_table,
+        create_term_words_table,
+        create_regex_rules_table,
+        create_ext_aliases_table,
+        create_acronyms_table,
+        create_stopwords_table,
+        create_small_words_table,
+    ] {
+        create(&conn)?;
+    }
+    migrate(&conn)?;
+
+    Ok(conn)
+}
+
+///Config rows of a kind with their keys and values of columns compared
+fn retrieve_keyed_rows(
+    conn: &Connection,
+    kind: &str,
+    columns: &[String],
+) -> Result<Vec<(String, ConfigRow, Vec<Value>)>> {
+    let (kind, table, rule, _) = config_kind(kind)?;
+    let deleted = match table {
+        "term_words" => " WHERE deleted_at IS NULL",
+        _ => "",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {},id,{},tag,enabled,{} FROM {}{} ORDER BY id",
+        kind_key(kind),
+        rule,
+        columns.join(","),
+        table,
+        deleted
+    ))?;
+    let rows = stmt.query_map(params![], |row| {
+        let values = (0..columns.len())
+            .map(|i| row.get(5 + i))
+            .collect::<rusqlite::Result<_>>()?;
+        let config_row = ConfigRow {
+            kind: kind.to_owned(),
+            id: row.get(1)?,
+            rule: row.get(2)?,
+            tag: row.get(3)?,
+            enabled: row.get(4)?,
+        };
+        Ok((row.get(0)?, config_row, values))
+    })?;
+
+    let mut results = Vec::new();
+    for row_rlt in rows {
+        results.push(row_rlt?);
+    }
+
+    Ok(results)
+}
+
+///Config rows differing between the database and another one via database connections,rows of
+///the database first then those only in the other one
+pub fn diff_config_rows(conn: &Connection, other: &Connection) -> Result<Vec<RuleDiff>> {
+    let mut diffs = Vec::new();
+    for (kind, table, ..) in CONFIG_KINDS {
+        let columns: Vec<_> = table_columns(conn, "main", table)?
+            .into_iter()
+            .filter(|c| !UNCOMPARED_COLUMNS.contains(&c.as_str()))
+            .collect();
+        let locals = retrieve_keyed_rows(conn, kind, &columns)?;
+        let others = retrieve_keyed_rows(other, kind, &columns)?;
+        let others_map: HashMap<_, _> = others.iter().map(|(k, r, v)| (k, (r, v))).collect();
+        let local_keys: HashSet<_> = locals.iter().map(|(k, ..)| k).collect();
+
+        for (key, row, values) in &locals {
+            let diff = match others_map.get(key) {
+                None => RuleDiff {
+                    key: key.clone(),
+                    local: Some(row.clone()),
+                    other: None,
+                    columns: Vec::new(),
+                },
+                Some((o, ov)) if *ov != values => RuleDiff {
+                    key: key.clone(),
+                    local: Some(row.clone()),
+                    other: Some((*o).clone()),
+                    columns: columns
+                        .iter()
+                        .zip(values.iter().zip(ov.iter()))
+                        .filter(|(_, (a, b))| a != b)
+                        .map(|(c, _)| c.clone())
+                        .collect(),
+                },
+                Some(_) => continue,
+            };
+            diffs.push(diff);
+        }
+        for (key, row, _) in &others {
+            if !local_keys.contains(key) {
+                diffs.push(RuleDiff {
+                    key: key.clone(),
+                    local: None,
+                    other: Some(row.clone()),
+                    columns: Vec::new(),
+                });
+            }
+        }
+    }
+
+    Ok(diffs)
+}
+
+///Copy a config row of a kind by id from another database via database connections,replacing
+///the row of the id given and any other row it then collides with
+pub fn copy_config_row(
+    from: &Connection,
+    to: &Connection,
+    kind: &str,
+    id: i32,
+    replace: Option<i32>,
+) -> Result<()> {
+    let table = kind_table(kind)?;
+    let columns: Vec<_> = table_columns(to, "main", table)?
+        .into_iter()
+        .filter(|c| c != "id")
+        .collect();
+    let mut values: Vec<Value> = from.query_row(
+        &format!("SELECT {} FROM {} WHERE id = ?1", columns.join(","), table),
+        [id],
+        |row| (0..columns.len()).map(|i| row.get(i)).collect(),
+    )?;
+    let sql = match replace {
+        Some(to_id) => {
+            values.push(Value::Integer(to_id.into()));
+            let sets: Vec<_> = columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| format!("{} = ?{}", c, i + 1))
+                .collect();
+            format!(
+                "UPDATE OR REPLACE {} SET {} WHERE id = ?{}",
+                table,
+                sets.join(","),
+                columns.len() + 1
+            )
+        }
+        //a soft deleted term word of the same key replaced
+        None => format!(
+            "INSERT OR REPLACE INTO {} ({}) VALUES ({})",
+            table,
+            columns.join(","),
+            vec!["?"; columns.len()].join(",")
+        ),
+    };
+    to.execute(&sql, params_from_iter(values))?;
+
+    Ok(())
+}
+
+///Schema version of the database via database connection
+pub fn schema_version(conn: &Connection) -> Result<i32> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+//
+fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
+    let sql = format!(
+        "SELECT name FROM sqlite_master WHERE type='table' AND name='{}';",
+        name
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let exists = stmt.exists([])?;
+
+    Ok(exists)
+}
+
+///Return default database path and create its directory if not exists
+pub fn default_db_path() -> Result<PathBuf> {
+    let db_dir = match UserDirs::new() {
+        Some(v) => {
+            let path = v.home_dir().to_path_buf().join(CONFIG_DIR_NAME);
+            if !path.exists() {
+                fs::create_dir_all(path.clone())?;
+            }
+            path
+        }
+        None => PathBuf::from("."),
+    };
+
+    Ok(db_dir.join(DEFAULT_DB_NAME))
+}
+
+///Open database and return database connection via Result
+pub fn open_db(db_path: Option<&str>) -> Result<Connection> {
+    let mut t_c_map: HashMap<String, fn(&Connection) -> Result<()>> = HashMap::new();
+    t_c_map.insert(String::from("separators"), create_separators_table);
+    t_c_map.insert(String::from("to_sep_words"), create_to_sep_words_table);
+    t_c_map.insert(String::from("term_words"), create_term_words_table);
+    t_c_map.insert(String::from("regex_rules"), create_regex_rules_table);
+    t_c_map.insert(String::from("records"), create_records_table);
+    t_c_map.insert(String::from("operations"), create_operations_table);
+    t_c_map.insert(String::from("settings"), create_settings_table);
+    t_c_map.insert(String::from("checkpoints"), create_checkpoints_table);
+    t_c_map.insert(
+        String::from("checkpoint_paths"),
+        create_checkpoint_paths_table,
+    );
+    t_c_map.insert(String::from("rule_stats"), create_rule_stats_table);
+    t_c_map.insert(String::from("ext_aliases"), create_ext_aliases_table);
+    t_c_map.insert(String::from("acronyms"), create_acronyms_table);
+    t_c_map.insert(String::from("stopwords"), create_stopwords_table);
+    t_c_map.insert(String::from("small_words"), create_small_words_table);
+    t_c_map.insert(String::from("packs"), create_packs_table);
+    t_c_map.insert(String::from("aliases"), create_aliases_table);
+
+    let db_path = match db_path {
+        Some(v) => Path::new(v),
+        None => &default_db_path()?,
+    };
+
+    //another fdn may hold the lock while creating or migrating tables
+    let mut delay = BUSY_RETRY_DELAY;
+    for _ in 1..BUSY_RETRY_TIMES {
+        match open_db_once(db_path, &t_c_map) {
+            Err(err) if is_busy(&err) => {
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            rlt => return rlt,
+        }
+    }
+
+    open_db_once(db_path, &t_c_map)
+}
+
+///Check an error is caused by database busy or locked
+fn is_busy(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<rusqlite::Error>() {
+        Some(rusqlite::Error::SqliteFailure(e, _)) => {
+            matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+        }
+        _ => false,
+    }
+}
+
+///Open connection in WAL journal mode with busy timeout
+fn connect(db_path: &Path) -> Result<Connection> {
+    let conn = Connection::open(db_path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))?;
+
+    Ok(conn)
+}
+
+fn open_db_once(
+    db_path: &Path,
+    t_c_map: &HashMap<String, fn(&Connection) -> Result<()>>,
+) -> Result<Connection> {
+    if !db_path.exists() {
+        match connect(db_path) {
+            core::result::Result::Ok(conn) => {
+                //Create separators table and initial it with default value
+                create_separators_table(&conn)?;
+                let sep = Separator {
+                    id: 0,
+                    value: SEP_WORD.to_owned(),
+                };
+                insert_separator(&conn, &sep.value)?;
+
+                //Create to_sep_words table and initial it with default value
+                create_to_sep_words_table(&conn)?;
+                for w in TOBE_SEP_S {
+                    insert_to_sep_word(&conn, w, "")?;
+                }
+
+                //Create term words table
+                create_term_words_table(&conn)?;
+
+                //Create regex rules table
+                create_regex_rules_table(&conn)?;
+
+                //Create records table
+                create_records_table(&conn)?;
+
+                //Create operations table
+                create_operations_table(&conn)?;
+
+                //Create settings table
+                create_settings_table(&conn)?;
+
+                //Create checkpoints tables
+                create_checkpoints_table(&conn)?;
+                create_checkpoint_paths_table(&conn)?;
+
+                //Create rule stats table
+                create_rule_stats_table(&conn)?;
+
+                //Create extension aliases table,default value initialed by migrate
+                create_ext_aliases_table(&conn)?;
+
+                //Create acronyms table,default value initialed by migrate
+                create_acronyms_table(&conn)?;
+
+                //Create stopwords table
+                create_stopwords_table(&conn)?;
+
+                //Create small words table,default value initialed by migrate
+                create_small_words_table(&conn)?;
+
+                //Create packs table
+                create_packs_table(&conn)?;
+
+                //Create aliases table
+                create_aliases_table(&conn)?;
+
+                migrate(&conn)?;
+
+                Ok(conn)
+            }
+            Err(err) => Err(err),
+        }
+    } else {
+        match connect(db_path) {
+            core::result::Result::Ok(conn) => {
+                for (tb, c) in t_c_map {
+                    match table_exists(&conn, tb) {
+                        Ok(true) => {}
+                        Ok(false) => c(&conn)?,
+                        Err(err) => return Err(anyhow!(format!("{}", err))),
+                    }
+                }
+                migrate(&conn)?;
+                Ok(conn)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        open_db,
+        utils::db::{
+            backup_db, copy_config_row, delete_acronym, delete_checkpoint_done, delete_config_row,
+            delete_ext_alias, delete_rule_stats, delete_setting, delete_small_word,
+            delete_stopword, delete_term_word, diff_config_rows, insert_acronym, insert_checkpoint,
+            insert_operation, insert_regex_rule, insert_separator, insert_small_word,
+            insert_stopword, insert_term_word, latest_operation_id, open_other_db,
+            purge_term_words, restore_db, restore_term_word, retrieve_acronyms,
+            retrieve_checkpoints, retrieve_config_rows, retrieve_deleted_term_words,
+            retrieve_ext_aliases, retrieve_operations, retrieve_pending_paths,
+            retrieve_regex_rules, retrieve_rule_stats, retrieve_separators, retrieve_setting,
+            retrieve_small_words, retrieve_stopwords, retrieve_term_words, retrieve_toggled_rows,
+            update_checkpoint_path, update_config_row, update_enabled, update_tag,
+            update_tag_enabled, upsert_ext_alias, upsert_rule_hits, upsert_setting,
+            DEFAULT_DB_NAME,
+        },
+        DirBase, Operation, TermFlags,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_initial_db() {
+        let exist = fs::metadata(DEFAULT_DB_NAME).is_ok();
+        let rlt = open_db(Some(DEFAULT_DB_NAME));
+        assert!(rlt.is_ok());
+        assert!(fs::metadata(DEFAULT_DB_NAME).is_ok());
+        //close connection to clean up WAL files
+        drop(rlt);
+        if !exist {
+            assert!(fs::remove_file(DEFAULT_DB_NAME).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_operations() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        for (dir, status) in [("/a", "renamed"), ("/a/b", "failed"), ("/ab", "renamed")] {
+            let d_b = DirBase {
+                dir: dir.to_owned(),
+                base: "x y".to_owned(),
+            };
+            let op = Operation::new("s", &d_b, "x_y", status);
+            insert_operation(&conn, &op).unwrap();
+        }
+
+        let ops = retrieve_operations(&conn, 0, Some("/a"), None, 10).unwrap();
+        assert_eq!(ops.len(), 2);
+        let ops = retrieve_operations(&conn, 0, None, Some("renamed"), 10).unwrap();
+        assert_eq!(ops.len(), 2);
+        let ops = retrieve_operations(&conn, 0, None, None, 1).unwrap();
+        assert_eq!(ops[0].dir, "/ab");
+        assert_eq!(latest_operation_id(&conn).unwrap(), ops[0].id);
+    }
+
+    #[test]
+    fn test_settings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        assert_eq!(retrieve_setting(&conn, "log_file").unwrap(), None);
+        upsert_setting(&conn, "log_file", "a.log").unwrap();
+        upsert_setting(&conn, "log_file", "b.log").unwrap();
+        assert_eq!(
+            retrieve_setting(&conn, "log_file").unwrap(),
+            Some("b.log".to_owned())
+        );
+        delete_setting(&conn, "log_file").unwrap();
+        assert_eq!(retrieve_setting(&conn, "log_file").unwrap(), None);
+    }
+
+    #[test]
+    fn test_ext_aliases() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        let aliases = retrieve_ext_aliases(&conn).unwrap();
+        assert!(aliases.contains(&("jpeg".to_owned(), "jpg".to_owned())));
+
+        upsert_ext_alias(&conn, "TIFF", "tif").unwrap();
+        upsert_ext_alias(&conn, "tiff", "TIF").unwrap();
+        let aliases = retrieve_ext_aliases(&conn).unwrap();
+        assert!(aliases.contains(&("tiff".to_owned(), "TIF".to_owned())));
+        assert!(delete_ext_alias(&conn, "TIFF").unwrap());
+        assert!(!delete_ext_alias(&conn, "tiff").unwrap());
+
+        //a database of version 2 seeded on open,changed aliases kept
+        assert!(delete_ext_alias(&conn, "jpeg").unwrap());
+        upsert_ext_alias(&conn, "htm", "HTML").unwrap();
+        conn.pragma_update(None, "user_version", 2).unwrap();
+        drop(conn);
+        let conn = open_db(db.to_str()).unwrap();
+        let aliases = retrieve_ext_aliases(&conn).unwrap();
+        assert!(aliases.contains(&("jpeg".to_owned(), "jpg".to_owned())));
+        assert!(aliases.contains(&("htm".to_owned(), "HTML".to_owned())));
+    }
+
+    #[test]
+    fn test_acronyms() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        assert!(retrieve_acronyms(&conn)
+            .unwrap()
+            .contains(&"PDF".to_owned()));
+
+        insert_acronym(&conn, "NASA").unwrap();
+        insert_acronym(&conn, "NASA").unwrap();
+        let acronyms = retrieve_acronyms(&conn).unwrap();
+        assert_eq!(acronyms.iter().filter(|a| *a == "NASA").count(), 1);
+        assert!(delete_acronym(&conn, "NASA").unwrap());
+        assert!(!delete_acronym(&conn, "NASA").unwrap());
+
+        //a database of version 2 seeded on open,one of the current version not
+        assert!(delete_acronym(&conn, "PDF").unwrap());
+        drop(conn);
+        let conn = open_db(db.to_str()).unwrap();
+        assert!(!retrieve_acronyms(&conn)
+            .unwrap()
+            .contains(&"PDF".to_owned()));
+        conn.pragma_update(None, "user_version", 2).unwrap();
+        drop(conn);
+        let conn = open_db(db.to_str()).unwrap();
+        assert!(retrieve_acronyms(&conn)
+            .unwrap()
+            .contains(&"PDF".to_owned()));
+    }
+
+    #[test]
+    fn test_small_words() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        assert!(retrieve_small_words(&conn)
+            .unwrap()
+            .contains(&"of".to_owned()));
+
+        insert_small_word(&conn, "Per").unwrap();
+        insert_small_word(&conn, "per").unwrap();
+        let words = retrieve_small_words(&conn).unwrap();
+        assert_eq!(words.iter().filter(|w| *w == "per").count(), 1);
+        assert!(delete_small_word(&conn, "PER").unwrap());
+        assert!(!delete_small_word(&conn, "per").unwrap());
+    }
+
+    #[test]
+    fn test_config_rows() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        insert_separator(&conn, "-").unwrap();
+        insert_term_word(&conn, "ft.", "feat.", TermFlags::default(), "").unwrap();
+        let seps = retrieve_config_rows(&conn, "sep").unwrap();
+        assert_eq!(
+            seps.iter().map(|r| r.rule.as_str()).collect::<Vec<_>>(),
+            ["_", "-"]
+        );
+        let jpeg = retrieve_config_rows(&conn, "ext")
+            .unwrap()
+            .into_iter()
+            .find(|r| r.rule == "ext:jpeg:jpg")
+            .unwrap();
+
+        assert!(delete_config_row(&conn, "sep", seps[0].id).unwrap());
+        assert!(!delete_config_row(&conn, "sep", seps[0].id).unwrap());
+        assert_eq!(retrieve_separators(&conn).unwrap()[0].value, "-");
+        let id = retrieve_term_words(&conn).unwrap()[0].id;
+        assert!(update_config_row(&conn, "term", id, "Feat.").unwrap());
+        assert_eq!(retrieve_term_words(&conn).unwrap()[0].value, "Feat.");
+        assert!(delete_config_row(&conn, "term", id).unwrap());
+        assert_eq!(retrieve_deleted_term_words(&conn).unwrap().len(), 1);
+        assert!(retrieve_config_rows(&conn, "term").unwrap().is_empty());
+        assert!(update_config_row(&conn, "ext", jpeg.id, "JPG").unwrap());
+        assert!(retrieve_ext_aliases(&conn)
+            .unwrap()
+            .contains(&("jpeg".to_owned(), "JPG".to_owned())));
+        assert!(update_config_row(&conn, "nope", 1, "x").is_err());
+    }
+
+    #[test]
+    fn test_backup_db() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let mut conn = open_db(db.to_str()).unwrap();
+        upsert_setting(&conn, "case", "lower").unwrap();
+        upsert_rule_hits(&conn, "term-words", "a:b", 2).unwrap();
+        let (all, rules) = (tmp.path().join("all.bak"), tmp.path().join("rules.bak"));
+        backup_db(&conn, &all, "all").unwrap();
+        backup_db(&conn, &rules, "rules").unwrap();
+        assert!(backup_db(&conn, &all, "all").is_err());
+
+        upsert_setting(&conn, "case", "upper").unwrap();
+        upsert_rule_hits(&conn, "term-words", "a:b", 1).unwrap();
+        assert!(restore_db(&mut conn, &rules, "records").is_err());
+        restore_db(&mut conn, &rules, "all").unwrap();
+        assert_eq!(retrieve_setting(&conn, "case").unwrap().unwrap(), "lower");
+        assert_eq!(
+            retrieve_rule_stats(&conn).unwrap().values().sum::<usize>(),
+            3
+        );
+
+        upsert_setting(&conn, "case", "upper").unwrap();
+        restore_db(&mut conn, &all, "records").unwrap();
+        assert_eq!(retrieve_setting(&conn, "case").unwrap().unwrap(), "upper");
+        assert_eq!(
+            retrieve_rule_stats(&conn).unwrap().values().sum::<usize>(),
+            2
+        );
+        restore_db(&mut conn, &all, "all").unwrap();
+        assert_eq!(retrieve_setting(&conn, "case").unwrap().unwrap(), "lower");
+        assert!(restore_db(&mut conn, &tmp.path().join("none.bak"), "all").is_err());
+    }
+
+    #[test]
+    fn test_diff_config_rows() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (db, other_db) = (
+            tmp.path().join(DEFAULT_DB_NAME),
+            tmp.path().join("other.db"),
+        );
+        let mut conn = open_db(db.to_str()).unwrap();
+        insert_term_word(&conn, "ft.", "feat.", TermFlags::default(), "").unwrap();
+        insert_stopword(&conn, "kopie von", "de").unwrap();
+        let other = open_db(other_db.to_str()).unwrap();
+        insert_term_word(&other, "ft.", "Feat.", TermFlags::default(), "").unwrap();
+        insert_separator(&other, "-").unwrap();
+        insert_regex_rule(&conn, "v(\\d)", "V$1", "").unwrap();
+        insert_regex_rule(&other, "v(\\d)", "ver$1", "").unwrap();
+        drop(other);
+
+        assert!(open_other_db(&tmp.path().join("none.db")).is_err());
+        let other = open_other_db(&other_db).unwrap();
+        let diffs = diff_config_rows(&conn, &other).unwrap();
+        assert_eq!(
+            diffs
+                .iter()
+                .map(|d| (
+                    d.key.as_str(),
+                    d.local.as_ref().map_or("", |r| r.rule.as_str()),
+                    d.other.as_ref().map_or("", |r| r.rule.as_str()),
+                    d.columns.join(",")
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("-", "", "-", String::new()),
+                ("ft.", "ft.:feat.", "ft.:Feat.", "value".to_owned()),
+                (
+                    "v(\\d)",
+                    "re:v(\\d):V$1",
+                    "re:v(\\d):ver$1",
+                    "replacement".to_owned()
+                ),
+                ("de:kopie von", "stop:de:kopie von", "", String::new()),
+            ]
+        );
+
+        let tx = conn.transaction().unwrap();
+        for d in &diffs {
+            if let Some(o) = &d.other {
+                let local = d.local.as_ref().map(|l| l.id);
+                copy_config_row(&other, &tx, &o.kind, o.id, local).unwrap();
+            }
+        }
+        tx.commit().unwrap();
+        assert_eq!(retrieve_term_words(&conn).unwrap()[0].value, "Feat.");
+        assert_eq!(retrieve_regex_rules(&conn).unwrap()[0].replacement, "ver$1");
+        assert_eq!(retrieve_separators(&conn).unwrap().len(), 2);
+        assert_eq!(diff_config_rows(&conn, &other).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_toggled_rows() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        insert_term_word(&conn, "ft.", "feat.", TermFlags::default(), "").unwrap();
+        insert_stopword(&conn, "kopie von", "de").unwrap();
+        let id = retrieve_term_words(&conn).unwrap()[0].id;
+        assert!(retrieve_toggled_rows(&conn).unwrap().is_empty());
+
+        assert!(update_enabled(&conn, "term", id, false).unwrap());
+        assert!(!update_enabled(&conn, "term", id + 1, false).unwrap());
+        assert!(update_enabled(&conn, "nope", id, false).is_err());
+        assert!(retrieve_term_words(&conn).unwrap().is_empty());
+        assert!(update_tag(&conn, "term", id, "music").unwrap());
+        let stop_id = conn
+            .query_row("SELECT id FROM stopwords", [], |row| row.get(0))
+            .unwrap();
+        assert!(update_tag(&conn, "stop", stop_id, "music").unwrap());
+        let rows = retrieve_toggled_rows(&conn).unwrap();
+        assert_eq!(
+            rows.iter()
+                .map(|r| (r.kind.as_str(), r.rule.as_str(), r.enabled))
+                .collect::<Vec<_>>(),
+            [
+                ("term", "ft.:feat.", false),
+                ("stop", "stop:de:kopie von", true)
+            ]
+        );
+
+        assert_eq!(update_tag_enabled(&conn, "music", false).unwrap(), 2);
+        assert!(retrieve_stopwords(&conn).unwrap().is_empty());
+        assert_eq!(update_tag_enabled(&conn, "music", true).unwrap(), 2);
+        assert_eq!(retrieve_term_words(&conn).unwrap().len(), 1);
+        assert_eq!(retrieve_stopwords(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        assert!(retrieve_stopwords(&conn).unwrap().is_empty());
+
+        insert_stopword(&conn, "copy of", "").unwrap();
+        insert_stopword(&conn, "copy of", "").unwrap();
+        insert_stopword(&conn, "kopie von", "de").unwrap();
+        assert_eq!(
+            retrieve_stopwords(&conn).unwrap(),
+            vec![
+                ("copy of".to_owned(), "".to_owned()),
+                ("kopie von".to_owned(), "de".to_owned())
+            ]
+        );
+        assert!(!delete_stopword(&conn, "kopie von", "").unwrap());
+        assert!(delete_stopword(&conn, "kopie von", "de").unwrap());
+    }
+
+    #[test]
+    fn test_soft_delete_term_word() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        insert_term_word(&conn, "usb", "USB", TermFlags::default(), "").unwrap();
+        let id = retrieve_term_words(&conn).unwrap()[0].id;
+
+        delete_term_word(&conn, id).unwrap();
+        assert!(retrieve_term_words(&conn).unwrap().is_empty());
+        assert_eq!(retrieve_deleted_term_words(&conn).unwrap().len(), 1);
+        assert_eq!(purge_term_words(&conn, 30).unwrap(), 0);
+
+        assert!(restore_term_word(&conn, id).unwrap());
+        assert!(!restore_term_word(&conn, id).unwrap());
+        assert_eq!(retrieve_term_words(&conn).unwrap().len(), 1);
+
+        delete_term_word(&conn, id).unwrap();
+        let flags = TermFlags {
+            ignore_case: true,
+            whole_word: false,
+        };
+        insert_term_word(&conn, "usb", "Usb", flags, "video").unwrap();
+        assert_eq!(retrieve_term_words(&conn).unwrap()[0].value, "Usb");
+        assert_eq!(retrieve_term_words(&conn).unwrap()[0].flags, flags);
+        assert_eq!(retrieve_term_words(&conn).unwrap()[0].scope, "video");
+        assert!(retrieve_deleted_term_words(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        let argv = vec!["fdn".to_owned(), "-i".to_owned()];
+        let paths = vec![("/a".to_owned(), None), ("/b".to_owned(), None)];
+        insert_checkpoint(&conn, "s", &argv, &paths).unwrap();
+        update_checkpoint_path(&conn, "s", "/a").unwrap();
+        //paths already in checkpoint are not reset
+        insert_checkpoint(&conn, "s", &argv, &paths).unwrap();
+
+        let cps = retrieve_checkpoints(&conn).unwrap();
+        assert_eq!(cps.len(), 1);
+        assert_eq!(cps[0].argv, argv);
+        assert_eq!(cps[0].pending, 1);
+        assert_eq!(
+            retrieve_pending_paths(&conn, "s").unwrap(),
+            vec![("/b".to_owned(), None)]
+        );
+
+        delete_checkpoint_done(&conn, "s").unwrap();
+        assert_eq!(retrieve_checkpoints(&conn).unwrap().len(), 1);
+        update_checkpoint_path(&conn, "s", "/b").unwrap();
+        delete_checkpoint_done(&conn, "s").unwrap();
+        assert!(retrieve_checkpoints(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rule_stats() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join(DEFAULT_DB_NAME);
+        let conn = open_db(db.to_str()).unwrap();
+        upsert_rule_hits(&conn, "to-sep-words", " ", 2).unwrap();
+        upsert_rule_hits(&conn, "to-sep-words", " ", 3).unwrap();
+        upsert_rule_hits(&conn, "term-words", "a:b", 1).unwrap();
+
+        let stats = retrieve_rule_stats(&conn).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[&("to-sep-words".to_owned(), " ".to_owned())], 5);
+
+        delete_rule_stats(&conn).unwrap();
+        assert!(retrieve_rule_stats(&conn).unwrap().is_empty());
     }
 }
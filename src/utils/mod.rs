@@ -1,14 +1,30 @@
 pub mod scmp;
-pub use scmp::s_compare;
+pub use scmp::{color_enabled, display_width, s_compare, truncate_width};
 
 pub mod enc_dec;
 pub use enc_dec::{decrypted, encrypted, hashed_name};
 
+pub mod validate;
+pub use validate::{validators, violations, Validator};
+
 pub mod db;
 pub use db::{
-    create_records_table, create_separators_table, create_term_words_table,
-    create_to_sep_words_table, delete_records, delete_separator, delete_term_word,
-    delete_to_sep_word, insert_record, insert_separator, insert_term_word, insert_to_sep_word,
-    open_db, retrieve_term_words, update_records, update_separator, update_term_word,
-    update_to_sep_word,
+    create_acronyms_table, create_checkpoint_paths_table, create_checkpoints_table,
+    create_ext_aliases_table, create_operations_table, create_records_table,
+    create_regex_rules_table, create_rule_stats_table, create_separators_table,
+    create_settings_table, create_small_words_table, create_stopwords_table,
+    create_term_words_table, create_to_sep_words_table, default_db_path, delete_acronym,
+    delete_checkpoint, delete_checkpoint_done, delete_config_row, delete_ext_alias, delete_records,
+    delete_regex_rule, delete_rule_stats, delete_separator, delete_setting, delete_small_word,
+    delete_stopword, delete_term_word, delete_to_sep_word, insert_acronym, insert_checkpoint,
+    insert_operation, insert_record, insert_regex_rule, insert_separator, insert_small_word,
+    insert_stopword, insert_term_word, insert_to_sep_word, latest_operation_id, open_db,
+    purge_term_words, restore_term_word, retrieve_acronyms, retrieve_checkpoints,
+    retrieve_config_rows, retrieve_deleted_term_words, retrieve_ext_aliases, retrieve_operations,
+    retrieve_pending_paths, retrieve_record_by_hash, retrieve_regex_rules, retrieve_rule_stats,
+    retrieve_session_operations, retrieve_setting, retrieve_settings, retrieve_small_words,
+    retrieve_stopwords, retrieve_term_words, retrieve_toggled_rows, schema_version,
+    update_checkpoint_path, update_config_row, update_enabled, update_records, update_separator,
+    update_tag, update_tag_enabled, update_term_word, update_to_sep_word, upsert_ext_alias,
+    upsert_rule_hits, upsert_setting, CONFIG_KINDS, SCHEMA_VERSION,
 };
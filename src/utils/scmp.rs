@@ -1,8 +1,12 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    env,
+    io::{stdout, IsTerminal},
+};
 
 use ansi_term::Color;
 use anyhow::{Error, Result};
-use difference::{Changeset, Difference};
+use difference::Difference;
 use regex::Regex;
 use tracing::trace;
 use unicode_width::UnicodeWidthStr;
@@ -11,24 +15,140 @@ const SPACE_BOX: &str = "▯";
 const EMPTY_BOX: &str = "␣";
 const GRAY: Color = Color::RGB(128, 128, 128);
 
+///Decide whether output should be colored by "auto","always" or "never"
+pub fn color_enabled(choice: &str) -> bool {
+    match choice {
+        "always" => true,
+        "never" => false,
+        _ => {
+            let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            !no_color && stdout().is_terminal()
+        }
+    }
+}
+
+///Paint string with color only if color enabled
+fn paint(color: Color, s: String, enabled: bool) -> String {
+    if enabled {
+        color.paint(s).to_string()
+    } else {
+        s
+    }
+}
+
+///Split string into words and single non-word characters
+fn words(s: &str) -> Vec<&str> {
+    let mut rlt = Vec::new();
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        if c.is_alphanumeric() {
+            if start.is_none() {
+                start = Some(i);
+            }
+        } else {
+            if let Some(st) = start.take() {
+                rlt.push(&s[st..i]);
+            }
+            rlt.push(&s[i..i + c.len_utf8()]);
+        }
+    }
+    if let Some(st) = start {
+        rlt.push(&s[st..]);
+    }
+
+    rlt
+}
+
+///Push difference into diffs and merge it with the last one if they are the same kind
+fn push_diff(diffs: &mut Vec<Difference>, diff: Difference) {
+    match (diffs.last_mut(), diff) {
+        (Some(Difference::Same(l)), Difference::Same(s))
+        | (Some(Difference::Add(l)), Difference::Add(s))
+        | (Some(Difference::Rem(l)), Difference::Rem(s)) => l.push_str(&s),
+        (_, diff) => diffs.push(diff),
+    }
+}
+
+///Word level differences between origin and edit by longest common subsequence
+fn word_diffs(origin: &str, edit: &str) -> Vec<Difference> {
+    let (o_ws, e_ws) = (words(origin), words(edit));
+    let (m, n) = (o_ws.len(), e_ws.len());
+
+    let mut lcs = vec![vec![0_usize; n + 1]; m + 1];
+    for i in (0..m).rev() {
+        for j in (0..n).rev() {
+            lcs[i][j] = if o_ws[i] == e_ws[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < m || j < n {
+        if i < m && j < n && o_ws[i] == e_ws[j] {
+            push_diff(&mut diffs, Difference::Same(o_ws[i].to_owned()));
+            i += 1;
+            j += 1;
+        } else if i < m && (j == n || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push_diff(&mut diffs, Difference::Rem(o_ws[i].to_owned()));
+            i += 1;
+        } else {
+            push_diff(&mut diffs, Difference::Add(e_ws[j].to_owned()));
+            j += 1;
+        }
+    }
+
+    diffs
+}
+
 fn noesc(s: &str) -> Result<String> {
     let re = Regex::new(r"\x1B\[([0-9;]+)m")?;
     Ok(re.replace_all(s, "").to_string())
 }
 
+///Display width of string without escape sequences
+pub fn display_width(s: &str) -> Result<usize> {
+    Ok(noesc(s)?.width())
+}
+
+///Truncate string to display width,the truncated tail is replaced by "…"
+pub fn truncate_width(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_owned();
+    }
+
+    let mut rlt = String::new();
+    let mut w = 0;
+    for c in s.chars() {
+        let cw = c.to_string().width();
+        if w + cw + 1 > width {
+            break;
+        }
+        rlt.push(c);
+        w += cw;
+    }
+    rlt.push('…');
+
+    rlt
+}
+
 fn trace_noesc(sar: &str, origin: &str, edit: &str) {
     let noesc_origin = noesc(origin);
     let noesc_edit = noesc(edit);
     trace!("{}\n{:?}\n{:?}", sar, noesc_origin, noesc_edit);
 }
 
-pub fn s_compare(origin: &str, edit: &str, mode: &str) -> Result<(String, String)> {
-    let changeset = Changeset::new(origin, edit, "");
+///Compare origin and edit word by word and return rich text,mode "a" for alignment
+pub fn s_compare(origin: &str, edit: &str, mode: &str, color: bool) -> Result<(String, String)> {
+    let diffs = word_diffs(origin, edit);
 
     let mut c_origin = "".to_string();
     let mut c_edit = "".to_string();
 
-    changeset.diffs.iter().try_for_each(|diff| {
+    diffs.iter().try_for_each(|diff| {
         match diff {
             Difference::Same(s) => {
                 let s = s.replace(char::is_whitespace, SPACE_BOX);
@@ -42,14 +162,14 @@ pub fn s_compare(origin: &str, edit: &str, mode: &str) -> Result<(String, String
                     match noesc_origin.width().cmp(&noesc_edit.width()) {
                         Ordering::Less => {
                             let fill = EMPTY_BOX.repeat(noesc_edit.width() - noesc_origin.width());
-                            c_origin.push_str(&GRAY.paint(fill).to_string());
+                            c_origin.push_str(&paint(GRAY, fill, color));
                         }
                         Ordering::Equal => {}
                         Ordering::Greater => {
                             let dif_len = noesc_origin.width() - noesc_edit.width();
 
                             let fill = EMPTY_BOX.repeat(dif_len);
-                            c_edit.push_str(&GRAY.paint(fill).to_string());
+                            c_edit.push_str(&paint(GRAY, fill, color));
                         }
                     }
                 }
@@ -63,7 +183,7 @@ pub fn s_compare(origin: &str, edit: &str, mode: &str) -> Result<(String, String
                 trace_noesc("Add", &c_origin, &c_edit);
 
                 let s = s.replace(char::is_whitespace, SPACE_BOX);
-                c_edit.push_str(&Color::Green.paint(s).to_string());
+                c_edit.push_str(&paint(Color::Green, s, color));
 
                 trace_noesc("", &c_origin, &c_edit);
             }
@@ -71,7 +191,7 @@ pub fn s_compare(origin: &str, edit: &str, mode: &str) -> Result<(String, String
                 trace_noesc("Rem", &c_origin, &c_edit);
 
                 let s = s.replace(char::is_whitespace, SPACE_BOX);
-                c_origin.push_str(&Color::Red.paint(s).to_string());
+                c_origin.push_str(&paint(Color::Red, s, color));
 
                 trace_noesc("", &c_origin, &c_edit);
             }
@@ -89,14 +209,14 @@ pub fn s_compare(origin: &str, edit: &str, mode: &str) -> Result<(String, String
         match noesc_origin.width().cmp(&noesc_edit.width()) {
             Ordering::Less => {
                 let fill = EMPTY_BOX.repeat(noesc_edit.width() - noesc_origin.width());
-                c_origin.push_str(&GRAY.paint(fill).to_string());
+                c_origin.push_str(&paint(GRAY, fill, color));
             }
             Ordering::Equal => {}
             Ordering::Greater => {
                 let dif_len = noesc_origin.width() - noesc_edit.width();
 
                 let fill = EMPTY_BOX.repeat(dif_len);
-                c_edit.push_str(&GRAY.paint(fill).to_string());
+                c_edit.push_str(&paint(GRAY, fill, color));
             }
         }
     }
@@ -106,16 +226,46 @@ pub fn s_compare(origin: &str, edit: &str, mode: &str) -> Result<(String, String
 
 #[cfg(test)]
 mod tests {
+    use difference::Difference;
+
     use crate::utils::s_compare;
 
+    use super::{truncate_width, word_diffs};
+
     #[test]
     fn test_s_compare() {
         let origin = "A B C";
         let origin_a = "A\u{1b}[31m▯\u{1b}[0mB\u{1b}[31m▯\u{1b}[0mC";
         let edit = "A_B_C";
         let edit_a = "A\u{1b}[32m_\u{1b}[0mB\u{1b}[32m_\u{1b}[0mC";
-        let (o_r, e_r) = s_compare(origin, edit, "a").unwrap();
+        let (o_r, e_r) = s_compare(origin, edit, "a", true).unwrap();
         assert_eq!(origin_a, o_r);
         assert_eq!(edit_a, e_r);
+
+        let (o_r, e_r) = s_compare(origin, edit, "", false).unwrap();
+        assert_eq!("A▯B▯C", o_r);
+        assert_eq!(edit, e_r);
+    }
+
+    #[test]
+    fn test_word_diffs() {
+        let diffs = word_diffs("Movie Name 1080p", "Movie_Name");
+        assert_eq!(
+            diffs,
+            vec![
+                Difference::Same("Movie".to_owned()),
+                Difference::Rem(" ".to_owned()),
+                Difference::Add("_".to_owned()),
+                Difference::Same("Name".to_owned()),
+                Difference::Rem(" 1080p".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncate_width() {
+        assert_eq!(truncate_width("short.txt", 10), "short.txt");
+        assert_eq!(truncate_width("a_long_name.txt", 8), "a_long_…");
+        assert_eq!(truncate_width("测试文件名.txt", 6), "测试…");
     }
 }
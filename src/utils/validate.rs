@@ -0,0 +1,109 @@
+use std::{fmt, fs};
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+///Constraint every planned target name must pass before apply
+pub trait Validator: fmt::Debug + Send + Sync {
+    ///Return the violated constraint if name not pass
+    fn validate(&self, name: &str) -> Option<String>;
+}
+
+///Only ASCII letters,digits,'.','_' and '-' allowed
+#[derive(Debug)]
+pub struct PortableCharset;
+
+impl Validator for PortableCharset {
+    fn validate(&self, name: &str) -> Option<String> {
+        name.chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+            .map(|c| format!("portable-charset:{:?} not allowed", c))
+    }
+}
+
+///Name length in bytes must not exceed the maximum
+#[derive(Debug)]
+pub struct MaxLength(pub usize);
+
+impl Validator for MaxLength {
+    fn validate(&self, name: &str) -> Option<String> {
+        if name.len() > self.0 {
+            Some(format!("max-length:{} > {}", name.len(), self.0))
+        } else {
+            None
+        }
+    }
+}
+
+///Name must match the user regex
+#[derive(Debug)]
+pub struct MatchRegex(pub Regex);
+
+impl Validator for MatchRegex {
+    fn validate(&self, name: &str) -> Option<String> {
+        if self.0.is_match(name) {
+            None
+        } else {
+            Some(format!("regex:not match {:?}", self.0.as_str()))
+        }
+    }
+}
+
+///Parse validator specs such as "portable-charset","max-length=255","regex=^[a-z]" and
+///"policy=FILE",a policy file holds one spec per line and '#' starts a comment
+pub fn validators(specs: &[String]) -> Result<Vec<Box<dyn Validator>>> {
+    let mut rlt: Vec<Box<dyn Validator>> = Vec::new();
+    for spec in specs {
+        let (key, value) = match spec.split_once('=') {
+            Some((k, v)) => (k.trim(), Some(v.trim())),
+            None => (spec.trim(), None),
+        };
+        match (key, value) {
+            ("portable-charset", None) => rlt.push(Box::new(PortableCharset)),
+            ("max-length", Some(v)) => rlt.push(Box::new(MaxLength(v.parse()?))),
+            ("regex", Some(v)) => rlt.push(Box::new(MatchRegex(Regex::new(v)?))),
+            ("policy", Some(v)) => {
+                let lines: Vec<_> = fs::read_to_string(v)?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(str::to_owned)
+                    .collect();
+                rlt.extend(validators(&lines)?);
+            }
+            _ => return Err(anyhow!("unknown validator:{:?}", spec)),
+        }
+    }
+
+    Ok(rlt)
+}
+
+///Return all violated constraints of name
+pub fn violations(validators: &[Box<dyn Validator>], name: &str) -> Vec<String> {
+    validators.iter().filter_map(|v| v.validate(name)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{validators, violations};
+
+    #[test]
+    fn test_validators() {
+        let specs = vec!["portable-charset".to_owned(), "max-length=8".to_owned()];
+        let vs = validators(&specs).unwrap();
+        assert!(violations(&vs, "a_b.txt").is_empty());
+        assert_eq!(violations(&vs, "a b c.txt").len(), 2);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let policy = tmp.path().join("policy");
+        fs::write(&policy, "# lower case only\nregex=^[a-z_.]+$\n").unwrap();
+        let specs = vec![format!("policy={}", policy.display())];
+        let vs = validators(&specs).unwrap();
+        assert!(violations(&vs, "a_b.txt").is_empty());
+        assert_eq!(violations(&vs, "A_b.txt").len(), 1);
+
+        assert!(validators(&["unknown".to_owned()]).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+///Suffixes of files still being written by browsers and download tools
+pub const TEMP_SUFFIXES: [&str; 8] = [
+    ".part",
+    ".partial",
+    ".crdownload",
+    ".download",
+    ".tmp",
+    ".temp",
+    ".!qb",
+    ".opdownload",
+];
+
+///Check a file is a temporary one of a download or an editor,such as 'a.zip.part' or '~$a.docx'
+pub fn is_temp(path: &Path) -> bool {
+    let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_lowercase()) else {
+        return false;
+    };
+
+    name.starts_with('~') || TEMP_SUFFIXES.iter().any(|s| name.ends_with(s))
+}
+
+///Size and modified time of a file,a file is stable while they stay the same
+pub type Stamp = (u64, Option<SystemTime>);
+
+///New files of a watched directory waiting until they stay unchanged for the debounce
+#[derive(Debug)]
+pub struct Watcher {
+    debounce: Duration,
+    ///new files with their last stamp and when it was first seen
+    pending: HashMap<PathBuf, (Stamp, Instant)>,
+}
+
+impl Watcher {
+    pub fn new(debounce: Duration) -> Self {
+        Self {
+            debounce,
+            pending: HashMap::new(),
+        }
+    }
+
+    ///Note a file created in or renamed into the directory by a notification,its debounce
+    ///restarted if it changed since it was noted
+    pub fn notice(&mut self, path: PathBuf, stamp: Stamp, now: Instant) {
+        match self.pending.get_mut(&path) {
+            Some((last, since)) if *last != stamp => (*last, *since) = (stamp, now),
+            Some(_) => {}
+            None => {
+                self.pending.insert(path, (stamp, now));
+            }
+        }
+    }
+
+    ///New files unchanged for the debounce by their current stamps,files gone dropped and files
+    ///still changing kept waiting
+    pub fn ready<F>(&mut self, now: Instant, stamp: F) -> Vec<PathBuf>
+    where
+        F: Fn(&Path) -> Option<Stamp>,
+    {
+        let mut ready = Vec::new();
+        self.pending
+            .retain(|path, (last, since)| match stamp(path) {
+                None => false,
+                Some(current) if current != *last => {
+                    (*last, *since) = (current, now);
+                    true
+                }
+                Some(_) if now.duration_since(*since) >= self.debounce => {
+                    ready.push(path.clone());
+                    false
+                }
+                Some(_) => true,
+            });
+        ready.sort();
+
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+        time::{Duration, Instant},
+    };
+
+    use crate::watch::{is_temp, Watcher};
+
+    #[test]
+    fn test_is_temp() {
+        assert!(is_temp(Path::new("dl/a.zip.part")));
+        assert!(is_temp(Path::new("dl/Video.MP4.crdownload")));
+        assert!(is_temp(Path::new("~$report.docx")));
+        assert!(!is_temp(Path::new("dl/a.zip")));
+    }
+
+    #[test]
+    fn test_watcher() {
+        let mut w = Watcher::new(Duration::from_secs(2));
+        let t = Instant::now();
+        let at = |s| t + Duration::from_secs(s);
+        let sizes = |files: &[(&str, u64)]| -> HashMap<PathBuf, u64> {
+            files.iter().map(|(n, s)| (PathBuf::from(n), *s)).collect()
+        };
+        let stamp = |files: HashMap<PathBuf, u64>| move |p: &Path| files.get(p).map(|s| (*s, None));
+
+        w.notice(PathBuf::from("new"), (1, None), at(0));
+        assert!(w.ready(at(1), stamp(sizes(&[("new", 1)]))).is_empty());
+        //still growing
+        assert!(w.ready(at(2), stamp(sizes(&[("new", 5)]))).is_empty());
+        assert!(w.ready(at(3), stamp(sizes(&[("new", 5)]))).is_empty());
+        assert_eq!(
+            w.ready(at(4), stamp(sizes(&[("new", 5)]))),
+            [PathBuf::from("new")]
+        );
+        //returned once
+        assert!(w.ready(at(9), stamp(sizes(&[("new", 5)]))).is_empty());
+        //noticed again while growing
+        w.notice(PathBuf::from("big"), (1, None), at(10));
+        w.notice(PathBuf::from("big"), (3, None), at(11));
+        assert!(w.ready(at(12), stamp(sizes(&[("big", 3)]))).is_empty());
+        assert_eq!(
+            w.ready(at(13), stamp(sizes(&[("big", 3)]))),
+            [PathBuf::from("big")]
+        );
+        //gone before stable
+        w.notice(PathBuf::from("gone"), (1, None), at(20));
+        assert!(w.ready(at(21), stamp(sizes(&[]))).is_empty());
+        assert!(w.ready(at(30), stamp(sizes(&[("gone", 1)]))).is_empty());
+    }
+}
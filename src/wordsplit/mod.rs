@@ -0,0 +1,120 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+///Words of the embedded dictionary,one per line,most frequent first
+const WORDS: &str = include_str!("words.txt");
+
+///Shortest runs of letters which are split into words
+pub const MIN_LEN: usize = 8;
+
+///Cost of every word of the dictionary by its frequency rank,rarer words cost more
+static COSTS: LazyLock<HashMap<&'static str, f64>> = LazyLock::new(|| {
+    let words: Vec<_> = WORDS
+        .lines()
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .collect();
+    let log_n = (words.len() as f64).ln();
+    let mut costs = HashMap::new();
+    for (rank, word) in words.into_iter().enumerate() {
+        //repeated words keep their first rank
+        costs
+            .entry(word)
+            .or_insert(((rank + 1) as f64 * log_n).ln());
+    }
+
+    costs
+});
+
+///Length of the longest word of the dictionary
+static MAX_WORD_LEN: LazyLock<usize> =
+    LazyLock::new(|| COSTS.keys().map(|w| w.len()).max().unwrap_or(0));
+
+///Split a run of ASCII letters into the fewest dictionary words,of the lowest total cost among
+///them so that longer whole words such as "notebook" win over fragments as "not" and "ebook",None
+///if it is a word itself or can not be covered by words
+fn split_run(run: &str) -> Option<Vec<&str>> {
+    let lower = run.to_ascii_lowercase();
+    if COSTS.contains_key(lower.as_str()) {
+        return None;
+    }
+
+    //fewest words,lowest cost and start of the last word of every prefix
+    let mut best: Vec<Option<(usize, f64, usize)>> = vec![None; lower.len() + 1];
+    best[0] = Some((0, 0.0, 0));
+    for end in 1..=lower.len() {
+        best[end] = (end.saturating_sub(*MAX_WORD_LEN)..end)
+            .filter_map(|start| {
+                let (count, cost, _) = best[start]?;
+                let word = COSTS.get(&lower[start..end])?;
+                Some((count + 1, cost + word, start))
+            })
+            .min_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    }
+
+    let mut words = Vec::new();
+    let mut end = lower.len();
+    while end > 0 {
+        let (.., start) = best[end]?;
+        words.push(&run[start..end]);
+        end = start;
+    }
+    words.reverse();
+
+    Some(words)
+}
+
+///Split long runs of ASCII letters without separators such as "annualreportfinalversion" into
+///dictionary words joined by sep,runs which are not all dictionary words kept
+pub fn split_words(s: &str, sep: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(end);
+        match (run.len() >= MIN_LEN).then(|| split_run(run)).flatten() {
+            Some(words) => out.push_str(&words.join(sep)),
+            None => out.push_str(run),
+        }
+
+        let c = match tail.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        out.push(c);
+        rest = &tail[c.len_utf8()..];
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wordsplit::split_words;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            split_words("annualreportfinalversion", "_"),
+            "annual_report_final_version"
+        );
+        assert_eq!(
+            split_words("2024 MeetingNotes draftcopy", "-"),
+            "2024 Meeting-Notes draft-copy"
+        );
+        assert_eq!(
+            split_words("birthdaypartyphotos.v2", "_"),
+            "birthday_party_photos.v2"
+        );
+        //words themselves and runs with unknown parts kept
+        assert_eq!(split_words("yesterday", "_"), "yesterday");
+        assert_eq!(
+            split_words("Notebook Anywhere Hometown Somewhere", "_"),
+            "Notebook Anywhere Hometown Somewhere"
+        );
+        assert_eq!(split_words("mynotebookphotos", "_"), "my_notebook_photos");
+        assert_eq!(split_words("qwxzreport2024", "_"), "qwxzreport2024");
+        assert_eq!(split_words("中文reportfinal", "_"), "中文report_final");
+    }
+}